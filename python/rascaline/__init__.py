@@ -1,5 +1,5 @@
 # -*- coding: utf-8 -*-
-from .descriptor import Descriptor
+from .descriptor import Descriptor, BlockDescriptor
 from .systems import SystemBase
 from .calculator import CalculatorBase
 from .calculator import SortedDistances
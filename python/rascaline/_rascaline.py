@@ -18,10 +18,16 @@ elif arch == "64bit":
     c_uintptr_t = ctypes.c_uint64
 
 
+class rascal_index_type_t(enum.Enum):
+    RASCAL_INDEX_INT64 = 0
+    RASCAL_INDEX_FLOAT64 = 1
+
+
 class rascal_indexes(enum.Enum):
     RASCAL_INDEXES_FEATURES = 0
     RASCAL_INDEXES_ENVIRONMENTS = 1
     RASCAL_INDEXES_GRADIENTS = 2
+    RASCAL_INDEXES_CELL_GRADIENTS = 3
 
 
 class rascal_status_t(enum.Enum):
@@ -29,10 +35,17 @@ class rascal_status_t(enum.Enum):
     RASCAL_INVALID_PARAMETER_ERROR = 1
     RASCAL_JSON_ERROR = 2
     RASCAL_UTF8_ERROR = 3
+    RASCAL_SYSTEM_ERROR = 4
+    RASCAL_CALLBACK_ERROR = 5
+    RASCAL_IO_ERROR = 6
     RASCAL_UNKNOWN_ERROR = 254
     RASCAL_INTERNAL_PANIC = 255
 
 
+class rascal_block_descriptor_t(ctypes.Structure):
+    pass
+
+
 class rascal_calculator_t(ctypes.Structure):
     pass
 
@@ -41,24 +54,43 @@ class rascal_descriptor_t(ctypes.Structure):
     pass
 
 
+class rascal_indexes_t(ctypes.Structure):
+    pass
+
+
+class rascal_index_data_t(ctypes.Union):
+    _fields_ = [
+        ("int64", ctypes.c_int64),
+        ("float64", ctypes.c_double),
+    ]
+
+
+class rascal_index_value_t(ctypes.Structure):
+    _fields_ = [
+        ("index_type", ctypes.c_int),
+        ("data", rascal_index_data_t),
+    ]
+
+
 class rascal_pair_t(ctypes.Structure):
     _fields_ = [
         ("first", c_uintptr_t),
         ("second", c_uintptr_t),
         ("vector", ctypes.c_double * 3),
+        ("cell_shift", ctypes.c_int32 * 3),
     ]
 
 
 class rascal_system_t(ctypes.Structure):
     _fields_ = [
         ("user_data", ctypes.c_void_p),
-        ("size", CFUNCTYPE(None, ctypes.c_void_p, POINTER(c_uintptr_t))),
-        ("species", CFUNCTYPE(None, ctypes.c_void_p, POINTER(ndpointer(c_uintptr_t, flags='C_CONTIGUOUS')))),
-        ("positions", CFUNCTYPE(None, ctypes.c_void_p, POINTER(ndpointer(ctypes.c_double, flags='C_CONTIGUOUS')))),
-        ("cell", CFUNCTYPE(None, ctypes.c_void_p, POINTER(ctypes.c_double))),
-        ("compute_neighbors", CFUNCTYPE(None, ctypes.c_void_p, ctypes.c_double)),
-        ("pairs", CFUNCTYPE(None, ctypes.c_void_p, POINTER(ndpointer(rascal_pair_t, flags='C_CONTIGUOUS')), POINTER(c_uintptr_t))),
-        ("pairs_containing", CFUNCTYPE(None, ctypes.c_void_p, c_uintptr_t, POINTER(ndpointer(rascal_pair_t, flags='C_CONTIGUOUS')), POINTER(c_uintptr_t))),
+        ("size", CFUNCTYPE(ctypes.c_int, ctypes.c_void_p, POINTER(c_uintptr_t))),
+        ("species", CFUNCTYPE(ctypes.c_int, ctypes.c_void_p, POINTER(ndpointer(c_uintptr_t, flags='C_CONTIGUOUS')))),
+        ("positions", CFUNCTYPE(ctypes.c_int, ctypes.c_void_p, POINTER(ndpointer(ctypes.c_double, flags='C_CONTIGUOUS')))),
+        ("cell", CFUNCTYPE(ctypes.c_int, ctypes.c_void_p, POINTER(ctypes.c_double))),
+        ("compute_neighbors", CFUNCTYPE(ctypes.c_int, ctypes.c_void_p, ctypes.c_double)),
+        ("pairs", CFUNCTYPE(ctypes.c_int, ctypes.c_void_p, POINTER(ndpointer(rascal_pair_t, flags='C_CONTIGUOUS')), POINTER(c_uintptr_t))),
+        ("pairs_containing", CFUNCTYPE(ctypes.c_int, ctypes.c_void_p, c_uintptr_t, POINTER(ndpointer(rascal_pair_t, flags='C_CONTIGUOUS')), POINTER(c_uintptr_t))),
     ]
 
 
@@ -69,6 +101,8 @@ class rascal_calculation_options_t(ctypes.Structure):
         ("selected_samples_count", c_uintptr_t),
         ("selected_features", POINTER(ctypes.c_double)),
         ("selected_features_count", c_uintptr_t),
+        ("threads", c_uintptr_t),
+        ("sparse_gradients", ctypes.c_bool),
     ]
 
 
@@ -106,15 +140,43 @@ def setup_functions(lib):
     ]
     lib.rascal_descriptor_gradients.restype = _check_rascal_status_t
 
+    lib.rascal_descriptor_cell_gradients.argtypes = [
+        POINTER(rascal_descriptor_t),
+        POINTER(POINTER(ctypes.c_double)),
+        POINTER(c_uintptr_t),
+        POINTER(c_uintptr_t)
+    ]
+    lib.rascal_descriptor_cell_gradients.restype = _check_rascal_status_t
+
+    lib.rascal_descriptor_sparse_gradients.argtypes = [
+        POINTER(rascal_descriptor_t),
+        POINTER(POINTER(c_uintptr_t)),
+        POINTER(POINTER(c_uintptr_t)),
+        POINTER(POINTER(c_uintptr_t)),
+        POINTER(c_uintptr_t),
+        POINTER(POINTER(c_uintptr_t)),
+        POINTER(POINTER(ctypes.c_double)),
+        POINTER(c_uintptr_t)
+    ]
+    lib.rascal_descriptor_sparse_gradients.restype = _check_rascal_status_t
+
     lib.rascal_descriptor_indexes.argtypes = [
         POINTER(rascal_descriptor_t),
         ctypes.c_int,
-        POINTER(POINTER(ctypes.c_double)),
+        POINTER(POINTER(rascal_index_value_t)),
         POINTER(c_uintptr_t),
         POINTER(c_uintptr_t)
     ]
     lib.rascal_descriptor_indexes.restype = _check_rascal_status_t
 
+    lib.rascal_descriptor_indexes_types.argtypes = [
+        POINTER(rascal_descriptor_t),
+        ctypes.c_int,
+        POINTER(ctypes.c_int),
+        c_uintptr_t
+    ]
+    lib.rascal_descriptor_indexes_types.restype = _check_rascal_status_t
+
     lib.rascal_descriptor_indexes_names.argtypes = [
         POINTER(rascal_descriptor_t),
         ctypes.c_int,
@@ -130,6 +192,190 @@ def setup_functions(lib):
     ]
     lib.rascal_descriptor_densify.restype = _check_rascal_status_t
 
+    lib.rascal_descriptor_save.argtypes = [
+        POINTER(rascal_descriptor_t),
+        ctypes.c_char_p
+    ]
+    lib.rascal_descriptor_save.restype = _check_rascal_status_t
+
+    lib.rascal_descriptor_load.argtypes = [
+        POINTER(rascal_descriptor_t),
+        ctypes.c_char_p
+    ]
+    lib.rascal_descriptor_load.restype = _check_rascal_status_t
+
+    lib.rascal_descriptor_select.argtypes = [
+        POINTER(rascal_descriptor_t),
+        POINTER(rascal_descriptor_t),
+        POINTER(rascal_indexes_t),
+        POINTER(rascal_indexes_t)
+    ]
+    lib.rascal_descriptor_select.restype = _check_rascal_status_t
+
+    lib.rascal_descriptor_concatenate_samples.argtypes = [
+        POINTER(POINTER(rascal_descriptor_t)),
+        c_uintptr_t,
+        POINTER(rascal_descriptor_t)
+    ]
+    lib.rascal_descriptor_concatenate_samples.restype = _check_rascal_status_t
+
+    lib.rascal_descriptor_concatenate_features.argtypes = [
+        POINTER(POINTER(rascal_descriptor_t)),
+        c_uintptr_t,
+        POINTER(rascal_descriptor_t)
+    ]
+    lib.rascal_descriptor_concatenate_features.restype = _check_rascal_status_t
+
+    lib.rascal_indexes_new.argtypes = [
+        POINTER(ctypes.c_char_p),
+        c_uintptr_t,
+        POINTER(rascal_index_value_t),
+        c_uintptr_t
+    ]
+    lib.rascal_indexes_new.restype = POINTER(rascal_indexes_t)
+
+    lib.rascal_indexes_from_descriptor.argtypes = [
+        POINTER(rascal_descriptor_t),
+        ctypes.c_int
+    ]
+    lib.rascal_indexes_from_descriptor.restype = POINTER(rascal_indexes_t)
+
+    lib.rascal_indexes_free.argtypes = [
+        POINTER(rascal_indexes_t)
+    ]
+    lib.rascal_indexes_free.restype = _check_rascal_status_t
+
+    lib.rascal_indexes_values.argtypes = [
+        POINTER(rascal_indexes_t),
+        POINTER(POINTER(rascal_index_value_t)),
+        POINTER(c_uintptr_t),
+        POINTER(c_uintptr_t)
+    ]
+    lib.rascal_indexes_values.restype = _check_rascal_status_t
+
+    lib.rascal_indexes_types.argtypes = [
+        POINTER(rascal_indexes_t),
+        POINTER(ctypes.c_int),
+        c_uintptr_t
+    ]
+    lib.rascal_indexes_types.restype = _check_rascal_status_t
+
+    lib.rascal_indexes_names.argtypes = [
+        POINTER(rascal_indexes_t),
+        POINTER(ctypes.c_char_p),
+        c_uintptr_t
+    ]
+    lib.rascal_indexes_names.restype = _check_rascal_status_t
+
+    lib.rascal_indexes_position.argtypes = [
+        POINTER(rascal_indexes_t),
+        POINTER(rascal_index_value_t),
+        c_uintptr_t,
+        POINTER(ctypes.c_int64)
+    ]
+    lib.rascal_indexes_position.restype = _check_rascal_status_t
+
+    lib.rascal_indexes_union.argtypes = [
+        POINTER(rascal_indexes_t),
+        POINTER(rascal_indexes_t)
+    ]
+    lib.rascal_indexes_union.restype = POINTER(rascal_indexes_t)
+
+    lib.rascal_indexes_intersection.argtypes = [
+        POINTER(rascal_indexes_t),
+        POINTER(rascal_indexes_t)
+    ]
+    lib.rascal_indexes_intersection.restype = POINTER(rascal_indexes_t)
+
+    lib.rascal_indexes_difference.argtypes = [
+        POINTER(rascal_indexes_t),
+        POINTER(rascal_indexes_t)
+    ]
+    lib.rascal_indexes_difference.restype = POINTER(rascal_indexes_t)
+
+    lib.rascal_indexes_filter.argtypes = [
+        POINTER(rascal_indexes_t),
+        POINTER(ctypes.c_char_p),
+        POINTER(rascal_index_value_t),
+        c_uintptr_t
+    ]
+    lib.rascal_indexes_filter.restype = POINTER(rascal_indexes_t)
+
+    lib.rascal_indexes_project.argtypes = [
+        POINTER(rascal_indexes_t),
+        POINTER(ctypes.c_char_p),
+        c_uintptr_t
+    ]
+    lib.rascal_indexes_project.restype = POINTER(rascal_indexes_t)
+
+    lib.rascal_indexes_sort.argtypes = [
+        POINTER(rascal_indexes_t)
+    ]
+    lib.rascal_indexes_sort.restype = _check_rascal_status_t
+
+    lib.rascal_block_descriptor.argtypes = [
+        
+    ]
+    lib.rascal_block_descriptor.restype = POINTER(rascal_block_descriptor_t)
+
+    lib.rascal_block_descriptor_free.argtypes = [
+        POINTER(rascal_block_descriptor_t)
+    ]
+    lib.rascal_block_descriptor_free.restype = _check_rascal_status_t
+
+    lib.rascal_block_descriptor_from_descriptor.argtypes = [
+        POINTER(rascal_block_descriptor_t),
+        POINTER(rascal_descriptor_t),
+        POINTER(ctypes.c_char_p),
+        c_uintptr_t
+    ]
+    lib.rascal_block_descriptor_from_descriptor.restype = _check_rascal_status_t
+
+    lib.rascal_block_descriptor_densify.argtypes = [
+        POINTER(rascal_block_descriptor_t),
+        POINTER(rascal_descriptor_t)
+    ]
+    lib.rascal_block_descriptor_densify.restype = _check_rascal_status_t
+
+    lib.rascal_block_descriptor_keys.argtypes = [
+        POINTER(rascal_block_descriptor_t)
+    ]
+    lib.rascal_block_descriptor_keys.restype = POINTER(rascal_indexes_t)
+
+    lib.rascal_block_descriptor_indexes.argtypes = [
+        POINTER(rascal_block_descriptor_t),
+        c_uintptr_t,
+        ctypes.c_int
+    ]
+    lib.rascal_block_descriptor_indexes.restype = POINTER(rascal_indexes_t)
+
+    lib.rascal_block_descriptor_values.argtypes = [
+        POINTER(rascal_block_descriptor_t),
+        c_uintptr_t,
+        POINTER(POINTER(ctypes.c_double)),
+        POINTER(c_uintptr_t),
+        POINTER(c_uintptr_t)
+    ]
+    lib.rascal_block_descriptor_values.restype = _check_rascal_status_t
+
+    lib.rascal_block_descriptor_gradients.argtypes = [
+        POINTER(rascal_block_descriptor_t),
+        c_uintptr_t,
+        POINTER(POINTER(ctypes.c_double)),
+        POINTER(c_uintptr_t),
+        POINTER(c_uintptr_t)
+    ]
+    lib.rascal_block_descriptor_gradients.restype = _check_rascal_status_t
+
+    lib.rascal_block_descriptor_cell_gradients.argtypes = [
+        POINTER(rascal_block_descriptor_t),
+        c_uintptr_t,
+        POINTER(POINTER(ctypes.c_double)),
+        POINTER(c_uintptr_t),
+        POINTER(c_uintptr_t)
+    ]
+    lib.rascal_block_descriptor_cell_gradients.restype = _check_rascal_status_t
+
     lib.rascal_calculator.argtypes = [
         ctypes.c_char_p,
         ctypes.c_char_p
@@ -163,3 +409,14 @@ def setup_functions(lib):
         rascal_calculation_options_t
     ]
     lib.rascal_calculator_compute.restype = _check_rascal_status_t
+
+    lib.rascal_calculator_compute_blocks.argtypes = [
+        POINTER(rascal_calculator_t),
+        POINTER(rascal_block_descriptor_t),
+        POINTER(rascal_system_t),
+        c_uintptr_t,
+        POINTER(ctypes.c_char_p),
+        c_uintptr_t,
+        rascal_calculation_options_t
+    ]
+    lib.rascal_calculator_compute_blocks.restype = _check_rascal_status_t
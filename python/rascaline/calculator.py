@@ -6,7 +6,7 @@ import numpy as np
 from ._rascaline import rascal_system_t, rascal_status_t, rascal_calculation_options_t
 from .clib import _get_library
 from .status import _check_rascal_pointer, RascalError
-from .descriptor import Descriptor
+from .descriptor import Descriptor, BlockDescriptor
 from .systems import wrap_system
 
 
@@ -30,6 +30,17 @@ def _call_with_growing_buffer(callback, initial=1024):
     return buffer.value.decode("utf8")
 
 
+def _structured_to_float64(indexes):
+    """
+    Convert a structured array (such as ``Descriptor.environments``) to a 2D
+    ``float64`` array, with one column for each field
+    """
+    columns = [indexes[name].astype(np.float64) for name in indexes.dtype.names]
+    if len(columns) == 0:
+        return np.zeros((len(indexes), 0), dtype=np.float64)
+    return np.stack(columns, axis=1)
+
+
 def _check_selected_indexes(indexes, kind):
     if len(indexes.shape) != 2:
         raise ValueError(f"selected {kind} array must be a two-dimensional array")
@@ -60,6 +71,8 @@ def _options_to_c(options):
         "use_native_system",
         "selected_samples",
         "selected_features",
+        "threads",
+        "sparse_gradients",
     ]
     for option in options.keys():
         if option not in known_options:
@@ -70,8 +83,7 @@ def _options_to_c(options):
         samples = np.array(samples)
         if samples.dtype.fields is not None:
             # convert structured array back to float64 array
-            size = len(samples)
-            samples = samples.view(dtype=np.float64).reshape((size, -1))
+            samples = _structured_to_float64(samples)
         else:
             _check_selected_indexes(samples, "samples")
             samples = np.array(samples, dtype=np.float64)
@@ -81,8 +93,7 @@ def _options_to_c(options):
         features = np.array(features)
         if features.dtype.fields is not None:
             # convert structured array back to float64 array
-            size = len(features)
-            features = features.view(dtype=np.float64).reshape((size, -1))
+            features = _structured_to_float64(features)
         else:
             _check_selected_indexes(features, "features")
             features = np.array(features, dtype=np.float64)
@@ -90,6 +101,8 @@ def _options_to_c(options):
     ptr_double = ctypes.POINTER(ctypes.c_double)
     c_options = rascal_calculation_options_t()
     c_options.use_native_system = bool(options.get("use_native_system", False))
+    c_options.threads = int(options.get("threads", 1))
+    c_options.sparse_gradients = bool(options.get("sparse_gradients", False))
 
     if samples is None:
         c_options.selected_samples = None
@@ -146,6 +159,29 @@ class CalculatorBase:
         )
         return descriptor
 
+    def compute_blocks(self, systems, keys, blocks=None, **kwargs):
+        if blocks is None:
+            blocks = BlockDescriptor()
+
+        if isinstance(keys, str):
+            keys = [keys]
+
+        c_keys = ctypes.ARRAY(ctypes.c_char_p, len(keys))()
+        for i, key in enumerate(keys):
+            c_keys[i] = key.encode("utf8")
+
+        c_systems = _convert_systems(systems)
+        self._lib.rascal_calculator_compute_blocks(
+            self,
+            blocks,
+            c_systems,
+            c_systems._length_,
+            c_keys,
+            c_keys._length_,
+            _options_to_c(kwargs),
+        )
+        return blocks
+
 
 class DummyCalculator(CalculatorBase):
     def __init__(self, cutoff, delta, name, gradients):
@@ -159,8 +195,12 @@ class DummyCalculator(CalculatorBase):
 
 
 class SortedDistances(CalculatorBase):
-    def __init__(self, cutoff, max_neighbors):
-        parameters = {"cutoff": cutoff, "max_neighbors": max_neighbors}
+    def __init__(self, cutoff, max_neighbors, cell_gradients=False):
+        parameters = {
+            "cutoff": cutoff,
+            "max_neighbors": max_neighbors,
+            "cell_gradients": cell_gradients,
+        }
         super().__init__("sorted_distances", **parameters)
 
 
@@ -174,7 +214,16 @@ class SphericalExpansion(CalculatorBase):
         radial_basis,
         gradients,
         cutoff_function,
+        radial_scaling="None",
+        center_atom_weight=1.0,
+        species_weights=None,
+        species_coupling=None,
+        spline_accuracy=None,
+        cell_gradients=False,
     ):
+        if species_weights is None:
+            species_weights = {}
+
         parameters = {
             "cutoff": cutoff,
             "max_radial": max_radial,
@@ -182,6 +231,127 @@ class SphericalExpansion(CalculatorBase):
             "atomic_gaussian_width": atomic_gaussian_width,
             "radial_basis": radial_basis,
             "gradients": gradients,
+            "cell_gradients": cell_gradients,
             "cutoff_function": cutoff_function,
+            "radial_scaling": radial_scaling,
+            "center_atom_weight": center_atom_weight,
+            "species_weights": species_weights,
+            "species_coupling": species_coupling,
+            "spline_accuracy": spline_accuracy,
         }
         super().__init__("spherical_expansion", **parameters)
+
+
+class LodeSphericalExpansion(CalculatorBase):
+    def __init__(
+        self,
+        cutoff,
+        max_radial,
+        max_angular,
+        atomic_gaussian_width,
+        potential_exponent,
+        gradients,
+        k_cutoff=None,
+    ):
+        parameters = {
+            "cutoff": cutoff,
+            "max_radial": max_radial,
+            "max_angular": max_angular,
+            "atomic_gaussian_width": atomic_gaussian_width,
+            "potential_exponent": potential_exponent,
+            "gradients": gradients,
+            "k_cutoff": k_cutoff,
+        }
+        super().__init__("lode_spherical_expansion", **parameters)
+
+
+class SoapPowerSpectrum(CalculatorBase):
+    def __init__(
+        self,
+        cutoff,
+        max_radial,
+        max_angular,
+        atomic_gaussian_width,
+        species,
+        radial_basis,
+        gradients,
+        cutoff_function,
+        spline_accuracy=None,
+    ):
+        parameters = {
+            "cutoff": cutoff,
+            "max_radial": max_radial,
+            "max_angular": max_angular,
+            "atomic_gaussian_width": atomic_gaussian_width,
+            "species": species,
+            "radial_basis": radial_basis,
+            "gradients": gradients,
+            "cutoff_function": cutoff_function,
+            "spline_accuracy": spline_accuracy,
+        }
+        super().__init__("soap_power_spectrum", **parameters)
+
+
+class SoapBispectrum(CalculatorBase):
+    def __init__(
+        self,
+        cutoff,
+        max_radial,
+        max_angular,
+        atomic_gaussian_width,
+        radial_basis,
+        gradients,
+        cutoff_function,
+        spline_accuracy=None,
+    ):
+        parameters = {
+            "cutoff": cutoff,
+            "max_radial": max_radial,
+            "max_angular": max_angular,
+            "atomic_gaussian_width": atomic_gaussian_width,
+            "radial_basis": radial_basis,
+            "gradients": gradients,
+            "cutoff_function": cutoff_function,
+            "spline_accuracy": spline_accuracy,
+        }
+        super().__init__("soap_bispectrum", **parameters)
+
+
+class SoapRadialSpectrum(CalculatorBase):
+    def __init__(
+        self,
+        cutoff,
+        max_radial,
+        atomic_gaussian_width,
+        radial_basis,
+        gradients,
+        cutoff_function,
+        spline_accuracy=None,
+    ):
+        parameters = {
+            "cutoff": cutoff,
+            "max_radial": max_radial,
+            "atomic_gaussian_width": atomic_gaussian_width,
+            "radial_basis": radial_basis,
+            "gradients": gradients,
+            "cutoff_function": cutoff_function,
+            "spline_accuracy": spline_accuracy,
+        }
+        super().__init__("soap_radial_spectrum", **parameters)
+
+
+class SymmetryFunctions(CalculatorBase):
+    def __init__(self, cutoff, cutoff_function, gradients, functions):
+        parameters = {
+            "cutoff": cutoff,
+            "cutoff_function": cutoff_function,
+            "gradients": gradients,
+            "functions": functions,
+        }
+        super().__init__("symmetry_functions", **parameters)
+
+
+class CoulombMatrix(CalculatorBase):
+    def __init__(self, max_atoms, variant):
+        parameters = {"max_atoms": max_atoms, "variant": variant}
+        super().__init__("coulomb_matrix", **parameters)
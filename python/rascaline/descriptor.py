@@ -1,8 +1,12 @@
 # -*- coding: utf-8 -*-
+import ctypes
+from collections import namedtuple
+
 import numpy as np
-from ctypes import c_double, c_char_p, POINTER, ARRAY
+from ctypes import c_double, c_char_p, c_int, c_uint8, POINTER, ARRAY
 
 from ._rascaline import c_uintptr_t, rascal_indexes
+from ._rascaline import rascal_index_type_t, rascal_index_value_t
 from .clib import _get_library
 from .status import _check_rascal_pointer
 
@@ -11,19 +15,23 @@ class Indexes(np.ndarray):
     """
     Small wrapper around `numpy.ndarray` that adds a `names` attribute
     containing the names of the indexes.
+
+    The array uses a structured dtype, with one field for each index. Integer
+    indexes are stored as ``int64`` and floating point indexes as
+    ``float64``.
     """
 
-    def __new__(cls, ptr, shape, names):
-        assert len(shape) == 2
-        assert len(names) == shape[1]
+    def __new__(cls, ptr, count, names, types):
+        assert len(names) == len(types)
 
-        dtype = [(name, np.float64) for name in names]
+        dtype = _indexes_dtype(names, types)
         if ptr is not None:
-            array = np.ctypeslib.as_array(ptr, shape=shape)
+            buffer = ctypes.cast(ptr, POINTER(c_uint8))
+            array = np.ctypeslib.as_array(buffer, shape=(count * dtype.itemsize,))
+            # view the raw memory as a numpy structured array containing
+            # multiple entries
+            array = array.view(dtype=dtype)
             array.flags.writeable = False
-            # view the array as a numpy structured array containing multiple
-            # entries
-            array = array.view(dtype=dtype).reshape((shape[0],))
         else:
             array = np.array([], dtype=dtype)
 
@@ -37,6 +45,21 @@ class Indexes(np.ndarray):
         self.names = getattr(obj, "names", tuple())
 
 
+SparseGradients = namedtuple(
+    "SparseGradients", ["samples", "atoms", "offsets", "features", "values"]
+)
+SparseGradients.__doc__ = """
+Gradients of a descriptor stored as sparse blocks, as computed with
+``sparse_gradients=True``.
+
+Block ``i`` contains the gradients of the sample ``samples[i]`` (i.e. row in
+the values) with respect to the position of the atom ``atoms[i]``. The
+features with non-zero gradients in this block are
+``features[offsets[i]:offsets[i + 1]]``, and the corresponding x/y/z
+gradients are ``values[offsets[i]:offsets[i + 1]]``.
+"""
+
+
 class Descriptor:
     def __init__(self):
         self._lib = _get_library()
@@ -71,10 +94,50 @@ class Descriptor:
             data, (environments.value, features.value), dtype=np.float64
         )
 
+    @property
+    def cell_gradients(self):
+        environments = c_uintptr_t()
+        features = c_uintptr_t()
+        data = POINTER(c_double)()
+        self._lib.rascal_descriptor_cell_gradients(self, data, environments, features)
+
+        if not data:
+            return None
+
+        return np_array_view(
+            data, (environments.value, features.value), dtype=np.float64
+        )
+
+    @property
+    def sparse_gradients(self):
+        samples = POINTER(c_uintptr_t)()
+        atoms = POINTER(c_uintptr_t)()
+        offsets = POINTER(c_uintptr_t)()
+        count = c_uintptr_t()
+        features = POINTER(c_uintptr_t)()
+        values = POINTER(c_double)()
+        non_zero = c_uintptr_t()
+        self._lib.rascal_descriptor_sparse_gradients(
+            self, samples, atoms, offsets, count, features, values, non_zero
+        )
+
+        if not offsets:
+            return None
+
+        count = count.value
+        non_zero = non_zero.value
+        return SparseGradients(
+            samples=np_vector_view(samples, count, dtype=np.uintp),
+            atoms=np_vector_view(atoms, count, dtype=np.uintp),
+            offsets=np_vector_view(offsets, count + 1, dtype=np.uintp),
+            features=np_vector_view(features, non_zero, dtype=np.uintp),
+            values=np_array_view(values, (non_zero, 3), dtype=np.float64),
+        )
+
     def _indexes(self, kind):
         count = c_uintptr_t()
         size = c_uintptr_t()
-        data = POINTER(c_double)()
+        data = POINTER(rascal_index_value_t)()
         self._lib.rascal_descriptor_indexes(self, kind.value, data, count, size)
 
         StringArray = c_char_p * size.value
@@ -82,9 +145,13 @@ class Descriptor:
         self._lib.rascal_descriptor_indexes_names(self, kind.value, names, size)
         names = list(map(lambda n: n.decode("utf8"), names))
 
-        shape = (count.value, size.value)
+        TypesArray = c_int * size.value
+        types = TypesArray()
+        self._lib.rascal_descriptor_indexes_types(self, kind.value, types, size)
+        types = list(map(rascal_index_type_t, types))
+
         ptr = data if count.value != 0 else None
-        return Indexes(ptr=ptr, shape=shape, names=names)
+        return Indexes(ptr=ptr, count=count.value, names=names, types=types)
 
     @property
     def environments(self):
@@ -98,6 +165,10 @@ class Descriptor:
     def gradients_environments(self):
         return self._indexes(rascal_indexes.RASCAL_INDEXES_GRADIENTS)
 
+    @property
+    def cell_gradients_environments(self):
+        return self._indexes(rascal_indexes.RASCAL_INDEXES_CELL_GRADIENTS)
+
     def densify(self, variables):
         if isinstance(variables, str):
             variables = [variables]
@@ -107,6 +178,145 @@ class Descriptor:
             c_variables[i] = v.encode("utf8")
         self._lib.rascal_descriptor_densify(self, c_variables, c_variables._length_)
 
+    def save(self, path):
+        self._lib.rascal_descriptor_save(self, str(path).encode("utf8"))
+
+    @staticmethod
+    def load(path):
+        descriptor = Descriptor()
+        descriptor._lib.rascal_descriptor_load(descriptor, str(path).encode("utf8"))
+        return descriptor
+
+
+class BlockDescriptor:
+    """
+    Descriptor stored as a set of blocks, with one block for each value of
+    the keys. Each block only contains the samples matching its key.
+    """
+
+    def __init__(self):
+        self._lib = _get_library()
+        self._as_parameter_ = self._lib.rascal_block_descriptor()
+        _check_rascal_pointer(self._as_parameter_)
+
+    def __del__(self):
+        self._lib.rascal_block_descriptor_free(self)
+        self._as_parameter_ = 0
+
+    def __len__(self):
+        return len(self.keys)
+
+    @property
+    def keys(self):
+        return _owned_indexes(self._lib, self._lib.rascal_block_descriptor_keys(self))
+
+    def values(self, block):
+        samples = c_uintptr_t()
+        features = c_uintptr_t()
+        data = POINTER(c_double)()
+        self._lib.rascal_block_descriptor_values(self, block, data, samples, features)
+        return np_array_view(data, (samples.value, features.value), dtype=np.float64)
+
+    def gradients(self, block):
+        return self._gradients(self._lib.rascal_block_descriptor_gradients, block)
+
+    def cell_gradients(self, block):
+        return self._gradients(self._lib.rascal_block_descriptor_cell_gradients, block)
+
+    def _gradients(self, function, block):
+        gradients = c_uintptr_t()
+        features = c_uintptr_t()
+        data = POINTER(c_double)()
+        function(self, block, data, gradients, features)
+
+        if not data:
+            return None
+
+        return np_array_view(data, (gradients.value, features.value), dtype=np.float64)
+
+    def _indexes(self, block, kind):
+        ptr = self._lib.rascal_block_descriptor_indexes(self, block, kind.value)
+        return _owned_indexes(self._lib, ptr)
+
+    def samples(self, block):
+        return self._indexes(block, rascal_indexes.RASCAL_INDEXES_ENVIRONMENTS)
+
+    def features(self, block):
+        return self._indexes(block, rascal_indexes.RASCAL_INDEXES_FEATURES)
+
+    def densify(self):
+        descriptor = Descriptor()
+        self._lib.rascal_block_descriptor_densify(self, descriptor)
+        return descriptor
+
+    @staticmethod
+    def from_descriptor(descriptor, variables):
+        if isinstance(variables, str):
+            variables = [variables]
+
+        c_variables = ARRAY(c_char_p, len(variables))()
+        for i, v in enumerate(variables):
+            c_variables[i] = v.encode("utf8")
+
+        blocks = BlockDescriptor()
+        blocks._lib.rascal_block_descriptor_from_descriptor(
+            blocks, descriptor, c_variables, c_variables._length_
+        )
+        return blocks
+
+
+def _owned_indexes(lib, ptr):
+    """
+    Copy the content of the ``rascal_indexes_t`` at ``ptr`` in a new
+    :py:class:`Indexes`, and free ``ptr``.
+    """
+    _check_rascal_pointer(ptr)
+    try:
+        count = c_uintptr_t()
+        size = c_uintptr_t()
+        data = POINTER(rascal_index_value_t)()
+        lib.rascal_indexes_values(ptr, data, count, size)
+
+        StringArray = c_char_p * size.value
+        names = StringArray()
+        lib.rascal_indexes_names(ptr, names, size)
+        names = list(map(lambda n: n.decode("utf8"), names))
+
+        TypesArray = c_int * size.value
+        types = TypesArray()
+        lib.rascal_indexes_types(ptr, types, size)
+        types = list(map(rascal_index_type_t, types))
+
+        data = data if count.value != 0 else None
+        return Indexes(ptr=data, count=count.value, names=names, types=types).copy()
+    finally:
+        lib.rascal_indexes_free(ptr)
+
+
+def _indexes_dtype(names, types):
+    """
+    Get the numpy dtype used to access an array of ``rascal_index_value_t``
+    containing indexes with the given ``names`` and ``types``, skipping over
+    the type tag stored with each value.
+    """
+    formats = []
+    for index_type in types:
+        if index_type == rascal_index_type_t.RASCAL_INDEX_INT64:
+            formats.append(np.int64)
+        else:
+            formats.append(np.float64)
+
+    value_size = ctypes.sizeof(rascal_index_value_t)
+    data_offset = rascal_index_value_t.data.offset
+    return np.dtype(
+        {
+            "names": names,
+            "formats": formats,
+            "offsets": [i * value_size + data_offset for i in range(len(names))],
+            "itemsize": len(names) * value_size,
+        }
+    )
+
 
 def np_array_view(ptr, shape, dtype):
     assert len(shape) == 2
@@ -117,3 +327,12 @@ def np_array_view(ptr, shape, dtype):
     else:
         data = np.array([], dtype=dtype)
         return data.reshape(shape)
+
+
+def np_vector_view(ptr, size, dtype):
+    if size != 0:
+        array = np.ctypeslib.as_array(ptr, shape=(size,))
+        array.flags.writeable = False
+        return array
+    else:
+        return np.array([], dtype=dtype)
@@ -9,9 +9,24 @@ class RascalError(Exception):
         self.status = status
 
 
+# Last exception raised by a Python callback called from the native code,
+# re-raised as the cause of the corresponding RascalError
+LAST_EXCEPTION = None
+
+
+def _save_exception(e):
+    global LAST_EXCEPTION
+    LAST_EXCEPTION = e
+
+
 def _check_rascal_status_t(status):
     if status == rascal_status_t.RASCAL_SUCCESS.value:
         return
+    elif status == rascal_status_t.RASCAL_CALLBACK_ERROR.value:
+        global LAST_EXCEPTION
+        exception = LAST_EXCEPTION
+        LAST_EXCEPTION = None
+        raise RascalError(last_error(), status) from exception
     else:
         raise RascalError(last_error(), status)
 
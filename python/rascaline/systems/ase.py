@@ -40,18 +40,22 @@ if HAVE_ASE:
 
             self._pairs = []
 
-            nl_result = neighborlist.neighbor_list("ijD", self._atoms, cutoff)
-            for (i, j, D) in zip(*nl_result):
+            nl_result = neighborlist.neighbor_list("ijDS", self._atoms, cutoff)
+            for (i, j, D, S) in zip(*nl_result):
                 if j < i:
                     # we want a half neighbor list, so drop all duplicated
                     # neighbors
                     continue
-                self._pairs.append((i, j, D))
+                elif i == j and not _is_positive_shift(S):
+                    # only keep one of the +S/-S periodic self-images
+                    continue
+                self._pairs.append((i, j, D, S))
 
-            self._pairs_by_center = [[]] * self.size()
-            for (i, j, D) in self._pairs:
-                self._pairs_by_center[i].append((i, j, D))
-                self._pairs_by_center[j].append((i, j, D))
+            self._pairs_by_center = [[] for _ in range(self.size())]
+            for (i, j, D, S) in self._pairs:
+                self._pairs_by_center[i].append((i, j, D, S))
+                if i != j:
+                    self._pairs_by_center[j].append((i, j, D, S))
 
         def pairs(self):
             return self._pairs
@@ -60,6 +64,19 @@ if HAVE_ASE:
             return self._pairs_by_center[center]
 
 
+    def _is_positive_shift(shift):
+        """
+        Does the cell ``shift`` belong to the "positive" half of all possible
+        shifts?
+        """
+        if shift[0] != 0:
+            return shift[0] > 0
+        elif shift[1] != 0:
+            return shift[1] > 0
+        else:
+            return shift[2] > 0
+
+
 else:
 
     class AseSystem(SystemBase):
@@ -3,7 +3,8 @@ import numpy as np
 import ctypes
 from ctypes import POINTER, pointer, c_void_p, c_double
 
-from .._rascaline import rascal_system_t, rascal_pair_t, c_uintptr_t
+from .._rascaline import rascal_system_t, rascal_pair_t, rascal_status_t, c_uintptr_t
+from ..status import _save_exception
 
 
 class SystemBase:
@@ -47,9 +48,21 @@ class SystemBase:
         raise NotImplementedError("System.compute_neighbors method is not implemented")
 
     def pairs(self):
+        """
+        Get all the pairs in this system, as a list of ``(i, j, vector,
+        cell_shift)`` tuples, where ``i`` and ``j`` are the indexes of the two
+        atoms, ``vector`` is the 3 components vector from ``i`` to ``j``, and
+        ``cell_shift`` the 3 integers multiples of the cell vectors applied to
+        ``j`` to get this pair. Tuples without ``cell_shift`` are accepted, and
+        use a zero shift.
+        """
         raise NotImplementedError("System.pairs method is not implemented")
 
     def pairs_containing(self, center):
+        """
+        Get all the pairs containing the atom ``center``, with the same
+        ``(i, j, vector, cell_shift)`` tuples as :py:meth:`SystemBase.pairs`.
+        """
         raise NotImplementedError("System.pairs_containing method is not implemented")
 
 
@@ -59,33 +72,71 @@ def _get_self(ptr):
     return self
 
 
+def _catch_exceptions(function):
+    """
+    Wrap a callback called from the native code, saving any exception it
+    raises and returning the corresponding status code instead, since
+    exceptions can not propagate through the native code.
+    """
+
+    def inner(*args):
+        try:
+            function(*args)
+        except Exception as e:
+            _save_exception(e)
+            return rascal_status_t.RASCAL_CALLBACK_ERROR.value
+        return rascal_status_t.RASCAL_SUCCESS.value
+
+    return inner
+
+
+@_catch_exceptions
 def _size_cb(user_data, size):
     size[0] = _get_self(user_data).size()
 
 
+@_catch_exceptions
 def _species_cb(user_data, data):
     self = _get_self(user_data)
 
     species = np.array(self.species(), dtype=c_uintptr_t)
+    if species.shape != (self.size(),):
+        raise ValueError(
+            "System.species() must return one value for each atom, "
+            f"expected shape ({self.size()},), got {species.shape}"
+        )
+
     data[0] = species.ctypes.data
     self._keepalive["species"] = species
 
 
+@_catch_exceptions
 def _positions_cb(user_data, data):
     self = _get_self(user_data)
     positions = np.array(self.positions(), dtype=c_double)
 
-    assert len(positions.shape) == 2
-    assert positions.shape[1] == 3
+    size = self.size()
+    if size == 0 and positions.size == 0:
+        positions = positions.reshape((0, 3))
+
+    if positions.shape != (size, 3):
+        raise ValueError(
+            "System.positions() must return three values for each atom, "
+            f"expected shape ({size}, 3), got {positions.shape}"
+        )
 
     data[0] = positions.ctypes.data
     self._keepalive["positions"] = positions
 
 
+@_catch_exceptions
 def _cell_cb(user_data, data):
     self = _get_self(user_data)
     cell = np.array(self.cell(), dtype=c_double)
-    assert len(cell) == 9
+    if cell.shape != (9,):
+        raise ValueError(
+            f"System.cell() must return 9 values, got an array with shape {cell.shape}"
+        )
 
     data[0] = cell[0]
     data[1] = cell[1]
@@ -98,25 +149,49 @@ def _cell_cb(user_data, data):
     data[8] = cell[8]
 
 
+@_catch_exceptions
 def _compute_neighbors_cb(user_data, cutoff):
     self = _get_self(user_data)
     self.compute_neighbors(float(cutoff))
 
 
+def _pairs_array(pairs, method):
+    """
+    Convert the ``pairs`` returned by ``System.<method>()`` to an array of
+    ``rascal_pair_t``, using a zero ``cell_shift`` for ``(i, j, vector)``
+    tuples.
+    """
+    converted = []
+    for pair in pairs:
+        pair = tuple(pair)
+        if len(pair) == 3:
+            pair = pair + ((0, 0, 0),)
+        elif len(pair) != 4:
+            raise TypeError(
+                f"System.{method}() must return (i, j, vector, cell_shift) "
+                f"tuples, got a tuple with {len(pair)} elements"
+            )
+        converted.append(pair)
+
+    return np.array(converted, dtype=rascal_pair_t)
+
+
+@_catch_exceptions
 def _pairs_cb(user_data, data, count):
     self = _get_self(user_data)
 
-    pairs = np.array(self.pairs(), dtype=rascal_pair_t)
+    pairs = _pairs_array(self.pairs(), "pairs")
 
     count[0] = c_uintptr_t(len(pairs))
     data[0] = pairs.ctypes.data
     self._keepalive["pairs"] = pairs
 
 
+@_catch_exceptions
 def _pairs_containing_cb(user_data, center, data, count):
     self = _get_self(user_data)
 
-    pairs = np.array(self.pairs_containing(center), dtype=rascal_pair_t)
+    pairs = _pairs_array(self.pairs_containing(center), "pairs_containing")
 
     count[0] = c_uintptr_t(len(pairs))
     data[0] = pairs.ctypes.data
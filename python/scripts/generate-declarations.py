@@ -23,8 +23,10 @@ class Function:
 
 
 class Struct:
-    def __init__(self, name):
+    def __init__(self, name, kind="Structure"):
         self.name = name
+        # either "Structure" or "Union", to use the corresponding ctypes class
+        self.kind = kind
         self.members = {}
 
     def add_member(self, name, type):
@@ -66,8 +68,11 @@ class FuncDefVisitor(c_ast.NodeVisitor):
                 enum.add_value(enumerator.name, enumerator.value.value)
             self.enums.append(enum)
 
-        elif isinstance(node.type.type, c_ast.Struct):
-            struct = Struct(node.name)
+        elif isinstance(node.type.type, (c_ast.Struct, c_ast.Union)):
+            if isinstance(node.type.type, c_ast.Union):
+                struct = Struct(node.name, kind="Union")
+            else:
+                struct = Struct(node.name)
             for _, member in node.type.type.children():
                 struct.add_member(member.name, member.type)
 
@@ -86,12 +91,14 @@ def parse(file):
 def c_type_name(name):
     if name.startswith("rascal_"):
         # enums are represente as int
-        if name == "rascal_indexes":
+        if name in ["rascal_indexes", "rascal_index_type_t"]:
             return "ctypes.c_int"
         else:
             return name
     elif name == "uintptr_t":
         return "c_uintptr_t"
+    elif name in ["int32_t", "int64_t"]:
+        return "ctypes.c_" + name[:-2]
     elif name == "void":
         return "None"
     else:
@@ -100,7 +107,7 @@ def c_type_name(name):
 
 def _typedecl_name(type):
     assert isinstance(type, c_ast.TypeDecl)
-    if isinstance(type.type, c_ast.Struct):
+    if isinstance(type.type, (c_ast.Struct, c_ast.Union)):
         return type.type.name
     elif isinstance(type.type, c_ast.Enum):
         return type.type.name
@@ -134,6 +141,9 @@ def type_to_ctypes(type, ndpointer=False):
 
         elif isinstance(type.type, c_ast.FuncDecl):
             restype = type_to_ctypes(type.type.type, ndpointer)
+            if restype == "rascal_status_t":
+                # enums are represented as int
+                restype = "ctypes.c_int"
             args = [type_to_ctypes(t.type, ndpointer) for t in type.type.args.params]
 
             return f'CFUNCTYPE({restype}, {", ".join(args)})'
@@ -162,7 +172,7 @@ def generate_enums(file, enums):
 
 def generate_structs(file, structs):
     for struct in structs:
-        file.write(f"\n\nclass {struct.name}(ctypes.Structure):\n")
+        file.write(f"\n\nclass {struct.name}(ctypes.{struct.kind}):\n")
         if len(struct.members) == 0:
             file.write("    pass\n")
             continue
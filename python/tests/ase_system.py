@@ -73,3 +73,24 @@ if HAVE_ASE:
 
             self.assertEqual(pairs[2][:2], (1, 2))
             self.assertTrue(np.all(pairs[2][2] == [0, 0, -3.0]))
+
+        def test_periodic_images(self):
+            atoms = ase.Atoms(
+                "C",
+                positions=[(0, 0, 0)],
+                cell=[2, 10, 10],
+                pbc=True,
+            )
+
+            system = AseSystem(atoms)
+            system.compute_neighbors(2.5)
+            pairs = system.pairs()
+            self.assertEqual(len(pairs), 1)
+            self.assertEqual(pairs[0][:2], (0, 0))
+            self.assertTrue(np.all(pairs[0][2] == [2, 0, 0]))
+            self.assertTrue(np.all(pairs[0][3] == [1, 0, 0]))
+
+            system.compute_neighbors(4.5)
+            pairs = system.pairs()
+            self.assertEqual(len(pairs), 2)
+            self.assertEqual(len(system.pairs_containing(0)), 2)
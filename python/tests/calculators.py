@@ -4,6 +4,8 @@ import numpy as np
 
 from rascaline import SortedDistances
 from rascaline.calculator import DummyCalculator
+from rascaline.status import RascalError
+from rascaline._rascaline import rascal_status_t
 
 from test_systems import TestSystem
 
@@ -58,6 +60,30 @@ class TestDummyCalculator(unittest.TestCase):
         for i in range(gradients.shape[0]):
             self.assertTrue(np.all(gradients[i] == (0, 1)))
 
+    def test_compute_parallel(self):
+        system = TestSystem()
+        calculator = DummyCalculator(cutoff=3.2, delta=2, name="", gradients=True)
+        expected = calculator.compute(system)
+        descriptor = calculator.compute(system, threads=2)
+
+        self.assertTrue(np.all(descriptor.environments == expected.environments))
+        self.assertTrue(np.all(descriptor.values == expected.values))
+        self.assertTrue(np.all(descriptor.gradients == expected.gradients))
+
+    def test_compute_sparse_gradients(self):
+        system = TestSystem()
+        calculator = DummyCalculator(cutoff=3.2, delta=2, name="", gradients=True)
+        descriptor = calculator.compute(system, sparse_gradients=True)
+        self.assertEqual(descriptor.gradients, None)
+
+        # the dense gradients contain 6 blocks, all set to (0, 1)
+        sparse = descriptor.sparse_gradients
+        self.assertEqual(len(sparse.samples), 6)
+        self.assertEqual(len(sparse.atoms), 6)
+        self.assertTrue(np.all(sparse.offsets == np.arange(7)))
+        self.assertTrue(np.all(sparse.features == 1))
+        self.assertTrue(np.all(sparse.values == 1.0))
+
     def test_compute_partial_samples(self):
         system = TestSystem()
         calculator = DummyCalculator(cutoff=3.2, delta=2, name="", gradients=True)
@@ -92,6 +118,16 @@ class TestDummyCalculator(unittest.TestCase):
         for i in range(gradients.shape[0]):
             self.assertTrue(np.all(gradients[i] == (0, 1)))
 
+        # Integer indexes must be selected with integer values
+        samples = [(0, 0.5)]
+        with self.assertRaises(RascalError) as cm:
+            calculator.compute(system, selected_samples=samples)
+
+        self.assertEqual(
+            str(cm.exception),
+            "invalid parameter: can not convert 0.5 to integer for the 'center' index",
+        )
+
     def test_compute_partial_features(self):
         system = TestSystem()
         calculator = DummyCalculator(cutoff=3.2, delta=2, name="", gradients=True)
@@ -129,6 +165,80 @@ class TestDummyCalculator(unittest.TestCase):
         for i in range(gradients.shape[0]):
             self.assertTrue(np.all(gradients[i] == [0]))
 
+        # Invalid selected features
+        features = [[1, 0, 4.2]]
+        with self.assertRaises(RascalError) as cm:
+            calculator.compute(system, selected_features=features)
+
+        self.assertEqual(
+            cm.exception.status, rascal_status_t.RASCAL_INVALID_PARAMETER_ERROR.value
+        )
+        self.assertEqual(
+            str(cm.exception), "invalid parameter: [1, 0, 4.2] is not a valid feature"
+        )
+
+    def test_errors_in_system(self):
+        class BadPositions(TestSystem):
+            def positions(self):
+                return [[0, 0, 0], [0, 0, 1], [0, 0, 2]]
+
+        class CallbackError(TestSystem):
+            def pairs(self):
+                raise ZeroDivisionError("oops")
+
+        calculator = DummyCalculator(cutoff=3.2, delta=2, name="", gradients=True)
+
+        with self.assertRaises(RascalError) as cm:
+            calculator.compute(BadPositions())
+
+        self.assertEqual(cm.exception.status, rascal_status_t.RASCAL_CALLBACK_ERROR.value)
+        self.assertIsInstance(cm.exception.__cause__, ValueError)
+        self.assertEqual(
+            str(cm.exception.__cause__),
+            "System.positions() must return three values for each atom, "
+            "expected shape (4, 3), got (3, 3)",
+        )
+
+        with self.assertRaises(RascalError) as cm:
+            calculator.compute(CallbackError())
+
+        self.assertEqual(cm.exception.status, rascal_status_t.RASCAL_CALLBACK_ERROR.value)
+        self.assertEqual(
+            str(cm.exception),
+            "error in callback: call to rascal_system_t.pairs failed "
+            "with status RASCAL_CALLBACK_ERROR",
+        )
+        self.assertIsInstance(cm.exception.__cause__, ZeroDivisionError)
+
+    def test_pairs_without_cell_shift(self):
+        class NoCellShift(TestSystem):
+            def pairs(self):
+                return [pair[:3] for pair in super().pairs()]
+
+            def pairs_containing(self, center):
+                return [pair[:3] for pair in super().pairs_containing(center)]
+
+        class BadPairs(TestSystem):
+            def pairs(self):
+                return [pair[:2] for pair in super().pairs()]
+
+        calculator = DummyCalculator(cutoff=3.2, delta=2, name="", gradients=True)
+        expected = calculator.compute(TestSystem())
+        descriptor = calculator.compute(NoCellShift())
+
+        self.assertTrue(np.all(descriptor.values == expected.values))
+        self.assertTrue(np.all(descriptor.gradients == expected.gradients))
+
+        with self.assertRaises(RascalError) as cm:
+            calculator.compute(BadPairs())
+
+        self.assertIsInstance(cm.exception.__cause__, TypeError)
+        self.assertEqual(
+            str(cm.exception.__cause__),
+            "System.pairs() must return (i, j, vector, cell_shift) tuples, "
+            "got a tuple with 2 elements",
+        )
+
 
 class TestSortedDistances(unittest.TestCase):
     def test_name(self):
@@ -138,5 +248,6 @@ class TestSortedDistances(unittest.TestCase):
     def test_parameters(self):
         calculator = SortedDistances(cutoff=3.5, max_neighbors=12)
         self.assertEqual(
-            calculator.parameters(), """{"cutoff": 3.5, "max_neighbors": 12}"""
+            calculator.parameters(),
+            """{"cutoff": 3.5, "max_neighbors": 12, "cell_gradients": false}""",
         )
@@ -1,9 +1,11 @@
 # -*- coding: utf-8 -*-
+import os
+import tempfile
 import unittest
 import numpy as np
 
-from rascaline import Descriptor
-from rascaline.calculator import DummyCalculator
+from rascaline import Descriptor, BlockDescriptor
+from rascaline.calculator import DummyCalculator, SortedDistances
 
 from test_systems import TestSystem, EmptySystem
 
@@ -17,6 +19,10 @@ class TestEmptyDescriptor(unittest.TestCase):
         descriptor = Descriptor()
         self.assertEqual(descriptor.gradients, None)
 
+    def test_sparse_gradients(self):
+        descriptor = Descriptor()
+        self.assertEqual(descriptor.sparse_gradients, None)
+
     def test_environments(self):
         descriptor = Descriptor()
         self.assertEqual(len(descriptor.environments), 0)
@@ -29,6 +35,11 @@ class TestEmptyDescriptor(unittest.TestCase):
         descriptor = Descriptor()
         self.assertEqual(len(descriptor.gradients_environments), 0)
 
+    def test_cell_gradients(self):
+        descriptor = Descriptor()
+        self.assertEqual(descriptor.cell_gradients, None)
+        self.assertEqual(len(descriptor.cell_gradients_environments), 0)
+
 
 class TestDummyDescriptor(unittest.TestCase):
     def test_values(self):
@@ -83,16 +94,13 @@ class TestDummyDescriptor(unittest.TestCase):
         self.assertTrue(np.all(environments["structure"] == [0, 0, 0, 0]))
         self.assertTrue(np.all(environments["center"] == [0, 1, 2, 3]))
 
-        # view & reshape for easier direct comparison of values
-        # numpy only consider structured arrays to be equal if they have
-        # the same dtype
-        environments = environments.view(dtype=np.float64).reshape(
-            (environments.shape[0], -1)
-        )
-        self.assertTrue(np.all(environments[0] == [0, 0]))
-        self.assertTrue(np.all(environments[1] == [0, 1]))
-        self.assertTrue(np.all(environments[2] == [0, 2]))
-        self.assertTrue(np.all(environments[3] == [0, 3]))
+        self.assertEqual(environments.dtype["structure"], np.int64)
+        self.assertEqual(environments.dtype["center"], np.int64)
+
+        self.assertEqual(tuple(environments[0]), (0, 0))
+        self.assertEqual(tuple(environments[1]), (0, 1))
+        self.assertEqual(tuple(environments[2]), (0, 2))
+        self.assertEqual(tuple(environments[3]), (0, 3))
 
     def test_gradient_indexes(self):
         system = TestSystem()
@@ -120,31 +128,24 @@ class TestDummyDescriptor(unittest.TestCase):
         expected = [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2]
         self.assertTrue(np.all(gradients_environments["spatial"] == expected))
 
-        # view & reshape for easier direct comparison of values
-        # numpy only consider structured arrays to be equal if they have
-        # the same dtype
-        gradients_environments = gradients_environments.view(dtype=np.float64).reshape(
-            (gradients_environments.shape[0], -1)
-        )
-
-        self.assertTrue(np.all(gradients_environments[0] == [0, 0, 1, 0]))
-        self.assertTrue(np.all(gradients_environments[1] == [0, 0, 1, 1]))
-        self.assertTrue(np.all(gradients_environments[2] == [0, 0, 1, 2]))
-        self.assertTrue(np.all(gradients_environments[3] == [0, 1, 0, 0]))
-        self.assertTrue(np.all(gradients_environments[4] == [0, 1, 0, 1]))
-        self.assertTrue(np.all(gradients_environments[5] == [0, 1, 0, 2]))
-        self.assertTrue(np.all(gradients_environments[6] == [0, 1, 2, 0]))
-        self.assertTrue(np.all(gradients_environments[7] == [0, 1, 2, 1]))
-        self.assertTrue(np.all(gradients_environments[8] == [0, 1, 2, 2]))
-        self.assertTrue(np.all(gradients_environments[9] == [0, 2, 1, 0]))
-        self.assertTrue(np.all(gradients_environments[10] == [0, 2, 1, 1]))
-        self.assertTrue(np.all(gradients_environments[11] == [0, 2, 1, 2]))
-        self.assertTrue(np.all(gradients_environments[12] == [0, 2, 3, 0]))
-        self.assertTrue(np.all(gradients_environments[13] == [0, 2, 3, 1]))
-        self.assertTrue(np.all(gradients_environments[14] == [0, 2, 3, 2]))
-        self.assertTrue(np.all(gradients_environments[15] == [0, 3, 2, 0]))
-        self.assertTrue(np.all(gradients_environments[16] == [0, 3, 2, 1]))
-        self.assertTrue(np.all(gradients_environments[17] == [0, 3, 2, 2]))
+        self.assertEqual(tuple(gradients_environments[0]), (0, 0, 1, 0))
+        self.assertEqual(tuple(gradients_environments[1]), (0, 0, 1, 1))
+        self.assertEqual(tuple(gradients_environments[2]), (0, 0, 1, 2))
+        self.assertEqual(tuple(gradients_environments[3]), (0, 1, 0, 0))
+        self.assertEqual(tuple(gradients_environments[4]), (0, 1, 0, 1))
+        self.assertEqual(tuple(gradients_environments[5]), (0, 1, 0, 2))
+        self.assertEqual(tuple(gradients_environments[6]), (0, 1, 2, 0))
+        self.assertEqual(tuple(gradients_environments[7]), (0, 1, 2, 1))
+        self.assertEqual(tuple(gradients_environments[8]), (0, 1, 2, 2))
+        self.assertEqual(tuple(gradients_environments[9]), (0, 2, 1, 0))
+        self.assertEqual(tuple(gradients_environments[10]), (0, 2, 1, 1))
+        self.assertEqual(tuple(gradients_environments[11]), (0, 2, 1, 2))
+        self.assertEqual(tuple(gradients_environments[12]), (0, 2, 3, 0))
+        self.assertEqual(tuple(gradients_environments[13]), (0, 2, 3, 1))
+        self.assertEqual(tuple(gradients_environments[14]), (0, 2, 3, 2))
+        self.assertEqual(tuple(gradients_environments[15]), (0, 3, 2, 0))
+        self.assertEqual(tuple(gradients_environments[16]), (0, 3, 2, 1))
+        self.assertEqual(tuple(gradients_environments[17]), (0, 3, 2, 2))
 
     def test_features(self):
         system = TestSystem()
@@ -160,12 +161,12 @@ class TestDummyDescriptor(unittest.TestCase):
         self.assertTrue(np.all(features["index_delta"] == [1, 0]))
         self.assertTrue(np.all(features["x_y_z"] == [0, 1]))
 
-        # view & reshape for easier direct comparison of values
-        # numpy only consider structured arrays to be equal if they have
-        # the same dtype
-        features = features.view(dtype=np.float64).reshape((features.shape[0], -1))
-        self.assertTrue(np.all(features[0] == [1, 0, 1.2]))
-        self.assertTrue(np.all(features[1] == [0, 1, 3.2]))
+        self.assertEqual(features.dtype["index_delta"], np.int64)
+        self.assertEqual(features.dtype["x_y_z"], np.int64)
+        self.assertEqual(features.dtype["float"], np.float64)
+
+        self.assertEqual(tuple(features[0]), (1, 0, 1.2))
+        self.assertEqual(tuple(features[1]), (0, 1, 3.2))
 
     def test_densify(self):
         system = TestSystem()
@@ -179,3 +180,105 @@ class TestDummyDescriptor(unittest.TestCase):
 
         self.assertEqual(descriptor.values.shape, (1, 8))
         self.assertEqual(descriptor.gradients.shape, (12, 8))
+
+    def test_save_load(self):
+        system = TestSystem()
+        calculator = DummyCalculator(cutoff=3.2, delta=12, name="", gradients=True)
+        descriptor = calculator.compute(system)
+
+        with tempfile.TemporaryDirectory() as directory:
+            path = os.path.join(directory, "descriptor.npz")
+            descriptor.save(path)
+
+            data = np.load(path)
+            self.assertTrue(np.all(data["values"] == descriptor.values))
+            self.assertEqual(data["environments"].dtype.names, ("structure", "center"))
+            self.assertEqual(data["environments"].dtype["center"], np.int64)
+            self.assertEqual(data["features"].dtype["float"], np.float64)
+
+            loaded = Descriptor.load(path)
+
+        self.assertTrue(np.all(loaded.values == descriptor.values))
+        self.assertTrue(np.all(loaded.gradients == descriptor.gradients))
+        self.assertTrue(np.all(loaded.environments == descriptor.environments))
+        self.assertEqual(loaded.features.names, descriptor.features.names)
+
+
+class TestCellGradients(unittest.TestCase):
+    def test_cell_gradients(self):
+        system = TestSystem()
+        calculator = SortedDistances(cutoff=3.2, max_neighbors=2, cell_gradients=True)
+        descriptor = calculator.compute(system)
+
+        environments = descriptor.environments
+        cell_gradients = descriptor.cell_gradients
+        self.assertEqual(cell_gradients.shape, (9 * len(environments), 2))
+
+        indexes = descriptor.cell_gradients_environments
+        self.assertEqual(len(indexes), 9 * len(environments))
+        self.assertEqual(
+            indexes.names,
+            (
+                "structure",
+                "center",
+                "species_center",
+                "species_neighbor",
+                "cell_vector",
+                "spatial",
+            ),
+        )
+
+        # all pairs are along z, with a length of 1 in a cubic cell of size 10
+        for i in range(len(environments)):
+            expected = np.zeros((9,))
+            expected[8] = 0.1
+            self.assertTrue(np.allclose(cell_gradients[9 * i : 9 * (i + 1), 0], expected))
+
+
+class TestBlockDescriptor(unittest.TestCase):
+    def test_compute_blocks(self):
+        system = TestSystem()
+        calculator = DummyCalculator(cutoff=3.2, delta=12, name="", gradients=True)
+        blocks = calculator.compute_blocks(system, "center")
+
+        keys = blocks.keys
+        self.assertEqual(keys.names, ("center",))
+        self.assertEqual(len(blocks), 4)
+        self.assertTrue(np.all(keys["center"] == (0, 1, 2, 3)))
+
+        for i in range(len(blocks)):
+            samples = blocks.samples(i)
+            self.assertEqual(samples.names, ("structure",))
+            self.assertEqual(len(samples), 1)
+
+            self.assertEqual(blocks.values(i).shape, (1, 2))
+            self.assertEqual(blocks.features(i).names, ("index_delta", "x_y_z", "float"))
+            self.assertEqual(blocks.gradients(i).shape[1], 2)
+            self.assertIsNone(blocks.cell_gradients(i))
+
+    def test_densify(self):
+        system = TestSystem()
+        calculator = DummyCalculator(cutoff=3.2, delta=12, name="", gradients=True)
+        blocks = calculator.compute_blocks(system, "center")
+        densified = blocks.densify()
+
+        descriptor = calculator.compute(system)
+        descriptor.densify("center")
+
+        self.assertTrue(np.all(densified.values == descriptor.values))
+        self.assertTrue(np.all(densified.gradients == descriptor.gradients))
+        self.assertTrue(np.all(densified.environments == descriptor.environments))
+        self.assertTrue(np.all(densified.features == descriptor.features))
+
+    def test_from_descriptor(self):
+        system = TestSystem()
+        calculator = DummyCalculator(cutoff=3.2, delta=12, name="", gradients=True)
+        descriptor = calculator.compute(system)
+
+        blocks = BlockDescriptor.from_descriptor(descriptor, "center")
+        self.assertEqual(len(blocks), 4)
+        self.assertTrue(np.all(blocks.values(2) == descriptor.values[2:3]))
+
+        message = "there is no 'foo' index in these indexes"
+        with self.assertRaisesRegex(Exception, message):
+            _ = BlockDescriptor.from_descriptor(descriptor, "foo")
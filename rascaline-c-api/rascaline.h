@@ -6,10 +6,27 @@
 #include <stdint.h>
 #include <stdlib.h>
 
+/*
+ Type of the values in a single index
+ */
+typedef enum rascal_index_type_t {
+  /*
+   The index contains signed 64-bit integers, stored in
+   `rascal_index_data_t.int64`
+   */
+  RASCAL_INDEX_INT64 = 0,
+  /*
+   The index contains 64-bit floating point values, stored in
+   `rascal_index_data_t.float64`
+   */
+  RASCAL_INDEX_FLOAT64 = 1,
+} rascal_index_type_t;
+
 typedef enum rascal_indexes {
   RASCAL_INDEXES_FEATURES = 0,
   RASCAL_INDEXES_ENVIRONMENTS = 1,
   RASCAL_INDEXES_GRADIENTS = 2,
+  RASCAL_INDEXES_CELL_GRADIENTS = 3,
 } rascal_indexes;
 
 /*
@@ -32,6 +49,18 @@ typedef enum rascal_status_t {
    A string contains non-utf8 data
    */
   RASCAL_UTF8_ERROR = 3,
+  /*
+   A system contains invalid data
+   */
+  RASCAL_SYSTEM_ERROR = 4,
+  /*
+   A user-provided callback returned an error
+   */
+  RASCAL_CALLBACK_ERROR = 5,
+  /*
+   There was an error reading or writing a file
+   */
+  RASCAL_IO_ERROR = 6,
   /*
    There was an error of unknown kind
    */
@@ -42,6 +71,12 @@ typedef enum rascal_status_t {
   RASCAL_INTERNAL_PANIC = 255,
 } rascal_status_t;
 
+/*
+ Opaque type representing a descriptor stored as a set of blocks, with one
+ block for each value of the keys.
+ */
+typedef struct rascal_block_descriptor_t rascal_block_descriptor_t;
+
 /*
  Opaque type representing a Calculator
  */
@@ -52,6 +87,41 @@ typedef struct rascal_calculator_t rascal_calculator_t;
  */
 typedef struct rascal_descriptor_t rascal_descriptor_t;
 
+/*
+ Opaque type representing a set of indexes, i.e. a list of entries where
+ each entry contains the values of multiple named indexes.
+ */
+typedef struct rascal_indexes_t rascal_indexes_t;
+
+/*
+ Storage for a single index value, the field to use depends on the type of
+ the value
+ */
+typedef union rascal_index_data_t {
+  /*
+   Value for `RASCAL_INDEX_INT64` indexes
+   */
+  int64_t int64;
+  /*
+   Value for `RASCAL_INDEX_FLOAT64` indexes
+   */
+  double float64;
+} rascal_index_data_t;
+
+/*
+ A single value in a set of indexes, together with its type
+ */
+typedef struct rascal_index_value_t {
+  /*
+   type of the value, all values of a given index have the same type
+   */
+  enum rascal_index_type_t index_type;
+  /*
+   the value itself
+   */
+  union rascal_index_data_t data;
+} rascal_index_value_t;
+
 /*
  Pair of atoms coming from a neighbor list
  */
@@ -69,12 +139,25 @@ typedef struct rascal_pair_t {
    cell as required by periodic boundary conditions.
    */
   double vector[3];
+  /*
+   number of unit cell vectors (along a, b and c) that must be added to
+   `positions[second] - positions[first]` to get `vector`. This
+   distinguishes between different periodic images of the same pair of
+   atoms.
+   */
+  int32_t cell_shift[3];
 } rascal_pair_t;
 
 /*
  A `rascal_system_t` deals with the storage of atoms and related information,
  as well as the computation of neighbor lists. This structures allow to
  implement the rust `System` trait using function pointer.
+
+ All the function pointers should return `RASCAL_SUCCESS` if the call
+ succeeded, and any other `rascal_status_t` value to signal an error. In
+ the latter case, the error is propagated back to the caller of the
+ function using this system (e.g. `rascal_calculator_compute`), which will
+ return `RASCAL_CALLBACK_ERROR`.
  */
 typedef struct rascal_system_t {
   /*
@@ -85,43 +168,50 @@ typedef struct rascal_system_t {
   /*
    This function should set `*size` to the number of atoms in this system
    */
-  void (*size)(const void *user_data, uintptr_t *size);
+  enum rascal_status_t (*size)(const void *user_data, uintptr_t *size);
   /*
    This function should set `*species` to a pointer to the first element of
    a contiguous array containing the atomic species. Each different atomic
    species should be identified with a different value. These values are
    usually the atomic number, but don't have to be.
    */
-  void (*species)(const void *user_data, const uintptr_t **species);
+  enum rascal_status_t (*species)(const void *user_data, const uintptr_t **species);
   /*
    This function should set `*positions` to a pointer to the first element
    of a contiguous array containing the atomic cartesian coordinates.
    `positions[0], positions[1], positions[2]` must contain the x, y, z
    cartesian coordinates of the first atom, and so on.
    */
-  void (*positions)(const void *user_data, const double **positions);
+  enum rascal_status_t (*positions)(const void *user_data, const double **positions);
   /*
    This function should write the unit cell matrix in `cell`, which have
    space for 9 values.
    */
-  void (*cell)(const void *user_data, double *cell);
+  enum rascal_status_t (*cell)(const void *user_data, double *cell);
   /*
    This function should compute the neighbor list with the given cutoff,
    and store it for later access using `pairs` or `pairs_containing`.
    */
-  void (*compute_neighbors)(void *user_data, double cutoff);
+  enum rascal_status_t (*compute_neighbors)(void *user_data, double cutoff);
   /*
    This function should set `*pairs` to a pointer to the first element of a
    contiguous array containing all pairs in this system; and `*count` to
    the size of the array/the number of pairs.
 
    This list of pair should only contain each pair once (and not twice as
-   `i-j` and `j-i`), should not contain self pairs (`i-i`); and should only
-   contains pairs where the distance between atoms is actually bellow the
-   cutoff passed in the last call to `compute_neighbors`. This function is
-   only valid to call after a call to `compute_neighbors`.
+   `i-j` and `j-i`); and should only contains pairs where the distance
+   between atoms is actually bellow the cutoff passed in the last call to
+   `compute_neighbors`. This function is only valid to call after a call
+   to `compute_neighbors`.
+
+   In periodic systems with small cells, the same `i-j` pair can appear
+   multiple times with different `cell_shift`, once for each periodic
+   image of `j` inside the cutoff. Pairs between an atom and its own
+   periodic images (`i-i` with a non-zero `cell_shift`) are allowed, but
+   only one of the `+cell_shift` and `-cell_shift` images should be
+   included.
    */
-  void (*pairs)(const void *user_data, const struct rascal_pair_t **pairs, uintptr_t *count);
+  enum rascal_status_t (*pairs)(const void *user_data, const struct rascal_pair_t **pairs, uintptr_t *count);
   /*
    This function should set `*pairs` to a pointer to the first element of a
    contiguous array containing all pairs in this system containing the atom
@@ -131,9 +221,10 @@ typedef struct rascal_system_t {
    The same restrictions on the list of pairs as `rascal_system_t::pairs`
    applies, with the additional condition that the pair `i-j` should be
    included both in the return of `pairs_containing(i)` and
-   `pairs_containing(j)`.
+   `pairs_containing(j)`. Self pairs `i-i` should be included only once in
+   `pairs_containing(i)`.
    */
-  void (*pairs_containing)(const void *user_data, uintptr_t center, const struct rascal_pair_t **pairs, uintptr_t *count);
+  enum rascal_status_t (*pairs_containing)(const void *user_data, uintptr_t center, const struct rascal_pair_t **pairs, uintptr_t *count);
 } rascal_system_t;
 
 typedef struct rascal_calculation_options_t {
@@ -144,7 +235,8 @@ typedef struct rascal_calculation_options_t {
   bool use_native_system;
   /*
    List of samples on which to run the calculation. Use `NULL` to run the
-   calculation on all samples.
+   calculation on all samples. The values are converted to the type of
+   the corresponding index, and must be integers for integer indexes.
    */
   const double *selected_samples;
   /*
@@ -154,7 +246,8 @@ typedef struct rascal_calculation_options_t {
   uintptr_t selected_samples_count;
   /*
    List of features on which to run the calculation. Use `NULL` to run the
-   calculation on all features.
+   calculation on all features. The values are converted to the type of
+   the corresponding index, and must be integers for integer indexes.
    */
   const double *selected_features;
   /*
@@ -162,6 +255,18 @@ typedef struct rascal_calculation_options_t {
    selected_features array
    */
   uintptr_t selected_features_count;
+  /*
+   Number of threads to use for the calculation. Use 1 to run the
+   calculation sequentially, and 0 to use as many threads as there are
+   CPU cores.
+   */
+  uintptr_t threads;
+  /*
+   Store the gradients as sparse gradients (see
+   `rascal_descriptor_sparse_gradients`) instead of dense gradients. The
+   calculation is then done one system at a time.
+   */
+  bool sparse_gradients;
 } rascal_calculation_options_t;
 
 #ifdef __cplusplus
@@ -187,12 +292,58 @@ enum rascal_status_t rascal_descriptor_gradients(const struct rascal_descriptor_
                                                  uintptr_t *environments,
                                                  uintptr_t *features);
 
+enum rascal_status_t rascal_descriptor_cell_gradients(const struct rascal_descriptor_t *descriptor,
+                                                      const double **data,
+                                                      uintptr_t *environments,
+                                                      uintptr_t *features);
+
+/*
+ Get the sparse gradients in the `descriptor`, as computed with the
+ `sparse_gradients` calculation option.
+
+ The gradients are stored in `count` blocks, each block containing the
+ gradients of the sample `samples[i]` (i.e. row in the values) with respect
+ to the position of the atom `atoms[i]`. The features (i.e. columns in the
+ values) with non-zero gradients in block `i` are stored in
+ `features[offsets[i]:offsets[i + 1]]`, and the corresponding x/y/z gradients
+ in `values[3 * offsets[i]:3 * offsets[i + 1]]`. `offsets` contains
+ `count + 1` entries, and `non_zero` is set to the total number of entries
+ in `features`.
+
+ All pointers are set to `NULL` and sizes to 0 if the descriptor does not
+ contain sparse gradients.
+ */
+enum rascal_status_t rascal_descriptor_sparse_gradients(const struct rascal_descriptor_t *descriptor,
+                                                        const uintptr_t **samples,
+                                                        const uintptr_t **atoms,
+                                                        const uintptr_t **offsets,
+                                                        uintptr_t *count,
+                                                        const uintptr_t **features,
+                                                        const double **values,
+                                                        uintptr_t *non_zero);
+
+/*
+ Get the values of the given kind of `indexes` in the `descriptor`.
+ `values` is set to a pointer to `count * size` values, where `count` is
+ the number of entries and `size` the number of indexes in each entry. The
+ type of each index can be obtained with `rascal_descriptor_indexes_types`.
+ */
 enum rascal_status_t rascal_descriptor_indexes(const struct rascal_descriptor_t *descriptor,
                                                enum rascal_indexes indexes,
-                                               const double **values,
+                                               const struct rascal_index_value_t **values,
                                                uintptr_t *count,
                                                uintptr_t *size);
 
+/*
+ Get the types of the given kind of `indexes` in the `descriptor`.
+ `types` should point to an array of at least `size` elements, where
+ `size` is the number of indexes. Extra entries are left unchanged.
+ */
+enum rascal_status_t rascal_descriptor_indexes_types(const struct rascal_descriptor_t *descriptor,
+                                                     enum rascal_indexes indexes,
+                                                     enum rascal_index_type_t *types,
+                                                     uintptr_t size);
+
 enum rascal_status_t rascal_descriptor_indexes_names(const struct rascal_descriptor_t *descriptor,
                                                      enum rascal_indexes indexes,
                                                      const char **names,
@@ -202,6 +353,268 @@ enum rascal_status_t rascal_descriptor_densify(struct rascal_descriptor_t *descr
                                                const char *const *variables,
                                                uintptr_t count);
 
+enum rascal_status_t rascal_descriptor_save(const struct rascal_descriptor_t *descriptor,
+                                            const char *path);
+
+enum rascal_status_t rascal_descriptor_load(struct rascal_descriptor_t *descriptor,
+                                            const char *path);
+
+/*
+ Extract the samples and features matching the `samples` and `features`
+ selections from `descriptor`, and store them in `selected`. Use `NULL` to
+ keep all the samples or all the features.
+
+ The selections can contain only some of the samples/features indexes, in
+ which case all the entries matching the selection on these indexes are
+ kept. For example, a selection containing only the `structure` index keeps
+ all the samples for the given structures. The gradients rows corresponding
+ to the selected samples are kept as well.
+ */
+enum rascal_status_t rascal_descriptor_select(const struct rascal_descriptor_t *descriptor,
+                                              struct rascal_descriptor_t *selected,
+                                              const struct rascal_indexes_t *samples,
+                                              const struct rascal_indexes_t *features);
+
+/*
+ Concatenate the `count` `descriptors` along the samples, and store the
+ result in `concatenated`. All descriptors must have the same features. If
+ the samples contain a `structure` index, it is renumbered so that the
+ structures of each descriptor come after the structures of the previous
+ descriptors.
+ */
+enum rascal_status_t rascal_descriptor_concatenate_samples(const struct rascal_descriptor_t *const *descriptors,
+                                                           uintptr_t count,
+                                                           struct rascal_descriptor_t *concatenated);
+
+/*
+ Concatenate the `count` `descriptors` along the features, and store the
+ result in `concatenated`. All descriptors must have the same samples and
+ gradients indexes.
+ */
+enum rascal_status_t rascal_descriptor_concatenate_features(const struct rascal_descriptor_t *const *descriptors,
+                                                            uintptr_t count,
+                                                            struct rascal_descriptor_t *concatenated);
+
+/*
+ Create new indexes with the given `size` `names`, containing `count`
+ entries. `values` should contain `count * size` values, where the values of
+ the first entry are followed by the values of the second entry and so on.
+ The type of each index is taken from the first entry, and defaults to
+ `RASCAL_INDEX_INT64` if there are no entries. All other entries must use
+ the same types as the first one.
+
+ The indexes must be freed with `rascal_indexes_free` when no longer needed.
+ This function returns `NULL` in case of error, use `rascal_last_error` to
+ get the error message.
+ */
+struct rascal_indexes_t *rascal_indexes_new(const char *const *names,
+                                            uintptr_t size,
+                                            const struct rascal_index_value_t *values,
+                                            uintptr_t count);
+
+/*
+ Create a new copy of the given kind of `indexes` in the `descriptor`. The
+ copy is independent from the descriptor, and must be freed with
+ `rascal_indexes_free` when no longer needed.
+
+ This function returns `NULL` in case of error (for example if the
+ descriptor does not contain gradients and gradients indexes were
+ requested), use `rascal_last_error` to get the error message.
+ */
+struct rascal_indexes_t *rascal_indexes_from_descriptor(const struct rascal_descriptor_t *descriptor,
+                                                        enum rascal_indexes indexes);
+
+enum rascal_status_t rascal_indexes_free(struct rascal_indexes_t *indexes);
+
+/*
+ Get the values in the given `indexes`. `values` is set to a pointer to
+ `count * size` values, where `count` is the number of entries and `size`
+ the number of indexes in each entry.
+ */
+enum rascal_status_t rascal_indexes_values(const struct rascal_indexes_t *indexes,
+                                           const struct rascal_index_value_t **values,
+                                           uintptr_t *count,
+                                           uintptr_t *size);
+
+/*
+ Get the types of the given `indexes`. `types` should point to an array of
+ at least `size` elements, where `size` is the number of indexes. Extra
+ entries are left unchanged.
+ */
+enum rascal_status_t rascal_indexes_types(const struct rascal_indexes_t *indexes,
+                                          enum rascal_index_type_t *types,
+                                          uintptr_t size);
+
+/*
+ Get the names of the given `indexes`. `names` should point to an array of
+ at least `size` elements, where `size` is the number of indexes. Extra
+ entries are set to `NULL`. The names are valid until the `indexes` are
+ freed.
+ */
+enum rascal_status_t rascal_indexes_names(const struct rascal_indexes_t *indexes,
+                                          const char **names,
+                                          uintptr_t size);
+
+/*
+ Get the position of the entry with the given `values` in `indexes`.
+ `values` should contain `size` elements, one for each index. `position` is
+ set to the position of the entry, or to -1 if the entry is not part of
+ these indexes.
+ */
+enum rascal_status_t rascal_indexes_position(const struct rascal_indexes_t *indexes,
+                                             const struct rascal_index_value_t *values,
+                                             uintptr_t size,
+                                             int64_t *position);
+
+/*
+ Get the union of `first` and `second`, containing all entries in `first`
+ followed by the entries of `second` which are not in `first`. Both indexes
+ must have the same names and types.
+
+ The resulting indexes must be freed with `rascal_indexes_free`. This
+ function returns `NULL` in case of error, use `rascal_last_error` to get
+ the error message.
+ */
+struct rascal_indexes_t *rascal_indexes_union(const struct rascal_indexes_t *first,
+                                              const struct rascal_indexes_t *second);
+
+/*
+ Get the intersection of `first` and `second`, containing the entries of
+ `first` which are also in `second`. Both indexes must have the same names
+ and types.
+
+ The resulting indexes must be freed with `rascal_indexes_free`. This
+ function returns `NULL` in case of error, use `rascal_last_error` to get
+ the error message.
+ */
+struct rascal_indexes_t *rascal_indexes_intersection(const struct rascal_indexes_t *first,
+                                                     const struct rascal_indexes_t *second);
+
+/*
+ Get the difference of `first` and `second`, containing the entries of
+ `first` which are not in `second`. Both indexes must have the same names
+ and types.
+
+ The resulting indexes must be freed with `rascal_indexes_free`. This
+ function returns `NULL` in case of error, use `rascal_last_error` to get
+ the error message.
+ */
+struct rascal_indexes_t *rascal_indexes_difference(const struct rascal_indexes_t *first,
+                                                   const struct rascal_indexes_t *second);
+
+/*
+ Get the entries of `indexes` where the index named `names[i]` is equal to
+ `values[i]`, for all `i` smaller than `count`.
+
+ The resulting indexes must be freed with `rascal_indexes_free`. This
+ function returns `NULL` in case of error, use `rascal_last_error` to get
+ the error message.
+ */
+struct rascal_indexes_t *rascal_indexes_filter(const struct rascal_indexes_t *indexes,
+                                               const char *const *names,
+                                               const struct rascal_index_value_t *values,
+                                               uintptr_t count);
+
+/*
+ Project `indexes` onto the `count` indexes with the given `names`, removing
+ duplicated entries.
+
+ The resulting indexes must be freed with `rascal_indexes_free`. This
+ function returns `NULL` in case of error, use `rascal_last_error` to get
+ the error message.
+ */
+struct rascal_indexes_t *rascal_indexes_project(const struct rascal_indexes_t *indexes,
+                                                const char *const *names,
+                                                uintptr_t count);
+
+/*
+ Sort the entries in `indexes` in lexicographic order
+ */
+enum rascal_status_t rascal_indexes_sort(struct rascal_indexes_t *indexes);
+
+/*
+ Create a new empty block descriptor, which must be freed with
+ `rascal_block_descriptor_free` when no longer needed.
+ */
+struct rascal_block_descriptor_t *rascal_block_descriptor(void);
+
+enum rascal_status_t rascal_block_descriptor_free(struct rascal_block_descriptor_t *blocks);
+
+/*
+ Split the `descriptor` in blocks, using the `count` sample `variables` as
+ the keys of the blocks, and store the result in `blocks`.
+ */
+enum rascal_status_t rascal_block_descriptor_from_descriptor(struct rascal_block_descriptor_t *blocks,
+                                                             const struct rascal_descriptor_t *descriptor,
+                                                             const char *const *variables,
+                                                             uintptr_t count);
+
+/*
+ Convert the `blocks` to a dense descriptor, moving the keys to the
+ features, and store the result in `descriptor`.
+ */
+enum rascal_status_t rascal_block_descriptor_densify(const struct rascal_block_descriptor_t *blocks,
+                                                     struct rascal_descriptor_t *descriptor);
+
+/*
+ Get a copy of the keys of the `blocks`, containing one entry for each
+ block.
+
+ The keys must be freed with `rascal_indexes_free` when no longer needed.
+ This function returns `NULL` in case of error, use `rascal_last_error` to
+ get the error message.
+ */
+struct rascal_indexes_t *rascal_block_descriptor_keys(const struct rascal_block_descriptor_t *blocks);
+
+/*
+ Get a copy of the given kind of `indexes` for the block at index `block`
+ in `blocks`. `RASCAL_INDEXES_ENVIRONMENTS` corresponds to the samples of
+ the block.
+
+ The indexes must be freed with `rascal_indexes_free` when no longer
+ needed. This function returns `NULL` in case of error (for example if the
+ block does not contain gradients and gradients indexes were requested),
+ use `rascal_last_error` to get the error message.
+ */
+struct rascal_indexes_t *rascal_block_descriptor_indexes(const struct rascal_block_descriptor_t *blocks,
+                                                         uintptr_t block,
+                                                         enum rascal_indexes indexes);
+
+/*
+ Get the values of the block at index `block` in `blocks`. `data` is set
+ to a pointer to a `samples x features` array in row-major order, which
+ stays valid until `blocks` is modified or freed.
+ */
+enum rascal_status_t rascal_block_descriptor_values(const struct rascal_block_descriptor_t *blocks,
+                                                    uintptr_t block,
+                                                    const double **data,
+                                                    uintptr_t *samples,
+                                                    uintptr_t *features);
+
+/*
+ Get the gradients with respect to atomic positions of the block at index
+ `block` in `blocks`. `data` is set to a pointer to a `gradients x
+ features` array in row-major order, or to `NULL` if the block does not
+ contain gradients.
+ */
+enum rascal_status_t rascal_block_descriptor_gradients(const struct rascal_block_descriptor_t *blocks,
+                                                       uintptr_t block,
+                                                       const double **data,
+                                                       uintptr_t *gradients,
+                                                       uintptr_t *features);
+
+/*
+ Get the gradients with respect to the unit cell of the block at index
+ `block` in `blocks`. `data` is set to a pointer to a `gradients x
+ features` array in row-major order, or to `NULL` if the block does not
+ contain cell gradients.
+ */
+enum rascal_status_t rascal_block_descriptor_cell_gradients(const struct rascal_block_descriptor_t *blocks,
+                                                            uintptr_t block,
+                                                            const double **data,
+                                                            uintptr_t *gradients,
+                                                            uintptr_t *features);
+
 struct rascal_calculator_t *rascal_calculator(const char *name, const char *parameters);
 
 enum rascal_status_t rascal_calculator_free(struct rascal_calculator_t *calculator);
@@ -220,6 +633,22 @@ enum rascal_status_t rascal_calculator_compute(struct rascal_calculator_t *calcu
                                                uintptr_t systems_count,
                                                struct rascal_calculation_options_t options);
 
+/*
+ Compute the descriptor for the `systems_count` `systems`, and store it in
+ `blocks` with one block for each value of the `keys_count` sample `keys`
+ (for example `species_center` and `species_neighbor`). Each block is
+ computed separately, using only the samples with the corresponding key.
+
+ Sparse gradients are not supported when computing blocks.
+ */
+enum rascal_status_t rascal_calculator_compute_blocks(struct rascal_calculator_t *calculator,
+                                                      struct rascal_block_descriptor_t *blocks,
+                                                      struct rascal_system_t *systems,
+                                                      uintptr_t systems_count,
+                                                      const char *const *keys,
+                                                      uintptr_t keys_count,
+                                                      struct rascal_calculation_options_t options);
+
 #ifdef __cplusplus
 } // extern "C"
 #endif // __cplusplus
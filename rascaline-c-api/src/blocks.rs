@@ -0,0 +1,235 @@
+use std::ops::{Deref, DerefMut};
+use std::os::raw::c_char;
+
+use rascaline::descriptor::{BlockDescriptor, DescriptorBlock, Indexes};
+use rascaline::Error;
+
+use super::{catch_unwind, rascal_status_t};
+use super::descriptor::{rascal_descriptor_t, rascal_indexes};
+use super::indexes::{rascal_indexes_t, new_indexes, names_from_c};
+
+/// Opaque type representing a descriptor stored as a set of blocks, with one
+/// block for each value of the keys.
+#[allow(non_camel_case_types)]
+pub struct rascal_block_descriptor_t(BlockDescriptor);
+
+impl Deref for rascal_block_descriptor_t {
+    type Target = BlockDescriptor;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for rascal_block_descriptor_t {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// Create a new empty block descriptor, which must be freed with
+/// `rascal_block_descriptor_free` when no longer needed.
+#[no_mangle]
+pub unsafe extern fn rascal_block_descriptor() -> *mut rascal_block_descriptor_t {
+    let blocks = Box::new(rascal_block_descriptor_t(BlockDescriptor::new()));
+    return Box::into_raw(blocks);
+}
+
+#[no_mangle]
+pub unsafe extern fn rascal_block_descriptor_free(blocks: *mut rascal_block_descriptor_t) -> rascal_status_t {
+    catch_unwind(|| {
+        if !blocks.is_null() {
+            let boxed = Box::from_raw(blocks);
+            std::mem::drop(boxed);
+        }
+        Ok(())
+    })
+}
+
+/// Split the `descriptor` in blocks, using the `count` sample `variables` as
+/// the keys of the blocks, and store the result in `blocks`.
+#[no_mangle]
+pub unsafe extern fn rascal_block_descriptor_from_descriptor(
+    blocks: *mut rascal_block_descriptor_t,
+    descriptor: *const rascal_descriptor_t,
+    variables: *const *const c_char,
+    count: usize,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(blocks, descriptor, variables);
+        let variables = names_from_c(variables, count)?;
+        (*blocks).0 = BlockDescriptor::from_descriptor(&*descriptor, variables)?;
+        Ok(())
+    })
+}
+
+/// Convert the `blocks` to a dense descriptor, moving the keys to the
+/// features, and store the result in `descriptor`.
+#[no_mangle]
+pub unsafe extern fn rascal_block_descriptor_densify(
+    blocks: *const rascal_block_descriptor_t,
+    descriptor: *mut rascal_descriptor_t,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(blocks, descriptor);
+        let blocks = &*blocks;
+        let descriptor = &mut *descriptor;
+        **descriptor = blocks.densify()?;
+        Ok(())
+    })
+}
+
+/// Get a copy of the keys of the `blocks`, containing one entry for each
+/// block.
+///
+/// The keys must be freed with `rascal_indexes_free` when no longer needed.
+/// This function returns `NULL` in case of error, use `rascal_last_error` to
+/// get the error message.
+#[no_mangle]
+pub unsafe extern fn rascal_block_descriptor_keys(
+    blocks: *const rascal_block_descriptor_t,
+) -> *mut rascal_indexes_t {
+    new_indexes(|| {
+        check_pointers!(blocks);
+        let blocks = &*blocks;
+        Ok(blocks.keys.clone())
+    })
+}
+
+/// Get a copy of the given kind of `indexes` for the block at index `block`
+/// in `blocks`. `RASCAL_INDEXES_ENVIRONMENTS` corresponds to the samples of
+/// the block.
+///
+/// The indexes must be freed with `rascal_indexes_free` when no longer
+/// needed. This function returns `NULL` in case of error (for example if the
+/// block does not contain gradients and gradients indexes were requested),
+/// use `rascal_last_error` to get the error message.
+#[no_mangle]
+pub unsafe extern fn rascal_block_descriptor_indexes(
+    blocks: *const rascal_block_descriptor_t,
+    block: usize,
+    indexes: rascal_indexes,
+) -> *mut rascal_indexes_t {
+    new_indexes(|| {
+        check_pointers!(blocks);
+        let block = get_block(&*blocks, block)?;
+        block_indexes(block, indexes).cloned().ok_or_else(|| Error::InvalidParameter(
+            "the block does not contain the requested indexes".into()
+        ))
+    })
+}
+
+/// Get the values of the block at index `block` in `blocks`. `data` is set
+/// to a pointer to a `samples x features` array in row-major order, which
+/// stays valid until `blocks` is modified or freed.
+#[no_mangle]
+pub unsafe extern fn rascal_block_descriptor_values(
+    blocks: *const rascal_block_descriptor_t,
+    block: usize,
+    data: *mut *const f64,
+    samples: *mut usize,
+    features: *mut usize,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(blocks, data, samples, features);
+        let block = get_block(&*blocks, block)?;
+
+        let shape = block.values.shape();
+        *samples = shape[0];
+        *features = shape[1];
+        if block.values.is_empty() {
+            *data = std::ptr::null();
+        } else {
+            *data = block.values.as_ptr();
+        }
+
+        Ok(())
+    })
+}
+
+/// Get the gradients with respect to atomic positions of the block at index
+/// `block` in `blocks`. `data` is set to a pointer to a `gradients x
+/// features` array in row-major order, or to `NULL` if the block does not
+/// contain gradients.
+#[no_mangle]
+pub unsafe extern fn rascal_block_descriptor_gradients(
+    blocks: *const rascal_block_descriptor_t,
+    block: usize,
+    data: *mut *const f64,
+    gradients: *mut usize,
+    features: *mut usize,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(blocks, data, gradients, features);
+        let block = get_block(&*blocks, block)?;
+
+        match &block.gradients {
+            Some(array) => {
+                *data = array.as_ptr();
+                let shape = array.shape();
+                *gradients = shape[0];
+                *features = shape[1];
+            }
+            None => {
+                *data = std::ptr::null();
+                *gradients = 0;
+                *features = 0;
+            }
+        }
+
+        Ok(())
+    })
+}
+
+/// Get the gradients with respect to the unit cell of the block at index
+/// `block` in `blocks`. `data` is set to a pointer to a `gradients x
+/// features` array in row-major order, or to `NULL` if the block does not
+/// contain cell gradients.
+#[no_mangle]
+pub unsafe extern fn rascal_block_descriptor_cell_gradients(
+    blocks: *const rascal_block_descriptor_t,
+    block: usize,
+    data: *mut *const f64,
+    gradients: *mut usize,
+    features: *mut usize,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(blocks, data, gradients, features);
+        let block = get_block(&*blocks, block)?;
+
+        match &block.cell_gradients {
+            Some(array) => {
+                *data = array.as_ptr();
+                let shape = array.shape();
+                *gradients = shape[0];
+                *features = shape[1];
+            }
+            None => {
+                *data = std::ptr::null();
+                *gradients = 0;
+                *features = 0;
+            }
+        }
+
+        Ok(())
+    })
+}
+
+/// Get the block at index `block` in `blocks`, or an error if there is no
+/// such block
+fn get_block(blocks: &BlockDescriptor, block: usize) -> Result<&DescriptorBlock, Error> {
+    blocks.blocks.get(block).ok_or_else(|| Error::InvalidParameter(format!(
+        "block index out of bounds: there are {} blocks but the index is {}",
+        blocks.blocks.len(), block
+    )))
+}
+
+/// Get the indexes of the given `kind` in the `block`, or `None` if the block
+/// does not contain gradients and gradients indexes were requested
+fn block_indexes(block: &DescriptorBlock, kind: rascal_indexes) -> Option<&Indexes> {
+    match kind {
+        rascal_indexes::RASCAL_INDEXES_FEATURES => Some(&block.features),
+        rascal_indexes::RASCAL_INDEXES_ENVIRONMENTS => Some(&block.samples),
+        rascal_indexes::RASCAL_INDEXES_GRADIENTS => block.gradients_indexes.as_ref(),
+        rascal_indexes::RASCAL_INDEXES_CELL_GRADIENTS => block.cell_gradients_indexes.as_ref(),
+    }
+}
@@ -3,12 +3,13 @@ use std::ffi::CStr;
 use std::ops::{Deref, DerefMut};
 
 use rascaline::{Calculator, System, CalculationOptions, SelectedIndexes};
-use rascaline::descriptor::IndexValue;
 
 use super::utils::copy_str_to_c;
 use super::{catch_unwind, rascal_status_t};
 
 use super::descriptor::rascal_descriptor_t;
+use super::blocks::rascal_block_descriptor_t;
+use super::indexes::names_from_c;
 use super::system::rascal_system_t;
 
 /// Opaque type representing a Calculator
@@ -95,17 +96,27 @@ pub struct rascal_calculation_options_t {
     /// faster than having to cross the FFI boundary too often.
     use_native_system: bool,
     /// List of samples on which to run the calculation. Use `NULL` to run the
-    /// calculation on all samples.
+    /// calculation on all samples. The values are converted to the type of
+    /// the corresponding index, and must be integers for integer indexes.
     selected_samples: *const f64,
     /// If selected_samples is not `NULL`, this should be set to the size of the
     /// selected_samples array
     selected_samples_count: usize,
     /// List of features on which to run the calculation. Use `NULL` to run the
-    /// calculation on all features.
+    /// calculation on all features. The values are converted to the type of
+    /// the corresponding index, and must be integers for integer indexes.
     selected_features: *const f64,
     /// If selected_features is not `NULL`, this should be set to the size of the
     /// selected_features array
     selected_features_count: usize,
+    /// Number of threads to use for the calculation. Use 1 to run the
+    /// calculation sequentially, and 0 to use as many threads as there are
+    /// CPU cores.
+    threads: usize,
+    /// Store the gradients as sparse gradients (see
+    /// `rascal_descriptor_sparse_gradients`) instead of dense gradients. The
+    /// calculation is then done one system at a time.
+    sparse_gradients: bool,
 }
 
 impl<'a> From<&'a rascal_calculation_options_t> for CalculationOptions<'a> {
@@ -115,7 +126,7 @@ impl<'a> From<&'a rascal_calculation_options_t> for CalculationOptions<'a> {
         } else {
             let slice = unsafe {
                 std::slice::from_raw_parts(
-                    options.selected_samples as *const IndexValue,
+                    options.selected_samples,
                     options.selected_samples_count
                 )
             };
@@ -127,7 +138,7 @@ impl<'a> From<&'a rascal_calculation_options_t> for CalculationOptions<'a> {
         } else {
             let slice = unsafe {
                 std::slice::from_raw_parts(
-                    options.selected_features as *const IndexValue,
+                    options.selected_features,
                     options.selected_features_count
                 )
             };
@@ -138,6 +149,8 @@ impl<'a> From<&'a rascal_calculation_options_t> for CalculationOptions<'a> {
             use_native_system: options.use_native_system,
             selected_samples: selected_samples,
             selected_features: selected_features,
+            threads: options.threads,
+            sparse_gradients: options.sparse_gradients,
         }
     }
 }
@@ -168,3 +181,37 @@ pub unsafe extern fn rascal_calculator_compute(
         (*calculator).compute(&mut references, &mut *descriptor, options)
     })
 }
+
+/// Compute the descriptor for the `systems_count` `systems`, and store it in
+/// `blocks` with one block for each value of the `keys_count` sample `keys`
+/// (for example `species_center` and `species_neighbor`). Each block is
+/// computed separately, using only the samples with the corresponding key.
+///
+/// Sparse gradients are not supported when computing blocks.
+#[no_mangle]
+pub unsafe extern fn rascal_calculator_compute_blocks(
+    calculator: *mut rascal_calculator_t,
+    blocks: *mut rascal_block_descriptor_t,
+    systems: *mut rascal_system_t,
+    systems_count: usize,
+    keys: *const *const c_char,
+    keys_count: usize,
+    options: rascal_calculation_options_t,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(calculator, blocks, systems, keys);
+        let keys = names_from_c(keys, keys_count)?;
+
+        // Create a Vec<&mut dyn System> from the passed systems
+        let systems = std::slice::from_raw_parts_mut(systems, systems_count);
+        let mut references = Vec::new();
+        for system in systems {
+            references.push(system as &mut dyn System);
+        }
+
+        let calculator = &mut *calculator;
+        let blocks = &mut *blocks;
+        **blocks = calculator.compute_blocks(&mut references, keys, (&options).into())?;
+        Ok(())
+    })
+}
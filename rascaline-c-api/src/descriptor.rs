@@ -3,7 +3,9 @@ use std::os::raw::c_char;
 use std::ffi::CStr;
 
 use rascaline::Descriptor;
+use rascaline::descriptor::{Indexes, IndexType, IndexValue};
 use super::{catch_unwind, rascal_status_t};
+use super::indexes::rascal_indexes_t;
 
 /// Opaque type representing a Descriptor
 #[allow(non_camel_case_types)]
@@ -50,7 +52,8 @@ pub unsafe extern fn rascal_descriptor_values(
     catch_unwind(|| {
         check_pointers!(descriptor, data, environments, features);
 
-        let array = &(*descriptor).values;
+        let descriptor = &*descriptor;
+        let array = &descriptor.values;
         if array.is_empty() {
             *data = std::ptr::null();
         } else {
@@ -75,7 +78,8 @@ pub unsafe extern fn rascal_descriptor_gradients(
     catch_unwind(|| {
         check_pointers!(descriptor, data, environments, features);
 
-        match &(*descriptor).gradients {
+        let descriptor = &*descriptor;
+        match &descriptor.gradients {
             Some(array) => {
                 *data = array.as_ptr();
                 let shape = array.shape();
@@ -85,7 +89,91 @@ pub unsafe extern fn rascal_descriptor_gradients(
             None => {
                 *data = std::ptr::null();
                 *environments = 0;
+                *features = 0;
+            }
+        }
+
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern fn rascal_descriptor_cell_gradients(
+    descriptor: *const rascal_descriptor_t,
+    data: *mut *const f64,
+    environments: *mut usize,
+    features: *mut usize
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(descriptor, data, environments, features);
+
+        let descriptor = &*descriptor;
+        match &descriptor.cell_gradients {
+            Some(array) => {
+                *data = array.as_ptr();
+                let shape = array.shape();
+                *environments = shape[0];
+                *features = shape[1];
+            }
+            None => {
+                *data = std::ptr::null();
                 *environments = 0;
+                *features = 0;
+            }
+        }
+
+        Ok(())
+    })
+}
+
+/// Get the sparse gradients in the `descriptor`, as computed with the
+/// `sparse_gradients` calculation option.
+///
+/// The gradients are stored in `count` blocks, each block containing the
+/// gradients of the sample `samples[i]` (i.e. row in the values) with respect
+/// to the position of the atom `atoms[i]`. The features (i.e. columns in the
+/// values) with non-zero gradients in block `i` are stored in
+/// `features[offsets[i]:offsets[i + 1]]`, and the corresponding x/y/z gradients
+/// in `values[3 * offsets[i]:3 * offsets[i + 1]]`. `offsets` contains
+/// `count + 1` entries, and `non_zero` is set to the total number of entries
+/// in `features`.
+///
+/// All pointers are set to `NULL` and sizes to 0 if the descriptor does not
+/// contain sparse gradients.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern fn rascal_descriptor_sparse_gradients(
+    descriptor: *const rascal_descriptor_t,
+    samples: *mut *const usize,
+    atoms: *mut *const usize,
+    offsets: *mut *const usize,
+    count: *mut usize,
+    features: *mut *const usize,
+    values: *mut *const f64,
+    non_zero: *mut usize,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(descriptor, samples, atoms, offsets, count, features, values, non_zero);
+
+        let descriptor = &*descriptor;
+        match &descriptor.sparse_gradients {
+            Some(gradients) => {
+                *samples = gradients.samples().as_ptr();
+                *atoms = gradients.atoms().as_ptr();
+                *offsets = gradients.offsets().as_ptr();
+                *count = gradients.count();
+                *features = gradients.features().as_ptr();
+                *values = gradients.values().as_ptr().cast();
+                *non_zero = gradients.non_zero();
+            }
+            None => {
+                *samples = std::ptr::null();
+                *atoms = std::ptr::null();
+                *offsets = std::ptr::null();
+                *count = 0;
+                *features = std::ptr::null();
+                *values = std::ptr::null();
+                *non_zero = 0;
             }
         }
 
@@ -99,32 +187,93 @@ pub enum rascal_indexes {
     RASCAL_INDEXES_FEATURES = 0,
     RASCAL_INDEXES_ENVIRONMENTS = 1,
     RASCAL_INDEXES_GRADIENTS = 2,
+    RASCAL_INDEXES_CELL_GRADIENTS = 3,
+}
+
+/// Type of the values in a single index
+#[repr(C)]
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum rascal_index_type_t {
+    /// The index contains signed 64-bit integers, stored in
+    /// `rascal_index_data_t.int64`
+    RASCAL_INDEX_INT64 = 0,
+    /// The index contains 64-bit floating point values, stored in
+    /// `rascal_index_data_t.float64`
+    RASCAL_INDEX_FLOAT64 = 1,
+}
+
+impl From<IndexType> for rascal_index_type_t {
+    fn from(index_type: IndexType) -> rascal_index_type_t {
+        match index_type {
+            IndexType::Int => rascal_index_type_t::RASCAL_INDEX_INT64,
+            IndexType::Float => rascal_index_type_t::RASCAL_INDEX_FLOAT64,
+        }
+    }
 }
 
+/// Storage for a single index value, the field to use depends on the type of
+/// the value
+#[repr(C)]
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy)]
+pub union rascal_index_data_t {
+    /// Value for `RASCAL_INDEX_INT64` indexes
+    pub int64: i64,
+    /// Value for `RASCAL_INDEX_FLOAT64` indexes
+    pub float64: f64,
+}
+
+/// A single value in a set of indexes, together with its type
+#[repr(C)]
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy)]
+pub struct rascal_index_value_t {
+    /// type of the value, all values of a given index have the same type
+    pub index_type: rascal_index_type_t,
+    /// the value itself
+    pub data: rascal_index_data_t,
+}
+
+// `rascal_index_value_t` is used to give C access to the memory of
+// `IndexValue` without copies, both types must have the same layout
+const _: () = assert!(std::mem::size_of::<rascal_index_value_t>() == std::mem::size_of::<IndexValue>());
+const _: () = assert!(std::mem::align_of::<rascal_index_value_t>() == std::mem::align_of::<IndexValue>());
+
+/// Get the indexes of the given `kind` in the `descriptor`, or `None` if the
+/// descriptor does not contain gradients and gradients indexes were requested
+pub(crate) unsafe fn descriptor_indexes<'a>(descriptor: *const rascal_descriptor_t, kind: rascal_indexes) -> Option<&'a Indexes> {
+    let descriptor = &*descriptor;
+    match kind {
+        rascal_indexes::RASCAL_INDEXES_FEATURES => Some(&descriptor.features),
+        rascal_indexes::RASCAL_INDEXES_ENVIRONMENTS => Some(&descriptor.environments),
+        rascal_indexes::RASCAL_INDEXES_GRADIENTS => descriptor.gradients_indexes.as_ref(),
+        rascal_indexes::RASCAL_INDEXES_CELL_GRADIENTS => descriptor.cell_gradients_indexes.as_ref(),
+    }
+}
+
+/// Get the values of the given kind of `indexes` in the `descriptor`.
+/// `values` is set to a pointer to `count * size` values, where `count` is
+/// the number of entries and `size` the number of indexes in each entry. The
+/// type of each index can be obtained with `rascal_descriptor_indexes_types`.
 #[no_mangle]
 pub unsafe extern fn rascal_descriptor_indexes(
     descriptor: *const rascal_descriptor_t,
     indexes: rascal_indexes,
-    values: *mut *const f64,
+    values: *mut *const rascal_index_value_t,
     count: *mut usize,
     size: *mut usize,
 ) -> rascal_status_t {
     catch_unwind(|| {
         check_pointers!(descriptor, values, size, count);
 
-        let indexes = match indexes {
-            rascal_indexes::RASCAL_INDEXES_FEATURES => &(*descriptor).features,
-            rascal_indexes::RASCAL_INDEXES_ENVIRONMENTS => &(*descriptor).environments,
-            rascal_indexes::RASCAL_INDEXES_GRADIENTS => {
-                if let Some(indexes) = &(*descriptor).gradients_indexes {
-                    indexes
-                } else {
-                    *values = std::ptr::null();
-                    *size = 0;
-                    *count = 0;
-                    return Ok(());
-                }
-            }
+        let indexes = if let Some(indexes) = descriptor_indexes(descriptor, indexes) {
+            indexes
+        } else {
+            *values = std::ptr::null();
+            *size = 0;
+            *count = 0;
+            return Ok(());
         };
 
         *size = indexes.size();
@@ -132,7 +281,30 @@ pub unsafe extern fn rascal_descriptor_indexes(
         if *count == 0 {
             *values = std::ptr::null();
         } else {
-            *values = &indexes[0][0] as *const _ as *const f64;
+            *values = indexes[0].as_ptr().cast();
+        }
+
+        Ok(())
+    })
+}
+
+/// Get the types of the given kind of `indexes` in the `descriptor`.
+/// `types` should point to an array of at least `size` elements, where
+/// `size` is the number of indexes. Extra entries are left unchanged.
+#[no_mangle]
+pub unsafe extern fn rascal_descriptor_indexes_types(
+    descriptor: *const rascal_descriptor_t,
+    indexes: rascal_indexes,
+    types: *mut rascal_index_type_t,
+    size: usize
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(descriptor, types);
+
+        if let Some(indexes) = descriptor_indexes(descriptor, indexes) {
+            for (i, &index_type) in indexes.types().iter().take(size).enumerate() {
+                types.add(i).write(index_type.into());
+            }
         }
 
         Ok(())
@@ -149,19 +321,13 @@ pub unsafe extern fn rascal_descriptor_indexes_names(
     catch_unwind(|| {
         check_pointers!(descriptor, names);
 
-        let indexes = match indexes {
-            rascal_indexes::RASCAL_INDEXES_FEATURES => &(*descriptor).features,
-            rascal_indexes::RASCAL_INDEXES_ENVIRONMENTS => &(*descriptor).environments,
-            rascal_indexes::RASCAL_INDEXES_GRADIENTS => {
-                if let Some(indexes) = &(*descriptor).gradients_indexes {
-                    indexes
-                } else {
-                    for i in 0..size {
-                        names.add(i).write(std::ptr::null());
-                    }
-                    return Ok(());
-                }
+        let indexes = if let Some(indexes) = descriptor_indexes(descriptor, indexes) {
+            indexes
+        } else {
+            for i in 0..size {
+                names.add(i).write(std::ptr::null());
             }
+            return Ok(());
         };
 
         for (i, name) in indexes.c_names().iter().enumerate() {
@@ -197,7 +363,109 @@ pub unsafe extern fn rascal_descriptor_densify(
             let variable = CStr::from_ptr(variable).to_str()?;
             rust_variables.push(variable);
         }
-        (*descriptor).densify(rust_variables);
+        (*descriptor).densify(rust_variables)?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern fn rascal_descriptor_save(
+    descriptor: *const rascal_descriptor_t,
+    path: *const c_char,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(descriptor, path);
+        let path = CStr::from_ptr(path).to_str()?;
+        (*descriptor).save(path)?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern fn rascal_descriptor_load(
+    descriptor: *mut rascal_descriptor_t,
+    path: *const c_char,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(descriptor, path);
+        let path = CStr::from_ptr(path).to_str()?;
+        (*descriptor).0 = Descriptor::load(path)?;
+        Ok(())
+    })
+}
+
+/// Extract the samples and features matching the `samples` and `features`
+/// selections from `descriptor`, and store them in `selected`. Use `NULL` to
+/// keep all the samples or all the features.
+///
+/// The selections can contain only some of the samples/features indexes, in
+/// which case all the entries matching the selection on these indexes are
+/// kept. For example, a selection containing only the `structure` index keeps
+/// all the samples for the given structures. The gradients rows corresponding
+/// to the selected samples are kept as well.
+#[no_mangle]
+pub unsafe extern fn rascal_descriptor_select(
+    descriptor: *const rascal_descriptor_t,
+    selected: *mut rascal_descriptor_t,
+    samples: *const rascal_indexes_t,
+    features: *const rascal_indexes_t,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(descriptor, selected);
+        let samples = samples.as_ref().map(|samples| &**samples);
+        let features = features.as_ref().map(|features| &**features);
+        (*selected).0 = (*descriptor).select(samples, features)?;
         Ok(())
     })
 }
+
+/// Concatenate the `count` `descriptors` along the samples, and store the
+/// result in `concatenated`. All descriptors must have the same features. If
+/// the samples contain a `structure` index, it is renumbered so that the
+/// structures of each descriptor come after the structures of the previous
+/// descriptors.
+#[no_mangle]
+pub unsafe extern fn rascal_descriptor_concatenate_samples(
+    descriptors: *const *const rascal_descriptor_t,
+    count: usize,
+    concatenated: *mut rascal_descriptor_t,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(concatenated);
+        let descriptors = descriptors_from_c(descriptors, count)?;
+        (*concatenated).0 = Descriptor::concatenate_samples(&descriptors)?;
+        Ok(())
+    })
+}
+
+/// Concatenate the `count` `descriptors` along the features, and store the
+/// result in `concatenated`. All descriptors must have the same samples and
+/// gradients indexes.
+#[no_mangle]
+pub unsafe extern fn rascal_descriptor_concatenate_features(
+    descriptors: *const *const rascal_descriptor_t,
+    count: usize,
+    concatenated: *mut rascal_descriptor_t,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(concatenated);
+        let descriptors = descriptors_from_c(descriptors, count)?;
+        (*concatenated).0 = Descriptor::concatenate_features(&descriptors)?;
+        Ok(())
+    })
+}
+
+/// Convert an array of `count` descriptors pointers to a `Vec<&Descriptor>`
+unsafe fn descriptors_from_c<'a>(descriptors: *const *const rascal_descriptor_t, count: usize) -> Result<Vec<&'a Descriptor>, rascaline::Error> {
+    let mut references = Vec::new();
+    if count == 0 {
+        return Ok(references);
+    }
+
+    check_pointers!(descriptors);
+    for &descriptor in std::slice::from_raw_parts(descriptors, count) {
+        check_pointers!(descriptor);
+        references.push(&**descriptor);
+    }
+    return Ok(references);
+}
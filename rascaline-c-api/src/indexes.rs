@@ -0,0 +1,375 @@
+use std::ops::{Deref, DerefMut};
+use std::os::raw::{c_char, c_int};
+use std::panic::UnwindSafe;
+use std::ffi::CStr;
+use std::convert::TryFrom;
+
+use rascaline::descriptor::{Indexes, IndexesBuilder, IndexValue};
+use rascaline::Error;
+
+use super::{catch_unwind, rascal_status_t};
+use super::descriptor::{rascal_descriptor_t, rascal_indexes, rascal_index_type_t, rascal_index_value_t};
+use super::descriptor::descriptor_indexes;
+
+/// Opaque type representing a set of indexes, i.e. a list of entries where
+/// each entry contains the values of multiple named indexes.
+#[allow(non_camel_case_types)]
+pub struct rascal_indexes_t(Indexes);
+
+impl Deref for rascal_indexes_t {
+    type Target = Indexes;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for rascal_indexes_t {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// Run `function` and box the resulting indexes for use from C, returning
+/// `NULL` if the function returned an error or panicked.
+pub(crate) fn new_indexes<F>(function: F) -> *mut rascal_indexes_t where F: FnOnce() -> Result<Indexes, Error> + UnwindSafe {
+    let mut raw = std::ptr::null_mut();
+    let unwind_wrapper = std::panic::AssertUnwindSafe(&mut raw);
+    let status = catch_unwind(move || {
+        let indexes = function()?;
+        *unwind_wrapper.0 = Box::into_raw(Box::new(rascal_indexes_t(indexes)));
+        Ok(())
+    });
+
+    if status == rascal_status_t::RASCAL_SUCCESS {
+        return raw;
+    } else {
+        return std::ptr::null_mut();
+    }
+}
+
+/// Convert the `count` values coming from C in `values` to `IndexValue`
+unsafe fn index_values_from_c(values: *const rascal_index_value_t, count: usize) -> Result<Vec<IndexValue>, Error> {
+    let mut rust_values = Vec::with_capacity(count);
+    for i in 0..count {
+        let value = values.add(i);
+        // C code can put any integer in the enum, read it as an integer to
+        // validate it before use
+        let index_type = std::ptr::addr_of!((*value).index_type).cast::<c_int>().read();
+        let data = std::ptr::addr_of!((*value).data).read();
+
+        if index_type == rascal_index_type_t::RASCAL_INDEX_INT64 as c_int {
+            rust_values.push(IndexValue::from(data.int64));
+        } else if index_type == rascal_index_type_t::RASCAL_INDEX_FLOAT64 as c_int {
+            if data.float64.is_nan() {
+                return Err(Error::InvalidParameter("index values can not be NaN".into()));
+            }
+            rust_values.push(IndexValue::from(data.float64));
+        } else {
+            return Err(Error::InvalidParameter(format!(
+                "invalid index type {}, expected RASCAL_INDEX_INT64 or RASCAL_INDEX_FLOAT64",
+                index_type
+            )));
+        }
+    }
+    return Ok(rust_values);
+}
+
+/// Convert an array of `count` C strings to a `Vec<&str>`
+pub(crate) unsafe fn names_from_c<'a>(names: *const *const c_char, count: usize) -> Result<Vec<&'a str>, Error> {
+    let mut rust_names = Vec::new();
+    if count == 0 {
+        return Ok(rust_names);
+    }
+
+    for &name in std::slice::from_raw_parts(names, count) {
+        check_pointers!(name);
+        rust_names.push(CStr::from_ptr(name).to_str()?);
+    }
+    return Ok(rust_names);
+}
+
+/// Create new indexes with the given `size` `names`, containing `count`
+/// entries. `values` should contain `count * size` values, where the values of
+/// the first entry are followed by the values of the second entry and so on.
+/// The type of each index is taken from the first entry, and defaults to
+/// `RASCAL_INDEX_INT64` if there are no entries. All other entries must use
+/// the same types as the first one.
+///
+/// The indexes must be freed with `rascal_indexes_free` when no longer needed.
+/// This function returns `NULL` in case of error, use `rascal_last_error` to
+/// get the error message.
+#[no_mangle]
+pub unsafe extern fn rascal_indexes_new(
+    names: *const *const c_char,
+    size: usize,
+    values: *const rascal_index_value_t,
+    count: usize,
+) -> *mut rascal_indexes_t {
+    new_indexes(|| {
+        check_pointers!(names);
+        let names = names_from_c(names, size)?;
+
+        let mut builder = IndexesBuilder::new(names.clone());
+        if count != 0 {
+            check_pointers!(values);
+            if size == 0 {
+                return Err(Error::InvalidParameter(
+                    "can not create indexes containing entries without any index names".into()
+                ));
+            }
+
+            let values = index_values_from_c(values, count * size)?;
+            let types = values[..size].iter().map(|value| value.index_type()).collect::<Vec<_>>();
+            for entry in values.chunks(size) {
+                for ((value, &expected), name) in entry.iter().zip(&types).zip(&names) {
+                    if value.index_type() != expected {
+                        return Err(Error::InvalidParameter(format!(
+                            "invalid type for the '{}' index: expected {} from the first entry, got {}",
+                            name, expected, value.index_type()
+                        )));
+                    }
+                }
+
+                if builder.contains(entry) {
+                    return Err(Error::InvalidParameter(format!(
+                        "can not have the same index value multiple time: [{}] is already present",
+                        entry.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ")
+                    )));
+                }
+                builder.add(entry);
+            }
+        }
+
+        Ok(builder.finish())
+    })
+}
+
+/// Create a new copy of the given kind of `indexes` in the `descriptor`. The
+/// copy is independent from the descriptor, and must be freed with
+/// `rascal_indexes_free` when no longer needed.
+///
+/// This function returns `NULL` in case of error (for example if the
+/// descriptor does not contain gradients and gradients indexes were
+/// requested), use `rascal_last_error` to get the error message.
+#[no_mangle]
+pub unsafe extern fn rascal_indexes_from_descriptor(
+    descriptor: *const rascal_descriptor_t,
+    indexes: rascal_indexes,
+) -> *mut rascal_indexes_t {
+    new_indexes(|| {
+        check_pointers!(descriptor);
+        descriptor_indexes(descriptor, indexes).cloned().ok_or_else(|| Error::InvalidParameter(
+            "the descriptor does not contain the requested indexes".into()
+        ))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern fn rascal_indexes_free(indexes: *mut rascal_indexes_t) -> rascal_status_t {
+    catch_unwind(|| {
+        if !indexes.is_null() {
+            let boxed = Box::from_raw(indexes);
+            std::mem::drop(boxed);
+        }
+        Ok(())
+    })
+}
+
+/// Get the values in the given `indexes`. `values` is set to a pointer to
+/// `count * size` values, where `count` is the number of entries and `size`
+/// the number of indexes in each entry.
+#[no_mangle]
+pub unsafe extern fn rascal_indexes_values(
+    indexes: *const rascal_indexes_t,
+    values: *mut *const rascal_index_value_t,
+    count: *mut usize,
+    size: *mut usize,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(indexes, values, count, size);
+
+        let indexes = &*indexes;
+        *size = indexes.size();
+        *count = indexes.count();
+        if *count == 0 {
+            *values = std::ptr::null();
+        } else {
+            *values = indexes[0].as_ptr().cast();
+        }
+
+        Ok(())
+    })
+}
+
+/// Get the types of the given `indexes`. `types` should point to an array of
+/// at least `size` elements, where `size` is the number of indexes. Extra
+/// entries are left unchanged.
+#[no_mangle]
+pub unsafe extern fn rascal_indexes_types(
+    indexes: *const rascal_indexes_t,
+    types: *mut rascal_index_type_t,
+    size: usize,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(indexes, types);
+
+        for (i, &index_type) in (*indexes).types().iter().take(size).enumerate() {
+            types.add(i).write(index_type.into());
+        }
+
+        Ok(())
+    })
+}
+
+/// Get the names of the given `indexes`. `names` should point to an array of
+/// at least `size` elements, where `size` is the number of indexes. Extra
+/// entries are set to `NULL`. The names are valid until the `indexes` are
+/// freed.
+#[no_mangle]
+pub unsafe extern fn rascal_indexes_names(
+    indexes: *const rascal_indexes_t,
+    names: *mut *const c_char,
+    size: usize,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(indexes, names);
+
+        let c_names = (*indexes).c_names();
+        for i in 0..size {
+            let name = c_names.get(i).map_or(std::ptr::null(), |name| name.as_ptr());
+            names.add(i).write(name);
+        }
+
+        Ok(())
+    })
+}
+
+/// Get the position of the entry with the given `values` in `indexes`.
+/// `values` should contain `size` elements, one for each index. `position` is
+/// set to the position of the entry, or to -1 if the entry is not part of
+/// these indexes.
+#[no_mangle]
+pub unsafe extern fn rascal_indexes_position(
+    indexes: *const rascal_indexes_t,
+    values: *const rascal_index_value_t,
+    size: usize,
+    position: *mut i64,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(indexes, values, position);
+
+        let values = index_values_from_c(values, size)?;
+
+        *position = match (*indexes).position(&values) {
+            Some(position) => i64::try_from(position).expect("position is too large for i64"),
+            None => -1,
+        };
+
+        Ok(())
+    })
+}
+
+/// Get the union of `first` and `second`, containing all entries in `first`
+/// followed by the entries of `second` which are not in `first`. Both indexes
+/// must have the same names and types.
+///
+/// The resulting indexes must be freed with `rascal_indexes_free`. This
+/// function returns `NULL` in case of error, use `rascal_last_error` to get
+/// the error message.
+#[no_mangle]
+pub unsafe extern fn rascal_indexes_union(
+    first: *const rascal_indexes_t,
+    second: *const rascal_indexes_t,
+) -> *mut rascal_indexes_t {
+    new_indexes(|| {
+        check_pointers!(first, second);
+        (*first).union(&*second)
+    })
+}
+
+/// Get the intersection of `first` and `second`, containing the entries of
+/// `first` which are also in `second`. Both indexes must have the same names
+/// and types.
+///
+/// The resulting indexes must be freed with `rascal_indexes_free`. This
+/// function returns `NULL` in case of error, use `rascal_last_error` to get
+/// the error message.
+#[no_mangle]
+pub unsafe extern fn rascal_indexes_intersection(
+    first: *const rascal_indexes_t,
+    second: *const rascal_indexes_t,
+) -> *mut rascal_indexes_t {
+    new_indexes(|| {
+        check_pointers!(first, second);
+        (*first).intersection(&*second)
+    })
+}
+
+/// Get the difference of `first` and `second`, containing the entries of
+/// `first` which are not in `second`. Both indexes must have the same names
+/// and types.
+///
+/// The resulting indexes must be freed with `rascal_indexes_free`. This
+/// function returns `NULL` in case of error, use `rascal_last_error` to get
+/// the error message.
+#[no_mangle]
+pub unsafe extern fn rascal_indexes_difference(
+    first: *const rascal_indexes_t,
+    second: *const rascal_indexes_t,
+) -> *mut rascal_indexes_t {
+    new_indexes(|| {
+        check_pointers!(first, second);
+        (*first).difference(&*second)
+    })
+}
+
+/// Get the entries of `indexes` where the index named `names[i]` is equal to
+/// `values[i]`, for all `i` smaller than `count`.
+///
+/// The resulting indexes must be freed with `rascal_indexes_free`. This
+/// function returns `NULL` in case of error, use `rascal_last_error` to get
+/// the error message.
+#[no_mangle]
+pub unsafe extern fn rascal_indexes_filter(
+    indexes: *const rascal_indexes_t,
+    names: *const *const c_char,
+    values: *const rascal_index_value_t,
+    count: usize,
+) -> *mut rascal_indexes_t {
+    new_indexes(|| {
+        check_pointers!(indexes, names, values);
+        let names = names_from_c(names, count)?;
+        let values = index_values_from_c(values, count)?;
+
+        (*indexes).filter(&names, |entry| entry == values)
+    })
+}
+
+/// Project `indexes` onto the `count` indexes with the given `names`, removing
+/// duplicated entries.
+///
+/// The resulting indexes must be freed with `rascal_indexes_free`. This
+/// function returns `NULL` in case of error, use `rascal_last_error` to get
+/// the error message.
+#[no_mangle]
+pub unsafe extern fn rascal_indexes_project(
+    indexes: *const rascal_indexes_t,
+    names: *const *const c_char,
+    count: usize,
+) -> *mut rascal_indexes_t {
+    new_indexes(|| {
+        check_pointers!(indexes, names);
+        let names = names_from_c(names, count)?;
+        (*indexes).project(&names)
+    })
+}
+
+/// Sort the entries in `indexes` in lexicographic order
+#[no_mangle]
+pub unsafe extern fn rascal_indexes_sort(indexes: *mut rascal_indexes_t) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(indexes);
+        (*indexes).sort();
+        Ok(())
+    })
+}
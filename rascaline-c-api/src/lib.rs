@@ -11,4 +11,6 @@ pub use self::status::{catch_unwind, rascal_status_t};
 
 mod system;
 mod descriptor;
+mod indexes;
+mod blocks;
 mod calculator;
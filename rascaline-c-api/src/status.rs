@@ -26,6 +26,12 @@ pub enum rascal_status_t {
     RASCAL_JSON_ERROR = 2,
     /// A string contains non-utf8 data
     RASCAL_UTF8_ERROR = 3,
+    /// A system contains invalid data
+    RASCAL_SYSTEM_ERROR = 4,
+    /// A user-provided callback returned an error
+    RASCAL_CALLBACK_ERROR = 5,
+    /// There was an error reading or writing a file
+    RASCAL_IO_ERROR = 6,
     /// There was an error of unknown kind
     RASCAL_UNKNOWN_ERROR = 254,
     /// There was an internal error (rust panic)
@@ -41,6 +47,9 @@ impl From<Error> for rascal_status_t {
             Error::InvalidParameter(_) => rascal_status_t::RASCAL_INVALID_PARAMETER_ERROR,
             Error::JSON(_) => rascal_status_t::RASCAL_JSON_ERROR,
             Error::Utf8(_) => rascal_status_t::RASCAL_UTF8_ERROR,
+            Error::InvalidSystem(_) => rascal_status_t::RASCAL_SYSTEM_ERROR,
+            Error::Callback(_) => rascal_status_t::RASCAL_CALLBACK_ERROR,
+            Error::Io(_) => rascal_status_t::RASCAL_IO_ERROR,
             Error::Panic(_) => rascal_status_t::RASCAL_INTERNAL_PANIC,
             _ => rascal_status_t::RASCAL_UNKNOWN_ERROR,
         }
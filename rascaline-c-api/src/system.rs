@@ -2,6 +2,9 @@ use std::os::raw::c_void;
 
 use rascaline::types::{Vector3D, Matrix3};
 use rascaline::system::{System, Pair, UnitCell};
+use rascaline::Error;
+
+use super::status::rascal_status_t;
 
 /// Pair of atoms coming from a neighbor list
 #[repr(C)]
@@ -13,44 +16,62 @@ pub struct rascal_pair_t {
     /// vector from the first atom to the second atom, wrapped inside the unit
     /// cell as required by periodic boundary conditions.
     pub vector: [f64; 3],
+    /// number of unit cell vectors (along a, b and c) that must be added to
+    /// `positions[second] - positions[first]` to get `vector`. This
+    /// distinguishes between different periodic images of the same pair of
+    /// atoms.
+    pub cell_shift: [i32; 3],
 }
 
 /// A `rascal_system_t` deals with the storage of atoms and related information,
 /// as well as the computation of neighbor lists. This structures allow to
 /// implement the rust `System` trait using function pointer.
+///
+/// All the function pointers should return `RASCAL_SUCCESS` if the call
+/// succeeded, and any other `rascal_status_t` value to signal an error. In
+/// the latter case, the error is propagated back to the caller of the
+/// function using this system (e.g. `rascal_calculator_compute`), which will
+/// return `RASCAL_CALLBACK_ERROR`.
 #[repr(C)]
 pub struct rascal_system_t {
     /// User-provided data should be stored here, it will be passed as the
     /// first parameter to all function pointers below.
     user_data: *mut c_void,
     /// This function should set `*size` to the number of atoms in this system
-    size: Option<unsafe extern fn(user_data: *const c_void, size: *mut usize)>,
+    size: Option<unsafe extern fn(user_data: *const c_void, size: *mut usize) -> rascal_status_t>,
     /// This function should set `*species` to a pointer to the first element of
     /// a contiguous array containing the atomic species. Each different atomic
     /// species should be identified with a different value. These values are
     /// usually the atomic number, but don't have to be.
-    species: Option<unsafe extern fn(user_data: *const c_void, species: *mut *const usize)>,
+    species: Option<unsafe extern fn(user_data: *const c_void, species: *mut *const usize) -> rascal_status_t>,
     /// This function should set `*positions` to a pointer to the first element
     /// of a contiguous array containing the atomic cartesian coordinates.
     /// `positions[0], positions[1], positions[2]` must contain the x, y, z
     /// cartesian coordinates of the first atom, and so on.
-    positions: Option<unsafe extern fn(user_data: *const c_void, positions: *mut *const f64)>,
+    positions: Option<unsafe extern fn(user_data: *const c_void, positions: *mut *const f64) -> rascal_status_t>,
     /// This function should write the unit cell matrix in `cell`, which have
     /// space for 9 values.
-    cell: Option<unsafe extern fn(user_data: *const c_void, cell: *mut f64)>,
+    cell: Option<unsafe extern fn(user_data: *const c_void, cell: *mut f64) -> rascal_status_t>,
     /// This function should compute the neighbor list with the given cutoff,
     /// and store it for later access using `pairs` or `pairs_containing`.
-    compute_neighbors: Option<unsafe extern fn(user_data: *mut c_void, cutoff: f64)>,
+    compute_neighbors: Option<unsafe extern fn(user_data: *mut c_void, cutoff: f64) -> rascal_status_t>,
     /// This function should set `*pairs` to a pointer to the first element of a
     /// contiguous array containing all pairs in this system; and `*count` to
     /// the size of the array/the number of pairs.
     ///
     /// This list of pair should only contain each pair once (and not twice as
-    /// `i-j` and `j-i`), should not contain self pairs (`i-i`); and should only
-    /// contains pairs where the distance between atoms is actually bellow the
-    /// cutoff passed in the last call to `compute_neighbors`. This function is
-    /// only valid to call after a call to `compute_neighbors`.
-    pairs: Option<unsafe extern fn(user_data: *const c_void, pairs: *mut *const rascal_pair_t, count: *mut usize)>,
+    /// `i-j` and `j-i`); and should only contains pairs where the distance
+    /// between atoms is actually bellow the cutoff passed in the last call to
+    /// `compute_neighbors`. This function is only valid to call after a call
+    /// to `compute_neighbors`.
+    ///
+    /// In periodic systems with small cells, the same `i-j` pair can appear
+    /// multiple times with different `cell_shift`, once for each periodic
+    /// image of `j` inside the cutoff. Pairs between an atom and its own
+    /// periodic images (`i-i` with a non-zero `cell_shift`) are allowed, but
+    /// only one of the `+cell_shift` and `-cell_shift` images should be
+    /// included.
+    pairs: Option<unsafe extern fn(user_data: *const c_void, pairs: *mut *const rascal_pair_t, count: *mut usize) -> rascal_status_t>,
     /// This function should set `*pairs` to a pointer to the first element of a
     /// contiguous array containing all pairs in this system containing the atom
     /// with index `center`; and `*count` to the size of the array/the number of
@@ -59,79 +80,125 @@ pub struct rascal_system_t {
     /// The same restrictions on the list of pairs as `rascal_system_t::pairs`
     /// applies, with the additional condition that the pair `i-j` should be
     /// included both in the return of `pairs_containing(i)` and
-    /// `pairs_containing(j)`.
-    pairs_containing: Option<unsafe extern fn(user_data: *const c_void, center: usize, pairs: *mut *const rascal_pair_t, count: *mut usize)>,
+    /// `pairs_containing(j)`. Self pairs `i-i` should be included only once in
+    /// `pairs_containing(i)`.
+    pairs_containing: Option<unsafe extern fn(user_data: *const c_void, center: usize, pairs: *mut *const rascal_pair_t, count: *mut usize) -> rascal_status_t>,
+}
+
+/// Get the function pointer `$name` from a `rascal_system_t`, returning an
+/// error if it is NULL
+macro_rules! get_function {
+    ($system: expr, $name: ident) => {
+        $system.$name.ok_or_else(|| Error::InvalidSystem(
+            format!("rascal_system_t.{} function is NULL", stringify!($name))
+        ))?
+    };
+}
+
+/// Convert the `status` returned by the `function` callback of a
+/// `rascal_system_t` to a Rust `Result`
+fn check_status(status: rascal_status_t, function: &str) -> Result<(), Error> {
+    if status == rascal_status_t::RASCAL_SUCCESS {
+        return Ok(());
+    } else {
+        return Err(Error::Callback(format!(
+            "call to rascal_system_t.{} failed with status {:?}", function, status
+        )));
+    }
+}
+
+/// Create a slice from `ptr` and `len`, checking that `ptr` is not NULL if
+/// `len` is not zero. `function` is the name of the callback which returned
+/// `ptr`, used in error messages.
+unsafe fn checked_slice<'a, T>(ptr: *const T, len: usize, function: &str) -> Result<&'a [T], Error> {
+    if len == 0 {
+        return Ok(&[]);
+    }
+
+    if ptr.is_null() {
+        return Err(Error::InvalidSystem(format!(
+            "rascal_system_t.{} returned a NULL pointer", function
+        )));
+    }
+
+    return Ok(std::slice::from_raw_parts(ptr, len));
 }
 
 impl System for rascal_system_t {
-    fn size(&self) -> usize {
+    fn size(&self) -> Result<usize, Error> {
+        let function = get_function!(self, size);
         let mut value = 0;
-        let function = self.size.expect("rascal_system_t.size is NULL");
-        unsafe {
-            function(self.user_data, &mut value);
-        }
-        return value;
+        let status = unsafe {
+            function(self.user_data, &mut value)
+        };
+        check_status(status, "size")?;
+        return Ok(value);
     }
 
-    fn species(&self) -> &[usize] {
+    fn species(&self) -> Result<&[usize], Error> {
+        let function = get_function!(self, species);
         let mut ptr = std::ptr::null();
-        let function = self.species.expect("rascal_system_t.species is NULL");
-        unsafe {
-            function(self.user_data, &mut ptr);
-            // TODO: check if ptr.is_null() and error in some way?
-            return std::slice::from_raw_parts(ptr, self.size());
-        }
+        let status = unsafe {
+            function(self.user_data, &mut ptr)
+        };
+        check_status(status, "species")?;
+        return unsafe { checked_slice(ptr, self.size()?, "species") };
     }
 
-    fn positions(&self) -> &[Vector3D] {
+    fn positions(&self) -> Result<&[Vector3D], Error> {
+        let function = get_function!(self, positions);
         let mut ptr = std::ptr::null();
-        let function = self.positions.expect("rascal_system_t.positions is NULL");
-        unsafe {
-            function(self.user_data, &mut ptr);
-            let slice = std::slice::from_raw_parts(ptr as *const [f64; 3], self.size());
-            return &*(slice as *const [[f64; 3]] as *const [Vector3D]);
-        }
+        let status = unsafe {
+            function(self.user_data, &mut ptr)
+        };
+        check_status(status, "positions")?;
+        let slice = unsafe { checked_slice(ptr as *const [f64; 3], self.size()?, "positions")? };
+        return Ok(unsafe { &*(slice as *const [[f64; 3]] as *const [Vector3D]) });
     }
 
-    fn cell(&self) -> UnitCell {
+    fn cell(&self) -> Result<UnitCell, Error> {
+        let function = get_function!(self, cell);
         let mut value = [[0.0; 3]; 3];
-        let function = self.cell.expect("rascal_system_t.cell is NULL");
-        let matrix: Matrix3 = unsafe {
-            function(self.user_data, &mut value[0][0]);
-            std::mem::transmute(value)
+        let status = unsafe {
+            function(self.user_data, &mut value[0][0])
         };
+        check_status(status, "cell")?;
 
+        let matrix: Matrix3 = unsafe { std::mem::transmute(value) };
         if matrix == Matrix3::zero() {
-            return UnitCell::infinite();
+            return Ok(UnitCell::infinite());
         } else {
-            return UnitCell::from(matrix);
+            return Ok(UnitCell::from(matrix));
         }
     }
 
-    fn compute_neighbors(&mut self, cutoff: f64) {
-        let function = self.compute_neighbors.expect("rascal_system_t.compute_neighbors is NULL");
-        unsafe {
-            function(self.user_data, cutoff);
-        }
+    fn compute_neighbors(&mut self, cutoff: f64) -> Result<(), Error> {
+        let function = get_function!(self, compute_neighbors);
+        let status = unsafe {
+            function(self.user_data, cutoff)
+        };
+        return check_status(status, "compute_neighbors");
     }
 
-    fn pairs(&self) -> &[Pair] {
-        let function = self.pairs.expect("rascal_system_t.pairs is NULL");
+    fn pairs(&self) -> Result<&[Pair], Error> {
+        let function = get_function!(self, pairs);
         let mut ptr = std::ptr::null();
         let mut count = 0;
-        unsafe {
-            function(self.user_data, &mut ptr, &mut count);
-            return std::slice::from_raw_parts(ptr as *const Pair, count);
-        }
+        let status = unsafe {
+            function(self.user_data, &mut ptr, &mut count)
+        };
+        check_status(status, "pairs")?;
+        return unsafe { checked_slice(ptr as *const Pair, count, "pairs") };
     }
 
-    fn pairs_containing(&self, center: usize) -> &[Pair] {
-        let function = self.pairs_containing.expect("rascal_system_t.pairs_containing is NULL");
+    fn pairs_containing(&self, center: usize) -> Result<&[Pair], Error> {
+        let function = get_function!(self, pairs_containing);
         let mut ptr = std::ptr::null();
         let mut count = 0;
-        unsafe {
-            function(self.user_data, center, &mut ptr, &mut count);
-            return std::slice::from_raw_parts(ptr as *const Pair, count);
-        }
+        let status = unsafe {
+            function(self.user_data, center, &mut ptr, &mut count)
+        };
+        check_status(status, "pairs_containing")?;
+        return unsafe { checked_slice(ptr as *const Pair, count, "pairs_containing") };
     }
 }
@@ -0,0 +1,182 @@
+#include <string>
+#include <vector>
+
+#include "rascaline.h"
+#include "catch.hpp"
+#include "helpers.hpp"
+
+const char* HYPERS_JSON = R"({
+    "cutoff": 3.0,
+    "max_neighbors": 2,
+    "cell_gradients": true
+})";
+
+static rascal_calculation_options_t default_options() {
+    return rascal_calculation_options_t {
+        /* use_native_system */ false,
+        /* selected_samples */ nullptr,
+        /* selected_samples_count */ 0,
+        /* selected_features */ nullptr,
+        /* selected_features_count */ 0,
+        /* threads */ 1,
+        /* sparse_gradients */ false,
+    };
+}
+
+static rascal_block_descriptor_t* compute_blocks() {
+    auto* blocks = rascal_block_descriptor();
+    REQUIRE(blocks);
+    auto* calculator = rascal_calculator("sorted_distances", HYPERS_JSON);
+    REQUIRE(calculator);
+    auto system = simple_system();
+
+    const char* keys[] = {"species_center"};
+    CHECK_SUCCESS(rascal_calculator_compute_blocks(
+        calculator, blocks, &system, 1, keys, 1, default_options()
+    ));
+    CHECK_SUCCESS(rascal_calculator_free(calculator));
+
+    return blocks;
+}
+
+static rascal_descriptor_t* compute_descriptor() {
+    auto* descriptor = rascal_descriptor();
+    REQUIRE(descriptor);
+    auto* calculator = rascal_calculator("sorted_distances", HYPERS_JSON);
+    REQUIRE(calculator);
+    auto system = simple_system();
+
+    CHECK_SUCCESS(rascal_calculator_compute(
+        calculator, descriptor, &system, 1, default_options()
+    ));
+    CHECK_SUCCESS(rascal_calculator_free(calculator));
+
+    return descriptor;
+}
+
+static std::vector<int64_t> indexes_values(const rascal_indexes_t* indexes) {
+    const rascal_index_value_t* values = nullptr;
+    uintptr_t count = 0;
+    uintptr_t size = 0;
+    CHECK_SUCCESS(rascal_indexes_values(indexes, &values, &count, &size));
+
+    auto result = std::vector<int64_t>();
+    for (size_t i=0; i<count * size; i++) {
+        CHECK(values[i].index_type == RASCAL_INDEX_INT64);
+        result.push_back(values[i].data.int64);
+    }
+    return result;
+}
+
+TEST_CASE("rascal_block_descriptor_t") {
+    SECTION("compute blocks") {
+        auto* blocks = compute_blocks();
+
+        auto* keys = rascal_block_descriptor_keys(blocks);
+        REQUIRE(keys != nullptr);
+        CHECK(indexes_values(keys) == std::vector<int64_t>{6, 1});
+        CHECK_SUCCESS(rascal_indexes_free(keys));
+
+        auto* samples = rascal_block_descriptor_indexes(blocks, 1, RASCAL_INDEXES_ENVIRONMENTS);
+        REQUIRE(samples != nullptr);
+        const char* names[3] = {nullptr};
+        CHECK_SUCCESS(rascal_indexes_names(samples, names, 3));
+        CHECK(names[0] == std::string("structure"));
+        CHECK(names[1] == std::string("center"));
+        CHECK(names[2] == std::string("species_neighbor"));
+        CHECK(indexes_values(samples) == std::vector<int64_t>{
+            0, 1, 1,
+            0, 1, 6,
+            0, 2, 1,
+            0, 3, 1,
+        });
+        CHECK_SUCCESS(rascal_indexes_free(samples));
+
+        const double* data = nullptr;
+        uintptr_t shape[2] = {0};
+        CHECK_SUCCESS(rascal_block_descriptor_values(blocks, 0, &data, &shape[0], &shape[1]));
+        CHECK(data != nullptr);
+        CHECK(shape[0] == 1);
+        CHECK(shape[1] == 2);
+
+        CHECK_SUCCESS(rascal_block_descriptor_values(blocks, 1, &data, &shape[0], &shape[1]));
+        CHECK(data != nullptr);
+        CHECK(shape[0] == 4);
+        CHECK(shape[1] == 2);
+
+        // sorted distances does not compute gradients
+        CHECK_SUCCESS(rascal_block_descriptor_gradients(blocks, 1, &data, &shape[0], &shape[1]));
+        CHECK(data == nullptr);
+        auto* gradients = rascal_block_descriptor_indexes(blocks, 1, RASCAL_INDEXES_GRADIENTS);
+        CHECK(gradients == nullptr);
+        CHECK(rascal_last_error() == std::string(
+            "invalid parameter: the block does not contain the requested indexes"
+        ));
+
+        CHECK_SUCCESS(rascal_block_descriptor_cell_gradients(blocks, 1, &data, &shape[0], &shape[1]));
+        CHECK(data != nullptr);
+        CHECK(shape[0] == 36);
+        CHECK(shape[1] == 2);
+
+        CHECK(rascal_block_descriptor_values(blocks, 2, &data, &shape[0], &shape[1]) == RASCAL_INVALID_PARAMETER_ERROR);
+        CHECK(rascal_last_error() == std::string(
+            "invalid parameter: block index out of bounds: there are 2 blocks but the index is 2"
+        ));
+
+        CHECK_SUCCESS(rascal_block_descriptor_free(blocks));
+    }
+
+    SECTION("densify") {
+        auto* blocks = compute_blocks();
+        auto* densified = rascal_descriptor();
+        REQUIRE(densified != nullptr);
+        CHECK_SUCCESS(rascal_block_descriptor_densify(blocks, densified));
+        CHECK_SUCCESS(rascal_block_descriptor_free(blocks));
+
+        auto* expected = compute_descriptor();
+        const char* variables[] = {"species_center"};
+        CHECK_SUCCESS(rascal_descriptor_densify(expected, variables, 1));
+
+        const double* data = nullptr;
+        uintptr_t shape[2] = {0};
+        CHECK_SUCCESS(rascal_descriptor_values(densified, &data, &shape[0], &shape[1]));
+
+        const double* expected_data = nullptr;
+        uintptr_t expected_shape[2] = {0};
+        CHECK_SUCCESS(rascal_descriptor_values(expected, &expected_data, &expected_shape[0], &expected_shape[1]));
+
+        CHECK(shape[0] == expected_shape[0]);
+        CHECK(shape[1] == expected_shape[1]);
+        CHECK(std::vector<double>(data, data + shape[0] * shape[1]) == std::vector<double>(
+            expected_data, expected_data + shape[0] * shape[1]
+        ));
+
+        CHECK_SUCCESS(rascal_descriptor_free(densified));
+        CHECK_SUCCESS(rascal_descriptor_free(expected));
+    }
+
+    SECTION("from descriptor") {
+        auto* descriptor = compute_descriptor();
+        auto* blocks = rascal_block_descriptor();
+        REQUIRE(blocks != nullptr);
+
+        const char* variables[] = {"species_center", "species_neighbor"};
+        CHECK_SUCCESS(rascal_block_descriptor_from_descriptor(blocks, descriptor, variables, 2));
+
+        auto* keys = rascal_block_descriptor_keys(blocks);
+        REQUIRE(keys != nullptr);
+        CHECK(indexes_values(keys) == std::vector<int64_t>{6, 1, 1, 1, 1, 6});
+        CHECK_SUCCESS(rascal_indexes_free(keys));
+
+        const char* invalid[] = {"not_there"};
+        auto status = rascal_block_descriptor_from_descriptor(blocks, descriptor, invalid, 1);
+        CHECK(status == RASCAL_INVALID_PARAMETER_ERROR);
+        CHECK(rascal_last_error() == std::string(
+            "invalid parameter: there is no 'not_there' index in these indexes, "
+            "available indexes are [structure, center, species_center, species_neighbor]"
+        ));
+
+        CHECK_SUCCESS(rascal_block_descriptor_free(blocks));
+        CHECK_SUCCESS(rascal_descriptor_free(descriptor));
+    }
+}
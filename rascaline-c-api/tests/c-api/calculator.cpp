@@ -1,3 +1,4 @@
+#include <cmath>
 #include <vector>
 #include <string>
 
@@ -135,6 +136,8 @@ TEST_CASE("Compute descriptor") {
             /* selected_samples_count */ 0,
             /* selected_features */ nullptr,
             /* selected_features_count */ 0,
+            /* threads */ 1,
+            /* sparse_gradients */ false,
         };
         CHECK_SUCCESS(rascal_calculator_compute(
             calculator, descriptor, &system, 1, options
@@ -180,6 +183,53 @@ TEST_CASE("Compute descriptor") {
         }
     }
 
+    SECTION("Sparse gradients") {
+        auto system = simple_system();
+
+        auto options = rascal_calculation_options_t {
+            /* use_native_system */ false,
+            /* selected_samples */ nullptr,
+            /* selected_samples_count */ 0,
+            /* selected_features */ nullptr,
+            /* selected_features_count */ 0,
+            /* threads */ 1,
+            /* sparse_gradients */ true,
+        };
+        CHECK_SUCCESS(rascal_calculator_compute(
+            calculator, descriptor, &system, 1, options
+        ));
+
+        const double* data = nullptr;
+        uintptr_t shape[2] = {0};
+        CHECK_SUCCESS(rascal_descriptor_gradients(descriptor, &data, &shape[0], &shape[1]));
+        CHECK(data == nullptr);
+
+        const uintptr_t* samples = nullptr;
+        const uintptr_t* atoms = nullptr;
+        const uintptr_t* offsets = nullptr;
+        const uintptr_t* features = nullptr;
+        const double* values = nullptr;
+        uintptr_t count = 0;
+        uintptr_t non_zero = 0;
+        CHECK_SUCCESS(rascal_descriptor_sparse_gradients(
+            descriptor, &samples, &atoms, &offsets, &count, &features, &values, &non_zero
+        ));
+
+        // all the dense gradients are (0, 1), so each block contains only
+        // the second feature
+        CHECK(count == 6);
+        CHECK(non_zero == 6);
+        for (size_t i=0; i<count; i++) {
+            CHECK(samples[i] < 4);
+            CHECK(offsets[i] == i);
+            CHECK(features[i] == 1);
+            CHECK(values[3 * i] == 1);
+            CHECK(values[3 * i + 1] == 1);
+            CHECK(values[3 * i + 2] == 1);
+        }
+        CHECK(offsets[count] == 6);
+    }
+
     SECTION("Partial compute -- samples") {
         auto system = simple_system();
 
@@ -193,6 +243,8 @@ TEST_CASE("Compute descriptor") {
             /* selected_samples_count */ samples.size(),
             /* selected_features */ nullptr,
             /* selected_features_count */ 0,
+            /* threads */ 1,
+            /* sparse_gradients */ false,
         };
         CHECK_SUCCESS(rascal_calculator_compute(
             calculator, descriptor, &system, 1, options
@@ -247,6 +299,8 @@ TEST_CASE("Compute descriptor") {
             /* selected_samples_count */ 0,
             /* selected_features */ features.data(),
             /* selected_features_count */ features.size(),
+            /* threads */ 1,
+            /* sparse_gradients */ false,
         };
         CHECK_SUCCESS(rascal_calculator_compute(
             calculator, descriptor, &system, 1, options
@@ -296,6 +350,8 @@ TEST_CASE("Compute descriptor") {
             /* selected_samples_count */ samples.size(),
             /* selected_features */ nullptr,
             /* selected_features_count */ 0,
+            /* threads */ 1,
+            /* sparse_gradients */ false,
         };
         auto status = rascal_calculator_compute(
             calculator, descriptor, &system, 1, options
@@ -310,6 +366,8 @@ TEST_CASE("Compute descriptor") {
             /* selected_samples_count */ 0,
             /* selected_features */ features.data(),
             /* selected_features_count */ features.size(),
+            /* threads */ 1,
+            /* sparse_gradients */ false,
         };
         status = rascal_calculator_compute(
             calculator, descriptor, &system, 1, options
@@ -318,6 +376,56 @@ TEST_CASE("Compute descriptor") {
         CHECK(std::string(rascal_last_error()) == "invalid parameter: wrong size for partial features list, expected a multiple of 3, got 4");
     }
 
+    SECTION("Errors in system callbacks") {
+        auto options = rascal_calculation_options_t {
+            /* use_native_system */ false,
+            /* selected_samples */ nullptr,
+            /* selected_samples_count */ 0,
+            /* selected_features */ nullptr,
+            /* selected_features_count */ 0,
+            /* threads */ 1,
+            /* sparse_gradients */ false,
+        };
+
+        auto system = simple_system();
+        system.size = [](const void* _, uintptr_t* size){
+            return RASCAL_CALLBACK_ERROR;
+        };
+        auto status = rascal_calculator_compute(
+            calculator, descriptor, &system, 1, options
+        );
+        CHECK(status == RASCAL_CALLBACK_ERROR);
+        CHECK(std::string(rascal_last_error()) == "error in callback: call to rascal_system_t.size failed with status RASCAL_CALLBACK_ERROR");
+
+        system = simple_system();
+        system.species = [](const void* _, const uintptr_t** species){
+            *species = nullptr;
+            return RASCAL_SUCCESS;
+        };
+        status = rascal_calculator_compute(
+            calculator, descriptor, &system, 1, options
+        );
+        CHECK(status == RASCAL_SYSTEM_ERROR);
+        CHECK(std::string(rascal_last_error()) == "invalid system: rascal_system_t.species returned a NULL pointer");
+
+        system = simple_system();
+        system.positions = [](const void* _, const double** positions){
+            static double POSITIONS[4][3] = {
+                {0, 0, 0},
+                {1, 1, 1},
+                {2, NAN, 2},
+                {3, 3, 3},
+            };
+            *positions = POSITIONS[0];
+            return RASCAL_SUCCESS;
+        };
+        status = rascal_calculator_compute(
+            calculator, descriptor, &system, 1, options
+        );
+        CHECK(status == RASCAL_SYSTEM_ERROR);
+        CHECK(std::string(rascal_last_error()) == "invalid system: got non-finite position [2.0, NaN, 2.0] for atom 2 in system 0");
+    }
+
     rascal_calculator_free(calculator);
     rascal_descriptor_free(descriptor);
 }
@@ -330,7 +438,7 @@ void check_indexes(
     uintptr_t count,
     uintptr_t size
 ) {
-    const double* actual_values = nullptr;
+    const rascal_index_value_t* actual_values = nullptr;
     uintptr_t actual_count = 0;
     uintptr_t actual_size = 0;
 
@@ -343,7 +451,12 @@ void check_indexes(
 
     for (size_t i=0; i<count; i++) {
         for (size_t j=0; j<size; j++) {
-            CHECK(actual_values[i * size + j] == values[i * size + j]);
+            auto value = actual_values[i * size + j];
+            if (value.index_type == RASCAL_INDEX_INT64) {
+                CHECK(value.data.int64 == values[i * size + j]);
+            } else {
+                CHECK(value.data.float64 == values[i * size + j]);
+            }
         }
     }
 
@@ -1,3 +1,6 @@
+#include <cmath>
+#include <cstdio>
+
 #include "rascaline.h"
 #include "catch.hpp"
 #include "helpers.hpp"
@@ -20,6 +23,8 @@ static void compute_descriptor(rascal_descriptor_t* descriptor) {
         /* selected_samples_count */ 0,
         /* selected_features */ nullptr,
         /* selected_features_count */ 0,
+        /* threads */ 1,
+        /* sparse_gradients */ false,
     };
     CHECK_SUCCESS(rascal_calculator_compute(calculator, descriptor, &system, 1, options));
     CHECK_SUCCESS(rascal_calculator_free(calculator));
@@ -30,14 +35,14 @@ TEST_CASE("rascal_descriptor_t") {
         auto* descriptor = rascal_descriptor();
         REQUIRE(descriptor != nullptr);
 
-        const double* data = nullptr;
+        const rascal_index_value_t* values = nullptr;
         uintptr_t count = 0;
         uintptr_t size = 0;
 
         CHECK_SUCCESS(rascal_descriptor_indexes(
-            descriptor, RASCAL_INDEXES_FEATURES, &data, &count, &size
+            descriptor, RASCAL_INDEXES_FEATURES, &values, &count, &size
         ));
-        CHECK(data == nullptr);
+        CHECK(values == nullptr);
         CHECK(count == 0);
         CHECK(size == 0);
 
@@ -50,18 +55,29 @@ TEST_CASE("rascal_descriptor_t") {
 
         compute_descriptor(descriptor);
         CHECK_SUCCESS(rascal_descriptor_indexes(
-            descriptor, RASCAL_INDEXES_FEATURES, &data, &count, &size
+            descriptor, RASCAL_INDEXES_FEATURES, &values, &count, &size
         ));
-        CHECK(data != nullptr);
+        CHECK(values != nullptr);
         CHECK(count == 2);
         CHECK(size == 3);
 
-        CHECK(data[0 * size + 0] == 1);
-        CHECK(data[0 * size + 1] == 0);
-        CHECK(data[0 * size + 2] == 1.2);
-        CHECK(data[1 * size + 0] == 0);
-        CHECK(data[1 * size + 1] == 1);
-        CHECK(data[1 * size + 2] == 3.2);
+        rascal_index_type_t types[3] = {RASCAL_INDEX_FLOAT64, RASCAL_INDEX_FLOAT64, RASCAL_INDEX_INT64};
+        CHECK_SUCCESS(rascal_descriptor_indexes_types(
+            descriptor, RASCAL_INDEXES_FEATURES, types, 3
+        ));
+        CHECK(types[0] == RASCAL_INDEX_INT64);
+        CHECK(types[1] == RASCAL_INDEX_INT64);
+        CHECK(types[2] == RASCAL_INDEX_FLOAT64);
+
+        CHECK(values[0 * size + 0].index_type == RASCAL_INDEX_INT64);
+        CHECK(values[0 * size + 2].index_type == RASCAL_INDEX_FLOAT64);
+
+        CHECK(values[0 * size + 0].data.int64 == 1);
+        CHECK(values[0 * size + 1].data.int64 == 0);
+        CHECK(values[0 * size + 2].data.float64 == 1.2);
+        CHECK(values[1 * size + 0].data.int64 == 0);
+        CHECK(values[1 * size + 1].data.int64 == 1);
+        CHECK(values[1 * size + 2].data.float64 == 3.2);
 
         CHECK_SUCCESS(rascal_descriptor_indexes_names(
             descriptor, RASCAL_INDEXES_FEATURES, names, 2
@@ -76,14 +92,14 @@ TEST_CASE("rascal_descriptor_t") {
         auto* descriptor = rascal_descriptor();
         REQUIRE(descriptor != nullptr);
 
-        const double* data = nullptr;
+        const rascal_index_value_t* values = nullptr;
         uintptr_t count = 0;
         uintptr_t size = 0;
 
         CHECK_SUCCESS(rascal_descriptor_indexes(
-            descriptor, RASCAL_INDEXES_ENVIRONMENTS, &data, &count, &size
+            descriptor, RASCAL_INDEXES_ENVIRONMENTS, &values, &count, &size
         ));
-        CHECK(data == nullptr);
+        CHECK(values == nullptr);
         CHECK(count == 0);
         CHECK(size == 0);
 
@@ -95,16 +111,16 @@ TEST_CASE("rascal_descriptor_t") {
 
         compute_descriptor(descriptor);
         CHECK_SUCCESS(rascal_descriptor_indexes(
-            descriptor, RASCAL_INDEXES_ENVIRONMENTS, &data, &count, &size
+            descriptor, RASCAL_INDEXES_ENVIRONMENTS, &values, &count, &size
         ));
-        CHECK(data != nullptr);
+        CHECK(values != nullptr);
         CHECK(count == 4);
         CHECK(size == 2);
 
         for (size_t i=0; i<count; i++) {
             // structure 0, atom i
-            CHECK(data[i * size + 0] == 0);
-            CHECK(data[i * size + 1] == i);
+            CHECK(values[i * size + 0].data.int64 == 0);
+            CHECK(values[i * size + 1].data.int64 == i);
         }
 
         CHECK_SUCCESS(rascal_descriptor_indexes_names(
@@ -151,14 +167,14 @@ TEST_CASE("rascal_descriptor_t") {
         auto* descriptor = rascal_descriptor();
         REQUIRE(descriptor != nullptr);
 
-        const double* data = nullptr;
+        const rascal_index_value_t* values = nullptr;
         uintptr_t count = 0;
         uintptr_t size = 0;
 
         CHECK_SUCCESS(rascal_descriptor_indexes(
-            descriptor, RASCAL_INDEXES_GRADIENTS, &data, &count, &size
+            descriptor, RASCAL_INDEXES_GRADIENTS, &values, &count, &size
         ));
-        CHECK(data == nullptr);
+        CHECK(values == nullptr);
         CHECK(count == 0);
         CHECK(size == 0);
 
@@ -172,13 +188,13 @@ TEST_CASE("rascal_descriptor_t") {
 
         compute_descriptor(descriptor);
         CHECK_SUCCESS(rascal_descriptor_indexes(
-            descriptor, RASCAL_INDEXES_GRADIENTS, &data, &count, &size
+            descriptor, RASCAL_INDEXES_GRADIENTS, &values, &count, &size
         ));
-        CHECK(data != nullptr);
+        CHECK(values != nullptr);
         CHECK(count == 18);
         CHECK(size == 4);
 
-        auto expected = std::vector<double> {
+        auto expected = std::vector<int64_t> {
             // structure, atom, neighbor atom, spatial
             /* x */ 0, 0, 1, 0, /* y */ 0, 0, 1, 1, /* z */ 0, 0, 1, 2,
             /* x */ 0, 1, 0, 0, /* y */ 0, 1, 0, 1, /* z */ 0, 1, 0, 2,
@@ -188,7 +204,10 @@ TEST_CASE("rascal_descriptor_t") {
             /* x */ 0, 3, 2, 0, /* y */ 0, 3, 2, 1, /* z */ 0, 3, 2, 2,
         };
 
-        CHECK(std::vector<double>(data, data + (count * size)) == expected);
+        for (size_t i=0; i<count * size; i++) {
+            CHECK(values[i].index_type == RASCAL_INDEX_INT64);
+            CHECK(values[i].data.int64 == expected[i]);
+        }
 
         CHECK_SUCCESS(rascal_descriptor_indexes_names(
             descriptor, RASCAL_INDEXES_GRADIENTS, names, 4
@@ -225,6 +244,74 @@ TEST_CASE("rascal_descriptor_t") {
         CHECK_SUCCESS(rascal_descriptor_free(descriptor));
     }
 
+    SECTION("cell gradient values") {
+        auto* descriptor = rascal_descriptor();
+        REQUIRE(descriptor != nullptr);
+
+        const double* data = nullptr;
+        uintptr_t shape[2] = {0};
+        CHECK_SUCCESS(rascal_descriptor_cell_gradients(descriptor, &data, &shape[0], &shape[1]));
+        CHECK(data == nullptr);
+        CHECK(shape[0] == 0);
+        CHECK(shape[1] == 0);
+
+        // the dummy calculator does not compute cell gradients
+        compute_descriptor(descriptor);
+        CHECK_SUCCESS(rascal_descriptor_cell_gradients(descriptor, &data, &shape[0], &shape[1]));
+        CHECK(data == nullptr);
+
+        auto* calculator = rascal_calculator("sorted_distances", R"({
+            "cutoff": 3.0,
+            "max_neighbors": 2,
+            "cell_gradients": true
+        })");
+        REQUIRE(calculator);
+        auto system = simple_system();
+
+        auto options = rascal_calculation_options_t {
+            /* use_native_system */ false,
+            /* selected_samples */ nullptr,
+            /* selected_samples_count */ 0,
+            /* selected_features */ nullptr,
+            /* selected_features_count */ 0,
+            /* threads */ 1,
+            /* sparse_gradients */ false,
+        };
+        CHECK_SUCCESS(rascal_calculator_compute(calculator, descriptor, &system, 1, options));
+        CHECK_SUCCESS(rascal_calculator_free(calculator));
+
+        CHECK_SUCCESS(rascal_descriptor_cell_gradients(descriptor, &data, &shape[0], &shape[1]));
+        CHECK(shape[0] == 45);
+        CHECK(shape[1] == 2);
+
+        // all pairs have a vector of (1, 1, 1) in a cubic cell of size 10,
+        // and the first feature always corresponds to an actual pair
+        auto expected = 0.1 / std::sqrt(3.0);
+        for (size_t i=0; i<shape[0]; i++) {
+            CHECK(std::abs(data[i * shape[1] + 0] - expected) < 1e-12);
+        }
+
+        const rascal_index_value_t* indexes = nullptr;
+        uintptr_t count = 0;
+        uintptr_t size = 0;
+        CHECK_SUCCESS(rascal_descriptor_indexes(
+            descriptor, RASCAL_INDEXES_CELL_GRADIENTS, &indexes, &count, &size
+        ));
+        CHECK(count == 45);
+        CHECK(size == 6);
+
+        const char* names[6] = {nullptr};
+        CHECK_SUCCESS(rascal_descriptor_indexes_names(
+            descriptor, RASCAL_INDEXES_CELL_GRADIENTS, names, 6
+        ));
+        CHECK(names[0] == std::string("structure"));
+        CHECK(names[3] == std::string("species_neighbor"));
+        CHECK(names[4] == std::string("cell_vector"));
+        CHECK(names[5] == std::string("spatial"));
+
+        CHECK_SUCCESS(rascal_descriptor_free(descriptor));
+    }
+
     SECTION("densify") {
         auto* descriptor = rascal_descriptor();
         REQUIRE(descriptor != nullptr);
@@ -257,4 +344,163 @@ TEST_CASE("rascal_descriptor_t") {
 
         rascal_descriptor_free(descriptor);
     }
+
+    SECTION("save and load") {
+        auto* descriptor = rascal_descriptor();
+        REQUIRE(descriptor != nullptr);
+        compute_descriptor(descriptor);
+
+        CHECK_SUCCESS(rascal_descriptor_save(descriptor, "rascaline-descriptor.npz"));
+
+        auto* loaded = rascal_descriptor();
+        REQUIRE(loaded != nullptr);
+        CHECK_SUCCESS(rascal_descriptor_load(loaded, "rascaline-descriptor.npz"));
+
+        const double* expected = nullptr;
+        const double* data = nullptr;
+        uintptr_t environments = 0;
+        uintptr_t features = 0;
+        CHECK_SUCCESS(rascal_descriptor_values(
+            descriptor, &expected, &environments, &features
+        ));
+        CHECK_SUCCESS(rascal_descriptor_values(
+            loaded, &data, &environments, &features
+        ));
+        CHECK(environments == 4);
+        CHECK(features == 2);
+        for (size_t i=0; i<environments * features; i++) {
+            CHECK(data[i] == expected[i]);
+        }
+
+        CHECK_SUCCESS(rascal_descriptor_gradients(
+            descriptor, &expected, &environments, &features
+        ));
+        CHECK_SUCCESS(rascal_descriptor_gradients(
+            loaded, &data, &environments, &features
+        ));
+        CHECK(environments == 18);
+        CHECK(features == 2);
+        for (size_t i=0; i<environments * features; i++) {
+            CHECK(data[i] == expected[i]);
+        }
+
+        const rascal_index_value_t* indexes = nullptr;
+        uintptr_t count = 0;
+        uintptr_t size = 0;
+        CHECK_SUCCESS(rascal_descriptor_indexes(
+            loaded, RASCAL_INDEXES_GRADIENTS, &indexes, &count, &size
+        ));
+        CHECK(count == 18);
+        CHECK(size == 4);
+        for (size_t i=0; i<count * size; i++) {
+            CHECK(indexes[i].index_type == RASCAL_INDEX_INT64);
+        }
+
+        CHECK(rascal_descriptor_load(loaded, "not-there.npz") != RASCAL_SUCCESS);
+
+        std::remove("rascaline-descriptor.npz");
+        rascal_descriptor_free(loaded);
+        rascal_descriptor_free(descriptor);
+    }
+
+    SECTION("select") {
+        auto* descriptor = rascal_descriptor();
+        REQUIRE(descriptor != nullptr);
+        compute_descriptor(descriptor);
+
+        const char* names[] = {"center"};
+        rascal_index_value_t values[2];
+        values[0].index_type = RASCAL_INDEX_INT64;
+        values[0].data.int64 = 1;
+        values[1].index_type = RASCAL_INDEX_INT64;
+        values[1].data.int64 = 3;
+        auto* samples = rascal_indexes_new(names, 1, values, 2);
+        REQUIRE(samples != nullptr);
+
+        auto* selected = rascal_descriptor();
+        REQUIRE(selected != nullptr);
+        CHECK_SUCCESS(rascal_descriptor_select(descriptor, selected, samples, nullptr));
+
+        const double* data = nullptr;
+        uintptr_t shape[2] = {0};
+        CHECK_SUCCESS(rascal_descriptor_values(selected, &data, &shape[0], &shape[1]));
+        CHECK(shape[0] == 2);
+        CHECK(shape[1] == 2);
+        auto expected = std::vector<double>{6, 9, 8, 15};
+        for (size_t i=0; i<shape[0] * shape[1]; i++) {
+            CHECK(data[i] == expected[i]);
+        }
+
+        CHECK_SUCCESS(rascal_descriptor_gradients(selected, &data, &shape[0], &shape[1]));
+        CHECK(shape[0] == 9);
+        CHECK(shape[1] == 2);
+
+        const rascal_index_value_t* indexes = nullptr;
+        uintptr_t count = 0;
+        uintptr_t size = 0;
+        CHECK_SUCCESS(rascal_descriptor_indexes(
+            selected, RASCAL_INDEXES_GRADIENTS, &indexes, &count, &size
+        ));
+        CHECK(count == 9);
+        CHECK(size == 4);
+        for (size_t i=0; i<count; i++) {
+            CHECK((indexes[i * size + 1].data.int64 == 1 || indexes[i * size + 1].data.int64 == 3));
+        }
+
+        names[0] = "not_there";
+        auto* invalid = rascal_indexes_new(names, 1, values, 2);
+        REQUIRE(invalid != nullptr);
+        CHECK(rascal_descriptor_select(descriptor, selected, nullptr, invalid) == RASCAL_INVALID_PARAMETER_ERROR);
+        CHECK(rascal_last_error() == std::string(
+            "invalid parameter: can not select features using 'not_there', "
+            "which is not part of the features indexes: [index_delta, x_y_z, float]"
+        ));
+
+        rascal_indexes_free(invalid);
+        rascal_indexes_free(samples);
+        rascal_descriptor_free(selected);
+        rascal_descriptor_free(descriptor);
+    }
+
+    SECTION("concatenate") {
+        auto* descriptor = rascal_descriptor();
+        REQUIRE(descriptor != nullptr);
+        compute_descriptor(descriptor);
+
+        auto* concatenated = rascal_descriptor();
+        REQUIRE(concatenated != nullptr);
+
+        const rascal_descriptor_t* descriptors[] = {descriptor, descriptor};
+        CHECK_SUCCESS(rascal_descriptor_concatenate_samples(descriptors, 2, concatenated));
+
+        const double* data = nullptr;
+        uintptr_t shape[2] = {0};
+        CHECK_SUCCESS(rascal_descriptor_values(concatenated, &data, &shape[0], &shape[1]));
+        CHECK(shape[0] == 8);
+        CHECK(shape[1] == 2);
+        CHECK(data[4 * shape[1] + 0] == 5);
+        CHECK(data[4 * shape[1] + 1] == 3);
+
+        CHECK_SUCCESS(rascal_descriptor_gradients(concatenated, &data, &shape[0], &shape[1]));
+        CHECK(shape[0] == 36);
+
+        const rascal_index_value_t* indexes = nullptr;
+        uintptr_t count = 0;
+        uintptr_t size = 0;
+        CHECK_SUCCESS(rascal_descriptor_indexes(
+            concatenated, RASCAL_INDEXES_ENVIRONMENTS, &indexes, &count, &size
+        ));
+        CHECK(count == 8);
+        // structure is renumbered for the second descriptor
+        CHECK(indexes[4 * size + 0].data.int64 == 1);
+        CHECK(indexes[4 * size + 1].data.int64 == 0);
+
+        CHECK(rascal_descriptor_concatenate_features(descriptors, 2, concatenated) == RASCAL_INVALID_PARAMETER_ERROR);
+        CHECK(rascal_last_error() == std::string(
+            "invalid parameter: can not concatenate descriptors: [1, 0, 1.2] is present in multiple descriptors"
+        ));
+
+        rascal_descriptor_free(concatenated);
+        rascal_descriptor_free(descriptor);
+    }
 }
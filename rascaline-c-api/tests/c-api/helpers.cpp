@@ -1,8 +1,6 @@
 #include <cstring>
 #include <cassert>
 
-#include <stdexcept>
-
 #include "helpers.hpp"
 
 rascal_system_t simple_system() {
@@ -11,6 +9,7 @@ rascal_system_t simple_system() {
 
     system.size = [](const void* _, uintptr_t* size){
         *size = 4;
+        return RASCAL_SUCCESS;
     };
 
     system.positions = [](const void* _, const double** positions){
@@ -21,11 +20,13 @@ rascal_system_t simple_system() {
             {3, 3, 3},
         };
         *positions = POSITIONS[0];
+        return RASCAL_SUCCESS;
     };
 
     system.species = [](const void* _, const uintptr_t** species){
         static uintptr_t SPECIES[4] = {6, 1, 1, 1};
         *species = SPECIES;
+        return RASCAL_SUCCESS;
     };
 
     system.cell = [](const void* _, double* cell){
@@ -35,41 +36,44 @@ rascal_system_t simple_system() {
             {0, 0, 10},
         };
         std::memcpy(cell, CELL, sizeof(CELL));
+        return RASCAL_SUCCESS;
     };
 
     // basic compute_neighbors, always returning the same pairs
     system.compute_neighbors = [](void* _, double cutoff){
         assert(cutoff > 1.73205080756887729352 && cutoff < 3.46410161513775458704);
+        return RASCAL_SUCCESS;
     };
 
     system.pairs = [](const void* _, const rascal_pair_t** pairs, uintptr_t* count){
         static rascal_pair_t PAIRS[] = {
-            {0, 1, {1, 1, 1}},
-            {1, 2, {1, 1, 1}},
-            {2, 3, {1, 1, 1}},
+            {0, 1, {1, 1, 1}, {0, 0, 0}},
+            {1, 2, {1, 1, 1}, {0, 0, 0}},
+            {2, 3, {1, 1, 1}, {0, 0, 0}},
         };
 
         *pairs = PAIRS;
         *count = 3;
+        return RASCAL_SUCCESS;
     };
 
     system.pairs_containing = [](const void* _, uintptr_t center, const rascal_pair_t** pairs, uintptr_t* count){
         static rascal_pair_t PAIRS_0[] = {
-            {0, 1, {1, 1, 1}},
+            {0, 1, {1, 1, 1}, {0, 0, 0}},
         };
 
         static rascal_pair_t PAIRS_1[] = {
-            {0, 1, {1, 1, 1}},
-            {1, 2, {1, 1, 1}},
+            {0, 1, {1, 1, 1}, {0, 0, 0}},
+            {1, 2, {1, 1, 1}, {0, 0, 0}},
         };
 
         static rascal_pair_t PAIRS_2[] = {
-            {1, 2, {1, 1, 1}},
-            {2, 3, {1, 1, 1}},
+            {1, 2, {1, 1, 1}, {0, 0, 0}},
+            {2, 3, {1, 1, 1}, {0, 0, 0}},
         };
 
         static rascal_pair_t PAIRS_3[] = {
-            {2, 3, {1, 1, 1}},
+            {2, 3, {1, 1, 1}, {0, 0, 0}},
         };
 
         if (center == 0) {
@@ -85,8 +89,9 @@ rascal_system_t simple_system() {
             *pairs = PAIRS_3;
             *count = 1;
         } else {
-            throw std::runtime_error("got invalid center atom");
+            return RASCAL_INVALID_PARAMETER_ERROR;
         }
+        return RASCAL_SUCCESS;
     };
 
     return system;
@@ -0,0 +1,251 @@
+#include <string>
+#include <vector>
+
+#include "rascaline.h"
+#include "catch.hpp"
+#include "helpers.hpp"
+
+const char* HYPERS_JSON = R"({
+    "cutoff": 3.0,
+    "delta": 5,
+    "name": "bar",
+    "gradients": true
+})";
+
+static rascal_descriptor_t* compute_descriptor() {
+    auto* descriptor = rascal_descriptor();
+    REQUIRE(descriptor);
+    auto* calculator = rascal_calculator("dummy_calculator", HYPERS_JSON);
+    REQUIRE(calculator);
+    auto system = simple_system();
+
+    auto options = rascal_calculation_options_t {
+        /* use_native_system */ false,
+        /* selected_samples */ nullptr,
+        /* selected_samples_count */ 0,
+        /* selected_features */ nullptr,
+        /* selected_features_count */ 0,
+        /* threads */ 1,
+        /* sparse_gradients */ false,
+    };
+    CHECK_SUCCESS(rascal_calculator_compute(calculator, descriptor, &system, 1, options));
+    CHECK_SUCCESS(rascal_calculator_free(calculator));
+
+    return descriptor;
+}
+
+static std::vector<int64_t> indexes_values(const rascal_indexes_t* indexes) {
+    const rascal_index_value_t* values = nullptr;
+    uintptr_t count = 0;
+    uintptr_t size = 0;
+    CHECK_SUCCESS(rascal_indexes_values(indexes, &values, &count, &size));
+
+    auto result = std::vector<int64_t>();
+    for (size_t i=0; i<count * size; i++) {
+        CHECK(values[i].index_type == RASCAL_INDEX_INT64);
+        result.push_back(values[i].data.int64);
+    }
+    return result;
+}
+
+static rascal_index_value_t int_value(int64_t value) {
+    rascal_index_value_t result;
+    result.index_type = RASCAL_INDEX_INT64;
+    result.data.int64 = value;
+    return result;
+}
+
+TEST_CASE("rascal_indexes_t") {
+    SECTION("from descriptor") {
+        auto* descriptor = rascal_descriptor();
+        REQUIRE(descriptor != nullptr);
+
+        auto* indexes = rascal_indexes_from_descriptor(descriptor, RASCAL_INDEXES_GRADIENTS);
+        CHECK(indexes == nullptr);
+        CHECK(rascal_last_error() == std::string(
+            "invalid parameter: the descriptor does not contain the requested indexes"
+        ));
+        CHECK_SUCCESS(rascal_descriptor_free(descriptor));
+
+        descriptor = compute_descriptor();
+        indexes = rascal_indexes_from_descriptor(descriptor, RASCAL_INDEXES_ENVIRONMENTS);
+        REQUIRE(indexes != nullptr);
+        // the indexes stay valid after the descriptor is freed
+        CHECK_SUCCESS(rascal_descriptor_free(descriptor));
+
+        CHECK(indexes_values(indexes) == std::vector<int64_t>{0, 0, 0, 1, 0, 2, 0, 3});
+
+        const char* names[3] = {"foo", "bar", "fizz"};
+        CHECK_SUCCESS(rascal_indexes_names(indexes, names, 3));
+        CHECK(names[0] == std::string("structure"));
+        CHECK(names[1] == std::string("center"));
+        CHECK(names[2] == nullptr);
+
+        rascal_index_type_t types[2] = {RASCAL_INDEX_FLOAT64, RASCAL_INDEX_FLOAT64};
+        CHECK_SUCCESS(rascal_indexes_types(indexes, types, 2));
+        CHECK(types[0] == RASCAL_INDEX_INT64);
+        CHECK(types[1] == RASCAL_INDEX_INT64);
+
+        CHECK_SUCCESS(rascal_indexes_free(indexes));
+    }
+
+    SECTION("new") {
+        const char* names[] = {"structure", "center"};
+        rascal_index_value_t values[] = {int_value(0), int_value(1), int_value(3), int_value(2)};
+        auto* indexes = rascal_indexes_new(names, 2, values, 2);
+        REQUIRE(indexes != nullptr);
+        CHECK(indexes_values(indexes) == std::vector<int64_t>{0, 1, 3, 2});
+
+        const char* indexes_names[2] = {nullptr, nullptr};
+        CHECK_SUCCESS(rascal_indexes_names(indexes, indexes_names, 2));
+        CHECK(indexes_names[0] == std::string("structure"));
+        CHECK(indexes_names[1] == std::string("center"));
+        CHECK_SUCCESS(rascal_indexes_free(indexes));
+
+        values[2] = int_value(0);
+        values[3] = int_value(1);
+        CHECK(rascal_indexes_new(names, 2, values, 2) == nullptr);
+        CHECK(rascal_last_error() == std::string(
+            "invalid parameter: can not have the same index value multiple time: [0, 1] is already present"
+        ));
+
+        values[3].index_type = RASCAL_INDEX_FLOAT64;
+        values[3].data.float64 = 2.5;
+        CHECK(rascal_indexes_new(names, 2, values, 2) == nullptr);
+        CHECK(rascal_last_error() == std::string(
+            "invalid parameter: invalid type for the 'center' index: expected integer from the first entry, got float"
+        ));
+
+        values[3].index_type = static_cast<rascal_index_type_t>(42);
+        CHECK(rascal_indexes_new(names, 2, values, 2) == nullptr);
+        CHECK(rascal_last_error() == std::string(
+            "invalid parameter: invalid index type 42, expected RASCAL_INDEX_INT64 or RASCAL_INDEX_FLOAT64"
+        ));
+
+        indexes = rascal_indexes_new(names, 2, nullptr, 0);
+        REQUIRE(indexes != nullptr);
+        CHECK(indexes_values(indexes).empty());
+        CHECK_SUCCESS(rascal_indexes_free(indexes));
+    }
+
+    SECTION("position") {
+        auto* descriptor = compute_descriptor();
+        auto* indexes = rascal_indexes_from_descriptor(descriptor, RASCAL_INDEXES_ENVIRONMENTS);
+        REQUIRE(indexes != nullptr);
+
+        rascal_index_value_t value[2] = {int_value(0), int_value(2)};
+        int64_t position = 0;
+        CHECK_SUCCESS(rascal_indexes_position(indexes, value, 2, &position));
+        CHECK(position == 2);
+
+        value[1].index_type = RASCAL_INDEX_FLOAT64;
+        value[1].data.float64 = 3.0;
+        CHECK_SUCCESS(rascal_indexes_position(indexes, value, 2, &position));
+        CHECK(position == 3);
+
+        value[1] = int_value(6);
+        CHECK_SUCCESS(rascal_indexes_position(indexes, value, 2, &position));
+        CHECK(position == -1);
+
+        CHECK_SUCCESS(rascal_indexes_free(indexes));
+        CHECK_SUCCESS(rascal_descriptor_free(descriptor));
+    }
+
+    SECTION("filter and project") {
+        auto* descriptor = compute_descriptor();
+        auto* gradients = rascal_indexes_from_descriptor(descriptor, RASCAL_INDEXES_GRADIENTS);
+        REQUIRE(gradients != nullptr);
+
+        const char* names[] = {"spatial", "center"};
+        rascal_index_value_t values[] = {int_value(1), int_value(2)};
+        auto* filtered = rascal_indexes_filter(gradients, names, values, 2);
+        REQUIRE(filtered != nullptr);
+        CHECK(indexes_values(filtered) == std::vector<int64_t>{
+            0, 2, 1, 1,
+            0, 2, 3, 1,
+        });
+        CHECK_SUCCESS(rascal_indexes_free(filtered));
+
+        const char* projected_names[] = {"neighbor", "center"};
+        auto* projected = rascal_indexes_project(gradients, projected_names, 2);
+        REQUIRE(projected != nullptr);
+        CHECK(indexes_values(projected) == std::vector<int64_t>{
+            1, 0,
+            0, 1,
+            2, 1,
+            1, 2,
+            3, 2,
+            2, 3,
+        });
+
+        CHECK_SUCCESS(rascal_indexes_sort(projected));
+        CHECK(indexes_values(projected) == std::vector<int64_t>{
+            0, 1,
+            1, 0,
+            1, 2,
+            2, 1,
+            2, 3,
+            3, 2,
+        });
+
+        const char* missing[] = {"species"};
+        CHECK(rascal_indexes_project(gradients, missing, 1) == nullptr);
+        CHECK(rascal_last_error() == std::string(
+            "invalid parameter: there is no 'species' index in these indexes, "
+            "available indexes are [structure, center, neighbor, spatial]"
+        ));
+
+        CHECK(rascal_indexes_project(gradients, missing, 0) == nullptr);
+        CHECK(rascal_last_error() == std::string(
+            "invalid parameter: at least one index name is required to project indexes"
+        ));
+
+        CHECK_SUCCESS(rascal_indexes_free(projected));
+        CHECK_SUCCESS(rascal_indexes_free(gradients));
+        CHECK_SUCCESS(rascal_descriptor_free(descriptor));
+    }
+
+    SECTION("set operations") {
+        auto* descriptor = compute_descriptor();
+        auto* environments = rascal_indexes_from_descriptor(descriptor, RASCAL_INDEXES_ENVIRONMENTS);
+        REQUIRE(environments != nullptr);
+
+        const char* names[] = {"center"};
+        rascal_index_value_t values[] = {int_value(1)};
+        auto* first = rascal_indexes_filter(environments, names, values, 1);
+        REQUIRE(first != nullptr);
+
+        values[0] = int_value(3);
+        auto* second = rascal_indexes_filter(environments, names, values, 1);
+        REQUIRE(second != nullptr);
+
+        auto* merged = rascal_indexes_union(second, first);
+        REQUIRE(merged != nullptr);
+        CHECK(indexes_values(merged) == std::vector<int64_t>{0, 3, 0, 1});
+
+        auto* intersection = rascal_indexes_intersection(environments, merged);
+        REQUIRE(intersection != nullptr);
+        CHECK(indexes_values(intersection) == std::vector<int64_t>{0, 1, 0, 3});
+
+        auto* difference = rascal_indexes_difference(environments, merged);
+        REQUIRE(difference != nullptr);
+        CHECK(indexes_values(difference) == std::vector<int64_t>{0, 0, 0, 2});
+
+        auto* gradients = rascal_indexes_from_descriptor(descriptor, RASCAL_INDEXES_GRADIENTS);
+        REQUIRE(gradients != nullptr);
+        CHECK(rascal_indexes_union(environments, gradients) == nullptr);
+        CHECK(rascal_last_error() == std::string(
+            "invalid parameter: can not compute the union of indexes with different "
+            "names: [structure, center] and [structure, center, neighbor, spatial]"
+        ));
+
+        CHECK_SUCCESS(rascal_indexes_free(gradients));
+        CHECK_SUCCESS(rascal_indexes_free(difference));
+        CHECK_SUCCESS(rascal_indexes_free(intersection));
+        CHECK_SUCCESS(rascal_indexes_free(merged));
+        CHECK_SUCCESS(rascal_indexes_free(second));
+        CHECK_SUCCESS(rascal_indexes_free(first));
+        CHECK_SUCCESS(rascal_indexes_free(environments));
+        CHECK_SUCCESS(rascal_descriptor_free(descriptor));
+    }
+}
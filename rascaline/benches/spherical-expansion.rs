@@ -1,5 +1,7 @@
+use std::collections::BTreeMap;
+
 use rascaline::calculators::{CalculatorBase, SphericalExpansion, SphericalExpansionParameters};
-use rascaline::calculators::soap::{RadialBasis, CutoffFunction};
+use rascaline::calculators::soap::{RadialBasis, CutoffFunction, RadialScaling};
 
 use rascaline::system::{System, SimpleSystem, UnitCell};
 use rascaline::Descriptor;
@@ -24,7 +26,7 @@ fn spherical_expansion(c: &mut Criterion) {
     group.measurement_time(std::time::Duration::from_secs(10));
 
     let mut system = SimpleSystem::from_xyz(UnitCell::infinite(), TESTING_FRAME);
-    let n_centers = system.size();
+    let n_centers = system.size().unwrap();
     let systems = &mut [&mut system as &mut dyn System];
 
     for &max_radial in black_box(&[2, 8, 14]) {
@@ -35,14 +37,20 @@ fn spherical_expansion(c: &mut Criterion) {
                 cutoff: 4.5,
                 atomic_gaussian_width: 0.5,
                 gradients: false,
+                cell_gradients: false,
                 radial_basis: RadialBasis::GTO,
                 cutoff_function: CutoffFunction::ShiftedCosine{ width: 0.5 },
+                radial_scaling: RadialScaling::None,
+                center_atom_weight: 1.0,
+                species_weights: BTreeMap::new(),
+                species_coupling: None,
+                spline_accuracy: None,
             };
-            let mut calculator = SphericalExpansion::new(parameters);
+            let mut calculator = SphericalExpansion::new(parameters).unwrap();
 
             let mut descriptor = Descriptor::new();
             let environments = calculator.environments();
-            descriptor.prepare(environments.indexes(systems), calculator.features());
+            descriptor.prepare(environments.indexes(systems).unwrap(), calculator.features());
 
             group.bench_function(&format!("n_max = {}, l_max = {}", max_radial, max_angular), |b| b.iter_custom(|repeat| {
                 let start = std::time::Instant::now();
@@ -61,7 +69,7 @@ fn spherical_expansion_gradients(c: &mut Criterion) {
     group.measurement_time(std::time::Duration::from_secs(10));
 
     let mut system = SimpleSystem::from_xyz(UnitCell::infinite(), TESTING_FRAME);
-    let n_centers = system.size();
+    let n_centers = system.size().unwrap();
     let systems = &mut [&mut system as &mut dyn System];
 
     for &max_radial in black_box(&[2, 8, 14]) {
@@ -72,14 +80,20 @@ fn spherical_expansion_gradients(c: &mut Criterion) {
                 cutoff: 4.5,
                 atomic_gaussian_width: 0.5,
                 gradients: true,
+                cell_gradients: false,
                 radial_basis: RadialBasis::GTO,
                 cutoff_function: CutoffFunction::ShiftedCosine{ width: 0.5 },
+                radial_scaling: RadialScaling::None,
+                center_atom_weight: 1.0,
+                species_weights: BTreeMap::new(),
+                species_coupling: None,
+                spline_accuracy: None,
             };
-            let mut calculator = SphericalExpansion::new(parameters);
+            let mut calculator = SphericalExpansion::new(parameters).unwrap();
 
             let mut descriptor = Descriptor::new();
             let environments = calculator.environments();
-            let (samples, gradients) = environments.with_gradients(systems);
+            let (samples, gradients) = environments.with_gradients(systems).unwrap();
             descriptor.prepare_gradients(samples, gradients.unwrap(), calculator.features());
 
             group.bench_function(&format!("n_max = {}, l_max = {}", max_radial, max_angular), |b| b.iter_custom(|repeat| {
@@ -1,6 +1,13 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::ops::Range;
+#[cfg(feature = "rayon")]
+use std::sync::Mutex;
 
-use crate::{SimpleSystem, descriptor::{Descriptor, Indexes, IndexValue, IndexesBuilder}};
+use ndarray::{Array2, s};
+
+use crate::{SimpleSystem, descriptor::{Descriptor, Indexes, IndexValue, IndexType, IndexesBuilder, SparseGradients}};
+use crate::descriptor::{BlockDescriptor, DescriptorBlock};
 use crate::system::System;
 use crate::Error;
 
@@ -9,6 +16,11 @@ use crate::calculators::CalculatorBase;
 pub struct Calculator {
     implementation: Box<dyn CalculatorBase>,
     parameters: String,
+    /// Function used to create new instances of the implementation from the
+    /// parameters, giving one independent instance (with its own scratch
+    /// buffers) per thread in parallel calculations. This is `None` for
+    /// calculators created directly from a `CalculatorBase`.
+    creator: Option<CalculatorCreator>,
 }
 
 /// List of pre-selected indexes on which the user wants to run a calculation
@@ -19,32 +31,21 @@ pub enum SelectedIndexes<'a> {
     Some(Indexes),
     /// Internal use: list of selected indexes as passed through the C API
     #[doc(hidden)]
-    FromC(&'a [IndexValue]),
+    FromC(&'a [f64]),
 }
 
 impl<'a> SelectedIndexes<'a> {
     fn into_features(self, calculator: &dyn CalculatorBase) -> Result<Indexes, Error> {
+        let default = calculator.features();
+        let types = default.types().to_vec();
         let indexes = match self {
-            SelectedIndexes::All => calculator.features(),
+            SelectedIndexes::All => default,
             SelectedIndexes::Some(indexes) => indexes,
-            SelectedIndexes::FromC(list) => {
-                let mut builder = IndexesBuilder::new(calculator.features_names());
-
-                if list.len() % builder.size() != 0 {
-                    return Err(Error::InvalidParameter(format!(
-                        "wrong size for partial features list, expected a multiple of {}, got {}",
-                        builder.size(), list.len()
-                    )))
-                }
-
-                for chunk in list.chunks(builder.size()) {
-                    builder.add(chunk);
-                }
-                builder.finish()
-            }
+            SelectedIndexes::FromC(list) => indexes_from_c("features", calculator.features_names(), list)?,
         };
 
-        calculator.check_features(&indexes);
+        let indexes = convert_types(indexes, &types)?;
+        calculator.check_features(&indexes)?;
         return Ok(indexes);
     }
 
@@ -53,33 +54,64 @@ impl<'a> SelectedIndexes<'a> {
         calculator: &dyn CalculatorBase,
         systems: &mut [&mut dyn System],
     ) -> Result<Indexes, Error> {
+        let environments = calculator.environments();
         let indexes = match self {
-            SelectedIndexes::All => {
-                let environments = calculator.environments();
-                environments.indexes(systems)
-            },
+            SelectedIndexes::All => environments.indexes(systems)?,
             SelectedIndexes::Some(indexes) => indexes,
-            SelectedIndexes::FromC(list) => {
-                let environments = calculator.environments();
-                let mut builder = IndexesBuilder::new(environments.names());
-
-                if list.len() % builder.size() != 0 {
-                    return Err(Error::InvalidParameter(format!(
-                        "wrong size for partial samples list, expected a multiple of {}, got {}",
-                        builder.size(), list.len()
-                    )))
-                }
+            SelectedIndexes::FromC(list) => indexes_from_c("samples", environments.names(), list)?,
+        };
 
-                for chunk in list.chunks(builder.size()) {
-                    builder.add(chunk);
-                }
-                builder.finish()
+        let indexes = convert_types(indexes, &environments.types())?;
+        calculator.check_environments(&indexes, systems)?;
+        return Ok(indexes);
+    }
+}
+
+/// Create `Indexes` with the given `names` from a list of `f64` values
+/// passed through the C API
+fn indexes_from_c(kind: &str, names: Vec<&str>, list: &[f64]) -> Result<Indexes, Error> {
+    let mut builder = IndexesBuilder::new(names);
+    if list.len() % builder.size() != 0 {
+        return Err(Error::InvalidParameter(format!(
+            "wrong size for partial {} list, expected a multiple of {}, got {}",
+            kind, builder.size(), list.len()
+        )))
+    }
+
+    for chunk in list.chunks(builder.size()) {
+        let values = chunk.iter().map(|&value| {
+            if value.is_nan() {
+                return Err(Error::InvalidParameter(format!("got NaN in partial {} list", kind)));
             }
-        };
+            Ok(IndexValue::from(value))
+        }).collect::<Result<Vec<_>, _>>()?;
+        builder.add(&values);
+    }
+
+    return Ok(builder.finish());
+}
 
-        calculator.check_environments(&indexes, systems);
+/// Convert user-provided `indexes` (for example created from `f64` values)
+/// to the `types` expected by the calculator. Indexes with the wrong number
+/// of names are returned as-is, and rejected later when checking the names.
+fn convert_types(indexes: Indexes, types: &[IndexType]) -> Result<Indexes, Error> {
+    if indexes.types() == types || indexes.size() != types.len() {
         return Ok(indexes);
     }
+
+    let mut builder = IndexesBuilder::with_types(indexes.names(), types.to_vec());
+    for values in &indexes {
+        for ((value, &index_type), name) in values.iter().zip(types).zip(indexes.names()) {
+            if value.cast(index_type).is_none() {
+                return Err(Error::InvalidParameter(format!(
+                    "can not convert {} to {} for the '{}' index", value, index_type, name
+                )));
+            }
+        }
+        builder.add(values);
+    }
+
+    return Ok(builder.finish());
 }
 
 /// Parameters specific to a single call to `compute`
@@ -91,6 +123,17 @@ pub struct CalculationOptions<'a> {
     pub selected_samples: SelectedIndexes<'a>,
     /// List of selected features on which to run the computation
     pub selected_features: SelectedIndexes<'a>,
+    /// Number of threads to use for the computation. The default (1) runs
+    /// the computation sequentially, and 0 uses as many threads as there are
+    /// CPU cores. Parallel computations require the `rayon` cargo feature,
+    /// and always use native copies of the systems.
+    pub threads: usize,
+    /// Store the gradients in `Descriptor::sparse_gradients` instead of the
+    /// dense `Descriptor::gradients`. The calculation is then done one system
+    /// at a time, so that the dense gradients are never allocated for all the
+    /// systems at once. The dense gradients of a single system are still
+    /// allocated during the calculation.
+    pub sparse_gradients: bool,
 }
 
 impl<'a> Default for CalculationOptions<'a> {
@@ -99,6 +142,8 @@ impl<'a> Default for CalculationOptions<'a> {
             use_native_system: false,
             selected_samples: SelectedIndexes::All,
             selected_features: SelectedIndexes::All,
+            threads: 1,
+            sparse_gradients: false,
         }
     }
 }
@@ -109,6 +154,7 @@ impl From<Box<dyn CalculatorBase>> for Calculator {
         Calculator {
             implementation: implementation,
             parameters: parameters,
+            creator: None,
         }
     }
 }
@@ -127,6 +173,7 @@ impl Calculator {
         return Ok(Calculator {
             implementation: creator(&parameters)?,
             parameters: parameters,
+            creator: Some(*creator),
         })
     }
 
@@ -154,46 +201,489 @@ impl Calculator {
         descriptor: &mut Descriptor,
         options: CalculationOptions,
     ) -> Result<(), Error> {
+        if options.threads != 1 {
+            if !cfg!(feature = "rayon") {
+                return Err(Error::InvalidParameter(
+                    "parallel calculations require the 'rayon' feature of rascaline".into()
+                ));
+            }
+
+            if self.creator.is_none() {
+                return Err(Error::InvalidParameter(
+                    "parallel calculations are only supported for calculators created with Calculator::new".into()
+                ));
+            }
+        }
+
+        check_systems(systems)?;
+
         let features = options.selected_features.into_features(&*self.implementation)?;
         let samples = options.selected_samples.into_samples(&*self.implementation, systems)?;
 
-        let environments_builder = self.implementation.environments();
-        if self.implementation.compute_gradients() {
-            let gradients = environments_builder
-                .gradients_for(systems, &samples)
-                .expect("this environments definition do not support gradients");
-            descriptor.prepare_gradients(samples, gradients, features);
-        } else {
+        let sparse_gradients = options.sparse_gradients && self.implementation.compute_gradients();
+        if options.threads != 1 || sparse_gradients {
+            // the gradients are computed separately for chunks of samples,
+            // and then added to the descriptor
             descriptor.prepare(samples, features);
+            if self.implementation.compute_cell_gradients() {
+                descriptor.prepare_cell_gradients();
+            }
+        } else {
+            prepare_descriptor(&*self.implementation, systems, descriptor, samples, features)?;
         }
 
-        if options.use_native_system {
-            let mut native_systems = to_native_systems(systems);
+        if options.threads != 1 {
+            #[cfg(feature = "rayon")]
+            self.compute_parallel(systems, descriptor, options.threads, sparse_gradients)?;
+        } else if sparse_gradients {
+            self.compute_sparse(systems, descriptor, options.use_native_system)?;
+        } else if options.use_native_system {
+            let mut native_systems = to_native_systems(systems)?;
             let mut references = Vec::with_capacity(systems.len());
             for system in &mut native_systems {
                 references.push(system as &mut dyn System);
             }
 
-            self.implementation.compute(&mut references, descriptor);
+            self.implementation.compute(&mut references, descriptor)?;
         } else {
-            self.implementation.compute(systems, descriptor);
+            self.implementation.compute(systems, descriptor)?;
         }
 
         return Ok(());
     }
+
+    /// Compute the descriptor for all the given `systems`, and store it as a
+    /// `BlockDescriptor` with one block for each value taken by the `keys`
+    /// sample variables (for example `species_center` and
+    /// `species_neighbor`).
+    ///
+    /// Each block is computed separately using only the samples with the
+    /// corresponding key, so the dense descriptor is never allocated for all
+    /// the samples at once. `options.selected_samples` and
+    /// `options.selected_features` are applied to all blocks, and sparse
+    /// gradients are not supported.
+    pub fn compute_blocks(
+        &mut self,
+        systems: &mut [&mut dyn System],
+        keys: Vec<&str>,
+        options: CalculationOptions,
+    ) -> Result<BlockDescriptor, Error> {
+        if keys.is_empty() {
+            return Err(Error::InvalidParameter(
+                "at least one variable is required as block keys".into()
+            ));
+        }
+
+        if options.sparse_gradients {
+            return Err(Error::InvalidParameter(
+                "sparse gradients are not supported when computing a block descriptor".into()
+            ));
+        }
+
+        if options.use_native_system {
+            // convert the systems once, instead of once for each block
+            let mut native_systems = to_native_systems(systems)?;
+            let mut references = native_systems.iter_mut()
+                .map(|system| system as &mut dyn System)
+                .collect::<Vec<_>>();
+            let options = CalculationOptions { use_native_system: false, ..options };
+            return self.compute_blocks(&mut references, keys, options);
+        }
+
+        check_systems(systems)?;
+
+        let features = options.selected_features.into_features(&*self.implementation)?;
+        let samples = options.selected_samples.into_samples(&*self.implementation, systems)?;
+
+        let keys_values = samples.project(&keys)?;
+        let mut blocks = Vec::with_capacity(keys_values.count());
+        for key in &keys_values {
+            let block_samples = samples.filter(&keys, |values| values == key)?;
+
+            let mut descriptor = Descriptor::new();
+            self.compute(systems, &mut descriptor, CalculationOptions {
+                use_native_system: false,
+                selected_samples: SelectedIndexes::Some(block_samples),
+                selected_features: SelectedIndexes::Some(features.clone()),
+                threads: options.threads,
+                sparse_gradients: false,
+            })?;
+
+            blocks.push(DescriptorBlock::from_key_descriptor(descriptor, &keys)?);
+        }
+
+        return Ok(BlockDescriptor {
+            keys: keys_values,
+            blocks: blocks,
+        });
+    }
+
+    /// Compute the already prepared `descriptor` one system at a time, storing
+    /// the gradients in `descriptor.sparse_gradients`. This ensures that the
+    /// dense gradients are never allocated for all systems at once.
+    ///
+    /// The dense gradients of each system are still computed in a temporary
+    /// descriptor before being converted to sparse gradients, so this does not
+    /// reduce the peak memory use when computing a single large system.
+    fn compute_sparse(
+        &mut self,
+        systems: &mut [&mut dyn System],
+        descriptor: &mut Descriptor,
+        use_native_system: bool,
+    ) -> Result<(), Error> {
+        let n_structures = descriptor.environments.iter()
+            .map(|sample| sample[0])
+            .collect::<BTreeSet<_>>()
+            .len();
+
+        let mut merger = ChunksMerger::new(true);
+        for chunk in split_samples(&descriptor.environments, n_structures) {
+            let SamplesChunk { rows, structures, samples } = chunk;
+
+            let mut native_systems;
+            let mut references: Vec<&mut dyn System> = if use_native_system {
+                native_systems = structures.iter()
+                    .map(|&structure| SimpleSystem::try_from(&*systems[structure] as &dyn System))
+                    .collect::<Result<Vec<_>, _>>()?;
+                native_systems.iter_mut().map(|system| system as &mut dyn System).collect()
+            } else {
+                // structures are sorted, so this gives the systems in the
+                // same order as the chunk structure indexes
+                systems.iter_mut()
+                    .enumerate()
+                    .filter(|(i, _)| structures.binary_search(i).is_ok())
+                    .map(|(_, system)| &mut **system as &mut dyn System)
+                    .collect()
+            };
+
+            let mut chunk_descriptor = Descriptor::new();
+            prepare_descriptor(&*self.implementation, &mut references, &mut chunk_descriptor, samples, descriptor.features.clone())?;
+            self.implementation.compute(&mut references, &mut chunk_descriptor)?;
+
+            merger.merge(descriptor, rows, &structures, chunk_descriptor);
+        }
+        merger.finish(descriptor);
+
+        return Ok(());
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl Calculator {
+    /// Compute the already prepared `descriptor` using `threads` threads.
+    ///
+    /// The samples are split in contiguous chunks, and each thread computes
+    /// some of these chunks in separate descriptors, using its own instance of
+    /// the calculator and native copies of the systems. The native copies are
+    /// created on the calling thread, since `System` implementations are not
+    /// required to be thread-safe. The values and gradients are then copied
+    /// back in the corresponding (disjoint) rows of `descriptor`.
+    fn compute_parallel(
+        &self,
+        systems: &mut [&mut dyn System],
+        descriptor: &mut Descriptor,
+        threads: usize,
+        sparse_gradients: bool,
+    ) -> Result<(), Error> {
+        let creator = self.creator.expect("missing creator for parallel calculation");
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .map_err(|e| Error::InvalidParameter(format!("failed to create threads: {}", e)))?;
+
+        // use more chunks than threads to balance the load between threads
+        let n_threads = pool.current_num_threads();
+        let chunks = split_samples(&descriptor.environments, 4 * n_threads);
+
+        let mut jobs = Vec::with_capacity(chunks.len());
+        for (i_chunk, chunk) in chunks.iter().enumerate() {
+            let native_systems = chunk.structures.iter()
+                .map(|&structure| SimpleSystem::try_from(&*systems[structure] as &dyn System))
+                .collect::<Result<Vec<_>, _>>()?;
+            jobs.push((i_chunk, native_systems));
+        }
+
+        let worker = ChunksWorker {
+            creator: creator,
+            parameters: &self.parameters,
+            features: &descriptor.features,
+            chunks: &chunks,
+            jobs: Mutex::new(jobs.into_iter()),
+        };
+
+        let results = Mutex::new(Vec::with_capacity(chunks.len()));
+        pool.scope(|scope| {
+            for _ in 0..n_threads {
+                scope.spawn(|_| {
+                    let computed = worker.run();
+                    results.lock().expect("mutex was poisoned").push(computed);
+                });
+            }
+        });
+
+        let mut computed = Vec::with_capacity(chunks.len());
+        for result in results.into_inner().expect("mutex was poisoned") {
+            computed.extend(result?);
+        }
+        // the gradients are merged in the same order as the samples
+        computed.sort_by_key(|&(i_chunk, _)| i_chunk);
+        // make sure all the chunks have been computed
+        debug_assert!(computed.iter().map(|&(i_chunk, _)| i_chunk).eq(0..chunks.len()));
+
+        let mut merger = ChunksMerger::new(sparse_gradients);
+        for (chunk, (_, chunk_descriptor)) in chunks.into_iter().zip(computed) {
+            merger.merge(descriptor, chunk.rows, &chunk.structures, chunk_descriptor);
+        }
+        merger.finish(descriptor);
+
+        return Ok(());
+    }
+}
+
+/// Shared state of the threads in a parallel calculation, each thread taking
+/// the next chunk to compute until all chunks are done.
+#[cfg(feature = "rayon")]
+struct ChunksWorker<'a> {
+    creator: CalculatorCreator,
+    parameters: &'a str,
+    features: &'a Indexes,
+    chunks: &'a [SamplesChunk],
+    /// Index and native copies of the systems of the chunks remaining to be
+    /// computed
+    jobs: Mutex<std::vec::IntoIter<(usize, Vec<SimpleSystem>)>>,
+}
+
+#[cfg(feature = "rayon")]
+impl ChunksWorker<'_> {
+    /// Compute chunks until there are none left, using a single instance of
+    /// the calculator for all of them. This returns the index of the computed
+    /// chunks together with the corresponding descriptor.
+    fn run(&self) -> Result<Vec<(usize, Descriptor)>, Error> {
+        let mut calculator = None;
+        let mut computed = Vec::new();
+        loop {
+            let job = self.jobs.lock().expect("mutex was poisoned").next();
+            let (i_chunk, mut native_systems) = match job {
+                Some(job) => job,
+                None => return Ok(computed),
+            };
+            let chunk = &self.chunks[i_chunk];
+
+            if calculator.is_none() {
+                calculator = Some((self.creator)(self.parameters)?);
+            }
+            let calculator = calculator.as_mut().expect("missing calculator");
+
+            let mut references = native_systems.iter_mut()
+                .map(|system| system as &mut dyn System)
+                .collect::<Vec<_>>();
+
+            let mut chunk_descriptor = Descriptor::new();
+            prepare_descriptor(&**calculator, &mut references, &mut chunk_descriptor, chunk.samples.clone(), self.features.clone())?;
+            calculator.compute(&mut references, &mut chunk_descriptor)?;
+
+            computed.push((i_chunk, chunk_descriptor));
+        }
+    }
+}
+
+/// Part of the samples, used to run a calculation on a subset of the systems
+struct SamplesChunk {
+    /// Rows of the full samples in this chunk
+    rows: Range<usize>,
+    /// Sorted indexes of the systems used by this chunk in the full list of
+    /// systems
+    structures: Vec<usize>,
+    /// Samples in this chunk, using the position of the systems in
+    /// `structures` as the structure index
+    samples: Indexes,
+}
+
+/// Split `samples` in about `n_chunks` chunks of contiguous rows. Systems are
+/// only split between multiple chunks if there are less systems than chunks.
+fn split_samples(samples: &Indexes, n_chunks: usize) -> Vec<SamplesChunk> {
+    let n_samples = samples.count();
+    let n_structures = samples.iter().map(|sample| sample[0]).collect::<BTreeSet<_>>().len();
+    let split_systems = n_structures < n_chunks;
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    for i_chunk in 1..=n_chunks {
+        let mut end = usize::max(start, i_chunk * n_samples / n_chunks);
+        if !split_systems {
+            while end > 0 && end < n_samples && samples[end][0] == samples[end - 1][0] {
+                end += 1;
+            }
+        }
+
+        if end == start {
+            continue;
+        }
+
+        let structures = (start..end)
+            .map(|row| samples[row][0].usize())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        let mut builder = IndexesBuilder::new(samples.names());
+        for row in start..end {
+            let mut sample = samples[row].to_vec();
+            let local = structures.binary_search(&sample[0].usize()).expect("missing structure");
+            sample[0] = IndexValue::from(local);
+            builder.add(&sample);
+        }
+
+        chunks.push(SamplesChunk {
+            rows: start..end,
+            structures: structures,
+            samples: builder.finish(),
+        });
+        start = end;
+    }
+
+    return chunks;
+}
+
+/// Copy the values and gradients computed on chunks of samples back into the
+/// full descriptor. The gradients of all chunks are concatenated, either as
+/// dense or sparse gradients.
+struct ChunksMerger {
+    /// Dense gradients indexes for the full descriptor
+    gradients_indexes: Option<IndexesBuilder>,
+    /// Dense gradients values for each chunk
+    gradients: Vec<Array2<f64>>,
+    /// Sparse gradients for the full descriptor
+    sparse_gradients: Option<SparseGradients>,
+}
+
+impl ChunksMerger {
+    fn new(sparse_gradients: bool) -> ChunksMerger {
+        ChunksMerger {
+            gradients_indexes: None,
+            gradients: Vec::new(),
+            sparse_gradients: if sparse_gradients { Some(SparseGradients::new()) } else { None },
+        }
+    }
+
+    fn merge(&mut self, descriptor: &mut Descriptor, rows: Range<usize>, structures: &[usize], chunk: Descriptor) {
+        descriptor.values.slice_mut(s![rows.clone(), ..]).assign(&chunk.values);
+
+        if let Some(cell_gradients) = &mut descriptor.cell_gradients {
+            let chunk_cell_gradients = chunk.cell_gradients.as_ref().expect("missing cell gradients");
+            cell_gradients.slice_mut(s![(9 * rows.start)..(9 * rows.end), ..]).assign(chunk_cell_gradients);
+        }
+
+        if let (Some(gradients), Some(indexes)) = (chunk.gradients, &chunk.gradients_indexes) {
+            if let Some(sparse_gradients) = &mut self.sparse_gradients {
+                sparse_gradients.add_dense(&chunk.environments, indexes, &gradients, rows.start);
+            } else {
+                let builder = self.gradients_indexes.get_or_insert_with(|| IndexesBuilder::new(indexes.names()));
+                for index in indexes {
+                    let mut index = index.to_vec();
+                    index[0] = IndexValue::from(structures[index[0].usize()]);
+                    builder.add(&index);
+                }
+                self.gradients.push(gradients);
+            }
+        }
+    }
+
+    fn finish(self, descriptor: &mut Descriptor) {
+        if let Some(builder) = self.gradients_indexes {
+            let indexes = builder.finish();
+            let mut gradients = Array2::zeros((indexes.count(), descriptor.features.count()));
+            let mut start = 0;
+            for chunk_gradients in &self.gradients {
+                let stop = start + chunk_gradients.nrows();
+                gradients.slice_mut(s![start..stop, ..]).assign(chunk_gradients);
+                start = stop;
+            }
+            descriptor.gradients = Some(gradients);
+            descriptor.gradients_indexes = Some(indexes);
+        }
+
+        if self.sparse_gradients.is_some() {
+            descriptor.sparse_gradients = self.sparse_gradients;
+        }
+    }
+}
+
+/// Allocate memory in `descriptor` for the given `samples` and `features`,
+/// including gradients if the `calculator` computes them
+fn prepare_descriptor(
+    calculator: &dyn CalculatorBase,
+    systems: &mut [&mut dyn System],
+    descriptor: &mut Descriptor,
+    samples: Indexes,
+    features: Indexes,
+) -> Result<(), Error> {
+    if calculator.compute_gradients() {
+        let gradients = calculator.environments()
+            .gradients_for(systems, &samples)?
+            .expect("this environments definition do not support gradients");
+        descriptor.prepare_gradients(samples, gradients, features);
+    } else {
+        descriptor.prepare(samples, features);
+    }
+
+    if calculator.compute_cell_gradients() {
+        descriptor.prepare_cell_gradients();
+    }
+
+    return Ok(());
 }
 
-fn to_native_systems(systems: &mut [&mut dyn System]) -> Vec<SimpleSystem> {
+fn to_native_systems(systems: &mut [&mut dyn System]) -> Result<Vec<SimpleSystem>, Error> {
     let mut native_systems = Vec::with_capacity(systems.len());
     for system in systems.iter() {
-        native_systems.push(SimpleSystem::from(*system as &dyn System));
+        native_systems.push(SimpleSystem::try_from(*system as &dyn System)?);
     }
-    return native_systems;
+    return Ok(native_systems);
+}
+
+/// Check that the data in all `systems` is consistent before running a
+/// calculation: species and positions must contain one value for each atom,
+/// and all positions must be finite.
+fn check_systems(systems: &mut [&mut dyn System]) -> Result<(), Error> {
+    for (i_system, system) in systems.iter().enumerate() {
+        let size = system.size()?;
+
+        let species = system.species()?;
+        if species.len() != size {
+            return Err(Error::InvalidSystem(format!(
+                "system {} contains {} atoms, but got {} species", i_system, size, species.len()
+            )));
+        }
+
+        let positions = system.positions()?;
+        if positions.len() != size {
+            return Err(Error::InvalidSystem(format!(
+                "system {} contains {} atoms, but got {} positions", i_system, size, positions.len()
+            )));
+        }
+
+        for (i_atom, position) in positions.iter().enumerate() {
+            if !position.iter().all(|x| x.is_finite()) {
+                return Err(Error::InvalidSystem(format!(
+                    "got non-finite position {:?} for atom {} in system {}", **position, i_atom, i_system
+                )));
+            }
+        }
+    }
+
+    return Ok(());
 }
 
 /// Registration of calculator implementations
 use crate::calculators::{DummyCalculator, SortedDistances};
 use crate::calculators::{SphericalExpansion, SphericalExpansionParameters};
+use crate::calculators::{LodeSphericalExpansion, LodeSphericalExpansionParameters};
+use crate::calculators::{SoapPowerSpectrum, PowerSpectrumParameters};
+use crate::calculators::{SoapBispectrum, BispectrumParameters};
+use crate::calculators::{SoapRadialSpectrum, RadialSpectrumParameters};
+use crate::calculators::{SymmetryFunctions, SymmetryFunctionsParameters};
+use crate::calculators::{CoulombMatrix, CoulombMatrixParameters};
 type CalculatorCreator = fn(&str) -> Result<Box<dyn CalculatorBase>, Error>;
 
 macro_rules! add_calculator {
@@ -206,7 +696,7 @@ macro_rules! add_calculator {
     ($map :expr, $name :literal, $type :ty, $parameters :ty) => (
         $map.insert($name, (|json| {
             let parameters = serde_json::from_str::<$parameters>(json)?;
-            Ok(Box::new(<$type>::new(parameters)))
+            Ok(Box::new(<$type>::new(parameters)?))
         }) as CalculatorCreator);
     );
 }
@@ -217,6 +707,258 @@ lazy_static::lazy_static!{
         add_calculator!(map, "dummy_calculator", DummyCalculator);
         add_calculator!(map, "sorted_distances", SortedDistances);
         add_calculator!(map, "spherical_expansion", SphericalExpansion, SphericalExpansionParameters);
+        add_calculator!(map, "lode_spherical_expansion", LodeSphericalExpansion, LodeSphericalExpansionParameters);
+        add_calculator!(map, "soap_power_spectrum", SoapPowerSpectrum, PowerSpectrumParameters);
+        add_calculator!(map, "soap_bispectrum", SoapBispectrum, BispectrumParameters);
+        add_calculator!(map, "soap_radial_spectrum", SoapRadialSpectrum, RadialSpectrumParameters);
+        add_calculator!(map, "symmetry_functions", SymmetryFunctions, SymmetryFunctionsParameters);
+        add_calculator!(map, "coulomb_matrix", CoulombMatrix, CoulombMatrixParameters);
         return map;
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::test_systems;
+    use approx::assert_relative_eq;
+
+    fn spherical_expansion() -> Calculator {
+        Calculator::new("spherical_expansion", r#"{
+            "cutoff": 3.5,
+            "max_radial": 4,
+            "max_angular": 3,
+            "atomic_gaussian_width": 0.3,
+            "gradients": true,
+            "cell_gradients": true,
+            "radial_basis": "GTO",
+            "cutoff_function": {"ShiftedCosine": {"width": 0.5}}
+        }"#.into()).unwrap()
+    }
+
+    #[cfg(feature = "rayon")]
+    fn check_parallel(systems: &mut crate::system::SimpleSystems, threads: usize) {
+        let mut calculator = spherical_expansion();
+
+        let mut expected = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut expected, Default::default()).unwrap();
+
+        let mut descriptor = Descriptor::new();
+        let options = CalculationOptions {
+            threads: threads,
+            ..Default::default()
+        };
+        calculator.compute(&mut systems.get(), &mut descriptor, options).unwrap();
+
+        assert!(descriptor.environments == expected.environments);
+        assert_relative_eq!(descriptor.values, expected.values, max_relative = 1e-12);
+
+        assert!(descriptor.gradients_indexes == expected.gradients_indexes);
+        assert_relative_eq!(
+            descriptor.gradients.unwrap(), expected.gradients.unwrap(), max_relative = 1e-12
+        );
+
+        assert!(descriptor.cell_gradients_indexes == expected.cell_gradients_indexes);
+        assert_relative_eq!(
+            descriptor.cell_gradients.unwrap(), expected.cell_gradients.unwrap(), max_relative = 1e-12
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn parallel_systems() {
+        let mut systems = test_systems(&["water", "methane", "CH", "water", "methane", "CH"]);
+        check_parallel(&mut systems, 2);
+        check_parallel(&mut systems, 0);
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn parallel_environments() {
+        // a single system is split between multiple threads
+        let mut systems = test_systems(&["methane"]);
+        check_parallel(&mut systems, 3);
+    }
+
+    #[test]
+    fn sparse_gradients() {
+        let mut systems = test_systems(&["water", "methane", "CH"]);
+        let mut calculator = spherical_expansion();
+
+        let mut dense = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut dense, Default::default()).unwrap();
+
+        let mut descriptor = Descriptor::new();
+        let options = CalculationOptions {
+            sparse_gradients: true,
+            ..Default::default()
+        };
+        calculator.compute(&mut systems.get(), &mut descriptor, options).unwrap();
+
+        assert!(descriptor.environments == dense.environments);
+        assert_relative_eq!(descriptor.values, dense.values, max_relative = 1e-12);
+        assert_relative_eq!(
+            descriptor.cell_gradients.unwrap(), dense.cell_gradients.unwrap(), max_relative = 1e-12
+        );
+        assert!(descriptor.gradients.is_none());
+        assert!(descriptor.gradients_indexes.is_none());
+
+        let sparse = descriptor.sparse_gradients.unwrap();
+        let dense_gradients = dense.gradients.unwrap();
+        let dense_indexes = dense.gradients_indexes.unwrap();
+
+        // the spherical expansion gradients contain a lot of zeros, since
+        // neighbors only contribute to the environments with their species
+        assert!(sparse.non_zero() < dense_gradients.len() / 3);
+
+        let n_environment = dense.environments.size();
+        let mut non_zero = 0;
+        for block in sparse.iter() {
+            let mut index = dense.environments[block.sample].to_vec();
+            index.push(IndexValue::from(block.atom));
+            index.push(IndexValue::from(0_usize));
+            let row = dense_indexes.position(&index).unwrap();
+            assert_eq!(dense_indexes[row + 2][n_environment], IndexValue::from(block.atom));
+
+            for (&feature, values) in block.features.iter().zip(block.values) {
+                for spatial in 0..3 {
+                    assert_eq!(values[spatial], dense_gradients[[row + spatial, feature]]);
+                }
+            }
+            non_zero += block.features.len();
+        }
+
+        // all the non-zero values are in the sparse gradients
+        let n_dense_non_zero = dense_gradients.exact_chunks((3, dense_gradients.ncols()))
+            .into_iter()
+            .map(|block| {
+                (0..block.ncols()).filter(|&feature| block.column(feature).iter().any(|&v| v != 0.0)).count()
+            })
+            .sum::<usize>();
+        assert_eq!(non_zero, n_dense_non_zero);
+        assert_eq!(sparse.non_zero(), n_dense_non_zero);
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn parallel_sparse_gradients() {
+        let mut systems = test_systems(&["water", "methane", "CH"]);
+        let mut calculator = spherical_expansion();
+
+        let mut expected = Descriptor::new();
+        let options = CalculationOptions {
+            sparse_gradients: true,
+            ..Default::default()
+        };
+        calculator.compute(&mut systems.get(), &mut expected, options).unwrap();
+
+        let mut descriptor = Descriptor::new();
+        let options = CalculationOptions {
+            sparse_gradients: true,
+            threads: 2,
+            ..Default::default()
+        };
+        calculator.compute(&mut systems.get(), &mut descriptor, options).unwrap();
+
+        assert_relative_eq!(descriptor.values, expected.values, max_relative = 1e-12);
+        let sparse = descriptor.sparse_gradients.unwrap();
+        let expected = expected.sparse_gradients.unwrap();
+        assert_eq!(sparse.count(), expected.count());
+        for (block, expected) in sparse.iter().zip(expected.iter()) {
+            assert_eq!(block.sample, expected.sample);
+            assert_eq!(block.atom, expected.atom);
+            assert_eq!(block.features, expected.features);
+            for (value, expected) in block.values.iter().zip(expected.values) {
+                assert_relative_eq!(value[..], expected[..], max_relative = 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        let error = Calculator::new("spherical_expansion", r#"{
+            "cutoff": -3.5,
+            "max_radial": 4,
+            "max_angular": 3,
+            "atomic_gaussian_width": 0.3,
+            "gradients": false,
+            "radial_basis": "GTO",
+            "cutoff_function": {"ShiftedCosine": {"width": 0.5}}
+        }"#.into()).err().unwrap();
+        assert!(matches!(error, Error::InvalidParameter(_)));
+        assert_eq!(error.to_string(), "invalid parameter: cutoff must be a positive number");
+
+        let mut calculator = spherical_expansion();
+        let mut systems = test_systems(&["water"]);
+        let mut descriptor = Descriptor::new();
+
+        let mut features = IndexesBuilder::new(vec!["n", "l", "m"]);
+        features.add(&[IndexValue::from(4_usize), IndexValue::from(0_usize), IndexValue::from(0_isize)]);
+        let options = CalculationOptions {
+            selected_features: SelectedIndexes::Some(features.finish()),
+            ..Default::default()
+        };
+        let error = calculator.compute(&mut systems.get(), &mut descriptor, options).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: invalid feature [4, 0, 0]: n must be lower than max_radial (4)");
+
+        let mut features = IndexesBuilder::new(vec!["n", "l"]);
+        features.add(&[IndexValue::from(0_usize), IndexValue::from(0_usize)]);
+        let options = CalculationOptions {
+            selected_features: SelectedIndexes::Some(features.finish()),
+            ..Default::default()
+        };
+        let error = calculator.compute(&mut systems.get(), &mut descriptor, options).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: invalid features names: expected [n, l, m], got [n, l]");
+
+        let mut samples = IndexesBuilder::new(vec!["structure", "center", "species_center", "species_neighbor"]);
+        samples.add(&[IndexValue::from(0_usize), IndexValue::from(5_usize), IndexValue::from(1_usize), IndexValue::from(1_usize)]);
+        let options = CalculationOptions {
+            selected_samples: SelectedIndexes::Some(samples.finish()),
+            ..Default::default()
+        };
+        let error = calculator.compute(&mut systems.get(), &mut descriptor, options).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: [0, 5, 1, 1] is not a valid environment for this calculator");
+    }
+
+    #[test]
+    fn selected_indexes_types() {
+        let mut systems = test_systems(&["water"]);
+        let mut calculator = Calculator::new("dummy_calculator", r#"{
+            "cutoff": 3.0,
+            "delta": 5,
+            "name": "",
+            "gradients": false
+        }"#.into()).unwrap();
+
+        // samples created from f64 are converted to integers
+        let mut samples = IndexesBuilder::new(vec!["structure", "center"]);
+        samples.add(&[IndexValue::from(0.0), IndexValue::from(1.0)]);
+        let options = CalculationOptions {
+            selected_samples: SelectedIndexes::Some(samples.finish()),
+            ..Default::default()
+        };
+
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut descriptor, options).unwrap();
+        assert_eq!(descriptor.environments.types(), &[IndexType::Int, IndexType::Int]);
+        assert_eq!(descriptor.features.types(), &[IndexType::Int, IndexType::Int, IndexType::Float]);
+
+        let options = CalculationOptions {
+            selected_samples: SelectedIndexes::FromC(&[0.0, 1.5]),
+            ..Default::default()
+        };
+        let error = calculator.compute(&mut systems.get(), &mut descriptor, options).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: can not convert 1.5 to integer for the 'center' index");
+    }
+
+    #[test]
+    fn invalid_systems() {
+        let mut systems = test_systems(&["water"]);
+        systems.systems[0].positions_mut()[1][1] = f64::NAN;
+
+        let mut calculator = spherical_expansion();
+        let mut descriptor = Descriptor::new();
+        let error = calculator.compute(&mut systems.get(), &mut descriptor, Default::default()).err().unwrap();
+        assert_eq!(error.to_string(), "invalid system: got non-finite position [0.0, NaN, -0.58895] for atom 1 in system 0");
+    }
+}
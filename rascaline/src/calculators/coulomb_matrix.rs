@@ -0,0 +1,391 @@
+use nalgebra as na;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use crate::descriptor::{IndexesBuilder, IndexValue, Indexes, EnvironmentIndexes, StructureEnvironment};
+use crate::{Descriptor, Error, System};
+
+use super::{CalculatorBase, check_indexes_names};
+
+/// Possible variants of the Coulomb matrix, making it invariant with respect to
+/// the permutation of atoms in the structures.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum CoulombMatrixVariant {
+    /// Rows and columns of the matrix are sorted by decreasing row norm, and
+    /// the upper triangle of the sorted matrix is used as features
+    SortedRowNorm,
+    /// The eigenvalues of the matrix, sorted by decreasing absolute value, are
+    /// used as features
+    Eigenvalues,
+    /// Rows and columns of the matrix are sorted by decreasing row norm after
+    /// adding gaussian noise with standard deviation `noise` to the norms, as
+    /// proposed in "Learning Invariant Representations of Molecules for
+    /// Atomization Energy Prediction", Montavon et al. (2012). The random
+    /// number generator is initialized with `seed`, and each call to compute
+    /// produces a new random sorting.
+    RandomSorted {
+        noise: f64,
+        seed: u64,
+    },
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[allow(clippy::module_name_repetitions)]
+pub struct CoulombMatrixParameters {
+    /// Maximal number of atoms in the structures. Smaller structures are
+    /// padded with zeros to this size.
+    pub max_atoms: usize,
+    /// Which variant of the Coulomb matrix should we compute?
+    pub variant: CoulombMatrixVariant,
+}
+
+/// Calculator implementing the Coulomb matrix, from "Fast and Accurate Modeling
+/// of Molecular Atomization Energies with Machine Learning", Rupp et al.
+/// (2012).
+///
+/// The matrix elements are `M_ii = 0.5 Z_i^2.4` and `M_ij = Z_i Z_j / r_ij`,
+/// where the species of the atoms are used as the nuclear charges `Z`. Each
+/// structure is described by a single feature vector, and this calculator
+/// does not support gradients.
+pub struct CoulombMatrix {
+    parameters: CoulombMatrixParameters,
+    /// random number generator used for the random sorting
+    rng: StdRng,
+}
+
+impl CoulombMatrix {
+    pub fn new(parameters: CoulombMatrixParameters) -> Result<CoulombMatrix, Error> {
+        if parameters.max_atoms == 0 {
+            return Err(Error::InvalidParameter("max_atoms must be at least 1".into()));
+        }
+
+        let seed = if let CoulombMatrixVariant::RandomSorted { noise, seed } = parameters.variant {
+            if !(noise >= 0.0 && noise.is_finite()) {
+                return Err(Error::InvalidParameter("noise must be a positive number".into()));
+            }
+            seed
+        } else {
+            0
+        };
+
+        Ok(CoulombMatrix {
+            parameters: parameters,
+            rng: StdRng::seed_from_u64(seed),
+        })
+    }
+
+    /// Compute the Coulomb matrix for the given system, padded to `max_atoms`
+    fn matrix(&self, system: &dyn System) -> Result<na::DMatrix<f64>, Error> {
+        let size = system.size()?;
+        if size > self.parameters.max_atoms {
+            return Err(Error::InvalidSystem(format!(
+                "a system contains {} atoms, but max_atoms is {}",
+                size, self.parameters.max_atoms
+            )));
+        }
+
+        let species = system.species()?;
+        let positions = system.positions()?;
+        let cell = system.cell()?;
+
+        let max_atoms = self.parameters.max_atoms;
+        let mut matrix = na::DMatrix::zeros(max_atoms, max_atoms);
+        for i in 0..size {
+            let z_i = species[i] as f64;
+            matrix[(i, i)] = 0.5 * f64::powf(z_i, 2.4);
+            for j in (i + 1)..size {
+                let z_j = species[j] as f64;
+                let value = z_i * z_j / cell.distance(&positions[i], &positions[j]);
+                matrix[(i, j)] = value;
+                matrix[(j, i)] = value;
+            }
+        }
+
+        return Ok(matrix);
+    }
+
+    /// Sort the rows and columns of the `matrix` by decreasing values of the
+    /// row norms, with some added gaussian `noise`.
+    fn sort_matrix(&mut self, matrix: &na::DMatrix<f64>, noise: f64) -> na::DMatrix<f64> {
+        let mut norms = Vec::with_capacity(matrix.nrows());
+        for (i, row) in matrix.row_iter().enumerate() {
+            let mut norm = row.norm();
+            if noise > 0.0 {
+                // Box-Muller transform to get normally distributed noise
+                let u1 = 1.0 - self.rng.gen::<f64>();
+                let u2 = self.rng.gen::<f64>();
+                let normal = f64::sqrt(-2.0 * f64::ln(u1)) * f64::cos(2.0 * std::f64::consts::PI * u2);
+                norm += noise * normal;
+            }
+            norms.push((norm, i));
+        }
+        norms.sort_by(|a, b| b.0.partial_cmp(&a.0).expect("got NaN while sorting the Coulomb matrix"));
+
+        let size = matrix.nrows();
+        return na::DMatrix::from_fn(size, size, |i, j| matrix[(norms[i].1, norms[j].1)]);
+    }
+}
+
+impl std::fmt::Debug for CoulombMatrix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.parameters)
+    }
+}
+
+impl CalculatorBase for CoulombMatrix {
+    fn name(&self) -> String {
+        "Coulomb matrix".into()
+    }
+
+    fn get_parameters(&self) -> String {
+        serde_json::to_string(&self.parameters).expect("failed to serialize to JSON")
+    }
+
+    fn features_names(&self) -> Vec<&str> {
+        if self.parameters.variant == CoulombMatrixVariant::Eigenvalues {
+            vec!["eigenvalue"]
+        } else {
+            vec!["i", "j"]
+        }
+    }
+
+    fn features(&self) -> Indexes {
+        let mut features = IndexesBuilder::new(self.features_names());
+        let max_atoms = self.parameters.max_atoms;
+        if self.parameters.variant == CoulombMatrixVariant::Eigenvalues {
+            for i in 0..max_atoms {
+                features.add(&[IndexValue::from(i)]);
+            }
+        } else {
+            for i in 0..max_atoms {
+                for j in i..max_atoms {
+                    features.add(&[IndexValue::from(i), IndexValue::from(j)]);
+                }
+            }
+        }
+        return features.finish();
+    }
+
+    fn environments(&self) -> Box<dyn EnvironmentIndexes> {
+        Box::new(StructureEnvironment)
+    }
+
+    fn compute_gradients(&self) -> bool {
+        false
+    }
+
+    fn check_features(&self, indexes: &Indexes) -> Result<(), Error> {
+        check_indexes_names("features", indexes, &self.features_names())?;
+        for value in indexes {
+            for index in value {
+                if index.usize() >= self.parameters.max_atoms {
+                    return Err(Error::InvalidParameter(format!(
+                        "invalid feature {:?}: all values must be lower than max_atoms ({})",
+                        value, self.parameters.max_atoms
+                    )));
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    fn check_environments(&self, indexes: &Indexes, systems: &mut [&mut dyn System]) -> Result<(), Error> {
+        check_indexes_names("environments", indexes, &["structure"])?;
+        for value in indexes {
+            if value[0].usize() >= systems.len() {
+                return Err(Error::InvalidParameter(format!(
+                    "{:?} is not a valid environment for this calculator", value
+                )));
+            }
+        }
+        return Ok(());
+    }
+
+    fn compute(&mut self, systems: &mut [&mut dyn System], descriptor: &mut Descriptor) -> Result<(), Error> {
+        assert_eq!(descriptor.environments.names(), &["structure"]);
+        assert_eq!(descriptor.features.names(), self.features_names());
+
+        for (i_env, requested_env) in descriptor.environments.iter().enumerate() {
+            let matrix = self.matrix(&*systems[requested_env[0].usize()])?;
+
+            match self.parameters.variant {
+                CoulombMatrixVariant::Eigenvalues => {
+                    let mut eigenvalues = matrix.symmetric_eigenvalues().iter().copied().collect::<Vec<_>>();
+                    eigenvalues.sort_by(|a, b| b.abs().partial_cmp(&a.abs()).expect("got NaN eigenvalue"));
+
+                    for (i_feature, feature) in descriptor.features.iter().enumerate() {
+                        descriptor.values[[i_env, i_feature]] = eigenvalues[feature[0].usize()];
+                    }
+                }
+                CoulombMatrixVariant::SortedRowNorm | CoulombMatrixVariant::RandomSorted { .. } => {
+                    let noise = if let CoulombMatrixVariant::RandomSorted { noise, .. } = self.parameters.variant {
+                        noise
+                    } else {
+                        0.0
+                    };
+                    let sorted = self.sort_matrix(&matrix, noise);
+
+                    for (i_feature, feature) in descriptor.features.iter().enumerate() {
+                        let i = feature[0].usize();
+                        let j = feature[1].usize();
+                        descriptor.values[[i_env, i_feature]] = sorted[(i, j)];
+                    }
+                }
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use ndarray::{s, aview1};
+
+    use crate::system::test_systems;
+    use crate::descriptor::IndexesBuilder;
+    use crate::{Descriptor, Calculator};
+    use crate::{CalculationOptions, SelectedIndexes};
+
+    use super::{CoulombMatrix, CoulombMatrixParameters, CoulombMatrixVariant};
+    use super::super::CalculatorBase;
+
+    /// Convenience macro to create IndexValue
+    macro_rules! v {
+        ($value: expr) => {
+            crate::descriptor::IndexValue::from($value as i64)
+        };
+    }
+
+    fn calculator(variant: CoulombMatrixVariant) -> Calculator {
+        Calculator::from(Box::new(CoulombMatrix::new(CoulombMatrixParameters {
+            max_atoms: 3,
+            variant: variant,
+        }).unwrap()) as Box<dyn CalculatorBase>)
+    }
+
+    #[test]
+    fn sorted_row_norm() {
+        let mut calculator = calculator(CoulombMatrixVariant::SortedRowNorm);
+
+        let mut systems = test_systems(&["CH"]);
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut descriptor, Default::default()).unwrap();
+
+        assert_eq!(descriptor.environments.names(), ["structure"]);
+        assert_eq!(descriptor.features.names(), ["i", "j"]);
+        assert_eq!(descriptor.features.count(), 6);
+        assert!(descriptor.gradients.is_none());
+
+        // the carbon atom has the largest row norm and comes first
+        let carbon = 0.5 * f64::powf(6.0, 2.4);
+        let expected = [carbon, 6.0 / 1.2, 0.0, 0.5, 0.0, 0.0];
+        assert_relative_eq!(descriptor.values.slice(s![0, ..]), aview1(&expected), max_relative=1e-12);
+    }
+
+    #[test]
+    fn eigenvalues() {
+        let mut calculator = calculator(CoulombMatrixVariant::Eigenvalues);
+
+        let mut systems = test_systems(&["CH"]);
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut descriptor, Default::default()).unwrap();
+
+        assert_eq!(descriptor.features.names(), ["eigenvalue"]);
+        assert_eq!(descriptor.features.count(), 3);
+
+        let carbon = 0.5 * f64::powf(6.0, 2.4);
+        let mean = 0.5 * (carbon + 0.5);
+        let delta = f64::sqrt(0.25 * (carbon - 0.5) * (carbon - 0.5) + 25.0);
+        let expected = [mean + delta, mean - delta, 0.0];
+        assert_relative_eq!(descriptor.values.slice(s![0, ..]), aview1(&expected), max_relative=1e-12);
+    }
+
+    #[test]
+    fn random_sorted() {
+        let mut systems = test_systems(&["water", "CH"]);
+
+        // without noise, this is the same as sorting by row norm
+        let mut reference = Descriptor::new();
+        calculator(CoulombMatrixVariant::SortedRowNorm).compute(
+            &mut systems.get(), &mut reference, Default::default()
+        ).unwrap();
+
+        let mut descriptor = Descriptor::new();
+        calculator(CoulombMatrixVariant::RandomSorted { noise: 0.0, seed: 42 }).compute(
+            &mut systems.get(), &mut descriptor, Default::default()
+        ).unwrap();
+        assert_eq!(descriptor.values, reference.values);
+
+        // the same seed gives the same sorting
+        let variant = CoulombMatrixVariant::RandomSorted { noise: 1e6, seed: 42 };
+        let mut first = Descriptor::new();
+        calculator(variant.clone()).compute(&mut systems.get(), &mut first, Default::default()).unwrap();
+        let mut second = Descriptor::new();
+        calculator(variant).compute(&mut systems.get(), &mut second, Default::default()).unwrap();
+        assert_eq!(first.values, second.values);
+
+        // the random sorting only changes the order of the diagonal entries
+        let diagonal = |descriptor: &Descriptor| {
+            let mut values = [0, 3, 5].iter().map(|&i| descriptor.values[[0, i]]).collect::<Vec<_>>();
+            values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            values
+        };
+        assert_eq!(diagonal(&first), diagonal(&reference));
+    }
+
+    #[test]
+    fn compute_partial() {
+        let mut calculator = calculator(CoulombMatrixVariant::SortedRowNorm);
+
+        let mut systems = test_systems(&["water", "CH", "water"]);
+        let mut full = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut full, Default::default()).unwrap();
+
+        let mut samples = IndexesBuilder::new(vec!["structure"]);
+        samples.add(&[v!(1)]);
+        samples.add(&[v!(0)]);
+
+        let mut features = IndexesBuilder::new(vec!["i", "j"]);
+        features.add(&[v!(1), v!(2)]);
+        features.add(&[v!(0), v!(0)]);
+
+        let options = CalculationOptions {
+            selected_samples: SelectedIndexes::Some(samples.finish()),
+            selected_features: SelectedIndexes::Some(features.finish()),
+            ..Default::default()
+        };
+        let mut partial = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut partial, options).unwrap();
+
+        for (env_i, environment) in partial.environments.iter().enumerate() {
+            for (feature_i, feature) in partial.features.iter().enumerate() {
+                let full_env = full.environments.position(environment).unwrap();
+                let full_feature = full.features.position(feature).unwrap();
+                assert_eq!(partial.values[[env_i, feature_i]], full.values[[full_env, full_feature]]);
+            }
+        }
+    }
+
+    #[test]
+    fn too_many_atoms() {
+        let mut calculator = calculator(CoulombMatrixVariant::Eigenvalues);
+        let mut systems = test_systems(&["methane"]);
+        let mut descriptor = Descriptor::new();
+        let error = calculator.compute(&mut systems.get(), &mut descriptor, Default::default()).err().unwrap();
+        assert_eq!(error.to_string(), "invalid system: a system contains 5 atoms, but max_atoms is 3");
+    }
+
+    #[test]
+    fn parameters() {
+        let calculator = Calculator::new("coulomb_matrix", r#"{
+            "max_atoms": 12,
+            "variant": {"RandomSorted": {"noise": 0.5, "seed": 3}}
+        }"#.into()).unwrap();
+
+        let parameters: CoulombMatrixParameters = serde_json::from_str(&calculator.parameters()).unwrap();
+        assert_eq!(parameters.max_atoms, 12);
+        assert_eq!(parameters.variant, CoulombMatrixVariant::RandomSorted { noise: 0.5, seed: 3 });
+    }
+}
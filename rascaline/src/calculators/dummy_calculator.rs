@@ -1,4 +1,6 @@
-use super::CalculatorBase;
+use super::{CalculatorBase, check_indexes_names, check_environments_subset};
+
+use crate::Error;
 
 use crate::descriptor::Descriptor;
 use crate::descriptor::{IndexesBuilder, Indexes, IndexValue, EnvironmentIndexes, AtomEnvironment};
@@ -54,27 +56,24 @@ impl CalculatorBase for DummyCalculator {
         self.gradients
     }
 
-    fn check_features(&self, indexes: &Indexes) {
-        assert_eq!(indexes.names(), &["index_delta", "x_y_z", "float"]);
+    fn check_features(&self, indexes: &Indexes) -> Result<(), Error> {
+        check_indexes_names("features", indexes, &["index_delta", "x_y_z", "float"])?;
         let first = [IndexValue::from(1_usize), IndexValue::from(0_isize), IndexValue::from(1.2)];
         let second = [IndexValue::from(0_usize), IndexValue::from(1_isize), IndexValue::from(3.2)];
         for value in indexes.iter() {
-            assert!(value == first || value == second);
+            if value != first && value != second {
+                return Err(Error::InvalidParameter(format!("{:?} is not a valid feature", value)));
+            }
         }
+        return Ok(());
     }
 
-    fn check_environments(&self, indexes: &Indexes, systems: &mut [&mut dyn System]) {
-        assert_eq!(indexes.names(), ["structure", "center"]);
-        // This could be made much faster by not recomputing the full list of
-        // potential environments
-        let allowed = self.environments().indexes(systems);
-        for value in indexes.iter() {
-            assert!(allowed.contains(value), "{:?} is not a valid environment", value);
-        }
+    fn check_environments(&self, indexes: &Indexes, systems: &mut [&mut dyn System]) -> Result<(), Error> {
+        return check_environments_subset(indexes, &*self.environments(), systems);
     }
 
     #[allow(clippy::clippy::cast_precision_loss)]
-    fn compute(&mut self, systems: &mut [&mut dyn System], descriptor: &mut Descriptor) {
+    fn compute(&mut self, systems: &mut [&mut dyn System], descriptor: &mut Descriptor) -> Result<(), Error> {
         for (i_sample, indexes) in descriptor.environments.iter().enumerate() {
             let i_system = indexes[0].usize();
             let center = indexes[1].usize();
@@ -84,11 +83,11 @@ impl CalculatorBase for DummyCalculator {
                     descriptor.values[[i_sample, i_feature]] = center as f64 + self.delta as f64;
                 } else if feature[1].isize() == 1 {
                     let system = &mut *systems[i_system];
-                    system.compute_neighbors(self.cutoff);
+                    system.compute_neighbors(self.cutoff)?;
 
-                    let positions = system.positions();
+                    let positions = system.positions()?;
                     let mut sum = positions[center][0] + positions[center][1] + positions[center][2];
-                    for pair in system.pairs() {
+                    for pair in system.pairs()? {
                         if pair.first == center {
                             sum += positions[pair.second][0] + positions[pair.second][1] + positions[pair.second][2];
                         }
@@ -119,6 +118,8 @@ impl CalculatorBase for DummyCalculator {
                 }
             }
         }
+
+        return Ok(());
     }
 }
 
@@ -1,5 +1,6 @@
 use crate::descriptor::{Descriptor, Indexes, EnvironmentIndexes};
 use crate::system::System;
+use crate::Error;
 
 /// TODO: docs
 ///
@@ -21,15 +22,22 @@ pub trait CalculatorBase: std::panic::RefUnwindSafe {
     fn environments(&self) -> Box<dyn EnvironmentIndexes>;
     /// Does this environment compute gradients?
     fn compute_gradients(&self) -> bool;
+    /// Does this calculator compute gradients with respect to the unit cell?
+    /// This is opt-in, and only supported by some calculators.
+    fn compute_cell_gradients(&self) -> bool {
+        false
+    }
 
     /// Check that the given indexes are valid feature indexes for this
     /// Calculator. This is used by `Calculator::compute_partial` to ensure
-    /// only valid features are requested
-    fn check_features(&self, indexes: &Indexes);
+    /// only valid features are requested, returning an error describing the
+    /// first invalid feature otherwise.
+    fn check_features(&self, indexes: &Indexes) -> Result<(), Error>;
     /// Check that the given indexes are valid environment indexes for this
     /// Calculator. This is used by `Calculator::compute_partial` to ensure
-    /// only valid environments are requested
-    fn check_environments(&self, indexes: &Indexes, systems: &mut [&mut dyn System]);
+    /// only valid environments are requested, returning an error describing
+    /// the first invalid environment otherwise.
+    fn check_environments(&self, indexes: &Indexes, systems: &mut [&mut dyn System]) -> Result<(), Error>;
 
     /// Core implementation of the descriptor.
     ///
@@ -39,7 +47,40 @@ pub trait CalculatorBase: std::panic::RefUnwindSafe {
     /// environments and features coming from `Descriptor::environments` and
     /// `Descriptor::features` respectively; but this can be overrode to only
     /// compute them on a subset though `Descriptor::compute_partial`.
-    fn compute(&mut self, systems: &mut [&mut dyn System], descriptor: &mut Descriptor);
+    fn compute(&mut self, systems: &mut [&mut dyn System], descriptor: &mut Descriptor) -> Result<(), Error>;
+}
+
+/// Check that the `indexes` names are the `expected` ones, `kind` being used
+/// to describe the indexes (e.g. "features") in the error message.
+pub(crate) fn check_indexes_names(kind: &str, indexes: &Indexes, expected: &[&str]) -> Result<(), Error> {
+    let names = indexes.names();
+    if names != expected {
+        return Err(Error::InvalidParameter(format!(
+            "invalid {} names: expected [{}], got [{}]", kind, expected.join(", "), names.join(", ")
+        )));
+    }
+    return Ok(());
+}
+
+/// Check that the `indexes` are a subset of the default `environments` for
+/// the given `systems`.
+pub(crate) fn check_environments_subset(
+    indexes: &Indexes,
+    environments: &dyn EnvironmentIndexes,
+    systems: &mut [&mut dyn System],
+) -> Result<(), Error> {
+    check_indexes_names("environments", indexes, &environments.names())?;
+    // This could be made much faster by not recomputing the full list of
+    // potential environments
+    let allowed = environments.indexes(systems)?;
+    for value in indexes.iter() {
+        if !allowed.contains(value) {
+            return Err(Error::InvalidParameter(format!(
+                "{:?} is not a valid environment for this calculator", value
+            )));
+        }
+    }
+    return Ok(());
 }
 
 mod sorted_distances;
@@ -50,3 +91,13 @@ pub use self::dummy_calculator::DummyCalculator;
 
 pub mod soap;
 pub use self::soap::{SphericalExpansion, SphericalExpansionParameters};
+pub use self::soap::{LodeSphericalExpansion, LodeSphericalExpansionParameters};
+pub use self::soap::{SoapPowerSpectrum, PowerSpectrumParameters};
+pub use self::soap::{SoapBispectrum, BispectrumParameters};
+pub use self::soap::{SoapRadialSpectrum, RadialSpectrumParameters};
+
+mod symmetry_functions;
+pub use self::symmetry_functions::{SymmetryFunctions, SymmetryFunctionsParameters, SymmetryFunction};
+
+mod coulomb_matrix;
+pub use self::coulomb_matrix::{CoulombMatrix, CoulombMatrixParameters, CoulombMatrixVariant};
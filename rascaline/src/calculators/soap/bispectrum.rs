@@ -0,0 +1,597 @@
+use std::collections::BTreeMap;
+
+use ndarray::{ArrayView1, s};
+
+use crate::descriptor::{Descriptor, Indexes, IndexesBuilder, IndexValue};
+use crate::descriptor::{EnvironmentIndexes, FourBodiesSpeciesEnvironment};
+use crate::{Calculator, Error, System};
+
+use super::super::{CalculatorBase, check_indexes_names, check_environments_subset};
+use super::{SphericalExpansion, SphericalExpansionParameters};
+use super::{RadialBasis, CutoffFunction, RadialScaling};
+use super::ClebschGordan;
+use super::expansion_request::{ExpansionRequest, gradients_positions};
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[allow(clippy::module_name_repetitions)]
+pub struct BispectrumParameters {
+    /// Spherical cutoff to use for atomic environments
+    pub cutoff: f64,
+    /// Number of radial basis function to use
+    pub max_radial: usize,
+    /// Number of spherical harmonics to use
+    pub max_angular: usize,
+    /// Width of the atom-centered gaussian creating the atomic density
+    pub atomic_gaussian_width: f64,
+    /// Should we also compute gradients of the feature?
+    pub gradients: bool,
+    /// radial basis to use for the radial integral
+    pub radial_basis: RadialBasis,
+    /// cutoff function used to smooth the behavior around the cutoff radius
+    pub cutoff_function: CutoffFunction,
+    /// If set, the radial integral is evaluated using splines with the given
+    /// absolute accuracy instead of being computed directly, which is much
+    /// faster for large numbers of neighbors
+    pub spline_accuracy: Option<f64>,
+}
+
+/// Calculator implementing the SOAP bispectrum, i.e. the rotationally
+/// invariant combination of three spherical expansion coefficients:
+///
+/// `b[α, β1, β2, β3](n1, n2, n3, l1, l2, l3) = 1 / sqrt(2 l3 + 1)
+///     Σ_m1 Σ_m2 Σ_m3 <l1 m1; l2 m2 | l3 m3> c[α, β1](n1, l1, m1) c[α, β2](n2, l2, m2) c[α, β3](n3, l3, m3)`
+///
+/// where `<l1 m1; l2 m2 | l3 m3>` are Clebsch-Gordan coefficients for real
+/// spherical harmonics. Only the `(l1, l2, l3)` satisfying the triangle
+/// condition with `l1 + l2 + l3` even are included, which makes the bispectrum
+/// invariant under inversion as well as rotations.
+///
+/// Only the `β1 <= β2 <= β3` triplets are stored, and the corresponding values
+/// are multiplied by the square root of the number of distinct permutations of
+/// `(β1, β2, β3)`, to keep the same scalar product as the full bispectrum.
+pub struct SoapBispectrum {
+    parameters: BispectrumParameters,
+    spherical_expansion: Calculator,
+    clebsch_gordan: ClebschGordan,
+}
+
+impl SoapBispectrum {
+    pub fn new(parameters: BispectrumParameters) -> Result<SoapBispectrum, Error> {
+        let expansion_parameters = SphericalExpansionParameters {
+            cutoff: parameters.cutoff,
+            max_radial: parameters.max_radial,
+            max_angular: parameters.max_angular,
+            atomic_gaussian_width: parameters.atomic_gaussian_width,
+            gradients: parameters.gradients,
+            cell_gradients: false,
+            radial_basis: parameters.radial_basis.clone(),
+            cutoff_function: parameters.cutoff_function.clone(),
+            radial_scaling: RadialScaling::None,
+            center_atom_weight: 1.0,
+            species_weights: BTreeMap::new(),
+            species_coupling: None,
+            spline_accuracy: parameters.spline_accuracy,
+        };
+
+        let spherical_expansion = Calculator::from(Box::new(
+            SphericalExpansion::new(expansion_parameters)?
+        ) as Box<dyn CalculatorBase>);
+
+        let clebsch_gordan = ClebschGordan::new(parameters.max_angular);
+
+        return Ok(SoapBispectrum {
+            parameters: parameters,
+            spherical_expansion: spherical_expansion,
+            clebsch_gordan: clebsch_gordan,
+        });
+    }
+
+    /// Compute the contraction of three blocks of spherical expansion
+    /// coefficients (each containing all the `m` for a given `l`) with the
+    /// Clebsch-Gordan coefficients for `(l1, l2, l3)`.
+    fn contract(&self, l: [usize; 3], block_1: ArrayView1<f64>, block_2: ArrayView1<f64>, block_3: ArrayView1<f64>) -> f64 {
+        let mut sum = 0.0;
+        for coefficient in self.clebsch_gordan.coefficients(l[0], l[1], l[2]) {
+            let m1 = (coefficient.m1 + l[0] as isize) as usize;
+            let m2 = (coefficient.m2 + l[1] as isize) as usize;
+            let m3 = (coefficient.m3 + l[2] as isize) as usize;
+            sum += coefficient.value * block_1[m1] * block_2[m2] * block_3[m3];
+        }
+        return sum / f64::sqrt((2 * l[2] + 1) as f64);
+    }
+}
+
+impl std::fmt::Debug for SoapBispectrum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.parameters)
+    }
+}
+
+/// Are `l1`, `l2` and `l3` a valid set of angular channels for the bispectrum?
+fn valid_angular(l1: usize, l2: usize, l3: usize) -> bool {
+    let triangle = l3 + l1 >= l2 && l3 + l2 >= l1 && l3 <= l1 + l2;
+    return triangle && (l1 + l2 + l3) % 2 == 0;
+}
+
+/// Get the factor accounting for the number of distinct permutations of the
+/// species triplet `(β1, β2, β3)`
+fn species_factor(species_1: IndexValue, species_2: IndexValue, species_3: IndexValue) -> f64 {
+    if species_1 == species_2 && species_2 == species_3 {
+        1.0
+    } else if species_1 == species_2 || species_2 == species_3 || species_1 == species_3 {
+        f64::sqrt(3.0)
+    } else {
+        f64::sqrt(6.0)
+    }
+}
+
+/// Get the set of spherical expansion samples & features required to compute
+/// the bispectrum samples & features in `descriptor`
+fn expansion_request(bispectrum: &Descriptor) -> ExpansionRequest {
+    let mut request = ExpansionRequest::new();
+    for sample in &bispectrum.environments {
+        let (structure, center, species_center) = (sample[0], sample[1], sample[2]);
+        request.add_sample([structure, center, species_center, sample[3]]);
+        request.add_sample([structure, center, species_center, sample[4]]);
+        request.add_sample([structure, center, species_center, sample[5]]);
+    }
+
+    for feature in &bispectrum.features {
+        request.add_block(feature[0].usize(), feature[3].usize());
+        request.add_block(feature[1].usize(), feature[4].usize());
+        request.add_block(feature[2].usize(), feature[5].usize());
+    }
+
+    return request;
+}
+
+impl CalculatorBase for SoapBispectrum {
+    fn name(&self) -> String {
+        "SOAP bispectrum".into()
+    }
+
+    fn get_parameters(&self) -> String {
+        serde_json::to_string(&self.parameters).expect("failed to serialize to JSON")
+    }
+
+    fn features_names(&self) -> Vec<&str> {
+        vec!["n1", "n2", "n3", "l1", "l2", "l3"]
+    }
+
+    fn features(&self) -> Indexes {
+        let max_radial = self.parameters.max_radial;
+        let max_angular = self.parameters.max_angular;
+
+        let mut features = IndexesBuilder::new(self.features_names());
+        for n1 in 0..max_radial {
+            for n2 in 0..max_radial {
+                for n3 in 0..max_radial {
+                    for l1 in 0..(max_angular + 1) {
+                        for l2 in 0..(max_angular + 1) {
+                            for l3 in 0..(max_angular + 1) {
+                                if !valid_angular(l1, l2, l3) {
+                                    continue;
+                                }
+
+                                features.add(&[
+                                    IndexValue::from(n1), IndexValue::from(n2), IndexValue::from(n3),
+                                    IndexValue::from(l1), IndexValue::from(l2), IndexValue::from(l3),
+                                ]);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        return features.finish();
+    }
+
+    fn environments(&self) -> Box<dyn EnvironmentIndexes> {
+        Box::new(FourBodiesSpeciesEnvironment::with_self_contribution(self.parameters.cutoff))
+    }
+
+    fn compute_gradients(&self) -> bool {
+        self.parameters.gradients
+    }
+
+    fn check_features(&self, indexes: &Indexes) -> Result<(), Error> {
+        check_indexes_names("features", indexes, &self.features_names())?;
+        for value in indexes {
+            let (n1, n2, n3) = (value[0].usize(), value[1].usize(), value[2].usize());
+            if n1 >= self.parameters.max_radial || n2 >= self.parameters.max_radial || n3 >= self.parameters.max_radial {
+                return Err(Error::InvalidParameter(format!(
+                    "invalid feature {:?}: n1, n2 and n3 must be lower than max_radial ({})",
+                    value, self.parameters.max_radial
+                )));
+            }
+
+            let (l1, l2, l3) = (value[3].usize(), value[4].usize(), value[5].usize());
+            if l1 > self.parameters.max_angular || l2 > self.parameters.max_angular || l3 > self.parameters.max_angular {
+                return Err(Error::InvalidParameter(format!(
+                    "invalid feature {:?}: l1, l2 and l3 must be at most max_angular ({})",
+                    value, self.parameters.max_angular
+                )));
+            }
+
+            if !valid_angular(l1, l2, l3) {
+                return Err(Error::InvalidParameter(format!("invalid angular channels in {:?}", value)));
+            }
+        }
+        return Ok(());
+    }
+
+    fn check_environments(&self, indexes: &Indexes, systems: &mut [&mut dyn System]) -> Result<(), Error> {
+        return check_environments_subset(indexes, &*self.environments(), systems);
+    }
+
+    #[allow(clippy::similar_names)]
+    fn compute(&mut self, systems: &mut [&mut dyn System], descriptor: &mut Descriptor) -> Result<(), Error> {
+        assert_eq!(descriptor.environments.names(), &["structure", "center", "species_center", "species_neighbor_1", "species_neighbor_2", "species_neighbor_3"]);
+        assert_eq!(descriptor.features.names(), &["n1", "n2", "n3", "l1", "l2", "l3"]);
+
+        let request = expansion_request(descriptor);
+        let expansion = request.compute(&mut self.spherical_expansion, systems)?;
+
+        for (i_env, requested_env) in descriptor.environments.iter().enumerate() {
+            let (structure, center, species_center) = (requested_env[0], requested_env[1], requested_env[2]);
+            let species_neighbor = [requested_env[3], requested_env[4], requested_env[5]];
+
+            let mut envs = [0; 3];
+            for i in 0..3 {
+                envs[i] = request.sample(&[structure, center, species_center, species_neighbor[i]])
+                    .expect("missing spherical expansion environment");
+            }
+
+            let factor = species_factor(species_neighbor[0], species_neighbor[1], species_neighbor[2]);
+
+            for (i_feature, feature) in descriptor.features.iter().enumerate() {
+                let l = [feature[3].usize(), feature[4].usize(), feature[5].usize()];
+                let block = |i: usize| {
+                    let start = request.block(feature[i].usize(), l[i]);
+                    expansion.values.slice(s![envs[i], start..(start + 2 * l[i] + 1)])
+                };
+
+                descriptor.values[[i_env, i_feature]] = factor * self.contract(l, block(0), block(1), block(2));
+            }
+        }
+
+        if self.parameters.gradients {
+            let expansion_gradients = expansion.gradients.as_ref().expect("missing spherical expansion gradients");
+            let expansion_gradients_positions = gradients_positions(&expansion);
+
+            let gradients = descriptor.gradients.as_mut().expect("missing storage for gradients");
+            let gradients_indexes = descriptor.gradients_indexes.as_ref().expect("missing gradients indexes");
+            assert_eq!(gradients_indexes.names(), &["structure", "center", "species_center", "species_neighbor_1", "species_neighbor_2", "species_neighbor_3", "neighbor", "spatial"]);
+
+            for (i_grad, index) in gradients_indexes.iter().enumerate() {
+                if index[7].usize() != 0 {
+                    // all spatial components are computed at the same time
+                    continue;
+                }
+
+                let (structure, center, species_center) = (index[0], index[1], index[2]);
+                let species_neighbor = [index[3], index[4], index[5]];
+                let neighbor = index[6];
+
+                let mut envs = [0; 3];
+                let mut grads = [None; 3];
+                for i in 0..3 {
+                    envs[i] = request.sample(&[structure, center, species_center, species_neighbor[i]])
+                        .expect("missing spherical expansion environment");
+                    grads[i] = expansion_gradients_positions
+                        .get(&[structure, center, species_center, species_neighbor[i], neighbor])
+                        .copied();
+                }
+
+                let factor = species_factor(species_neighbor[0], species_neighbor[1], species_neighbor[2]);
+
+                for (i_feature, feature) in descriptor.features.iter().enumerate() {
+                    let l = [feature[3].usize(), feature[4].usize(), feature[5].usize()];
+                    let start = |i: usize| request.block(feature[i].usize(), l[i]);
+                    let block = |i: usize| {
+                        expansion.values.slice(s![envs[i], start(i)..(start(i) + 2 * l[i] + 1)])
+                    };
+
+                    for spatial in 0..3 {
+                        let gradient_block = |grad: usize, i: usize| {
+                            expansion_gradients.slice(s![grad + spatial, start(i)..(start(i) + 2 * l[i] + 1)])
+                        };
+
+                        // product rule over the three coefficients
+                        let mut sum = 0.0;
+                        if let Some(grad) = grads[0] {
+                            sum += self.contract(l, gradient_block(grad, 0), block(1), block(2));
+                        }
+                        if let Some(grad) = grads[1] {
+                            sum += self.contract(l, block(0), gradient_block(grad, 1), block(2));
+                        }
+                        if let Some(grad) = grads[2] {
+                            sum += self.contract(l, block(0), block(1), gradient_block(grad, 2));
+                        }
+
+                        // assumes that the three spatial derivative are stored
+                        // one after the other
+                        gradients[[i_grad + spatial, i_feature]] = factor * sum;
+                    }
+                }
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::system::test_systems;
+    use crate::descriptor::IndexesBuilder;
+    use crate::{Descriptor, Calculator, System, Matrix3, Vector3D};
+    use crate::{CalculationOptions, SelectedIndexes};
+
+    use approx::assert_relative_eq;
+    use ndarray::s;
+
+    use super::{SoapBispectrum, BispectrumParameters};
+    use super::super::{CutoffFunction, RadialBasis};
+    use super::super::super::CalculatorBase;
+
+    /// Convenience macro to create IndexValue
+    macro_rules! v {
+        ($value: expr) => {
+            crate::descriptor::IndexValue::from($value as i64)
+        };
+    }
+
+    fn parameters(gradients: bool) -> BispectrumParameters {
+        BispectrumParameters {
+            atomic_gaussian_width: 0.3,
+            cutoff: 3.5,
+            cutoff_function: CutoffFunction::ShiftedCosine { width: 0.5 },
+            gradients: gradients,
+            max_radial: 3,
+            max_angular: 3,
+            radial_basis: RadialBasis::GTO,
+            spline_accuracy: None,
+        }
+    }
+
+    #[test]
+    fn features() {
+        let calculator = SoapBispectrum::new(parameters(false)).unwrap();
+        let features = calculator.features();
+        assert_eq!(features.names(), ["n1", "n2", "n3", "l1", "l2", "l3"]);
+
+        // (l1, l2, l3) with l <= 3, triangle condition and even sum
+        let n_angular = 23;
+        assert_eq!(features.count(), 3 * 3 * 3 * n_angular);
+
+        assert_eq!(features[0], [v!(0), v!(0), v!(0), v!(0), v!(0), v!(0)]);
+        assert_eq!(features[1], [v!(0), v!(0), v!(0), v!(0), v!(1), v!(1)]);
+        assert_eq!(features[2], [v!(0), v!(0), v!(0), v!(0), v!(2), v!(2)]);
+
+        for feature in &features {
+            let (l1, l2, l3) = (feature[3].usize(), feature[4].usize(), feature[5].usize());
+            assert_eq!((l1 + l2 + l3) % 2, 0);
+            assert!(l3 <= l1 + l2 && l1 <= l2 + l3 && l2 <= l1 + l3);
+        }
+    }
+
+    #[test]
+    fn values() {
+        let mut calculator = Calculator::from(Box::new(SoapBispectrum::new(
+            parameters(false)
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water"]);
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut descriptor, Default::default()).unwrap();
+
+        assert_eq!(descriptor.environments.names(), ["structure", "center", "species_center", "species_neighbor_1", "species_neighbor_2", "species_neighbor_3"]);
+        assert_eq!(descriptor.features.names(), ["n1", "n2", "n3", "l1", "l2", "l3"]);
+
+        // the (l1, l2, 0) bispectrum is the power spectrum multiplied by the
+        // l = 0 spherical expansion coefficients
+        let mut expansion = Descriptor::new();
+        let mut expansion_calculator = Calculator::new("spherical_expansion", calculator.parameters().into()).unwrap();
+        expansion_calculator.compute(&mut systems.get(), &mut expansion, Default::default()).unwrap();
+
+        let mut power_spectrum = Descriptor::new();
+        let mut power_spectrum_parameters = serde_json::from_str::<serde_json::Value>(&calculator.parameters()).unwrap();
+        power_spectrum_parameters["species"] = serde_json::json!([1, 123456]);
+        let mut power_spectrum_calculator = Calculator::new("soap_power_spectrum", power_spectrum_parameters.to_string()).unwrap();
+        power_spectrum_calculator.compute(&mut systems.get(), &mut power_spectrum, Default::default()).unwrap();
+
+        for (i_env, env) in descriptor.environments.iter().enumerate() {
+            if env[3] != env[4] || env[4] != env[5] {
+                // only check environments without species multiplicity factors
+                continue;
+            }
+
+            let ps_env = power_spectrum.environments.position(&[env[0], env[1], env[2]]).unwrap();
+            let se_env = expansion.environments.position(&[env[0], env[1], env[2], env[5]]).unwrap();
+
+            for (i_feature, feature) in descriptor.features.iter().enumerate() {
+                if feature[5].usize() != 0 {
+                    continue;
+                }
+
+                let ps_feature = power_spectrum.features.position(&[env[3], env[4], feature[0], feature[1], feature[3]]).unwrap();
+                let se_feature = expansion.features.position(&[feature[2], v!(0), v!(0)]).unwrap();
+
+                let l = feature[3].usize();
+                // the real Clebsch-Gordan coefficients for `(l, l, 0)` are
+                // `±1 / sqrt(2l + 1)`, the same normalization as the power
+                // spectrum
+                let expected = power_spectrum.values[[ps_env, ps_feature]] * expansion.values[[se_env, se_feature]];
+                let sign = if l % 2 == 0 { 1.0 } else { -1.0 };
+                assert_relative_eq!(descriptor.values[[i_env, i_feature]], sign * expected, epsilon=1e-14, max_relative=1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn rotation_invariance() {
+        let mut calculator = Calculator::from(Box::new(SoapBispectrum::new(
+            parameters(false)
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["methane"]);
+        let mut reference = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut reference, Default::default()).unwrap();
+
+        let center = systems.systems[0].positions().unwrap()[0];
+        let rotation = Matrix3::rotation(&Vector3D::new(0.3, -0.2, 1.0), 0.75);
+        for position in systems.systems[0].positions_mut() {
+            *position = center + rotation * (*position - center);
+        }
+
+        let mut rotated = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut rotated, Default::default()).unwrap();
+
+        assert_eq!(reference.environments, rotated.environments);
+        assert_relative_eq!(reference.values, rotated.values, epsilon=1e-12, max_relative=1e-6);
+    }
+
+    #[test]
+    fn finite_differences() {
+        let mut calculator = Calculator::from(Box::new(SoapBispectrum::new(
+            parameters(true)
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water"]);
+        let mut reference = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut reference, Default::default()).unwrap();
+
+        let gradients_indexes = reference.gradients_indexes.as_ref().unwrap();
+        assert_eq!(
+            gradients_indexes.names(),
+            ["structure", "center", "species_center", "species_neighbor_1", "species_neighbor_2", "species_neighbor_3", "neighbor", "spatial"]
+        );
+
+        // get the list of modified gradient environments when moving atom_i
+        let modified_indexes = |atom_i: usize, spatial_index: usize| {
+            let mut results = Vec::new();
+            for (env_i, env) in gradients_indexes.iter().enumerate() {
+                let center = env[1];
+                let neighbor = env[6];
+                let spatial = env[7];
+                if center.usize() != atom_i && neighbor.usize() == atom_i && spatial.usize() == spatial_index {
+                    results.push((env_i, &env[..6]));
+                }
+            }
+            return results;
+        };
+
+        let delta = 1e-9;
+        let gradients = reference.gradients.as_ref().unwrap();
+        for atom_i in 0..systems.systems[0].size().unwrap() {
+            for spatial in 0..3 {
+                systems.systems[0].positions_mut()[atom_i][spatial] += delta;
+
+                let mut updated = Descriptor::new();
+                calculator.compute(&mut systems.get(), &mut updated, Default::default()).unwrap();
+
+                for (grad_i, env) in modified_indexes(atom_i, spatial) {
+                    let env_i = reference.environments.position(env).expect(
+                        "missing environment in reference values"
+                    );
+                    assert_eq!(updated.environments.position(env).unwrap(), env_i);
+
+                    let value = reference.values.slice(s![env_i, ..]);
+                    let value_delta = updated.values.slice(s![env_i, ..]);
+                    let gradient = gradients.slice(s![grad_i, ..]);
+
+                    assert_eq!(value.shape(), value_delta.shape());
+                    assert_eq!(value.shape(), gradient.shape());
+
+                    let mut finite_difference = value_delta.to_owned().clone();
+                    finite_difference -= &value;
+                    finite_difference /= delta;
+
+                    assert_relative_eq!(
+                        finite_difference, gradient,
+                        epsilon=1e-6,
+                        max_relative=5e-4,
+                    );
+                }
+
+                systems.systems[0].positions_mut()[atom_i][spatial] -= delta;
+            }
+        }
+    }
+
+    #[test]
+    fn compute_partial() {
+        let mut calculator = Calculator::from(Box::new(SoapBispectrum::new(
+            parameters(true)
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water", "methane"]);
+        let mut full = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut full, Default::default()).unwrap();
+
+        // partial set of features, all environments
+        let mut features = IndexesBuilder::new(vec!["n1", "n2", "n3", "l1", "l2", "l3"]);
+        features.add(&[v!(0), v!(1), v!(0), v!(0), v!(0), v!(0)]);
+        features.add(&[v!(2), v!(2), v!(1), v!(3), v!(2), v!(1)]);
+        features.add(&[v!(1), v!(0), v!(2), v!(1), v!(1), v!(2)]);
+        features.add(&[v!(0), v!(2), v!(2), v!(2), v!(3), v!(3)]);
+        let features = features.finish();
+
+        let mut partial = Descriptor::new();
+        let options = CalculationOptions {
+            selected_samples: SelectedIndexes::All,
+            selected_features: SelectedIndexes::Some(features.clone()),
+            ..Default::default()
+        };
+        calculator.compute(&mut systems.get(), &mut partial, options).unwrap();
+
+        assert_eq!(full.environments, partial.environments);
+        for (partial_i, feature) in features.iter().enumerate() {
+            let index = full.features.position(feature).unwrap();
+            assert_eq!(
+                full.values.slice(s![.., index]),
+                partial.values.slice(s![.., partial_i])
+            );
+
+            assert_eq!(
+                full.gradients.as_ref().unwrap().slice(s![.., index]),
+                partial.gradients.as_ref().unwrap().slice(s![.., partial_i])
+            );
+        }
+
+        // all features, partial set of environments
+        let mut environments = IndexesBuilder::new(vec![
+            "structure", "center", "species_center", "species_neighbor_1", "species_neighbor_2", "species_neighbor_3"
+        ]);
+        environments.add(&[v!(0), v!(1), v!(1), v!(1), v!(1), v!(1)]);
+        environments.add(&[v!(0), v!(2), v!(1), v!(1), v!(123456), v!(123456)]);
+        environments.add(&[v!(1), v!(0), v!(6), v!(1), v!(1), v!(6)]);
+        environments.add(&[v!(1), v!(2), v!(1), v!(1), v!(1), v!(1)]);
+        let environments = environments.finish();
+
+        let options = CalculationOptions {
+            selected_samples: SelectedIndexes::Some(environments.clone()),
+            selected_features: SelectedIndexes::All,
+            ..Default::default()
+        };
+        calculator.compute(&mut systems.get(), &mut partial, options).unwrap();
+
+        assert_eq!(full.features, partial.features);
+        for (partial_i, environment) in environments.iter().enumerate() {
+            let index = full.environments.position(environment).unwrap();
+            assert_eq!(
+                full.values.slice(s![index, ..]),
+                partial.values.slice(s![partial_i, ..])
+            );
+        }
+
+        for (partial_i, gradient) in partial.gradients_indexes.as_ref().unwrap().iter().enumerate() {
+            let index = full.gradients_indexes.as_ref().unwrap().position(gradient).unwrap();
+            assert_eq!(
+                full.gradients.as_ref().unwrap().slice(s![index, ..]),
+                partial.gradients.as_ref().unwrap().slice(s![partial_i, ..])
+            );
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use std::f64::consts::SQRT_2;
+
+use nalgebra::Complex;
+
+/// Compute `n!` as a floating point number
+fn factorial(n: isize) -> f64 {
+    debug_assert!(n >= 0);
+    let mut result = 1.0;
+    for i in 2..=n {
+        result *= i as f64;
+    }
+    return result;
+}
+
+/// Compute the Wigner 3j symbol `(j1 j2 j3; m1 m2 m3)` using Racah formula.
+///
+/// The factorials are evaluated with floating point numbers, which gives
+/// accurate results for angular momenta up to ~20.
+#[allow(clippy::many_single_char_names)]
+pub fn wigner_3j(j1: usize, j2: usize, j3: usize, m1: isize, m2: isize, m3: isize) -> f64 {
+    let (j1, j2, j3) = (j1 as isize, j2 as isize, j3 as isize);
+    if m1 + m2 + m3 != 0 || m1.abs() > j1 || m2.abs() > j2 || m3.abs() > j3 {
+        return 0.0;
+    }
+
+    if j3 < (j1 - j2).abs() || j3 > j1 + j2 {
+        return 0.0;
+    }
+
+    let triangle = factorial(j1 + j2 - j3) * factorial(j1 - j2 + j3) * factorial(-j1 + j2 + j3)
+        / factorial(j1 + j2 + j3 + 1);
+
+    let prefactor = f64::sqrt(
+        triangle
+        * factorial(j1 + m1) * factorial(j1 - m1)
+        * factorial(j2 + m2) * factorial(j2 - m2)
+        * factorial(j3 + m3) * factorial(j3 - m3)
+    );
+
+    let k_min = *[0, j2 - j3 - m1, j1 - j3 + m2].iter().max().expect("empty array");
+    let k_max = *[j1 + j2 - j3, j1 - m1, j2 + m2].iter().min().expect("empty array");
+
+    let mut sum = 0.0;
+    for k in k_min..=k_max {
+        let denominator = factorial(k)
+            * factorial(j3 - j2 + k + m1)
+            * factorial(j3 - j1 + k - m2)
+            * factorial(j1 + j2 - j3 - k)
+            * factorial(j1 - k - m1)
+            * factorial(j2 - k + m2);
+
+        let sign = if k % 2 == 0 { 1.0 } else { -1.0 };
+        sum += sign / denominator;
+    }
+
+    let sign = if (j1 - j2 - m3).rem_euclid(2) == 0 { 1.0 } else { -1.0 };
+    return sign * prefactor * sum;
+}
+
+/// Compute the Clebsch-Gordan coefficient `<l1 m1; l2 m2 | l3 m3>` for
+/// complex spherical harmonics
+pub fn clebsch_gordan(l1: usize, m1: isize, l2: usize, m2: isize, l3: usize, m3: isize) -> f64 {
+    let sign = if (l1 as isize - l2 as isize + m3).rem_euclid(2) == 0 { 1.0 } else { -1.0 };
+    return sign * f64::sqrt((2 * l3 + 1) as f64) * wigner_3j(l1, l2, l3, m1, m2, -m3);
+}
+
+/// Coefficient of the unitary matrix going from complex spherical harmonics
+/// `Y_l^μ` to the real spherical harmonics `Y_{l, m}` used in the spherical
+/// expansion (up to an overall `1 / sqrt(2)` factor): `Y_{l, m} = Σ_μ U[m, μ]
+/// Y_l^μ`
+fn complex_to_real(m: isize, mu: isize) -> Complex<f64> {
+    let sign = if mu.rem_euclid(2) == 0 { 1.0 } else { -1.0 };
+    if m == 0 {
+        if mu == 0 {
+            return Complex::new(1.0, 0.0);
+        }
+    } else if m > 0 {
+        if mu == m {
+            return Complex::new(1.0 / SQRT_2, 0.0);
+        } else if mu == -m {
+            return Complex::new(sign / SQRT_2, 0.0);
+        }
+    } else if mu == -m {
+        return Complex::new(0.0, -1.0 / SQRT_2);
+    } else if mu == m {
+        return Complex::new(0.0, sign / SQRT_2);
+    }
+
+    return Complex::new(0.0, 0.0);
+}
+
+/// Get the values of `μ` for which `U[m, μ]` (see `complex_to_real`) is non
+/// zero
+fn complex_components(m: isize) -> Vec<isize> {
+    if m == 0 {
+        vec![0]
+    } else {
+        vec![m.abs(), -m.abs()]
+    }
+}
+
+/// A single non-zero Clebsch-Gordan coefficient
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClebschGordanCoefficient {
+    pub m1: isize,
+    pub m2: isize,
+    pub m3: isize,
+    pub value: f64,
+}
+
+/// Table of Clebsch-Gordan coefficients coupling two real spherical harmonics
+/// (as used in the spherical expansion) of angular order `l1` and `l2` to real
+/// spherical harmonics of angular order `l3`.
+///
+/// The coefficients in the real basis are purely real when `l1 + l2 + l3` is
+/// even, and purely imaginary when `l1 + l2 + l3` is odd. In the latter case,
+/// this table stores the imaginary part, which still couples the spherical
+/// harmonics in a rotationally equivariant way.
+#[derive(Debug, Clone)]
+pub struct ClebschGordan {
+    max_angular: usize,
+    /// non-zero coefficients for each `(l1, l2, l3)`, indexed by
+    /// `self.linear_index(l1, l2, l3)`
+    coefficients: Vec<Vec<ClebschGordanCoefficient>>,
+}
+
+impl ClebschGordan {
+    /// Compute all the Clebsch-Gordan coefficients with `l1, l2, l3 <=
+    /// max_angular`
+    pub fn new(max_angular: usize) -> ClebschGordan {
+        let size = (max_angular + 1) * (max_angular + 1) * (max_angular + 1);
+        let mut table = ClebschGordan {
+            max_angular: max_angular,
+            coefficients: vec![Vec::new(); size],
+        };
+
+        for l1 in 0..=max_angular {
+            for l2 in 0..=max_angular {
+                let l3_min = (l1 as isize - l2 as isize).abs() as usize;
+                let l3_max = usize::min(l1 + l2, max_angular);
+                for l3 in l3_min..=l3_max {
+                    let index = table.linear_index(l1, l2, l3);
+                    table.coefficients[index] = ClebschGordan::real_coefficients(l1, l2, l3);
+                }
+            }
+        }
+
+        return table;
+    }
+
+    /// Get the maximal angular order in this table
+    pub fn max_angular(&self) -> usize {
+        self.max_angular
+    }
+
+    /// Get the list of non-zero coefficients coupling `l1` and `l2` to `l3`.
+    /// This is empty if `l1`, `l2` and `l3` do not satisfy the triangle
+    /// condition.
+    pub fn coefficients(&self, l1: usize, l2: usize, l3: usize) -> &[ClebschGordanCoefficient] {
+        assert!(
+            l1 <= self.max_angular && l2 <= self.max_angular && l3 <= self.max_angular,
+            "angular order too large for this Clebsch-Gordan table"
+        );
+        return &self.coefficients[self.linear_index(l1, l2, l3)];
+    }
+
+    fn linear_index(&self, l1: usize, l2: usize, l3: usize) -> usize {
+        let size = self.max_angular + 1;
+        return (l1 * size + l2) * size + l3;
+    }
+
+    /// Compute the non-zero coefficients coupling `l1` and `l2` to `l3` in the
+    /// real spherical harmonics basis, by transforming the coefficients
+    /// for complex spherical harmonics
+    #[allow(clippy::similar_names)]
+    fn real_coefficients(l1: usize, l2: usize, l3: usize) -> Vec<ClebschGordanCoefficient> {
+        let (l1_i, l2_i, l3_i) = (l1 as isize, l2 as isize, l3 as isize);
+        let even = (l1 + l2 + l3) % 2 == 0;
+
+        let mut coefficients = Vec::new();
+        for m1 in -l1_i..=l1_i {
+            for m2 in -l2_i..=l2_i {
+                for m3 in -l3_i..=l3_i {
+                    let mut value = Complex::new(0.0, 0.0);
+                    for &mu1 in &complex_components(m1) {
+                        for &mu2 in &complex_components(m2) {
+                            let mu3 = mu1 + mu2;
+                            if mu3.abs() != m3.abs() {
+                                continue;
+                            }
+
+                            let cg = clebsch_gordan(l1, mu1, l2, mu2, l3, mu3);
+                            value += complex_to_real(m1, mu1).conj()
+                                * complex_to_real(m2, mu2).conj()
+                                * complex_to_real(m3, mu3)
+                                * cg;
+                        }
+                    }
+
+                    let value = if even { value.re } else { value.im };
+                    if value.abs() > 1e-14 {
+                        coefficients.push(ClebschGordanCoefficient {
+                            m1: m1,
+                            m2: m2,
+                            m3: m3,
+                            value: value,
+                        });
+                    }
+                }
+            }
+        }
+
+        return coefficients;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::{Matrix3, Vector3D};
+    use super::super::{SphericalHarmonics, SphericalHarmonicsArray};
+    use super::{ClebschGordan, wigner_3j};
+
+    #[test]
+    fn wigner_3j_values() {
+        assert_relative_eq!(wigner_3j(1, 1, 0, 0, 0, 0), -f64::sqrt(1.0 / 3.0), max_relative=1e-12);
+        assert_relative_eq!(wigner_3j(1, 1, 2, 1, -1, 0), f64::sqrt(1.0 / 30.0), max_relative=1e-12);
+        assert_relative_eq!(wigner_3j(2, 2, 2, 0, 0, 0), -f64::sqrt(2.0 / 35.0), max_relative=1e-12);
+        assert_relative_eq!(wigner_3j(2, 1, 1, 2, -1, -1), f64::sqrt(1.0 / 5.0), max_relative=1e-12);
+
+        // m1 + m2 + m3 != 0
+        assert_eq!(wigner_3j(2, 2, 2, 1, 0, 0), 0.0);
+        // triangle condition
+        assert_eq!(wigner_3j(1, 1, 3, 0, 0, 0), 0.0);
+        // odd sum of l with all m = 0
+        assert_relative_eq!(wigner_3j(1, 1, 1, 0, 0, 0), 0.0);
+    }
+
+    #[test]
+    fn orthogonality() {
+        let max_angular = 4;
+        let table = ClebschGordan::new(max_angular);
+
+        for l1 in 0..=max_angular {
+            for l2 in 0..=max_angular {
+                // dense version of the coefficients, indexed by (l3, m1, m2, m3)
+                let mut dense = ndarray::Array4::zeros((max_angular + 1, 2 * l1 + 1, 2 * l2 + 1, 2 * max_angular + 1));
+                for l3 in 0..=max_angular {
+                    for coefficient in table.coefficients(l1, l2, l3) {
+                        let m1 = (coefficient.m1 + l1 as isize) as usize;
+                        let m2 = (coefficient.m2 + l2 as isize) as usize;
+                        let m3 = (coefficient.m3 + l3 as isize) as usize;
+                        dense[[l3, m1, m2, m3]] = coefficient.value;
+                    }
+                }
+
+                let l3_min = (l1 as isize - l2 as isize).abs() as usize;
+                let l3_max = usize::min(l1 + l2, max_angular);
+                for l3 in l3_min..=l3_max {
+                    for l3_bis in l3_min..=l3_max {
+                        for m3 in 0..(2 * l3 + 1) {
+                            for m3_bis in 0..(2 * l3_bis + 1) {
+                                let mut sum = 0.0;
+                                for m1 in 0..(2 * l1 + 1) {
+                                    for m2 in 0..(2 * l2 + 1) {
+                                        sum += dense[[l3, m1, m2, m3]] * dense[[l3_bis, m1, m2, m3_bis]];
+                                    }
+                                }
+
+                                let expected = if l3 == l3_bis && m3 == m3_bis { 1.0 } else { 0.0 };
+                                assert_relative_eq!(sum, expected, epsilon=1e-12);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rotation_invariance() {
+        let max_angular = 5;
+        let table = ClebschGordan::new(max_angular);
+
+        let directions = [
+            Vector3D::new(0.3, -0.2, 0.9).normalized(),
+            Vector3D::new(-1.2, 0.4, 0.1).normalized(),
+            Vector3D::new(0.5, 0.7, -0.6).normalized(),
+        ];
+        let rotation = Matrix3::rotation(&Vector3D::new(0.2, 1.0, -0.4), 1.23);
+
+        let mut spherical_harmonics = SphericalHarmonics::new(max_angular);
+        let mut compute_all = |directions: &[Vector3D]| {
+            let mut all = Vec::new();
+            for &direction in directions {
+                let mut values = SphericalHarmonicsArray::new(max_angular);
+                spherical_harmonics.compute(direction, &mut values, None);
+                all.push(values);
+            }
+            return all;
+        };
+
+        let reference = compute_all(&directions);
+        let rotated = compute_all(&[
+            rotation * directions[0], rotation * directions[1], rotation * directions[2]
+        ]);
+
+        let contract = |values: &[SphericalHarmonicsArray], l1: usize, l2: usize, l3: usize| {
+            let mut sum = 0.0;
+            for coefficient in table.coefficients(l1, l2, l3) {
+                sum += coefficient.value
+                    * values[0][[l1 as isize, coefficient.m1]]
+                    * values[1][[l2 as isize, coefficient.m2]]
+                    * values[2][[l3 as isize, coefficient.m3]];
+            }
+            return sum;
+        };
+
+        for l1 in 0..=max_angular {
+            for l2 in 0..=max_angular {
+                for l3 in 0..=max_angular {
+                    let expected = contract(&reference, l1, l2, l3);
+                    let actual = contract(&rotated, l1, l2, l3);
+                    assert_relative_eq!(expected, actual, epsilon=1e-12, max_relative=1e-9);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use indexmap::{IndexMap, IndexSet};
+
+use crate::descriptor::{Descriptor, Indexes, IndexesBuilder, IndexValue};
+use crate::{Calculator, CalculationOptions, SelectedIndexes, System, Error};
+
+/// Set of spherical expansion samples & features required to compute
+/// descriptors built by combining multiple spherical expansion coefficients
+/// (power spectrum, bispectrum, ...).
+pub(super) struct ExpansionRequest {
+    /// spherical expansion samples, in the same order as the rows of the
+    /// spherical expansion values
+    samples: IndexSet<[IndexValue; 4]>,
+    /// Position of the `m = -l` feature for a given `(n, l)` pair in the
+    /// spherical expansion features. All the other `m` follow consecutively.
+    blocks: IndexMap<(usize, usize), usize>,
+    /// Total number of spherical expansion features
+    n_features: usize,
+}
+
+impl ExpansionRequest {
+    pub fn new() -> ExpansionRequest {
+        ExpansionRequest {
+            samples: IndexSet::new(),
+            blocks: IndexMap::new(),
+            n_features: 0,
+        }
+    }
+
+    /// Request the spherical expansion for the given `[structure, center,
+    /// species_center, species_neighbor]` sample
+    pub fn add_sample(&mut self, sample: [IndexValue; 4]) {
+        self.samples.insert(sample);
+    }
+
+    /// Request all the `m` features for the given `n` and `l`
+    pub fn add_block(&mut self, n: usize, l: usize) {
+        if !self.blocks.contains_key(&(n, l)) {
+            self.blocks.insert((n, l), self.n_features);
+            self.n_features += 2 * l + 1;
+        }
+    }
+
+    /// Get the row corresponding to the given sample in the spherical
+    /// expansion values, if this sample was requested
+    pub fn sample(&self, sample: &[IndexValue; 4]) -> Option<usize> {
+        self.samples.get_index_of(sample)
+    }
+
+    /// Get the column corresponding to `m = -l` for the given `n` and `l` in
+    /// the spherical expansion values. The columns for the other values of `m`
+    /// follow consecutively.
+    pub fn block(&self, n: usize, l: usize) -> usize {
+        self.blocks[&(n, l)]
+    }
+
+    fn samples(&self) -> Indexes {
+        let mut samples = IndexesBuilder::new(vec!["structure", "center", "species_center", "species_neighbor"]);
+        for sample in &self.samples {
+            samples.add(sample);
+        }
+        return samples.finish();
+    }
+
+    fn features(&self) -> Indexes {
+        let mut features = IndexesBuilder::new(vec!["n", "l", "m"]);
+        for &(n, l) in self.blocks.keys() {
+            let l = l as isize;
+            for m in -l..=l {
+                features.add(&[IndexValue::from(n), IndexValue::from(l), IndexValue::from(m)]);
+            }
+        }
+        return features.finish();
+    }
+
+    /// Run the spherical expansion `calculator` on the requested samples and
+    /// features.
+    pub fn compute(&self, calculator: &mut Calculator, systems: &mut [&mut dyn System]) -> Result<Descriptor, Error> {
+        let options = CalculationOptions {
+            selected_samples: SelectedIndexes::Some(self.samples()),
+            selected_features: SelectedIndexes::Some(self.features()),
+            ..Default::default()
+        };
+
+        let mut expansion = Descriptor::new();
+        calculator.compute(systems, &mut expansion, options)?;
+
+        debug_assert_eq!(expansion.values.ncols(), self.n_features);
+        return Ok(expansion);
+    }
+}
+
+/// Get the position of the first spatial component of the gradient for each
+/// `[structure, center, species_center, species_neighbor, neighbor]` in a
+/// spherical expansion `descriptor`.
+pub(super) fn gradients_positions(expansion: &Descriptor) -> HashMap<[IndexValue; 5], usize> {
+    let gradients_indexes = expansion.gradients_indexes.as_ref().expect("missing spherical expansion gradients indexes");
+    assert_eq!(gradients_indexes.names(), &["structure", "center", "species_center", "species_neighbor", "neighbor", "spatial"]);
+
+    let mut positions = HashMap::new();
+    for (i_grad, index) in gradients_indexes.iter().enumerate() {
+        if index[5].usize() == 0 {
+            positions.insert([index[0], index[1], index[2], index[3], index[4]], i_grad);
+        }
+    }
+    return positions;
+}
@@ -508,8 +508,10 @@ impl HyperGeometricSphericalExpansion {
                 for l in 0..(self.max_angular + 1) {
                     gradients[[n, l]] = self.hypergeometric[[n, l]].compute(z, z2, true);
                 }
+                // dz/drij = 2 z / rij, written in a way that is also valid
+                // for rij = 0
                 let mut row = gradients.index_axis_mut(Axis(0), n);
-                row *= 2.0 * z / rij;
+                row *= 2.0 * alpha * alpha_rij / (alpha + parameters.gto_gaussian_constants[n]);
             }
 
             azip!((gradient in gradients, &value in &values)
@@ -596,8 +598,10 @@ impl HyperGeometricSphericalExpansion {
             }
 
             if let Some(ref mut gradients) = gradients {
+                // dz/drij = 2 z / rij, written in a way that is also valid
+                // for rij = 0
                 let mut row = gradients.index_axis_mut(Axis(0), n);
-                row *= 2.0 * z / rij;
+                row *= 2.0 * alpha * alpha_rij / (alpha + parameters.gto_gaussian_constants[n]);
             }
         }
 
@@ -0,0 +1,912 @@
+use ndarray::{Array2, Array3};
+
+use crate::descriptor::{IndexesBuilder, IndexValue, Indexes, EnvironmentIndexes};
+use crate::descriptor::LongRangeSpeciesEnvironment;
+use crate::math::{erf, erfc, gauss_legendre, spherical_bessel};
+use crate::system::UnitCell;
+use crate::{Descriptor, Error, System, Vector3D};
+
+use super::super::{CalculatorBase, check_indexes_names, check_environments_subset};
+use super::spherical_expansion::check_spherical_feature;
+use super::radial_integral::laplacian_eigenstate_quadrature;
+use super::{SphericalHarmonics, SphericalHarmonicsArray};
+
+/// Parameters for the long-distance equivariant (LODE) spherical expansion
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[allow(clippy::module_name_repetitions)]
+pub struct LodeSphericalExpansionParameters {
+    /// Radius of the sphere on which the potential is expanded around each
+    /// atom. Contrary to the spherical expansion, all atoms in the system
+    /// contribute to the potential, even outside of this sphere.
+    pub cutoff: f64,
+    /// Number of radial basis function to use
+    pub max_radial: usize,
+    /// Number of spherical harmonics to use
+    pub max_angular: usize,
+    /// Width of the atom-centered gaussian creating the atomic density
+    pub atomic_gaussian_width: f64,
+    /// Exponent `p` of the `1/r^p` potential created by each atom. Only 1
+    /// (Coulomb-like) and 2 (dispersion-like) are supported.
+    pub potential_exponent: usize,
+    /// Should we also compute gradients of the feature?
+    pub gradients: bool,
+    /// Cutoff for the reciprocal space sum used with periodic systems. If this
+    /// is not set, a value ensuring the convergence of the sum for the given
+    /// `atomic_gaussian_width` is used.
+    #[serde(default)]
+    pub k_cutoff: Option<f64>,
+}
+
+impl LodeSphericalExpansionParameters {
+    fn validate(&self) -> Result<(), Error> {
+        if self.max_radial == 0 {
+            return Err(Error::InvalidParameter("max_radial must be at least 1".into()));
+        }
+
+        if !(self.cutoff > 0.0 && self.cutoff.is_finite()) {
+            return Err(Error::InvalidParameter("cutoff must be a positive number".into()));
+        }
+
+        if !(self.atomic_gaussian_width > 0.0 && self.atomic_gaussian_width.is_finite()) {
+            return Err(Error::InvalidParameter("atomic_gaussian_width must be a positive number".into()));
+        }
+
+        if self.potential_exponent != 1 && self.potential_exponent != 2 {
+            return Err(Error::InvalidParameter(format!(
+                "potential_exponent must be 1 or 2, got {}", self.potential_exponent
+            )));
+        }
+
+        if let Some(k_cutoff) = self.k_cutoff {
+            if !(k_cutoff > 0.0 && k_cutoff.is_finite()) {
+                return Err(Error::InvalidParameter("k_cutoff must be a positive number".into()));
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Get the cutoff of the reciprocal space sum. The default value makes the
+    /// gaussian factor `exp(-σ^2 k^2 / 2)` of the smeared potential smaller
+    /// than `exp(-30) ~ 1e-13` for all neglected k-vectors.
+    fn k_cutoff(&self) -> f64 {
+        self.k_cutoff.unwrap_or_else(|| f64::sqrt(60.0) / self.atomic_gaussian_width)
+    }
+
+    /// Number of Gauss-Legendre quadrature points used for the radial
+    /// integration. This needs to resolve the smeared potential in real space,
+    /// and the oscillations of the plane waves up to `k_cutoff` in reciprocal
+    /// space.
+    fn radial_quadrature_size(&self) -> usize {
+        let density = 3.0 * self.cutoff / self.atomic_gaussian_width;
+        let plane_waves = self.k_cutoff() * self.cutoff;
+        return 2 * self.max_radial + self.max_angular + f64::max(density, plane_waves).ceil() as usize + 10;
+    }
+
+    /// Number of Gauss-Legendre quadrature points used for the angular
+    /// integration of the potential in the direct sum.
+    fn angular_quadrature_size(&self) -> usize {
+        let density = 4.0 * self.cutoff / self.atomic_gaussian_width;
+        return 2 * self.max_angular + density.ceil() as usize + 20;
+    }
+}
+
+/// Long-distance equivariant (LODE) spherical expansion. This projects the
+/// potential `φ(r)` generated by all the atoms in a system on the `(n, l, m)`
+/// basis made of Laplacian eigenstate radial functions and spherical
+/// harmonics, centered on each atom.
+///
+/// Each atom creates the potential of a normalized gaussian density with width
+/// `σ`, which is `φ(r) = erf(r / √2σ) / r` for `potential_exponent = 1`, and
+/// `φ(r) = (1 - exp(-r^2 / 2σ^2)) / r^2` for `potential_exponent = 2`.
+///
+/// For systems with an infinite unit cell, the potential is summed directly
+/// over all atoms. For periodic systems, the sum runs over all periodic images
+/// and is evaluated in reciprocal space, excluding the `k = 0` term. This
+/// corresponds to adding a uniform neutralizing background, which only
+/// changes the `l = 0` coefficients.
+pub struct LodeSphericalExpansion {
+    parameters: LodeSphericalExpansionParameters,
+    /// Gauss-Legendre quadrature points `x_k` in `[0, cutoff]`
+    radial_points: Vec<f64>,
+    /// `w_k x_k^2 R_nl(x_k)` for all radial quadrature points, with shape
+    /// `(max_angular + 1, max_radial, n_points)`
+    radial_basis: Array3<f64>,
+    /// Gauss-Legendre quadrature points `u_q` in `[-1, 1]`
+    angular_points: Vec<f64>,
+    /// `w_q P_l(u_q)` for all angular quadrature points, with shape
+    /// `(max_angular + 1, n_points)`
+    legendre: Array2<f64>,
+    spherical_harmonics: SphericalHarmonics,
+    sph_values: SphericalHarmonicsArray,
+    sph_gradients: Option<[SphericalHarmonicsArray; 3]>,
+}
+
+impl LodeSphericalExpansion {
+    pub fn new(parameters: LodeSphericalExpansionParameters) -> Result<LodeSphericalExpansion, Error> {
+        parameters.validate()?;
+
+        let (radial_points, radial_basis) = laplacian_eigenstate_quadrature(
+            parameters.max_radial,
+            parameters.max_angular,
+            parameters.cutoff,
+            parameters.radial_quadrature_size(),
+        );
+
+        let (angular_points, angular_weights) = gauss_legendre(parameters.angular_quadrature_size());
+        let mut legendre = Array2::from_elem((parameters.max_angular + 1, angular_points.len()), 0.0);
+        for (q, (&u, &w)) in angular_points.iter().zip(&angular_weights).enumerate() {
+            // Bonnet's recursion for the Legendre polynomials
+            let mut p_l = 1.0;
+            let mut p_l_minus_1 = 0.0;
+            for l in 0..=parameters.max_angular {
+                legendre[[l, q]] = w * p_l;
+
+                let p_l_plus_1 = ((2 * l + 1) as f64 * u * p_l - l as f64 * p_l_minus_1) / (l + 1) as f64;
+                p_l_minus_1 = p_l;
+                p_l = p_l_plus_1;
+            }
+        }
+
+        let spherical_harmonics = SphericalHarmonics::new(parameters.max_angular);
+        let sph_values = SphericalHarmonicsArray::new(parameters.max_angular);
+        let sph_gradients = if parameters.gradients {
+            Some([
+                SphericalHarmonicsArray::new(parameters.max_angular),
+                SphericalHarmonicsArray::new(parameters.max_angular),
+                SphericalHarmonicsArray::new(parameters.max_angular)
+            ])
+        } else {
+            None
+        };
+
+        Ok(LodeSphericalExpansion {
+            parameters: parameters,
+            radial_points: radial_points,
+            radial_basis: radial_basis,
+            angular_points: angular_points,
+            legendre: legendre,
+            spherical_harmonics: spherical_harmonics,
+            sph_values: sph_values,
+            sph_gradients: sph_gradients,
+        })
+    }
+
+    /// Evaluate the smeared potential `φ(r)` created by a single atom, and
+    /// `φ'(r) / r`. Both are evaluated with series expansions close to `r = 0`
+    /// to prevent catastrophic cancellations.
+    fn potential(&self, r: f64) -> (f64, f64) {
+        let sigma = self.parameters.atomic_gaussian_width;
+        if self.parameters.potential_exponent == 1 {
+            let a = 1.0 / (std::f64::consts::SQRT_2 * sigma);
+            let x = a * r;
+            let x2 = x * x;
+            let gaussian = std::f64::consts::FRAC_2_SQRT_PI * f64::exp(-x2);
+            if x >= 2.0 {
+                let value = erf(x) / r;
+                let gradient_over_r = (a * gaussian - value) / (r * r);
+                return (value, gradient_over_r);
+            }
+
+            // erf(x) / x = 2/√π exp(-x^2) (1 + x^2 T(x)), with
+            // T(x) = sum_{n >= 1} 2^n x^(2n - 2) / (2n + 1)!!
+            let mut term = 2.0 / 3.0;
+            let mut series = term;
+            let mut n = 1.0;
+            while term > 1e-17 * series {
+                n += 1.0;
+                term *= 2.0 * x2 / (2.0 * n + 1.0);
+                series += term;
+            }
+
+            let value = a * gaussian * (1.0 + x2 * series);
+            let gradient_over_r = -a * a * a * gaussian * series;
+            return (value, gradient_over_r);
+        }
+
+        debug_assert_eq!(self.parameters.potential_exponent, 2);
+        // φ(r) = b g(y) with y = b r^2 and g(y) = (1 - exp(-y)) / y
+        let b = 1.0 / (2.0 * sigma * sigma);
+        let y = b * r * r;
+        let (g, g_prime) = if y < 0.5 {
+            let mut term = 1.0;
+            let mut g = 1.0;
+            let mut g_prime = 0.0;
+            for n in 1..25 {
+                // term = (-1)^n y^(n - 1) / (n + 1)!
+                term *= -1.0 / (n + 1) as f64;
+                g += term * y;
+                g_prime += n as f64 * term;
+                term *= y;
+            }
+            (g, g_prime)
+        } else {
+            let exp = f64::exp(-y);
+            ((1.0 - exp) / y, (exp * (1.0 + y) - 1.0) / (y * y))
+        };
+
+        return (b * g, 2.0 * b * b * g_prime);
+    }
+
+    /// Evaluate the Fourier transform of the smeared potential for a
+    /// k-vector with norm `k`.
+    fn potential_fourier(&self, k: f64) -> f64 {
+        let sigma = self.parameters.atomic_gaussian_width;
+        let pi = std::f64::consts::PI;
+        if self.parameters.potential_exponent == 1 {
+            return 4.0 * pi * f64::exp(-0.5 * sigma * sigma * k * k) / (k * k);
+        }
+
+        debug_assert_eq!(self.parameters.potential_exponent, 2);
+        return 2.0 * pi * pi * erfc(sigma * k / std::f64::consts::SQRT_2) / k;
+    }
+
+    /// Compute the projection on the radial basis of the potential created
+    /// by an atom at the given `distance` from the center, for all `(n, l)`.
+    /// This returns the values and the gradients with respect to the distance,
+    /// in two `max_radial x (max_angular + 1)` arrays.
+    ///
+    /// Using the addition theorem for the spherical harmonics, the projection
+    /// is `2π ∫ r^2 R_nl(r) ∫ φ(|r - distance|) P_l(u) du dr`, where `u` is the
+    /// cosine of the angle between `r` and the atom.
+    fn direct_radial_integral(&self, distance: f64) -> (Array2<f64>, Array2<f64>) {
+        let n_angular = self.parameters.max_angular + 1;
+        let n_points = self.radial_points.len();
+
+        // projection of the potential on the Legendre polynomials, for all
+        // radial quadrature points
+        let mut projection = Array2::from_elem((n_angular, n_points), 0.0);
+        let mut projection_gradient = Array2::from_elem((n_angular, n_points), 0.0);
+        for (k, &x) in self.radial_points.iter().enumerate() {
+            for (q, &u) in self.angular_points.iter().enumerate() {
+                let r = f64::sqrt(x * x + distance * distance - 2.0 * x * distance * u);
+                let (value, gradient_over_r) = self.potential(r);
+                // dr/d(distance) = (distance - x u) / r
+                let gradient = gradient_over_r * (distance - x * u);
+
+                for l in 0..n_angular {
+                    projection[[l, k]] += self.legendre[[l, q]] * value;
+                    projection_gradient[[l, k]] += self.legendre[[l, q]] * gradient;
+                }
+            }
+        }
+
+        let shape = (self.parameters.max_radial, n_angular);
+        let mut values = Array2::from_elem(shape, 0.0);
+        let mut gradients = Array2::from_elem(shape, 0.0);
+        for l in 0..n_angular {
+            for n in 0..self.parameters.max_radial {
+                for k in 0..n_points {
+                    let basis = 2.0 * std::f64::consts::PI * self.radial_basis[[l, n, k]];
+                    values[[n, l]] += basis * projection[[l, k]];
+                    gradients[[n, l]] += basis * projection_gradient[[l, k]];
+                }
+            }
+        }
+
+        return (values, gradients);
+    }
+
+    /// Compute `∫ r^2 R_nl(r) j_l(k r) dr` for all `(n, l)`, in a
+    /// `max_radial x (max_angular + 1)` array.
+    fn plane_wave_radial_integral(&self, k: f64) -> Array2<f64> {
+        let n_angular = self.parameters.max_angular + 1;
+        let mut values = Array2::from_elem((self.parameters.max_radial, n_angular), 0.0);
+        let mut bessel = vec![0.0; n_angular];
+        for (i_point, &x) in self.radial_points.iter().enumerate() {
+            spherical_bessel(k * x, &mut bessel);
+            for l in 0..n_angular {
+                for n in 0..self.parameters.max_radial {
+                    values[[n, l]] += self.radial_basis[[l, n, i_point]] * bessel[l];
+                }
+            }
+        }
+        return values;
+    }
+
+    /// Get all the k-vectors with norm below the cutoff in half of the
+    /// reciprocal space, the other half being related by `k -> -k`.
+    fn k_vectors(&self, cell: &UnitCell) -> Vec<Vector3D> {
+        let k_cutoff = self.parameters.k_cutoff();
+        let two_pi = 2.0 * std::f64::consts::PI;
+
+        // the rows of the inverse cell matrix are the reciprocal vectors,
+        // divided by 2π
+        let inverse = cell.matrix().inverse();
+        let reciprocal = [
+            two_pi * Vector3D::new(inverse[0][0], inverse[0][1], inverse[0][2]),
+            two_pi * Vector3D::new(inverse[1][0], inverse[1][1], inverse[1][2]),
+            two_pi * Vector3D::new(inverse[2][0], inverse[2][1], inverse[2][2]),
+        ];
+
+        // |k . a| <= k_cutoff |a| gives the maximal multiple of each
+        // reciprocal vector to consider
+        let n_max = [
+            (k_cutoff * cell.a() / two_pi).ceil() as isize,
+            (k_cutoff * cell.b() / two_pi).ceil() as isize,
+            (k_cutoff * cell.c() / two_pi).ceil() as isize,
+        ];
+
+        let mut k_vectors = Vec::new();
+        for n1 in 0..=n_max[0] {
+            for n2 in -n_max[1]..=n_max[1] {
+                for n3 in -n_max[2]..=n_max[2] {
+                    if n1 == 0 && (n2 < 0 || (n2 == 0 && n3 <= 0)) {
+                        // the other half of reciprocal space, or k = 0
+                        continue;
+                    }
+
+                    let k = n1 as f64 * reciprocal[0] + n2 as f64 * reciprocal[1] + n3 as f64 * reciprocal[2];
+                    if k.norm() < k_cutoff {
+                        k_vectors.push(k);
+                    }
+                }
+            }
+        }
+
+        return k_vectors;
+    }
+
+    /// Compute the expansion coefficients for the given environments of a
+    /// non-periodic system, summing the contributions of all atoms directly.
+    #[allow(clippy::identity_op)]
+    fn compute_direct(
+        &mut self,
+        system: &dyn System,
+        environments: &[usize],
+        features: &[(usize, usize, isize)],
+        descriptor: &mut Descriptor,
+    ) -> Result<(), Error> {
+        let species = system.species()?;
+        let positions = system.positions()?;
+        for &i_env in environments {
+            let environment = descriptor.environments[i_env].to_vec();
+            let center = environment[1].usize();
+            let beta = environment[3].usize();
+
+            for (neighbor, &species_neighbor) in species.iter().enumerate() {
+                if species_neighbor != beta {
+                    continue;
+                }
+
+                let vector = positions[neighbor] - positions[center];
+                let distance = vector.norm();
+                // the direction does not matter for the central atom, which
+                // only contributes to l = 0
+                let direction = if distance == 0.0 {
+                    Vector3D::new(0.0, 0.0, 1.0)
+                } else {
+                    vector / distance
+                };
+
+                let (ri_values, ri_gradients) = self.direct_radial_integral(distance);
+                self.spherical_harmonics.compute(
+                    direction, &mut self.sph_values, self.sph_gradients.as_mut()
+                );
+
+                for (i_feature, &(n, l, m)) in features.iter().enumerate() {
+                    descriptor.values[[i_env, i_feature]] += ri_values[[n, l]] * self.sph_values[[l as isize, m]];
+                }
+
+                if !self.parameters.gradients || neighbor == center {
+                    continue;
+                }
+
+                let gradients_indexes = descriptor.gradients_indexes.as_ref().expect("missing gradients indexes");
+                let mut gradient_index = environment.clone();
+                gradient_index.extend_from_slice(&[IndexValue::from(neighbor), IndexValue::from(0_usize)]);
+                let grad_i = gradients_indexes.position(&gradient_index).expect("missing storage for gradient");
+
+                let gradients = descriptor.gradients.as_mut().expect("missing storage for gradients");
+                let sph_gradients = self.sph_gradients.as_ref().expect("missing spherical harmonics gradients");
+                for (i_feature, &(n, l, m)) in features.iter().enumerate() {
+                    let sph_value = self.sph_values[[l as isize, m]];
+                    let ri_value = ri_values[[n, l]];
+                    let ri_grad = ri_gradients[[n, l]];
+
+                    for spatial in 0..3 {
+                        let sph_grad = sph_gradients[spatial][[l as isize, m]];
+                        let grad = ri_grad * direction[spatial] * sph_value + ri_value * sph_grad / distance;
+                        // assumes that the three spatial derivative are stored
+                        // one after the other
+                        gradients[[grad_i + spatial, i_feature]] += grad;
+                    }
+                }
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Compute the expansion coefficients for the given environments of a
+    /// periodic system, using a sum over reciprocal space.
+    ///
+    /// Combining the contributions of `k` and `-k`, each k-vector contributes
+    /// `8π/V φ(k) I_nl(k) Y_lm(k) T_l(k . (r_i - r_j))` to the coefficients,
+    /// where `I_nl` is the projection of the spherical Bessel function `j_l`
+    /// on the radial basis, and `T_l` is `(-1)^(l/2) cos` for even `l` and
+    /// `(-1)^((l + 1)/2) sin` for odd `l`.
+    fn compute_reciprocal(
+        &mut self,
+        system: &dyn System,
+        environments: &[usize],
+        features: &[(usize, usize, isize)],
+        descriptor: &mut Descriptor,
+    ) -> Result<(), Error> {
+        let cell = system.cell()?;
+        let k_vectors = self.k_vectors(&cell);
+
+        let prefactor = 8.0 * std::f64::consts::PI / cell.volume();
+        let mut coefficients = Array2::from_elem((k_vectors.len(), features.len()), 0.0);
+        for (i_k, k_vector) in k_vectors.iter().enumerate() {
+            let k = k_vector.norm();
+            let potential = self.potential_fourier(k);
+            let radial = self.plane_wave_radial_integral(k);
+            self.spherical_harmonics.compute(*k_vector / k, &mut self.sph_values, None);
+
+            for (i_feature, &(n, l, m)) in features.iter().enumerate() {
+                // (-1)^(l/2) for even l and (-1)^((l + 1)/2) for odd l
+                let phase = if (l + 1) % 4 < 2 { 1.0 } else { -1.0 };
+                coefficients[[i_k, i_feature]] = phase * prefactor * potential * radial[[n, l]] * self.sph_values[[l as isize, m]];
+            }
+        }
+
+        let species = system.species()?;
+        let positions = system.positions()?;
+        for &i_env in environments {
+            let environment = descriptor.environments[i_env].to_vec();
+            let center = environment[1].usize();
+            let beta = environment[3].usize();
+
+            for (neighbor, &species_neighbor) in species.iter().enumerate() {
+                if species_neighbor != beta {
+                    continue;
+                }
+
+                let vector = positions[center] - positions[neighbor];
+                for (i_k, k_vector) in k_vectors.iter().enumerate() {
+                    let (sin, cos) = f64::sin_cos(*k_vector * vector);
+                    for (i_feature, &(_, l, _)) in features.iter().enumerate() {
+                        let trigonometric = if l % 2 == 0 { cos } else { sin };
+                        descriptor.values[[i_env, i_feature]] += coefficients[[i_k, i_feature]] * trigonometric;
+                    }
+                }
+
+                if !self.parameters.gradients || neighbor == center {
+                    continue;
+                }
+
+                let gradients_indexes = descriptor.gradients_indexes.as_ref().expect("missing gradients indexes");
+                let mut gradient_index = environment.clone();
+                gradient_index.extend_from_slice(&[IndexValue::from(neighbor), IndexValue::from(0_usize)]);
+                let grad_i = gradients_indexes.position(&gradient_index).expect("missing storage for gradient");
+
+                let gradients = descriptor.gradients.as_mut().expect("missing storage for gradients");
+                for (i_k, k_vector) in k_vectors.iter().enumerate() {
+                    let (sin, cos) = f64::sin_cos(*k_vector * vector);
+                    for (i_feature, &(_, l, _)) in features.iter().enumerate() {
+                        // derivative with respect to the neighbor position,
+                        // i.e. d/dr_j cos(k . (r_i - r_j)) = k sin(...)
+                        let trigonometric = if l % 2 == 0 { sin } else { -cos };
+                        let factor = coefficients[[i_k, i_feature]] * trigonometric;
+                        for spatial in 0..3 {
+                            gradients[[grad_i + spatial, i_feature]] += factor * k_vector[spatial];
+                        }
+                    }
+                }
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+impl std::fmt::Debug for LodeSphericalExpansion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.parameters)
+    }
+}
+
+impl CalculatorBase for LodeSphericalExpansion {
+    fn name(&self) -> String {
+        "LODE spherical expansion".into()
+    }
+
+    fn get_parameters(&self) -> String {
+        serde_json::to_string(&self.parameters).expect("failed to serialize to JSON")
+    }
+
+    fn features_names(&self) -> Vec<&str> {
+        vec!["n", "l", "m"]
+    }
+
+    fn features(&self) -> Indexes {
+        let mut features = IndexesBuilder::new(self.features_names());
+        for n in 0..(self.parameters.max_radial as isize) {
+            for l in 0..((self.parameters.max_angular + 1) as isize) {
+                for m in -l..=l {
+                    features.add(&[IndexValue::from(n), IndexValue::from(l), IndexValue::from(m)]);
+                }
+            }
+        }
+        return features.finish();
+    }
+
+    fn environments(&self) -> Box<dyn EnvironmentIndexes> {
+        Box::new(LongRangeSpeciesEnvironment)
+    }
+
+    fn compute_gradients(&self) -> bool {
+        self.parameters.gradients
+    }
+
+    fn check_features(&self, indexes: &Indexes) -> Result<(), Error> {
+        check_indexes_names("features", indexes, &self.features_names())?;
+        for value in indexes {
+            let n = value[0].usize();
+            let l = value[1].isize();
+            let m = value[2].isize();
+            check_spherical_feature(value, n, l, m, self.parameters.max_radial, self.parameters.max_angular)?;
+        }
+        return Ok(());
+    }
+
+    fn check_environments(&self, indexes: &Indexes, systems: &mut [&mut dyn System]) -> Result<(), Error> {
+        return check_environments_subset(indexes, &*self.environments(), systems);
+    }
+
+    fn compute(&mut self, systems: &mut [&mut dyn System], descriptor: &mut Descriptor) -> Result<(), Error> {
+        assert_eq!(descriptor.environments.names(), self.environments().names());
+        assert_eq!(descriptor.features.names(), self.features_names());
+
+        let features = descriptor.features.iter()
+            .map(|feature| (feature[0].usize(), feature[1].usize(), feature[2].isize()))
+            .collect::<Vec<_>>();
+
+        for (i_system, system) in systems.iter().enumerate() {
+            let environments = descriptor.environments.iter()
+                .enumerate()
+                .filter(|(_, environment)| environment[0].usize() == i_system)
+                .map(|(i_env, _)| i_env)
+                .collect::<Vec<_>>();
+
+            if environments.is_empty() {
+                continue;
+            }
+
+            if system.cell()?.is_infinite() {
+                self.compute_direct(&**system, &environments, &features, descriptor)?;
+            } else {
+                self.compute_reciprocal(&**system, &environments, &features, descriptor)?;
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::system::{test_systems, SimpleSystem, UnitCell};
+    use crate::descriptor::IndexesBuilder;
+    use crate::{Descriptor, Calculator, System};
+    use crate::{CalculationOptions, SelectedIndexes};
+
+    use approx::assert_relative_eq;
+    use ndarray::s;
+
+    use super::{LodeSphericalExpansion, LodeSphericalExpansionParameters};
+    use super::super::radial_integral::laplacian_eigenstate_quadrature;
+    use super::super::super::CalculatorBase;
+
+    /// Convenience macro to create IndexValue
+    macro_rules! v {
+        ($value: expr) => {
+            crate::descriptor::IndexValue::from($value as i64)
+        };
+    }
+
+    fn parameters(potential_exponent: usize) -> LodeSphericalExpansionParameters {
+        LodeSphericalExpansionParameters {
+            cutoff: 3.5,
+            max_radial: 3,
+            max_angular: 3,
+            atomic_gaussian_width: 1.0,
+            potential_exponent: potential_exponent,
+            gradients: true,
+            k_cutoff: None,
+        }
+    }
+
+    /// Copy the atoms of `system` in a new system with an infinite unit cell
+    fn infinite(system: &SimpleSystem) -> SimpleSystem {
+        let mut infinite = SimpleSystem::new(UnitCell::infinite());
+        for (&species, &position) in system.species().unwrap().iter().zip(system.positions().unwrap()) {
+            infinite.add_atom(species, position);
+        }
+        return infinite;
+    }
+
+    #[test]
+    fn values() {
+        let mut calculator = Calculator::from(Box::new(LodeSphericalExpansion::new(
+            parameters(1)
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water"]);
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut descriptor, Default::default()).unwrap();
+
+        assert_eq!(descriptor.environments.names(), ["structure", "center", "species_center", "species_neighbor"]);
+        assert_eq!(descriptor.environments.count(), 6);
+        assert_eq!(descriptor.features.names(), ["n", "l", "m"]);
+        assert_eq!(descriptor.features.count(), 3 * 16);
+    }
+
+    #[test]
+    fn potential() {
+        // the series expansions and closed forms should agree around the
+        // switching points
+        for &potential_exponent in &[1, 2] {
+            let calculator = LodeSphericalExpansion::new(LodeSphericalExpansionParameters {
+                atomic_gaussian_width: 0.5,
+                ..parameters(potential_exponent)
+            }).unwrap();
+
+            for &r in &[0.0, 0.1, 0.49, 0.5, 0.51, 1.0, 1.41, 1.42, 3.0, 20.0] {
+                let (value, gradient_over_r) = calculator.potential(r);
+                let expected = if potential_exponent == 1 {
+                    if r == 0.0 { f64::sqrt(2.0 / std::f64::consts::PI) / 0.5 } else { crate::math::erf(r / (f64::sqrt(2.0) * 0.5)) / r }
+                } else {
+                    if r == 0.0 { 2.0 } else { (1.0 - f64::exp(-2.0 * r * r)) / (r * r) }
+                };
+                assert_relative_eq!(value, expected, max_relative=1e-12);
+
+                if r > 0.0 {
+                    let delta = 1e-6;
+                    let finite_difference = (calculator.potential(r + delta).0 - calculator.potential(r - delta).0) / (2.0 * delta);
+                    assert_relative_eq!(gradient_over_r * r, finite_difference, epsilon=1e-9, max_relative=1e-6);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn multipole_expansion() {
+        // far from the atom, the erf-smeared Coulomb potential is the same as
+        // the bare Coulomb potential, and its projection on the Legendre
+        // polynomials is 2 / (2l + 1) r^l / d^(l + 1)
+        let parameters = LodeSphericalExpansionParameters {
+            atomic_gaussian_width: 0.5,
+            ..parameters(1)
+        };
+        let calculator = LodeSphericalExpansion::new(parameters.clone()).unwrap();
+
+        let distance = 20.0;
+        let (values, gradients) = calculator.direct_radial_integral(distance);
+
+        let (points, basis) = laplacian_eigenstate_quadrature(
+            parameters.max_radial, parameters.max_angular, parameters.cutoff, 100
+        );
+        for l in 0..=parameters.max_angular {
+            for n in 0..parameters.max_radial {
+                let moment = points.iter().enumerate()
+                    .map(|(k, x)| basis[[l, n, k]] * x.powi(l as i32))
+                    .sum::<f64>();
+
+                let factor = 4.0 * std::f64::consts::PI / (2 * l + 1) as f64 * moment;
+                assert_relative_eq!(
+                    values[[n, l]], factor / distance.powi(l as i32 + 1),
+                    max_relative=1e-10
+                );
+                assert_relative_eq!(
+                    gradients[[n, l]], -((l + 1) as f64) * factor / distance.powi(l as i32 + 2),
+                    max_relative=1e-10
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn periodic_large_cell() {
+        // in a large cell, the periodic images only give small contributions
+        // to l > 0, the k = 0 term changing the l = 0 coefficients
+        let mut calculator = Calculator::from(Box::new(LodeSphericalExpansion::new(
+            LodeSphericalExpansionParameters {
+                gradients: false,
+                ..parameters(1)
+            }
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["CH", "CH"]);
+        let mut system = SimpleSystem::new(UnitCell::cubic(30.0));
+        for (&species, &position) in systems.systems[0].species().unwrap().iter().zip(systems.systems[0].positions().unwrap()) {
+            system.add_atom(species, position);
+        }
+        systems.systems[1] = infinite(&system);
+        systems.systems[0] = system;
+
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut descriptor, Default::default()).unwrap();
+
+        let l = descriptor.features.iter().map(|feature| feature[1].usize()).collect::<Vec<_>>();
+        for i_env in 0..4 {
+            let mut periodic_env = descriptor.environments[i_env].to_vec();
+            periodic_env[0] = v!(0);
+            let mut infinite_env = descriptor.environments[i_env].to_vec();
+            infinite_env[0] = v!(1);
+
+            let periodic = descriptor.environments.position(&periodic_env).unwrap();
+            let infinite = descriptor.environments.position(&infinite_env).unwrap();
+
+            for (i_feature, &l) in l.iter().enumerate() {
+                if l == 0 {
+                    continue;
+                }
+                assert_relative_eq!(
+                    descriptor.values[[periodic, i_feature]],
+                    descriptor.values[[infinite, i_feature]],
+                    epsilon=1e-4,
+                    max_relative=1e-2,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn finite_differences() {
+        check_finite_differences(parameters(1), false);
+        check_finite_differences(parameters(2), false);
+    }
+
+    #[test]
+    fn finite_differences_periodic() {
+        check_finite_differences(parameters(1), true);
+        check_finite_differences(parameters(2), true);
+    }
+
+    fn check_finite_differences(parameters: LodeSphericalExpansionParameters, periodic: bool) {
+        let mut calculator = Calculator::from(Box::new(LodeSphericalExpansion::new(
+            parameters
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water"]);
+        if !periodic {
+            systems.systems[0] = infinite(&systems.systems[0]);
+        }
+
+        let mut reference = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut reference, Default::default()).unwrap();
+
+        let gradients_indexes = reference.gradients_indexes.as_ref().unwrap();
+        assert_eq!(
+            gradients_indexes.names(),
+            ["structure", "center", "species_center", "species_neighbor", "neighbor", "spatial"]
+        );
+
+        // get the list of modified gradient environments when moving atom_i
+        let modified_indexes = |atom_i: usize, spatial_index: usize| {
+            let mut results = Vec::new();
+            for (env_i, env) in gradients_indexes.iter().enumerate() {
+                let center = env[1];
+                let neighbor = env[4];
+                let spatial = env[5];
+                if center.usize() != atom_i && neighbor.usize() == atom_i && spatial.usize() == spatial_index {
+                    results.push((env_i, &env[..4]));
+                }
+            }
+            return results;
+        };
+
+        let delta = 1e-6;
+        let gradients = reference.gradients.as_ref().unwrap();
+        for atom_i in 0..systems.systems[0].size().unwrap() {
+            for spatial in 0..3 {
+                systems.systems[0].positions_mut()[atom_i][spatial] += delta;
+                let mut updated_plus = Descriptor::new();
+                calculator.compute(&mut systems.get(), &mut updated_plus, Default::default()).unwrap();
+
+                systems.systems[0].positions_mut()[atom_i][spatial] -= 2.0 * delta;
+                let mut updated_minus = Descriptor::new();
+                calculator.compute(&mut systems.get(), &mut updated_minus, Default::default()).unwrap();
+
+                let modified = modified_indexes(atom_i, spatial);
+                assert!(!modified.is_empty());
+                for (grad_i, env) in modified {
+                    let env_i = reference.environments.position(env).expect(
+                        "missing environment in reference values"
+                    );
+
+                    let mut finite_difference = updated_plus.values.slice(s![env_i, ..]).to_owned();
+                    finite_difference -= &updated_minus.values.slice(s![env_i, ..]);
+                    finite_difference /= 2.0 * delta;
+
+                    assert_relative_eq!(
+                        finite_difference, gradients.slice(s![grad_i, ..]),
+                        epsilon=1e-7,
+                        max_relative=1e-4,
+                    );
+                }
+
+                systems.systems[0].positions_mut()[atom_i][spatial] += delta;
+            }
+        }
+    }
+
+    #[test]
+    fn compute_partial() {
+        let mut calculator = Calculator::from(Box::new(LodeSphericalExpansion::new(
+            parameters(1)
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water", "CH"]);
+        systems.systems[1] = infinite(&systems.systems[1]);
+
+        let mut full = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut full, Default::default()).unwrap();
+
+        let mut features = IndexesBuilder::new(vec!["n", "l", "m"]);
+        features.add(&[v!(0), v!(1), v!(0)]);
+        features.add(&[v!(2), v!(3), v!(-2)]);
+        features.add(&[v!(1), v!(0), v!(0)]);
+        let features = features.finish();
+
+        let mut environments = IndexesBuilder::new(vec!["structure", "center", "species_center", "species_neighbor"]);
+        environments.add(&[v!(0), v!(1), v!(1), v!(123456)]);
+        environments.add(&[v!(1), v!(0), v!(1), v!(6)]);
+        environments.add(&[v!(0), v!(0), v!(123456), v!(1)]);
+        let environments = environments.finish();
+
+        let mut partial = Descriptor::new();
+        let options = CalculationOptions {
+            selected_samples: SelectedIndexes::Some(environments.clone()),
+            selected_features: SelectedIndexes::Some(features.clone()),
+            ..Default::default()
+        };
+        calculator.compute(&mut systems.get(), &mut partial, options).unwrap();
+
+        for (env_i, environment) in environments.iter().enumerate() {
+            for (feature_i, feature) in features.iter().enumerate() {
+                let full_env = full.environments.position(environment).unwrap();
+                let full_feature = full.features.position(feature).unwrap();
+                assert_relative_eq!(
+                    full.values[[full_env, full_feature]],
+                    partial.values[[env_i, feature_i]],
+                    max_relative=1e-12,
+                );
+            }
+        }
+
+        for (env_i, environment) in partial.gradients_indexes.as_ref().unwrap().iter().enumerate() {
+            for (feature_i, feature) in features.iter().enumerate() {
+                let full_env = full.gradients_indexes.as_ref().unwrap().position(environment).unwrap();
+                let full_feature = full.features.position(feature).unwrap();
+                assert_relative_eq!(
+                    full.gradients.as_ref().unwrap()[[full_env, full_feature]],
+                    partial.gradients.as_ref().unwrap()[[env_i, feature_i]],
+                    max_relative=1e-12,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn parameters_serialization() {
+        let parameters: LodeSphericalExpansionParameters = serde_json::from_str(r#"{
+            "cutoff": 3.5,
+            "max_radial": 4,
+            "max_angular": 2,
+            "atomic_gaussian_width": 0.5,
+            "potential_exponent": 1,
+            "gradients": false
+        }"#).unwrap();
+        assert_eq!(parameters.k_cutoff, None);
+        assert_relative_eq!(parameters.k_cutoff(), f64::sqrt(60.0) / 0.5);
+    }
+
+    #[test]
+    fn invalid_potential_exponent() {
+        let error = LodeSphericalExpansion::new(parameters(3)).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: potential_exponent must be 1 or 2, got 3");
+    }
+}
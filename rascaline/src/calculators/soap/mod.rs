@@ -4,10 +4,33 @@ pub use self::hypergeometric::{HyperGeometricSphericalExpansion, HyperGeometricP
 mod radial_integral;
 pub use self::radial_integral::RadialIntegral;
 pub use self::radial_integral::{GTO, GTOParameters};
+pub use self::radial_integral::{DVR, DVRParameters};
+pub use self::radial_integral::{LaplacianEigenstate, LaplacianEigenstateParameters};
+
+mod spline;
+pub use self::spline::{SplinedRadialIntegral, SplineParameters};
 
 mod spherical_harmonics;
 pub use self::spherical_harmonics::{SphericalHarmonics, SphericalHarmonicsArray};
 
 mod spherical_expansion;
 pub use self::spherical_expansion::{SphericalExpansion, SphericalExpansionParameters};
-pub use self::spherical_expansion::{RadialBasis, CutoffFunction};
+pub use self::spherical_expansion::{RadialBasis, CutoffFunction, RadialScaling};
+
+mod lode_spherical_expansion;
+pub use self::lode_spherical_expansion::{LodeSphericalExpansion, LodeSphericalExpansionParameters};
+
+mod expansion_request;
+
+mod power_spectrum;
+pub use self::power_spectrum::{SoapPowerSpectrum, PowerSpectrumParameters};
+
+mod clebsch_gordan;
+pub use self::clebsch_gordan::{ClebschGordan, ClebschGordanCoefficient};
+pub use self::clebsch_gordan::{wigner_3j, clebsch_gordan};
+
+mod bispectrum;
+pub use self::bispectrum::{SoapBispectrum, BispectrumParameters};
+
+mod radial_spectrum;
+pub use self::radial_spectrum::{SoapRadialSpectrum, RadialSpectrumParameters};
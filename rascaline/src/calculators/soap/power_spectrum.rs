@@ -0,0 +1,575 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::f64::consts::SQRT_2;
+
+use crate::descriptor::{Descriptor, Indexes, IndexesBuilder, IndexValue};
+use crate::descriptor::{EnvironmentIndexes, AtomSpeciesEnvironment, CenterSpeciesEnvironment};
+use crate::{Calculator, Error, System};
+
+use super::super::{CalculatorBase, check_indexes_names, check_environments_subset};
+use super::{SphericalExpansion, SphericalExpansionParameters};
+use super::{RadialBasis, CutoffFunction, RadialScaling};
+use super::expansion_request::{ExpansionRequest, gradients_positions};
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[allow(clippy::module_name_repetitions)]
+pub struct PowerSpectrumParameters {
+    /// Spherical cutoff to use for atomic environments
+    pub cutoff: f64,
+    /// Number of radial basis function to use
+    pub max_radial: usize,
+    /// Number of spherical harmonics to use
+    pub max_angular: usize,
+    /// Width of the atom-centered gaussian creating the atomic density
+    pub atomic_gaussian_width: f64,
+    /// Atomic species of the neighbors to include in the features
+    pub species: Vec<usize>,
+    /// Should we also compute gradients of the feature?
+    pub gradients: bool,
+    /// radial basis to use for the radial integral
+    pub radial_basis: RadialBasis,
+    /// cutoff function used to smooth the behavior around the cutoff radius
+    pub cutoff_function: CutoffFunction,
+    /// If set, the radial integral is evaluated using splines with the given
+    /// absolute accuracy instead of being computed directly, which is much
+    /// faster for large numbers of neighbors
+    pub spline_accuracy: Option<f64>,
+}
+
+/// Calculator implementing the SOAP power spectrum, i.e. the rotationally
+/// invariant combination of two spherical expansion coefficients:
+///
+/// `p[α](β1, β2, n1, n2, l) = 1 / sqrt(2l + 1) Σ_m c[α, β1](n1, l, m) c[α, β2](n2, l, m)`
+///
+/// The samples are the atomic environments `(structure, center,
+/// species_center)`, and the features are indexed by `(species_neighbor_1,
+/// species_neighbor_2, n1, n2, l)`, for all the pairs of neighbor `species`
+/// given in the parameters. Only the `β1 <= β2` pairs are stored, and the
+/// corresponding values are multiplied by `sqrt(2)` when `β1 != β2` to keep
+/// the same scalar product as the full power spectrum. Features for species
+/// without neighbors around a center are zero.
+pub struct SoapPowerSpectrum {
+    parameters: PowerSpectrumParameters,
+    /// Sorted list of the neighbor species
+    species: Vec<usize>,
+    spherical_expansion: Calculator,
+}
+
+impl SoapPowerSpectrum {
+    pub fn new(parameters: PowerSpectrumParameters) -> Result<SoapPowerSpectrum, Error> {
+        let species = parameters.species.iter().copied().collect::<BTreeSet<_>>();
+        if species.is_empty() {
+            return Err(Error::InvalidParameter("species must contain at least one species".into()));
+        }
+
+        if species.len() != parameters.species.len() {
+            return Err(Error::InvalidParameter("species must not contain the same species multiple times".into()));
+        }
+
+        let expansion_parameters = SphericalExpansionParameters {
+            cutoff: parameters.cutoff,
+            max_radial: parameters.max_radial,
+            max_angular: parameters.max_angular,
+            atomic_gaussian_width: parameters.atomic_gaussian_width,
+            gradients: parameters.gradients,
+            cell_gradients: false,
+            radial_basis: parameters.radial_basis.clone(),
+            cutoff_function: parameters.cutoff_function.clone(),
+            radial_scaling: RadialScaling::None,
+            center_atom_weight: 1.0,
+            species_weights: BTreeMap::new(),
+            species_coupling: None,
+            spline_accuracy: parameters.spline_accuracy,
+        };
+
+        let spherical_expansion = Calculator::from(Box::new(
+            SphericalExpansion::new(expansion_parameters)?
+        ) as Box<dyn CalculatorBase>);
+
+        return Ok(SoapPowerSpectrum {
+            parameters: parameters,
+            species: species.into_iter().collect(),
+            spherical_expansion: spherical_expansion,
+        });
+    }
+
+    /// Get the position of the species in the given feature index `value` in
+    /// `self.species`, if this is one of the neighbor species
+    fn species_position(&self, value: IndexValue) -> Option<usize> {
+        let species = usize::try_from(value.i64()).ok()?;
+        return self.species.binary_search(&species).ok();
+    }
+
+    /// Get the set of spherical expansion samples & features required to
+    /// compute the power spectrum samples & features in `descriptor`. Only
+    /// the spherical expansion samples with at least one neighbor of the
+    /// corresponding species are requested.
+    fn expansion_request(&self, descriptor: &Descriptor, systems: &mut [&mut dyn System]) -> Result<ExpansionRequest, Error> {
+        let centers = descriptor.environments.iter().collect::<BTreeSet<_>>();
+        let species = descriptor.features.iter()
+            .flat_map(|feature| [feature[0], feature[1]])
+            .collect::<BTreeSet<_>>();
+
+        let mut request = ExpansionRequest::new();
+        let expansion_environments = AtomSpeciesEnvironment::with_self_contribution(self.parameters.cutoff);
+        for sample in &expansion_environments.indexes(systems)? {
+            if centers.contains(&sample[..3]) && species.contains(&sample[3]) {
+                request.add_sample([sample[0], sample[1], sample[2], sample[3]]);
+            }
+        }
+
+        for feature in &descriptor.features {
+            let l = feature[4].usize();
+            request.add_block(feature[2].usize(), l);
+            request.add_block(feature[3].usize(), l);
+        }
+
+        return Ok(request);
+    }
+}
+
+impl std::fmt::Debug for SoapPowerSpectrum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.parameters)
+    }
+}
+
+impl CalculatorBase for SoapPowerSpectrum {
+    fn name(&self) -> String {
+        "SOAP power spectrum".into()
+    }
+
+    fn get_parameters(&self) -> String {
+        serde_json::to_string(&self.parameters).expect("failed to serialize to JSON")
+    }
+
+    fn features_names(&self) -> Vec<&str> {
+        vec!["species_neighbor_1", "species_neighbor_2", "n1", "n2", "l"]
+    }
+
+    fn features(&self) -> Indexes {
+        let mut features = IndexesBuilder::new(self.features_names());
+        for (i, &species_1) in self.species.iter().enumerate() {
+            for &species_2 in &self.species[i..] {
+                for n1 in 0..self.parameters.max_radial {
+                    for n2 in 0..self.parameters.max_radial {
+                        for l in 0..(self.parameters.max_angular + 1) {
+                            features.add(&[
+                                IndexValue::from(species_1), IndexValue::from(species_2),
+                                IndexValue::from(n1), IndexValue::from(n2), IndexValue::from(l)
+                            ]);
+                        }
+                    }
+                }
+            }
+        }
+        return features.finish();
+    }
+
+    fn environments(&self) -> Box<dyn EnvironmentIndexes> {
+        Box::new(CenterSpeciesEnvironment::new(self.parameters.cutoff))
+    }
+
+    fn compute_gradients(&self) -> bool {
+        self.parameters.gradients
+    }
+
+    fn check_features(&self, indexes: &Indexes) -> Result<(), Error> {
+        check_indexes_names("features", indexes, &self.features_names())?;
+        for value in indexes {
+            if self.species_position(value[0]).is_none() || self.species_position(value[1]).is_none() {
+                return Err(Error::InvalidParameter(format!(
+                    "invalid feature {:?}: species_neighbor_1 and species_neighbor_2 must be part of the species ({:?})",
+                    value, self.species
+                )));
+            }
+
+            let n1 = value[2].usize();
+            let n2 = value[3].usize();
+            let l = value[4].usize();
+            if n1 >= self.parameters.max_radial || n2 >= self.parameters.max_radial {
+                return Err(Error::InvalidParameter(format!(
+                    "invalid feature {:?}: n1 and n2 must be lower than max_radial ({})",
+                    value, self.parameters.max_radial
+                )));
+            }
+
+            if l > self.parameters.max_angular {
+                return Err(Error::InvalidParameter(format!(
+                    "invalid feature {:?}: l must be at most max_angular ({})",
+                    value, self.parameters.max_angular
+                )));
+            }
+        }
+        return Ok(());
+    }
+
+    fn check_environments(&self, indexes: &Indexes, systems: &mut [&mut dyn System]) -> Result<(), Error> {
+        return check_environments_subset(indexes, &*self.environments(), systems);
+    }
+
+    #[allow(clippy::similar_names, clippy::too_many_lines)]
+    fn compute(&mut self, systems: &mut [&mut dyn System], descriptor: &mut Descriptor) -> Result<(), Error> {
+        assert_eq!(descriptor.environments.names(), &["structure", "center", "species_center"]);
+        assert_eq!(descriptor.features.names(), &["species_neighbor_1", "species_neighbor_2", "n1", "n2", "l"]);
+
+        let request = self.expansion_request(descriptor, systems)?;
+        let expansion = request.compute(&mut self.spherical_expansion, systems)?;
+
+        // position of the neighbor species in `self.species`, and first
+        // column of the spherical expansion coefficients for each feature
+        let features = descriptor.features.iter().map(|feature| {
+            let species_1 = self.species_position(feature[0]).expect("unknown species in features");
+            let species_2 = self.species_position(feature[1]).expect("unknown species in features");
+            let l = feature[4].usize();
+            let start_1 = request.block(feature[2].usize(), l);
+            let start_2 = request.block(feature[3].usize(), l);
+            let factor = if species_1 == species_2 { 1.0 } else { SQRT_2 };
+            (species_1, species_2, start_1, start_2, l, factor / f64::sqrt((2 * l + 1) as f64))
+        }).collect::<Vec<_>>();
+
+        // row of the spherical expansion for each neighbor species, or `None`
+        // if there are no neighbors with this species
+        let expansion_rows = |structure, center, species_center| {
+            self.species.iter()
+                .map(|&species| request.sample(&[structure, center, species_center, IndexValue::from(species)]))
+                .collect::<Vec<_>>()
+        };
+
+        for (i_env, requested_env) in descriptor.environments.iter().enumerate() {
+            let rows = expansion_rows(requested_env[0], requested_env[1], requested_env[2]);
+
+            for (i_feature, &(species_1, species_2, start_1, start_2, l, normalization)) in features.iter().enumerate() {
+                let (env_1, env_2) = match (rows[species_1], rows[species_2]) {
+                    (Some(env_1), Some(env_2)) => (env_1, env_2),
+                    // no neighbors with one of the species
+                    _ => continue,
+                };
+
+                let mut sum = 0.0;
+                for m in 0..(2 * l + 1) {
+                    sum += expansion.values[[env_1, start_1 + m]] * expansion.values[[env_2, start_2 + m]];
+                }
+
+                descriptor.values[[i_env, i_feature]] = normalization * sum;
+            }
+        }
+
+        if self.parameters.gradients {
+            let expansion_gradients = expansion.gradients.as_ref().expect("missing spherical expansion gradients");
+            let expansion_gradients_positions = gradients_positions(&expansion);
+
+            let gradients = descriptor.gradients.as_mut().expect("missing storage for gradients");
+            let gradients_indexes = descriptor.gradients_indexes.as_ref().expect("missing gradients indexes");
+            assert_eq!(gradients_indexes.names(), &["structure", "center", "species_center", "neighbor", "spatial"]);
+
+            for (i_grad, index) in gradients_indexes.iter().enumerate() {
+                if index[4].usize() != 0 {
+                    // all spatial components are computed at the same time
+                    continue;
+                }
+
+                let (structure, center, species_center, neighbor) = (index[0], index[1], index[2], index[3]);
+                let rows = expansion_rows(structure, center, species_center);
+                let gradient_rows = self.species.iter().map(|&species| {
+                    let species = IndexValue::from(species);
+                    expansion_gradients_positions.get(&[structure, center, species_center, species, neighbor]).copied()
+                }).collect::<Vec<_>>();
+
+                for (i_feature, &(species_1, species_2, start_1, start_2, l, normalization)) in features.iter().enumerate() {
+                    let (env_1, env_2) = match (rows[species_1], rows[species_2]) {
+                        (Some(env_1), Some(env_2)) => (env_1, env_2),
+                        _ => continue,
+                    };
+                    let grad_1 = gradient_rows[species_1];
+                    let grad_2 = gradient_rows[species_2];
+
+                    for spatial in 0..3 {
+                        let mut sum = 0.0;
+                        for m in 0..(2 * l + 1) {
+                            if let Some(grad_1) = grad_1 {
+                                sum += expansion_gradients[[grad_1 + spatial, start_1 + m]] * expansion.values[[env_2, start_2 + m]];
+                            }
+
+                            if let Some(grad_2) = grad_2 {
+                                sum += expansion.values[[env_1, start_1 + m]] * expansion_gradients[[grad_2 + spatial, start_2 + m]];
+                            }
+                        }
+
+                        // assumes that the three spatial derivative are stored
+                        // one after the other
+                        gradients[[i_grad + spatial, i_feature]] = normalization * sum;
+                    }
+                }
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::system::test_systems;
+    use crate::descriptor::{IndexValue, IndexesBuilder};
+    use crate::{Descriptor, Calculator, System};
+    use crate::{CalculationOptions, SelectedIndexes};
+
+    use approx::assert_relative_eq;
+    use ndarray::s;
+
+    use super::{SoapPowerSpectrum, PowerSpectrumParameters};
+    use super::super::{CutoffFunction, RadialBasis};
+    use super::super::super::CalculatorBase;
+
+    /// Convenience macro to create IndexValue
+    macro_rules! v {
+        ($value: expr) => {
+            crate::descriptor::IndexValue::from($value as i64)
+        };
+    }
+
+    fn parameters(gradients: bool) -> PowerSpectrumParameters {
+        PowerSpectrumParameters {
+            atomic_gaussian_width: 0.3,
+            cutoff: 3.5,
+            cutoff_function: CutoffFunction::ShiftedCosine { width: 0.5 },
+            gradients: gradients,
+            max_radial: 6,
+            max_angular: 6,
+            radial_basis: RadialBasis::GTO,
+            species: vec![1, 6, 123456],
+            spline_accuracy: None,
+        }
+    }
+
+    #[test]
+    fn invalid_species() {
+        let mut parameters = parameters(false);
+        parameters.species = vec![];
+        assert_eq!(
+            SoapPowerSpectrum::new(parameters.clone()).err().unwrap().to_string(),
+            "invalid parameter: species must contain at least one species"
+        );
+
+        parameters.species = vec![1, 8, 1];
+        assert_eq!(
+            SoapPowerSpectrum::new(parameters).err().unwrap().to_string(),
+            "invalid parameter: species must not contain the same species multiple times"
+        );
+    }
+
+    #[test]
+    fn values() {
+        let mut calculator = Calculator::from(Box::new(SoapPowerSpectrum::new(
+            parameters(false)
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water"]);
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut descriptor, Default::default()).unwrap();
+
+        assert_eq!(descriptor.environments.names(), ["structure", "center", "species_center"]);
+        assert_eq!(descriptor.features.names(), ["species_neighbor_1", "species_neighbor_2", "n1", "n2", "l"]);
+
+        let mut index = 0;
+        for (species_1, species_2) in [(1, 1), (1, 6), (1, 123456), (6, 6), (6, 123456), (123456, 123456)] {
+            for n1 in 0..6_usize {
+                for n2 in 0..6_usize {
+                    for l in 0..=6_usize {
+                        let expected = [
+                            v!(species_1), v!(species_2),
+                            IndexValue::from(n1), IndexValue::from(n2), IndexValue::from(l)
+                        ];
+                        assert_eq!(descriptor.features[index], expected);
+                        index += 1;
+                    }
+                }
+            }
+        }
+        assert_eq!(descriptor.features.count(), index);
+
+        // check the values against an explicit contraction of the spherical
+        // expansion coefficients
+        let mut expansion = Descriptor::new();
+        let mut expansion_calculator = Calculator::new("spherical_expansion", calculator.parameters().into()).unwrap();
+        expansion_calculator.compute(&mut systems.get(), &mut expansion, Default::default()).unwrap();
+
+        for (i_env, env) in descriptor.environments.iter().enumerate() {
+            for (i_feature, feature) in descriptor.features.iter().enumerate() {
+                let (species_1, species_2) = (feature[0], feature[1]);
+                let (n1, n2, l) = (feature[2], feature[3], feature[4].isize());
+
+                let env_1 = expansion.environments.position(&[env[0], env[1], env[2], species_1]);
+                let env_2 = expansion.environments.position(&[env[0], env[1], env[2], species_2]);
+                let (env_1, env_2) = match (env_1, env_2) {
+                    (Some(env_1), Some(env_2)) => (env_1, env_2),
+                    _ => {
+                        // there is no carbon in water
+                        assert!(species_1.usize() == 6 || species_2.usize() == 6);
+                        assert_eq!(descriptor.values[[i_env, i_feature]], 0.0);
+                        continue;
+                    }
+                };
+                let factor = if species_1 == species_2 { 1.0 } else { f64::sqrt(2.0) };
+
+                let mut expected = 0.0;
+                for m in -l..=l {
+                    let feature_1 = expansion.features.position(&[n1, v!(l), v!(m)]).unwrap();
+                    let feature_2 = expansion.features.position(&[n2, v!(l), v!(m)]).unwrap();
+                    expected += expansion.values[[env_1, feature_1]] * expansion.values[[env_2, feature_2]];
+                }
+                expected *= factor / f64::sqrt((2 * l + 1) as f64);
+
+                assert_relative_eq!(descriptor.values[[i_env, i_feature]], expected, max_relative=1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn finite_differences() {
+        let mut calculator = Calculator::from(Box::new(SoapPowerSpectrum::new(
+            parameters(true)
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water"]);
+        let mut reference = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut reference, Default::default()).unwrap();
+
+        let gradients_indexes = reference.gradients_indexes.as_ref().unwrap();
+        assert_eq!(
+            gradients_indexes.names(),
+            ["structure", "center", "species_center", "neighbor", "spatial"]
+        );
+
+        // get the list of modified gradient environments when moving atom_i
+        let modified_indexes = |atom_i: usize, spatial_index: usize| {
+            let mut results = Vec::new();
+            for (env_i, env) in gradients_indexes.iter().enumerate() {
+                let center = env[1];
+                let neighbor = env[3];
+                let spatial = env[4];
+                if center.usize() != atom_i && neighbor.usize() == atom_i && spatial.usize() == spatial_index {
+                    results.push((env_i, &env[..3]));
+                }
+            }
+            return results;
+        };
+
+        let delta = 1e-9;
+        let gradients = reference.gradients.as_ref().unwrap();
+        for atom_i in 0..systems.systems[0].size().unwrap() {
+            for spatial in 0..3 {
+                systems.systems[0].positions_mut()[atom_i][spatial] += delta;
+
+                let mut updated = Descriptor::new();
+                calculator.compute(&mut systems.get(), &mut updated, Default::default()).unwrap();
+
+                for (grad_i, env) in modified_indexes(atom_i, spatial) {
+                    let env_i = reference.environments.position(env).expect(
+                        "missing environment in reference values"
+                    );
+                    assert_eq!(updated.environments.position(env).unwrap(), env_i);
+
+                    let value = reference.values.slice(s![env_i, ..]);
+                    let value_delta = updated.values.slice(s![env_i, ..]);
+                    let gradient = gradients.slice(s![grad_i, ..]);
+
+                    assert_eq!(value.shape(), value_delta.shape());
+                    assert_eq!(value.shape(), gradient.shape());
+
+                    let mut finite_difference = value_delta.to_owned().clone();
+                    finite_difference -= &value;
+                    finite_difference /= delta;
+
+                    assert_relative_eq!(
+                        finite_difference, gradient,
+                        epsilon=1e-6,
+                        max_relative=5e-4,
+                    );
+                }
+
+                systems.systems[0].positions_mut()[atom_i][spatial] -= delta;
+            }
+        }
+    }
+
+    #[test]
+    fn compute_partial() {
+        let mut calculator = Calculator::from(Box::new(SoapPowerSpectrum::new(
+            parameters(true)
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water", "methane"]);
+        let mut full = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut full, Default::default()).unwrap();
+
+        // partial set of features, all environments
+        let mut features = IndexesBuilder::new(vec!["species_neighbor_1", "species_neighbor_2", "n1", "n2", "l"]);
+        features.add(&[v!(1), v!(1), v!(0), v!(1), v!(0)]);
+        features.add(&[v!(6), v!(6), v!(3), v!(3), v!(6)]);
+        features.add(&[v!(1), v!(123456), v!(2), v!(5), v!(2)]);
+        features.add(&[v!(1), v!(6), v!(1), v!(4), v!(4)]);
+        features.add(&[v!(123456), v!(123456), v!(5), v!(2), v!(0)]);
+        features.add(&[v!(1), v!(1), v!(1), v!(1), v!(1)]);
+        let features = features.finish();
+
+        let mut partial = Descriptor::new();
+        let options = CalculationOptions {
+            selected_samples: SelectedIndexes::All,
+            selected_features: SelectedIndexes::Some(features.clone()),
+            ..Default::default()
+        };
+        calculator.compute(&mut systems.get(), &mut partial, options).unwrap();
+
+        assert_eq!(full.environments, partial.environments);
+        for (partial_i, feature) in features.iter().enumerate() {
+            let index = full.features.position(feature).unwrap();
+            assert_eq!(
+                full.values.slice(s![.., index]),
+                partial.values.slice(s![.., partial_i])
+            );
+
+            assert_eq!(
+                full.gradients.as_ref().unwrap().slice(s![.., index]),
+                partial.gradients.as_ref().unwrap().slice(s![.., partial_i])
+            );
+        }
+
+        // all features, partial set of environments
+        let mut environments = IndexesBuilder::new(vec!["structure", "center", "species_center"]);
+        environments.add(&[v!(0), v!(1), v!(1)]);
+        environments.add(&[v!(0), v!(0), v!(123456)]);
+        environments.add(&[v!(1), v!(0), v!(6)]);
+        environments.add(&[v!(1), v!(2), v!(1)]);
+        let environments = environments.finish();
+
+        let options = CalculationOptions {
+            selected_samples: SelectedIndexes::Some(environments.clone()),
+            selected_features: SelectedIndexes::All,
+            ..Default::default()
+        };
+        calculator.compute(&mut systems.get(), &mut partial, options).unwrap();
+
+        assert_eq!(full.features, partial.features);
+        for (partial_i, environment) in environments.iter().enumerate() {
+            let index = full.environments.position(environment).unwrap();
+            assert_eq!(
+                full.values.slice(s![index, ..]),
+                partial.values.slice(s![partial_i, ..])
+            );
+        }
+
+        for (partial_i, gradient) in partial.gradients_indexes.as_ref().unwrap().iter().enumerate() {
+            if !environments.contains(&gradient[..3]) {
+                continue;
+            }
+
+            let index = full.gradients_indexes.as_ref().unwrap().position(gradient).unwrap();
+            assert_eq!(
+                full.gradients.as_ref().unwrap().slice(s![index, ..]),
+                partial.gradients.as_ref().unwrap().slice(s![partial_i, ..])
+            );
+        }
+    }
+}
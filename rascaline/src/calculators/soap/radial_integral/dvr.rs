@@ -0,0 +1,207 @@
+use ndarray::ArrayViewMut2;
+
+use crate::math::gauss_legendre;
+use crate::Error;
+use super::{RadialIntegral, gaussian_density_projection};
+
+/// Parameters controlling the DVR radial basis
+#[derive(Debug, Clone, Copy)]
+pub struct DVRParameters {
+    /// Number of radial components
+    pub max_radial: usize,
+    /// Number of angular components
+    pub max_angular: usize,
+    /// atomic density gaussian width
+    pub atomic_gaussian_width: f64,
+    /// cutoff radius
+    pub cutoff: f64,
+}
+
+impl DVRParameters {
+    fn validate(&self) -> Result<(), Error> {
+        if self.max_radial == 0 {
+            return Err(Error::InvalidParameter("max_radial must be at least 1".into()));
+        }
+
+        if !(self.cutoff > 0.0 && self.cutoff.is_finite()) {
+            return Err(Error::InvalidParameter("cutoff must be a positive number".into()));
+        }
+
+        if !(self.atomic_gaussian_width > 0.0 && self.atomic_gaussian_width.is_finite()) {
+            return Err(Error::InvalidParameter("atomic_gaussian_width must be a positive number".into()));
+        }
+
+        return Ok(());
+    }
+}
+
+/// Discrete variable representation (DVR) radial basis.
+///
+/// The radial functions are associated with the points `x_n` of the
+/// `max_radial`-points Gauss-Legendre quadrature on `[0, cutoff]`, and are
+/// orthonormal with respect to this quadrature. The radial integral is
+/// evaluated as `sqrt(w_n) x_n g_l(x_n, rij)`, where `w_n` is the quadrature
+/// weight and `g_l` the projection of the atomic density on the spherical
+/// harmonics.
+#[derive(Debug, Clone)]
+pub struct DVR {
+    parameters: DVRParameters,
+    /// 1/2σ^2, with σ the atomic density gaussian width
+    atomic_gaussian_constant: f64,
+    /// Gauss-Legendre quadrature points in `[0, cutoff]`
+    points: Vec<f64>,
+    /// `sqrt(w_n) x_n` for each quadrature point and weight
+    factors: Vec<f64>,
+}
+
+impl DVR {
+    pub fn new(parameters: DVRParameters) -> Result<DVR, Error> {
+        parameters.validate()?;
+
+        // scale the quadrature from [-1, 1] to [0, cutoff]
+        let half_cutoff = 0.5 * parameters.cutoff;
+        let (points, weights) = gauss_legendre(parameters.max_radial);
+        let points = points.iter().map(|x| half_cutoff * (x + 1.0)).collect::<Vec<_>>();
+        let factors = points.iter().zip(&weights)
+            .map(|(x, w)| f64::sqrt(half_cutoff * w) * x)
+            .collect();
+
+        let sigma2 = parameters.atomic_gaussian_width * parameters.atomic_gaussian_width;
+        return Ok(DVR {
+            parameters: parameters,
+            atomic_gaussian_constant: 1.0 / (2.0 * sigma2),
+            points: points,
+            factors: factors,
+        });
+    }
+}
+
+impl RadialIntegral for DVR {
+    fn compute(
+        &self,
+        distance: f64,
+        mut values: ArrayViewMut2<f64>,
+        mut gradients: Option<ArrayViewMut2<f64>>
+    ) {
+        let expected_shape = [self.parameters.max_radial, self.parameters.max_angular + 1];
+        assert_eq!(
+            values.shape(), expected_shape,
+            "wrong size for values array, expected [{}, {}] but got [{}, {}]",
+            expected_shape[0], expected_shape[1], values.shape()[0], values.shape()[1]
+        );
+
+        if let Some(ref gradients) = gradients {
+            assert_eq!(
+                gradients.shape(), expected_shape,
+                "wrong size for gradients array, expected [{}, {}] but got [{}, {}]",
+                expected_shape[0], expected_shape[1], gradients.shape()[0], gradients.shape()[1]
+            );
+        }
+
+        let n_angular = self.parameters.max_angular + 1;
+        let mut bessel = vec![0.0; n_angular + 1];
+        let mut projection = vec![0.0; n_angular];
+        let mut projection_gradient = vec![0.0; n_angular];
+
+        for (n, (&point, &factor)) in self.points.iter().zip(&self.factors).enumerate() {
+            gaussian_density_projection(
+                self.atomic_gaussian_constant,
+                point,
+                distance,
+                &mut bessel,
+                &mut projection,
+                gradients.as_ref().map(|_| &mut *projection_gradient),
+            );
+
+            for l in 0..n_angular {
+                values[[n, l]] = factor * projection[l];
+            }
+
+            if let Some(ref mut gradients) = gradients {
+                for l in 0..n_angular {
+                    gradients[[n, l]] = factor * projection_gradient[l];
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use ndarray::Array2;
+
+    use super::{DVR, DVRParameters};
+    use super::super::RadialIntegral;
+
+    #[test]
+    fn invalid_max_radial() {
+        let error = DVR::new(DVRParameters {
+            max_radial: 0,
+            max_angular: 4,
+            cutoff: 3.0,
+            atomic_gaussian_width: 0.5
+        }).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: max_radial must be at least 1");
+    }
+
+    #[test]
+    fn negative_cutoff() {
+        let error = DVR::new(DVRParameters {
+            max_radial: 10,
+            max_angular: 4,
+            cutoff: -3.0,
+            atomic_gaussian_width: 0.5
+        }).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: cutoff must be a positive number");
+    }
+
+    #[test]
+    fn infinite_atomic_gaussian_width() {
+        let error = DVR::new(DVRParameters {
+            max_radial: 10,
+            max_angular: 4,
+            cutoff: 3.0,
+            atomic_gaussian_width: f64::INFINITY,
+        }).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: atomic_gaussian_width must be a positive number");
+    }
+
+    #[test]
+    #[should_panic = "wrong size for values array, expected [2, 4] but got [2, 3]"]
+    fn values_array_size() {
+        let dvr = DVR::new(DVRParameters {
+            max_radial: 2,
+            max_angular: 3,
+            cutoff: 5.0,
+            atomic_gaussian_width: 0.5,
+        }).unwrap();
+        let mut values = Array2::from_elem((2, 3), 0.0);
+
+        dvr.compute(1.0, values.view_mut(), None);
+    }
+
+    #[test]
+    fn dvr_finite_differences() {
+        let max_radial = 8;
+        let max_angular = 8;
+        let dvr = DVR::new(DVRParameters {
+            max_radial: max_radial,
+            max_angular: max_angular,
+            cutoff: 5.0,
+            atomic_gaussian_width: 0.5,
+        }).unwrap();
+
+        let delta = 1e-9;
+        let mut values = Array2::from_elem((max_radial, max_angular + 1), 0.0);
+        let mut values_delta = Array2::from_elem((max_radial, max_angular + 1), 0.0);
+        let mut gradients = Array2::from_elem((max_radial, max_angular + 1), 0.0);
+        for &rij in &[0.0, 1.2, 3.4] {
+            dvr.compute(rij, values.view_mut(), Some(gradients.view_mut()));
+            dvr.compute(rij + delta, values_delta.view_mut(), None);
+
+            let finite_differences = (&values_delta - &values) / delta;
+            assert_relative_eq!(finite_differences, gradients, epsilon=1e-5, max_relative=5e-5);
+        }
+    }
+}
@@ -4,17 +4,12 @@ use nalgebra as na;
 use nalgebra::linalg::SymmetricEigen;
 
 use crate::math::gamma;
-use super::{HyperGeometricSphericalExpansion, HyperGeometricParameters};
+use crate::Error;
+use super::super::{HyperGeometricSphericalExpansion, HyperGeometricParameters};
+use super::RadialIntegral;
 
 const PI_TO_THREE_HALF: f64 = 15.503138340149908;
 
-pub trait RadialIntegral: std::panic::RefUnwindSafe {
-    /// Compute the radial integral for a single atomic `distance` and store the
-    /// resulting data in the `max_radial x max_angular` array `values`. If
-    /// `gradients` is `Some`, also compute and store gradients.
-    fn compute(&self, rij: f64, values: ArrayViewMut2<f64>, gradients: Option<ArrayViewMut2<f64>>);
-}
-
 /// Parameters controlling GTO radial basis
 #[derive(Debug, Clone, Copy)]
 pub struct GTOParameters {
@@ -29,18 +24,20 @@ pub struct GTOParameters {
 }
 
 impl GTOParameters {
-    fn validate(&self) {
-        assert!(self.max_radial > 0, "max_radial must be at least 1");
+    fn validate(&self) -> Result<(), Error> {
+        if self.max_radial == 0 {
+            return Err(Error::InvalidParameter("max_radial must be at least 1".into()));
+        }
 
-        assert!(
-            self.cutoff > 0.0 && self.cutoff.is_finite(),
-            "cutoff must be a positive number"
-        );
+        if !(self.cutoff > 0.0 && self.cutoff.is_finite()) {
+            return Err(Error::InvalidParameter("cutoff must be a positive number".into()));
+        }
 
-        assert!(
-            self.atomic_gaussian_width > 0.0 && self.atomic_gaussian_width.is_finite(),
-            "atomic_gaussian_width must be a positive number"
-        );
+        if !(self.atomic_gaussian_width > 0.0 && self.atomic_gaussian_width.is_finite()) {
+            return Err(Error::InvalidParameter("atomic_gaussian_width must be a positive number".into()));
+        }
+
+        return Ok(());
     }
 }
 
@@ -96,8 +93,8 @@ pub struct GTO {
 }
 
 impl GTO {
-    pub fn new(parameters: GTOParameters) -> GTO {
-        parameters.validate();
+    pub fn new(parameters: GTOParameters) -> Result<GTO, Error> {
+        parameters.validate()?;
 
         let gto_gaussian_widths = (0..parameters.max_radial).into_iter().map(|n| {
             let n = n as f64;
@@ -142,10 +139,10 @@ impl GTO {
         let mut eigen = sorted_eigen(overlap); // .symmetric_eigen();
         for n in 0..parameters.max_radial {
             if eigen.eigenvalues[n] <= 0.0 {
-                panic!(
+                return Err(Error::InvalidParameter(format!(
                     "radial overlap matrix is singular, try with a lower \
                     max_radial (current value is {})", parameters.max_radial
-                );
+                )));
             }
             eigen.eigenvalues[n] = 1.0 / f64::sqrt(eigen.eigenvalues[n]);
         }
@@ -165,13 +162,13 @@ impl GTO {
         let hypergeometric = HyperGeometricSphericalExpansion::new(parameters.max_radial, parameters.max_angular);
 
         let sigma2 = parameters.atomic_gaussian_width * parameters.atomic_gaussian_width;
-        return GTO {
+        return Ok(GTO {
             parameters: parameters,
             hypergeometric: hypergeometric,
             atomic_gaussian_constant: 1.0 / (2.0 * sigma2),
             gto_gaussian_constants: gto_gaussian_constants,
             gto_orthonormalization: gto_orthonormalization,
-        }
+        });
     }
 }
 
@@ -209,18 +206,25 @@ impl RadialIntegral for GTO {
             let c_rij = c * distance;
             // `(c * rij)^l`
             let mut c_rij_l = 1.0;
+            // `(c * rij)^(l - 1)`, only used for `l > 0`
+            let mut c_rij_l_minus_1 = 0.0;
 
             for l in 0..(self.parameters.max_angular + 1) {
                 let n_l_3_over_2 = 0.5 * (n + l) as f64 + 1.5;
                 let c_dn = (c + gto_constant).powf(-n_l_3_over_2);
                 let factor = c_rij_l * c_dn;
-                c_rij_l *= c_rij;
 
-                values[[n, l]] *= factor;
                 if let Some(ref mut gradients) = gradients {
+                    // d/drij (c * rij)^l = l * c * (c * rij)^(l - 1), which
+                    // is also valid for rij = 0
+                    let factor_derivative = l as f64 * c * c_rij_l_minus_1 * c_dn;
                     gradients[[n, l]] *= factor;
-                    gradients[[n, l]] += values[[n, l]] * l as f64 / distance;
+                    gradients[[n, l]] += values[[n, l]] * factor_derivative;
                 }
+                values[[n, l]] *= factor;
+
+                c_rij_l_minus_1 = c_rij_l;
+                c_rij_l *= c_rij;
             }
         }
 
@@ -232,7 +236,8 @@ impl RadialIntegral for GTO {
         // integral evaluation, but this requires computing all n/l/m
         // coefficients all the time, forbidding partial feature evaluation.
         //
-        // Alternatively, we could use splined GTO and be happy.
+        // Alternatively, the GTO can be splined (see `SplinedRadialIntegral`)
+        // which removes this cost entirely.
         values.assign(&self.gto_orthonormalization.dot(&values));
         if let Some(ref mut gradients) = gradients {
             gradients.assign(&self.gto_orthonormalization.dot(&*gradients));
@@ -242,136 +247,135 @@ impl RadialIntegral for GTO {
 
 #[cfg(test)]
 mod tests {
-    mod gto {
-        use approx::assert_relative_eq;
-
-        use super::super::{GTO, GTOParameters, RadialIntegral};
-        use ndarray::Array2;
-
-        #[test]
-        #[should_panic = "max_radial must be at least 1"]
-        fn invalid_max_radial() {
-            GTO::new(GTOParameters {
-                max_radial: 0,
-                max_angular: 4,
-                cutoff: 3.0,
-                atomic_gaussian_width: 0.5
-            });
-        }
+    use approx::assert_relative_eq;
+
+    use super::{GTO, GTOParameters};
+    use super::super::RadialIntegral;
+    use ndarray::Array2;
+
+    #[test]
+    fn invalid_max_radial() {
+        let error = GTO::new(GTOParameters {
+            max_radial: 0,
+            max_angular: 4,
+            cutoff: 3.0,
+            atomic_gaussian_width: 0.5
+        }).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: max_radial must be at least 1");
+    }
 
-        #[test]
-        #[should_panic = "cutoff must be a positive number"]
-        fn negative_cutoff() {
-            GTO::new(GTOParameters {
-                max_radial: 10,
-                max_angular: 4,
-                cutoff: -3.0,
-                atomic_gaussian_width: 0.5
-            });
-        }
+    #[test]
+    fn negative_cutoff() {
+        let error = GTO::new(GTOParameters {
+            max_radial: 10,
+            max_angular: 4,
+            cutoff: -3.0,
+            atomic_gaussian_width: 0.5
+        }).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: cutoff must be a positive number");
+    }
 
-        #[test]
-        #[should_panic = "cutoff must be a positive number"]
-        fn infinite_cutoff() {
-            GTO::new(GTOParameters {
-                max_radial: 10,
-                max_angular: 4,
-                cutoff: f64::INFINITY,
-                atomic_gaussian_width: 0.5
-            });
-        }
+    #[test]
+    fn infinite_cutoff() {
+        let error = GTO::new(GTOParameters {
+            max_radial: 10,
+            max_angular: 4,
+            cutoff: f64::INFINITY,
+            atomic_gaussian_width: 0.5
+        }).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: cutoff must be a positive number");
+    }
 
-        #[test]
-        #[should_panic = "atomic_gaussian_width must be a positive number"]
-        fn negative_atomic_gaussian_width() {
-            GTO::new(GTOParameters {
-                max_radial: 10,
-                max_angular: 4,
-                cutoff: 3.0,
-                atomic_gaussian_width: -0.5
-            });
-        }
+    #[test]
+    fn negative_atomic_gaussian_width() {
+        let error = GTO::new(GTOParameters {
+            max_radial: 10,
+            max_angular: 4,
+            cutoff: 3.0,
+            atomic_gaussian_width: -0.5
+        }).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: atomic_gaussian_width must be a positive number");
+    }
 
-        #[test]
-        #[should_panic = "atomic_gaussian_width must be a positive number"]
-        fn infinite_atomic_gaussian_width() {
-            GTO::new(GTOParameters {
-                max_radial: 10,
-                max_angular: 4,
-                cutoff: 3.0,
-                atomic_gaussian_width: f64::INFINITY,
-            });
-        }
+    #[test]
+    fn infinite_atomic_gaussian_width() {
+        let error = GTO::new(GTOParameters {
+            max_radial: 10,
+            max_angular: 4,
+            cutoff: 3.0,
+            atomic_gaussian_width: f64::INFINITY,
+        }).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: atomic_gaussian_width must be a positive number");
+    }
 
-        #[test]
-        #[should_panic = "radial overlap matrix is singular, try with a lower max_radial (current value is 30)"]
-        fn ill_conditioned_orthonormalization() {
-            GTO::new(GTOParameters {
-                max_radial: 30,
-                max_angular: 3,
-                cutoff: 5.0,
-                atomic_gaussian_width: 0.5,
-            });
-        }
+    #[test]
+    fn ill_conditioned_orthonormalization() {
+        let error = GTO::new(GTOParameters {
+            max_radial: 30,
+            max_angular: 3,
+            cutoff: 5.0,
+            atomic_gaussian_width: 0.5,
+        }).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: radial overlap matrix is singular, try with a lower max_radial (current value is 30)");
+    }
 
-        #[test]
-        #[should_panic = "wrong size for values array, expected [2, 4] but got [2, 3]"]
-        fn values_array_size() {
-            let gto = GTO::new(GTOParameters {
-                max_radial: 2,
-                max_angular: 3,
-                cutoff: 5.0,
-                atomic_gaussian_width: 0.5,
-            });
-            let mut values = Array2::from_elem((2, 3), 0.0);
-
-            gto.compute(1.0, values.view_mut(), None);
-        }
+    #[test]
+    #[should_panic = "wrong size for values array, expected [2, 4] but got [2, 3]"]
+    fn values_array_size() {
+        let gto = GTO::new(GTOParameters {
+            max_radial: 2,
+            max_angular: 3,
+            cutoff: 5.0,
+            atomic_gaussian_width: 0.5,
+        }).unwrap();
+        let mut values = Array2::from_elem((2, 3), 0.0);
+
+        gto.compute(1.0, values.view_mut(), None);
+    }
 
-        #[test]
-        #[should_panic = "wrong size for gradients array, expected [2, 4] but got [2, 3]"]
-        fn gradient_array_size() {
-            let gto = GTO::new(GTOParameters {
-                max_radial: 2,
-                max_angular: 3,
-                cutoff: 5.0,
-                atomic_gaussian_width: 0.5,
-            });
-            let mut values = Array2::from_elem((2, 4), 0.0);
-            let mut gradients = Array2::from_elem((2, 3), 0.0);
-
-            gto.compute(1.0, values.view_mut(), Some(gradients.view_mut()));
-        }
+    #[test]
+    #[should_panic = "wrong size for gradients array, expected [2, 4] but got [2, 3]"]
+    fn gradient_array_size() {
+        let gto = GTO::new(GTOParameters {
+            max_radial: 2,
+            max_angular: 3,
+            cutoff: 5.0,
+            atomic_gaussian_width: 0.5,
+        }).unwrap();
+        let mut values = Array2::from_elem((2, 4), 0.0);
+        let mut gradients = Array2::from_elem((2, 3), 0.0);
+
+        gto.compute(1.0, values.view_mut(), Some(gradients.view_mut()));
+    }
 
-        #[test]
-        fn gto_finite_differences() {
-            let max_radial = 8;
-            let max_angular = 8;
-            let gto = GTO::new(GTOParameters {
-                max_radial: max_radial,
-                max_angular: max_angular,
-                cutoff: 5.0,
-                atomic_gaussian_width: 0.5,
-            });
-
-            let rij = 3.4;
-            let delta = 1e-9;
-
-            let mut values = Array2::from_elem((max_radial, max_angular + 1), 0.0);
-            let mut values_delta = Array2::from_elem((max_radial, max_angular + 1), 0.0);
-            let mut gradients = Array2::from_elem((max_radial, max_angular + 1), 0.0);
-            gto.compute(rij, values.view_mut(), Some(gradients.view_mut()));
-            gto.compute(rij + delta, values_delta.view_mut(), None);
-
-            let finite_differences = (&values_delta - &values) / delta;
-
-            for n in 0..max_radial {
-                for l in 0..(max_angular + 1) {
-                    assert_relative_eq!(
-                        finite_differences[[n, l]], gradients[[n, l]],
-                        epsilon=1e-5, max_relative=5e-5
-                    );
-                }
+    #[test]
+    fn gto_finite_differences() {
+        let max_radial = 8;
+        let max_angular = 8;
+        let gto = GTO::new(GTOParameters {
+            max_radial: max_radial,
+            max_angular: max_angular,
+            cutoff: 5.0,
+            atomic_gaussian_width: 0.5,
+        }).unwrap();
+
+        let rij = 3.4;
+        let delta = 1e-9;
+
+        let mut values = Array2::from_elem((max_radial, max_angular + 1), 0.0);
+        let mut values_delta = Array2::from_elem((max_radial, max_angular + 1), 0.0);
+        let mut gradients = Array2::from_elem((max_radial, max_angular + 1), 0.0);
+        gto.compute(rij, values.view_mut(), Some(gradients.view_mut()));
+        gto.compute(rij + delta, values_delta.view_mut(), None);
+
+        let finite_differences = (&values_delta - &values) / delta;
+
+        for n in 0..max_radial {
+            for l in 0..(max_angular + 1) {
+                assert_relative_eq!(
+                    finite_differences[[n, l]], gradients[[n, l]],
+                    epsilon=1e-5, max_relative=5e-5
+                );
             }
         }
     }
@@ -0,0 +1,332 @@
+use ndarray::{Array2, Array3, ArrayViewMut2, Axis};
+
+use crate::math::{gauss_legendre, spherical_bessel};
+use crate::Error;
+use super::{RadialIntegral, gaussian_density_projection};
+
+/// Parameters controlling the Laplacian eigenstate radial basis
+#[derive(Debug, Clone, Copy)]
+pub struct LaplacianEigenstateParameters {
+    /// Number of radial components
+    pub max_radial: usize,
+    /// Number of angular components
+    pub max_angular: usize,
+    /// atomic density gaussian width
+    pub atomic_gaussian_width: f64,
+    /// cutoff radius
+    pub cutoff: f64,
+}
+
+impl LaplacianEigenstateParameters {
+    fn validate(&self) -> Result<(), Error> {
+        if self.max_radial == 0 {
+            return Err(Error::InvalidParameter("max_radial must be at least 1".into()));
+        }
+
+        if !(self.cutoff > 0.0 && self.cutoff.is_finite()) {
+            return Err(Error::InvalidParameter("cutoff must be a positive number".into()));
+        }
+
+        if !(self.atomic_gaussian_width > 0.0 && self.atomic_gaussian_width.is_finite()) {
+            return Err(Error::InvalidParameter("atomic_gaussian_width must be a positive number".into()));
+        }
+
+        return Ok(());
+    }
+
+    /// Number of Gauss-Legendre quadrature points used to integrate the
+    /// product of the radial basis and the atomic density. This needs to be
+    /// large enough to resolve both the oscillations of the highest radial
+    /// basis function and the atomic gaussian density.
+    fn quadrature_size(&self) -> usize {
+        let density = 3.0 * self.cutoff / self.atomic_gaussian_width;
+        return 2 * self.max_radial + self.max_angular + density.ceil() as usize + 10;
+    }
+}
+
+/// Compute the first `max_radial` positive zeros of the spherical Bessel
+/// functions `j_l` for all `l` up to `max_angular`, and return them in a
+/// `(max_angular + 1) x max_radial` array.
+///
+/// This uses the fact that the zeros of `j_l` and `j_{l+1}` are interlaced,
+/// starting from the zeros of `j_0(x) = sin(x) / x`, i.e. `x = k π`.
+fn spherical_bessel_zeros(max_radial: usize, max_angular: usize) -> Array2<f64> {
+    let mut zeros = Array2::from_elem((max_angular + 1, max_radial), 0.0);
+
+    let n_zeros = max_radial + max_angular;
+    let mut previous = (1..=n_zeros).map(|k| k as f64 * std::f64::consts::PI).collect::<Vec<_>>();
+    for (i, zero) in zeros.row_mut(0).iter_mut().enumerate() {
+        *zero = previous[i];
+    }
+
+    let mut bessel = vec![0.0; max_angular + 1];
+    for l in 1..=max_angular {
+        let mut j_l = |x: f64| {
+            spherical_bessel(x, &mut bessel[..=l]);
+            bessel[l]
+        };
+
+        // there is exactly one zero of j_l between consecutive zeros of
+        // j_{l-1}, find it by bisection
+        let mut current = Vec::with_capacity(previous.len() - 1);
+        for window in previous.windows(2) {
+            let (mut low, mut high) = (window[0], window[1]);
+            let low_is_positive = j_l(low) > 0.0;
+            while high - low > 1e-14 * high {
+                let middle = 0.5 * (low + high);
+                if (j_l(middle) > 0.0) == low_is_positive {
+                    low = middle;
+                } else {
+                    high = middle;
+                }
+            }
+            current.push(0.5 * (low + high));
+        }
+
+        for (n, zero) in zeros.row_mut(l).iter_mut().enumerate() {
+            *zero = current[n];
+        }
+        previous = current;
+    }
+
+    return zeros;
+}
+
+/// Evaluate the Laplacian eigenstate radial basis on a `n_points`
+/// Gauss-Legendre quadrature of the `[0, cutoff]` interval.
+///
+/// This returns the quadrature points `x_k`, and the basis functions multiplied
+/// by the quadrature weights `w_k` and the `r^2` integration factor: `w_k x_k^2
+/// R_nl(x_k)`, in an array with shape `(max_angular + 1, max_radial,
+/// n_points)`. Summing this array multiplied by any function evaluated at
+/// `x_k` gives the projection of the function on the radial basis.
+pub(crate) fn laplacian_eigenstate_quadrature(
+    max_radial: usize,
+    max_angular: usize,
+    cutoff: f64,
+    n_points: usize,
+) -> (Vec<f64>, Array3<f64>) {
+    // scale the quadrature from [-1, 1] to [0, cutoff]
+    let (points, weights) = gauss_legendre(n_points);
+    let points = points.iter().map(|x| 0.5 * cutoff * (x + 1.0)).collect::<Vec<_>>();
+    let weights = weights.iter().map(|w| 0.5 * cutoff * w).collect::<Vec<_>>();
+
+    let zeros = spherical_bessel_zeros(max_radial, max_angular);
+
+    let mut basis = Array3::from_elem((max_angular + 1, max_radial, points.len()), 0.0);
+    let mut bessel = vec![0.0; max_angular + 2];
+    for l in 0..=max_angular {
+        for n in 0..max_radial {
+            let zero = zeros[[l, n]];
+
+            // ∫_0^r_cut r^2 j_l(z r / r_cut)^2 dr = r_cut^3 / 2 j_{l+1}(z)^2
+            spherical_bessel(zero, &mut bessel[..=(l + 1)]);
+            let normalization = f64::sqrt(2.0 / (cutoff * cutoff * cutoff)) / bessel[l + 1].abs();
+
+            for (k, (&x, &w)) in points.iter().zip(&weights).enumerate() {
+                spherical_bessel(zero * x / cutoff, &mut bessel[..=l]);
+                basis[[l, n, k]] = w * x * x * normalization * bessel[l];
+            }
+        }
+    }
+
+    return (points, basis);
+}
+
+/// Laplacian eigenstate radial basis, also called spherical Bessel radial
+/// basis.
+///
+/// The radial functions `R_nl(r) = N_nl j_l(z_nl r / r_cut)` are the
+/// eigenstates of the Laplacian in a sphere of radius `r_cut` with vanishing
+/// value at the boundary, where `z_nl` is the `n`-th zero of the spherical
+/// Bessel function `j_l`. These functions are orthonormal on `[0, r_cut]`.
+///
+/// There is no closed form for the radial integral with this basis, which is
+/// instead evaluated with Gauss-Legendre quadrature. This makes it quite slow
+/// to compute directly, and it should be used with splines when performance
+/// matters.
+#[derive(Debug, Clone)]
+pub struct LaplacianEigenstate {
+    parameters: LaplacianEigenstateParameters,
+    /// 1/2σ^2, with σ the atomic density gaussian width
+    atomic_gaussian_constant: f64,
+    /// Gauss-Legendre quadrature points in `[0, cutoff]`
+    points: Vec<f64>,
+    /// `w_k x_k^2 R_nl(x_k)` for all quadrature points `x_k` and weights
+    /// `w_k`, with shape `(max_angular + 1, max_radial, n_points)`
+    basis: Array3<f64>,
+}
+
+impl LaplacianEigenstate {
+    pub fn new(parameters: LaplacianEigenstateParameters) -> Result<LaplacianEigenstate, Error> {
+        parameters.validate()?;
+
+        let (points, basis) = laplacian_eigenstate_quadrature(
+            parameters.max_radial,
+            parameters.max_angular,
+            parameters.cutoff,
+            parameters.quadrature_size(),
+        );
+
+        let sigma2 = parameters.atomic_gaussian_width * parameters.atomic_gaussian_width;
+        return Ok(LaplacianEigenstate {
+            parameters: parameters,
+            atomic_gaussian_constant: 1.0 / (2.0 * sigma2),
+            points: points,
+            basis: basis,
+        });
+    }
+}
+
+impl RadialIntegral for LaplacianEigenstate {
+    fn compute(
+        &self,
+        distance: f64,
+        mut values: ArrayViewMut2<f64>,
+        mut gradients: Option<ArrayViewMut2<f64>>
+    ) {
+        let expected_shape = [self.parameters.max_radial, self.parameters.max_angular + 1];
+        assert_eq!(
+            values.shape(), expected_shape,
+            "wrong size for values array, expected [{}, {}] but got [{}, {}]",
+            expected_shape[0], expected_shape[1], values.shape()[0], values.shape()[1]
+        );
+
+        if let Some(ref gradients) = gradients {
+            assert_eq!(
+                gradients.shape(), expected_shape,
+                "wrong size for gradients array, expected [{}, {}] but got [{}, {}]",
+                expected_shape[0], expected_shape[1], gradients.shape()[0], gradients.shape()[1]
+            );
+        }
+
+        // evaluate the atomic density projection on all quadrature points
+        let n_angular = self.parameters.max_angular + 1;
+        let n_points = self.points.len();
+        let mut all_values = Array2::from_elem((n_angular, n_points), 0.0);
+        let mut all_gradients = Array2::from_elem((n_angular, n_points), 0.0);
+
+        let mut bessel = vec![0.0; n_angular + 1];
+        let mut point_values = vec![0.0; n_angular];
+        let mut point_gradients = vec![0.0; n_angular];
+        for (k, &point) in self.points.iter().enumerate() {
+            gaussian_density_projection(
+                self.atomic_gaussian_constant,
+                point,
+                distance,
+                &mut bessel,
+                &mut point_values,
+                gradients.as_ref().map(|_| &mut *point_gradients),
+            );
+
+            for l in 0..n_angular {
+                all_values[[l, k]] = point_values[l];
+                all_gradients[[l, k]] = point_gradients[l];
+            }
+        }
+
+        // integrate the product of the projections and the radial basis
+        for l in 0..n_angular {
+            let basis = self.basis.index_axis(Axis(0), l);
+            values.column_mut(l).assign(&basis.dot(&all_values.row(l)));
+
+            if let Some(ref mut gradients) = gradients {
+                gradients.column_mut(l).assign(&basis.dot(&all_gradients.row(l)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use ndarray::Array2;
+
+    use super::{LaplacianEigenstate, LaplacianEigenstateParameters};
+    use super::spherical_bessel_zeros;
+    use super::super::RadialIntegral;
+
+    #[test]
+    fn invalid_max_radial() {
+        let error = LaplacianEigenstate::new(LaplacianEigenstateParameters {
+            max_radial: 0,
+            max_angular: 4,
+            cutoff: 3.0,
+            atomic_gaussian_width: 0.5
+        }).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: max_radial must be at least 1");
+    }
+
+    #[test]
+    fn infinite_cutoff() {
+        let error = LaplacianEigenstate::new(LaplacianEigenstateParameters {
+            max_radial: 10,
+            max_angular: 4,
+            cutoff: f64::INFINITY,
+            atomic_gaussian_width: 0.5
+        }).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: cutoff must be a positive number");
+    }
+
+    #[test]
+    fn negative_atomic_gaussian_width() {
+        let error = LaplacianEigenstate::new(LaplacianEigenstateParameters {
+            max_radial: 10,
+            max_angular: 4,
+            cutoff: 3.0,
+            atomic_gaussian_width: -0.5
+        }).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: atomic_gaussian_width must be a positive number");
+    }
+
+    #[test]
+    #[should_panic = "wrong size for gradients array, expected [2, 4] but got [2, 3]"]
+    fn gradient_array_size() {
+        let laplacian = LaplacianEigenstate::new(LaplacianEigenstateParameters {
+            max_radial: 2,
+            max_angular: 3,
+            cutoff: 5.0,
+            atomic_gaussian_width: 0.5,
+        }).unwrap();
+        let mut values = Array2::from_elem((2, 4), 0.0);
+        let mut gradients = Array2::from_elem((2, 3), 0.0);
+
+        laplacian.compute(1.0, values.view_mut(), Some(gradients.view_mut()));
+    }
+
+    #[test]
+    fn zeros() {
+        let zeros = spherical_bessel_zeros(3, 2);
+        let pi = std::f64::consts::PI;
+        assert_relative_eq!(zeros[[0, 0]], pi);
+        assert_relative_eq!(zeros[[0, 2]], 3.0 * pi);
+        assert_relative_eq!(zeros[[1, 0]], 4.493409457909064, max_relative=1e-12);
+        assert_relative_eq!(zeros[[1, 2]], 10.904121659428899, max_relative=1e-12);
+        assert_relative_eq!(zeros[[2, 0]], 5.763459196894550, max_relative=1e-12);
+        assert_relative_eq!(zeros[[2, 1]], 9.095011330476355, max_relative=1e-12);
+    }
+
+    #[test]
+    fn laplacian_finite_differences() {
+        let max_radial = 8;
+        let max_angular = 8;
+        let laplacian = LaplacianEigenstate::new(LaplacianEigenstateParameters {
+            max_radial: max_radial,
+            max_angular: max_angular,
+            cutoff: 5.0,
+            atomic_gaussian_width: 0.5,
+        }).unwrap();
+
+        let delta = 1e-9;
+        let mut values = Array2::from_elem((max_radial, max_angular + 1), 0.0);
+        let mut values_delta = Array2::from_elem((max_radial, max_angular + 1), 0.0);
+        let mut gradients = Array2::from_elem((max_radial, max_angular + 1), 0.0);
+        for &rij in &[0.0, 1.2, 3.4] {
+            laplacian.compute(rij, values.view_mut(), Some(gradients.view_mut()));
+            laplacian.compute(rij + delta, values_delta.view_mut(), None);
+
+            let finite_differences = (&values_delta - &values) / delta;
+            assert_relative_eq!(finite_differences, gradients, epsilon=1e-5, max_relative=5e-5);
+        }
+    }
+}
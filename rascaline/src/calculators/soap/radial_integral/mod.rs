@@ -0,0 +1,123 @@
+use ndarray::ArrayViewMut2;
+
+use crate::math::scaled_modified_spherical_bessel;
+
+mod gto;
+pub use self::gto::{GTO, GTOParameters};
+
+mod dvr;
+pub use self::dvr::{DVR, DVRParameters};
+
+mod laplacian_eigenstate;
+pub use self::laplacian_eigenstate::{LaplacianEigenstate, LaplacianEigenstateParameters};
+pub(crate) use self::laplacian_eigenstate::laplacian_eigenstate_quadrature;
+
+/// Normalization factor for the atomic density projection, `π^{5/2} / 2`
+const DENSITY_PROJECTION_NORMALIZATION: f64 = 8.746709163812431;
+
+pub trait RadialIntegral: std::panic::RefUnwindSafe {
+    /// Compute the radial integral for a single atomic `distance` and store the
+    /// resulting data in the `max_radial x max_angular` array `values`. If
+    /// `gradients` is `Some`, also compute and store gradients.
+    fn compute(&self, rij: f64, values: ArrayViewMut2<f64>, gradients: Option<ArrayViewMut2<f64>>);
+}
+
+/// Compute the projection on the spherical harmonics of order `l` of a gaussian
+/// atomic density with constant `c = 1/2σ^2` centered at distance `rij`,
+/// evaluated at distance `r`: `π^{5/2} / 2 exp(-c (r^2 + rij^2)) i_l(2 c r rij)`,
+/// where `i_l` is the modified spherical Bessel function of the first kind.
+///
+/// The values for all `l` between 0 and `values.len() - 1` are stored in
+/// `values`, and if `gradients` is `Some`, the derivatives with respect to
+/// `rij` are stored in it. `bessel` is used as scratch space, and must contain
+/// `values.len() + 1` elements.
+///
+/// Integrating this function multiplied by `r^2 R_nl(r)` over `r` gives the
+/// radial integral for the radial basis `R_nl`. The normalization is the same
+/// as the one used by the [`GTO`] radial integral.
+fn gaussian_density_projection(
+    c: f64,
+    r: f64,
+    rij: f64,
+    bessel: &mut [f64],
+    values: &mut [f64],
+    gradients: Option<&mut [f64]>,
+) {
+    debug_assert_eq!(bessel.len(), values.len() + 1);
+
+    // use exp(-c (r^2 + rij^2)) i_l(2 c r rij) = exp(-c (r - rij)^2) * exp(-z) i_l(z)
+    // with z = 2 c r rij to prevent overflow for large z
+    scaled_modified_spherical_bessel(2.0 * c * r * rij, bessel);
+    let exp_factor = DENSITY_PROJECTION_NORMALIZATION * f64::exp(-c * (r - rij) * (r - rij));
+
+    for (l, value) in values.iter_mut().enumerate() {
+        *value = exp_factor * bessel[l];
+    }
+
+    if let Some(gradients) = gradients {
+        for (l, gradient) in gradients.iter_mut().enumerate() {
+            // derivative of the (unscaled) modified spherical Bessel function,
+            // using i_l'(z) = (l i_{l-1}(z) + (l + 1) i_{l+1}(z)) / (2l + 1)
+            let bessel_derivative = if l == 0 {
+                bessel[1]
+            } else {
+                (l as f64 * bessel[l - 1] + (l + 1) as f64 * bessel[l + 1]) / (2 * l + 1) as f64
+            };
+
+            *gradient = 2.0 * c * exp_factor * (r * bessel_derivative - rij * bessel[l]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use ndarray::Array2;
+
+    use crate::math::{gamma, gauss_legendre};
+    use super::{GTO, GTOParameters, RadialIntegral};
+    use super::gaussian_density_projection;
+
+    #[test]
+    fn gto_normalization() {
+        // with a single radial basis function, the GTO is not orthogonalized,
+        // and we can compare it with the numerical integration of the density
+        // projection
+        let max_angular = 4;
+        let cutoff = 5.0;
+        let atomic_gaussian_width = 0.5;
+        let gto = GTO::new(GTOParameters {
+            max_radial: 1,
+            max_angular: max_angular,
+            cutoff: cutoff,
+            atomic_gaussian_width: atomic_gaussian_width,
+        }).unwrap();
+
+        // R_0(r) = N exp(-r^2 / 2 σ_0^2), with σ_0 = cutoff
+        let gto_normalization = f64::sqrt(2.0 / (cutoff * cutoff * cutoff * gamma(1.5)));
+        let c = 1.0 / (2.0 * atomic_gaussian_width * atomic_gaussian_width);
+
+        // integrate on [0, 6 cutoff], the GTO is negligible after this
+        let half_range = 3.0 * cutoff;
+        let (points, weights) = gauss_legendre(200);
+
+        let mut values = Array2::from_elem((1, max_angular + 1), 0.0);
+        let mut bessel = vec![0.0; max_angular + 2];
+        let mut projection = vec![0.0; max_angular + 1];
+        for &rij in &[0.0, 0.7, 2.3] {
+            gto.compute(rij, values.view_mut(), None);
+
+            let mut expected = Array2::from_elem((1, max_angular + 1), 0.0);
+            for (&x, &w) in points.iter().zip(&weights) {
+                let r = half_range * (x + 1.0);
+                let radial = gto_normalization * f64::exp(-r * r / (2.0 * cutoff * cutoff));
+                gaussian_density_projection(c, r, rij, &mut bessel, &mut projection, None);
+                for l in 0..=max_angular {
+                    expected[[0, l]] += half_range * w * r * r * radial * projection[l];
+                }
+            }
+
+            assert_relative_eq!(values, expected, max_relative=1e-10);
+        }
+    }
+}
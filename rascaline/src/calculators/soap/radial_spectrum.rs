@@ -0,0 +1,464 @@
+use std::collections::BTreeSet;
+
+use ndarray::Array2;
+
+use crate::descriptor::{IndexesBuilder, IndexValue, Indexes, EnvironmentIndexes, AtomSpeciesEnvironment};
+use crate::{Descriptor, Error, System};
+
+use super::super::{CalculatorBase, check_indexes_names, check_environments_subset};
+use super::RadialIntegral;
+use super::{RadialBasis, CutoffFunction};
+use super::spherical_expansion::sort_pair;
+
+/// Value of the `l = 0, m = 0` spherical harmonic, as computed by
+/// `SphericalHarmonics`: `\sqrt{\frac{1}{8 \pi}}`
+const Y_00: f64 = 0.19947114020071635;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[allow(clippy::module_name_repetitions)]
+pub struct RadialSpectrumParameters {
+    /// Spherical cutoff to use for atomic environments
+    pub cutoff: f64,
+    /// Number of radial basis function to use
+    pub max_radial: usize,
+    /// Width of the atom-centered gaussian creating the atomic density
+    pub atomic_gaussian_width: f64,
+    /// Should we also compute gradients of the feature?
+    pub gradients: bool,
+    /// radial basis to use for the radial integral
+    pub radial_basis: RadialBasis,
+    /// cutoff function used to smooth the behavior around the cutoff radius
+    pub cutoff_function: CutoffFunction,
+    /// If set, the radial integral is evaluated using splines with the given
+    /// absolute accuracy instead of being computed directly, which is much
+    /// faster for large numbers of neighbors
+    pub spline_accuracy: Option<f64>,
+}
+
+/// Calculator implementing the SOAP radial spectrum, i.e. the `l = 0` part of
+/// the spherical expansion. This only contains two-body information, and is
+/// much cheaper to compute than the full spherical expansion.
+///
+/// The values are the same as the `(n, l = 0, m = 0)` coefficients of the
+/// spherical expansion with the same parameters.
+pub struct SoapRadialSpectrum {
+    parameters: RadialSpectrumParameters,
+    radial_integral: Box<dyn RadialIntegral>,
+    ri_values: Array2<f64>,
+    ri_gradients: Option<Array2<f64>>,
+}
+
+impl SoapRadialSpectrum {
+    pub fn new(parameters: RadialSpectrumParameters) -> Result<SoapRadialSpectrum, Error> {
+        let radial_integral = parameters.radial_basis.radial_integral(
+            parameters.max_radial,
+            0,
+            parameters.atomic_gaussian_width,
+            parameters.cutoff,
+            parameters.spline_accuracy,
+        )?;
+
+        let shape = (parameters.max_radial, 1);
+        let ri_values = Array2::from_elem(shape, 0.0);
+        let ri_gradients = if parameters.gradients {
+            Some(Array2::from_elem(shape, 0.0))
+        } else {
+            None
+        };
+
+        Ok(SoapRadialSpectrum {
+            parameters: parameters,
+            radial_integral: radial_integral,
+            ri_values: ri_values,
+            ri_gradients: ri_gradients,
+        })
+    }
+
+    fn do_self_contributions(&mut self, descriptor: &mut Descriptor) {
+        self.radial_integral.compute(0.0, self.ri_values.view_mut(), None);
+        let f_cut = self.parameters.cutoff_function.compute(0.0, self.parameters.cutoff);
+
+        for (i_env, requested_env) in descriptor.environments.iter().enumerate() {
+            let alpha = requested_env[2];
+            let beta = requested_env[3];
+
+            if alpha == beta {
+                for (i_feature, feature) in descriptor.features.iter().enumerate() {
+                    let n = feature[0].usize();
+                    descriptor.values[[i_env, i_feature]] += f_cut * self.ri_values[[n, 0]] * Y_00;
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for SoapRadialSpectrum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.parameters)
+    }
+}
+
+impl CalculatorBase for SoapRadialSpectrum {
+    fn name(&self) -> String {
+        "SOAP radial spectrum".into()
+    }
+
+    fn get_parameters(&self) -> String {
+        serde_json::to_string(&self.parameters).expect("failed to serialize to JSON")
+    }
+
+    fn features_names(&self) -> Vec<&str> {
+        vec!["n"]
+    }
+
+    fn features(&self) -> Indexes {
+        let mut features = IndexesBuilder::new(self.features_names());
+        for n in 0..self.parameters.max_radial {
+            features.add(&[IndexValue::from(n)]);
+        }
+        return features.finish();
+    }
+
+    fn environments(&self) -> Box<dyn EnvironmentIndexes> {
+        Box::new(AtomSpeciesEnvironment::with_self_contribution(self.parameters.cutoff))
+    }
+
+    fn compute_gradients(&self) -> bool {
+        self.parameters.gradients
+    }
+
+    fn check_features(&self, indexes: &Indexes) -> Result<(), Error> {
+        check_indexes_names("features", indexes, &["n"])?;
+        for value in indexes {
+            if value[0].usize() >= self.parameters.max_radial {
+                return Err(Error::InvalidParameter(format!(
+                    "invalid feature {:?}: n must be lower than max_radial ({})",
+                    value, self.parameters.max_radial
+                )));
+            }
+        }
+        return Ok(());
+    }
+
+    fn check_environments(&self, indexes: &Indexes, systems: &mut [&mut dyn System]) -> Result<(), Error> {
+        return check_environments_subset(indexes, &*self.environments(), systems);
+    }
+
+    #[allow(clippy::similar_names)]
+    fn compute(&mut self, systems: &mut [&mut dyn System], descriptor: &mut Descriptor) -> Result<(), Error> {
+        assert_eq!(descriptor.environments.names(), &["structure", "center", "species_center", "species_neighbor"]);
+        assert_eq!(descriptor.features.names(), &["n"]);
+
+        self.do_self_contributions(descriptor);
+
+        // keep the set of pairs already seen for each system
+        let mut already_computed_pairs = vec![BTreeSet::new(); systems.len()];
+
+        for (i_env, requested_env) in descriptor.environments.iter().enumerate() {
+            let i_system = requested_env[0];
+            let center = requested_env[1].usize();
+            let alpha = requested_env[2];
+            let beta = requested_env[3];
+
+            let system = &mut *systems[i_system.usize()];
+            system.compute_neighbors(self.parameters.cutoff)?;
+            let species = system.species()?;
+
+            for pair in system.pairs_containing(center)? {
+                let (neighbor, sign) = if center == pair.first {
+                    (pair.second, 1.0)
+                } else {
+                    (pair.first, -1.0)
+                };
+
+                if species[neighbor] != beta.usize() {
+                    continue;
+                }
+
+                if !already_computed_pairs[i_system.usize()].insert(sort_pair(pair)) {
+                    continue;
+                }
+
+                // the radial spectrum is symmetric under the exchange of center
+                // and neighbor, so we can also store the value for the
+                // neighbor--center pair if it was requested
+                let other_env_i = descriptor.environments.position(
+                    &[i_system, IndexValue::from(neighbor), beta, alpha]
+                );
+
+                let distance = pair.vector.norm();
+                self.radial_integral.compute(
+                    distance, self.ri_values.view_mut(), self.ri_gradients.as_mut().map(|o| o.view_mut())
+                );
+                let f_cut = self.parameters.cutoff_function.compute(distance, self.parameters.cutoff);
+
+                for (i_feature, feature) in descriptor.features.iter().enumerate() {
+                    let n = feature[0].usize();
+                    let n_value = f_cut * self.ri_values[[n, 0]] * Y_00;
+                    descriptor.values[[i_env, i_feature]] += n_value;
+                    if let Some(other_env_i) = other_env_i {
+                        descriptor.values[[other_env_i, i_feature]] += n_value;
+                    }
+                }
+
+                // pairs between an atom and its own periodic images move
+                // together with the atom, and do not contribute to gradients
+                if self.parameters.gradients && neighbor != center {
+                    let gradients_indexes = descriptor.gradients_indexes.as_ref().expect("missing gradients indexes");
+                    let center_grad_i = gradients_indexes.position(&[
+                        i_system, IndexValue::from(center), alpha, beta,
+                        IndexValue::from(neighbor), IndexValue::from(0_usize)
+                    ]).expect("missing storage for gradient");
+                    let neighbor_grad_i = gradients_indexes.position(&[
+                        i_system, IndexValue::from(neighbor), beta, alpha,
+                        IndexValue::from(center), IndexValue::from(0_usize)
+                    ]);
+
+                    let f_cut_grad = self.parameters.cutoff_function.derivative(distance, self.parameters.cutoff);
+                    let direction = sign * pair.vector / distance;
+
+                    let gradients = descriptor.gradients.as_mut().expect("missing storage for gradients");
+                    let ri_gradients = self.ri_gradients.as_ref().expect("missing radial integral gradients");
+
+                    for (i_feature, feature) in descriptor.features.iter().enumerate() {
+                        let n = feature[0].usize();
+                        let ri_value = self.ri_values[[n, 0]];
+                        let ri_grad = ri_gradients[[n, 0]];
+
+                        let radial_derivative = (f_cut_grad * ri_value + f_cut * ri_grad) * Y_00;
+                        for spatial in 0..3 {
+                            // assumes that the three spatial derivative are
+                            // stored one after the other
+                            let grad = radial_derivative * direction[spatial];
+                            gradients[[center_grad_i + spatial, i_feature]] += grad;
+                            if let Some(neighbor_grad_i) = neighbor_grad_i {
+                                gradients[[neighbor_grad_i + spatial, i_feature]] -= grad;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::system::test_systems;
+    use crate::descriptor::IndexesBuilder;
+    use crate::{Descriptor, Calculator, System};
+    use crate::{CalculationOptions, SelectedIndexes};
+
+    use approx::assert_relative_eq;
+    use ndarray::s;
+
+    use super::{SoapRadialSpectrum, RadialSpectrumParameters};
+    use super::super::{CutoffFunction, RadialBasis};
+    use super::super::super::CalculatorBase;
+
+    /// Convenience macro to create IndexValue
+    macro_rules! v {
+        ($value: expr) => {
+            crate::descriptor::IndexValue::from($value as i64)
+        };
+    }
+
+    fn parameters(gradients: bool) -> RadialSpectrumParameters {
+        RadialSpectrumParameters {
+            atomic_gaussian_width: 0.3,
+            cutoff: 3.5,
+            cutoff_function: CutoffFunction::ShiftedCosine { width: 0.5 },
+            gradients: gradients,
+            max_radial: 6,
+            radial_basis: RadialBasis::GTO,
+            spline_accuracy: None,
+        }
+    }
+
+    #[test]
+    fn values() {
+        let mut calculator = Calculator::from(Box::new(SoapRadialSpectrum::new(
+            parameters(true)
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water", "methane"]);
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut descriptor, Default::default()).unwrap();
+
+        assert_eq!(descriptor.environments.names(), ["structure", "center", "species_center", "species_neighbor"]);
+        assert_eq!(descriptor.features.names(), ["n"]);
+        assert_eq!(descriptor.features.count(), 6);
+
+        // the values and gradients should be the same as the l = 0 part of the
+        // spherical expansion
+        let mut features = IndexesBuilder::new(vec!["n", "l", "m"]);
+        for n in 0..6 {
+            features.add(&[v!(n), v!(0), v!(0)]);
+        }
+
+        let mut expansion = Descriptor::new();
+        let mut expansion_calculator = Calculator::new("spherical_expansion", r#"{
+            "atomic_gaussian_width": 0.3,
+            "cutoff": 3.5,
+            "cutoff_function": {"ShiftedCosine": {"width": 0.5}},
+            "gradients": true,
+            "max_radial": 6,
+            "max_angular": 0,
+            "radial_basis": "GTO"
+        }"#.into()).unwrap();
+        let options = CalculationOptions {
+            selected_features: SelectedIndexes::Some(features.finish()),
+            ..Default::default()
+        };
+        expansion_calculator.compute(&mut systems.get(), &mut expansion, options).unwrap();
+
+        assert_eq!(descriptor.environments, expansion.environments);
+        assert_relative_eq!(descriptor.values, expansion.values, epsilon=1e-14, max_relative=1e-12);
+
+        assert_eq!(descriptor.gradients_indexes, expansion.gradients_indexes);
+        assert_relative_eq!(
+            descriptor.gradients.as_ref().unwrap(),
+            expansion.gradients.as_ref().unwrap(),
+            epsilon=1e-14, max_relative=1e-12
+        );
+    }
+
+    #[test]
+    fn finite_differences() {
+        let mut calculator = Calculator::from(Box::new(SoapRadialSpectrum::new(
+            parameters(true)
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water"]);
+        let mut reference = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut reference, Default::default()).unwrap();
+
+        let gradients_indexes = reference.gradients_indexes.as_ref().unwrap();
+        assert_eq!(
+            gradients_indexes.names(),
+            ["structure", "center", "species_center", "species_neighbor", "neighbor", "spatial"]
+        );
+
+        // get the list of modified gradient environments when moving atom_i
+        let modified_indexes = |atom_i: usize, spatial_index: usize| {
+            let mut results = Vec::new();
+            for (env_i, env) in gradients_indexes.iter().enumerate() {
+                let center = env[1];
+                let neighbor = env[4];
+                let spatial = env[5];
+                if center.usize() != atom_i && neighbor.usize() == atom_i && spatial.usize() == spatial_index {
+                    results.push((env_i, &env[..4]));
+                }
+            }
+            return results;
+        };
+
+        let delta = 1e-9;
+        let gradients = reference.gradients.as_ref().unwrap();
+        for atom_i in 0..systems.systems[0].size().unwrap() {
+            for spatial in 0..3 {
+                systems.systems[0].positions_mut()[atom_i][spatial] += delta;
+
+                let mut updated = Descriptor::new();
+                calculator.compute(&mut systems.get(), &mut updated, Default::default()).unwrap();
+
+                for (grad_i, env) in modified_indexes(atom_i, spatial) {
+                    let env_i = reference.environments.position(env).expect(
+                        "missing environment in reference values"
+                    );
+                    assert_eq!(updated.environments.position(env).unwrap(), env_i);
+
+                    let value = reference.values.slice(s![env_i, ..]);
+                    let value_delta = updated.values.slice(s![env_i, ..]);
+                    let gradient = gradients.slice(s![grad_i, ..]);
+
+                    let mut finite_difference = value_delta.to_owned().clone();
+                    finite_difference -= &value;
+                    finite_difference /= delta;
+
+                    assert_relative_eq!(
+                        finite_difference, gradient,
+                        epsilon=1e-9,
+                        max_relative=5e-4,
+                    );
+                }
+
+                systems.systems[0].positions_mut()[atom_i][spatial] -= delta;
+            }
+        }
+    }
+
+    #[test]
+    fn compute_partial() {
+        let mut calculator = Calculator::from(Box::new(SoapRadialSpectrum::new(
+            parameters(true)
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water", "methane"]);
+        let mut full = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut full, Default::default()).unwrap();
+
+        // partial set of features, all environments
+        let mut features = IndexesBuilder::new(vec!["n"]);
+        features.add(&[v!(3)]);
+        features.add(&[v!(0)]);
+        features.add(&[v!(5)]);
+        let features = features.finish();
+
+        let mut partial = Descriptor::new();
+        let options = CalculationOptions {
+            selected_samples: SelectedIndexes::All,
+            selected_features: SelectedIndexes::Some(features.clone()),
+            ..Default::default()
+        };
+        calculator.compute(&mut systems.get(), &mut partial, options).unwrap();
+
+        assert_eq!(full.environments, partial.environments);
+        for (partial_i, feature) in features.iter().enumerate() {
+            let index = full.features.position(feature).unwrap();
+            assert_eq!(
+                full.values.slice(s![.., index]),
+                partial.values.slice(s![.., partial_i])
+            );
+
+            assert_eq!(
+                full.gradients.as_ref().unwrap().slice(s![.., index]),
+                partial.gradients.as_ref().unwrap().slice(s![.., partial_i])
+            );
+        }
+
+        // all features, partial set of environments
+        let mut environments = IndexesBuilder::new(vec!["structure", "center", "species_center", "species_neighbor"]);
+        environments.add(&[v!(0), v!(1), v!(1), v!(123456)]);
+        environments.add(&[v!(0), v!(2), v!(1), v!(1)]);
+        environments.add(&[v!(1), v!(0), v!(6), v!(1)]);
+        environments.add(&[v!(1), v!(2), v!(1), v!(6)]);
+        let environments = environments.finish();
+
+        let options = CalculationOptions {
+            selected_samples: SelectedIndexes::Some(environments.clone()),
+            selected_features: SelectedIndexes::All,
+            ..Default::default()
+        };
+        calculator.compute(&mut systems.get(), &mut partial, options).unwrap();
+
+        assert_eq!(full.features, partial.features);
+        for (partial_i, environment) in environments.iter().enumerate() {
+            let index = full.environments.position(environment).unwrap();
+            assert_eq!(
+                full.values.slice(s![index, ..]),
+                partial.values.slice(s![partial_i, ..])
+            );
+        }
+
+        for (partial_i, gradient) in partial.gradients_indexes.as_ref().unwrap().iter().enumerate() {
+            let index = full.gradients_indexes.as_ref().unwrap().position(gradient).unwrap();
+            assert_eq!(
+                full.gradients.as_ref().unwrap().slice(s![index, ..]),
+                partial.gradients.as_ref().unwrap().slice(s![partial_i, ..])
+            );
+        }
+    }
+}
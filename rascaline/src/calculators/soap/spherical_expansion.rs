@@ -1,19 +1,86 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
 use ndarray::Array2;
 
-use crate::descriptor::{IndexesBuilder, IndexValue, Indexes, EnvironmentIndexes, AtomSpeciesEnvironment};
+use crate::descriptor::{IndexesBuilder, IndexValue, Indexes, EnvironmentIndexes};
+use crate::descriptor::{AtomSpeciesEnvironment, CenterSpeciesEnvironment};
 use crate::system::Pair;
-use crate::{Descriptor, System, Vector3D};
+use crate::{Descriptor, Error, System, Vector3D};
 
-use super::super::CalculatorBase;
+use super::super::{CalculatorBase, check_indexes_names, check_environments_subset};
 use super::{GTO, GTOParameters, RadialIntegral};
+use super::{DVR, DVRParameters};
+use super::{LaplacianEigenstate, LaplacianEigenstateParameters};
+use super::{SplinedRadialIntegral, SplineParameters};
 use super::{SphericalHarmonics, SphericalHarmonicsArray};
 
 #[derive(Debug, Clone)]
 #[derive(serde::Deserialize, serde::Serialize)]
 pub enum RadialBasis {
+    /// Orthonormalized gaussian type orbitals
     GTO,
+    /// Discrete variable representation, using functions localized on the
+    /// points of a Gauss-Legendre quadrature
+    DVR,
+    /// Eigenstates of the Laplacian in a sphere of radius `cutoff`, i.e.
+    /// spherical Bessel functions
+    LaplacianEigenstate,
+}
+
+impl RadialBasis {
+    /// Create the radial integral implementation corresponding to this radial
+    /// basis, using splines with the given accuracy if `spline_accuracy` is
+    /// `Some`.
+    pub(super) fn radial_integral(
+        &self,
+        max_radial: usize,
+        max_angular: usize,
+        atomic_gaussian_width: f64,
+        cutoff: f64,
+        spline_accuracy: Option<f64>,
+    ) -> Result<Box<dyn RadialIntegral>, Error> {
+        let radial_integral = match self {
+            RadialBasis::GTO => {
+                let parameters = GTOParameters {
+                    max_radial: max_radial,
+                    max_angular: max_angular,
+                    atomic_gaussian_width: atomic_gaussian_width,
+                    cutoff: cutoff,
+                };
+                Box::new(GTO::new(parameters)?) as Box<dyn RadialIntegral>
+            }
+            RadialBasis::DVR => {
+                let parameters = DVRParameters {
+                    max_radial: max_radial,
+                    max_angular: max_angular,
+                    atomic_gaussian_width: atomic_gaussian_width,
+                    cutoff: cutoff,
+                };
+                Box::new(DVR::new(parameters)?)
+            }
+            RadialBasis::LaplacianEigenstate => {
+                let parameters = LaplacianEigenstateParameters {
+                    max_radial: max_radial,
+                    max_angular: max_angular,
+                    atomic_gaussian_width: atomic_gaussian_width,
+                    cutoff: cutoff,
+                };
+                Box::new(LaplacianEigenstate::new(parameters)?)
+            }
+        };
+
+        if let Some(accuracy) = spline_accuracy {
+            let parameters = SplineParameters {
+                max_radial: max_radial,
+                max_angular: max_angular,
+                cutoff: cutoff,
+                accuracy: accuracy,
+            };
+            return Ok(Box::new(SplinedRadialIntegral::new(parameters, &*radial_integral)?));
+        }
+
+        return Ok(radial_integral);
+    }
 }
 
 /// Possible values for the smoothing cutoff function
@@ -65,6 +132,74 @@ impl CutoffFunction {
     }
 }
 
+/// Possible values for the radial scaling of the neighbors contributions
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+pub enum RadialScaling {
+    /// No radial scaling, all neighbors contribute with the same weight
+    #[default]
+    None,
+    /// Radial scaling from "Comparing molecules and solids across structural
+    /// and alchemical space", Willatt, Musil and Ceriotti (2018):
+    /// `u(r) = rate / (rate + (r / scale)^exponent)` if `rate` is not zero,
+    /// and `u(r) = (scale / r)^exponent` otherwise.
+    Willatt2018 {
+        scale: f64,
+        rate: f64,
+        exponent: i32,
+    },
+}
+
+impl RadialScaling {
+    fn validate(&self) -> Result<(), Error> {
+        if let RadialScaling::Willatt2018 { scale, rate, .. } = self {
+            if !(*scale > 0.0 && scale.is_finite()) {
+                return Err(Error::InvalidParameter("radial scaling scale must be a positive number".into()));
+            }
+
+            if !(*rate >= 0.0 && rate.is_finite()) {
+                return Err(Error::InvalidParameter("radial scaling rate must be a positive number or zero".into()));
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Evaluate the radial scaling function at the distance `r`
+    pub fn compute(&self, r: f64) -> f64 {
+        match self {
+            RadialScaling::None => 1.0,
+            RadialScaling::Willatt2018 { scale, rate, exponent } => {
+                if *rate == 0.0 {
+                    return (r / scale).powi(-exponent);
+                }
+                return rate / (rate + (r / scale).powi(*exponent));
+            }
+        }
+    }
+
+    /// Evaluate the derivative of the radial scaling function at the distance
+    /// `r`
+    pub fn derivative(&self, r: f64) -> f64 {
+        match self {
+            RadialScaling::None => 0.0,
+            RadialScaling::Willatt2018 { scale, rate, exponent } => {
+                if *exponent == 0 {
+                    return 0.0;
+                }
+
+                let r_scale = r / scale;
+                let exponent_f64 = f64::from(*exponent);
+                if *rate == 0.0 {
+                    return -exponent_f64 * r_scale.powi(-exponent - 1) / scale;
+                }
+
+                let denominator = rate + r_scale.powi(*exponent);
+                return -rate * exponent_f64 * r_scale.powi(exponent - 1) / (scale * denominator * denominator);
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 #[allow(clippy::module_name_repetitions)]
 pub struct SphericalExpansionParameters {
@@ -78,10 +213,105 @@ pub struct SphericalExpansionParameters {
     pub atomic_gaussian_width: f64,
     /// Should we also compute gradients of the feature?
     pub gradients: bool,
+    /// Should we also compute gradients of the feature with respect to the
+    /// unit cell?
+    #[serde(default)]
+    pub cell_gradients: bool,
     /// radial basis to use for the radial integral
     pub radial_basis: RadialBasis,
     /// cutoff function used to smooth the behavior around the cutoff radius
     pub cutoff_function: CutoffFunction,
+    /// radial scaling of the neighbors contributions, used to down-weight
+    /// neighbors far from the central atom. This does not apply to the
+    /// central atom's own contribution.
+    #[serde(default)]
+    pub radial_scaling: RadialScaling,
+    /// Weight of the central atom contribution to its own atomic density.
+    /// Setting this to 0 removes the self contribution entirely.
+    #[serde(default = "default_center_atom_weight")]
+    pub center_atom_weight: f64,
+    /// Weight of the neighbors contributions to the atomic density, indexed by
+    /// the neighbor species. Species not in this map use a weight of 1.
+    #[serde(default)]
+    pub species_weights: BTreeMap<usize, f64>,
+    /// If set, the neighbors species are projected onto a fixed number of
+    /// pseudo-species channels instead of being treated separately. This maps
+    /// each species to its coupling coefficients with all the channels, and
+    /// all species present in the systems must be part of this map. The
+    /// features are then indexed by `channel, n, l, m`, and there is a single
+    /// environment per atom.
+    #[serde(default)]
+    pub species_coupling: Option<BTreeMap<usize, Vec<f64>>>,
+    /// If set, the radial integral is evaluated using splines with the given
+    /// absolute accuracy instead of being computed directly, which is much
+    /// faster for large numbers of neighbors
+    pub spline_accuracy: Option<f64>,
+}
+
+fn default_center_atom_weight() -> f64 {
+    1.0
+}
+
+impl SphericalExpansionParameters {
+    fn validate(&self) -> Result<(), Error> {
+        self.radial_scaling.validate()?;
+
+        if !self.center_atom_weight.is_finite() {
+            return Err(Error::InvalidParameter("center_atom_weight must be a finite number".into()));
+        }
+
+        for (species, weight) in &self.species_weights {
+            if !weight.is_finite() {
+                return Err(Error::InvalidParameter(format!("the weight for species {} must be a finite number", species)));
+            }
+        }
+
+        if let Some(ref coupling) = self.species_coupling {
+            let n_channels = self.channels_count();
+            if n_channels == 0 {
+                return Err(Error::InvalidParameter("species_coupling must contain at least one channel".into()));
+            }
+
+            for (species, coefficients) in coupling {
+                if coefficients.len() != n_channels {
+                    return Err(Error::InvalidParameter(
+                        "all species in species_coupling must have the same number of channels".into()
+                    ));
+                }
+
+                if !(coefficients.iter().all(|c| c.is_finite())) {
+                    return Err(Error::InvalidParameter(format!("the coupling coefficients for species {} must be finite numbers", species)));
+                }
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Get the number of pseudo-species channels in the features. Without
+    /// species coupling, all species use the same single channel.
+    fn channels_count(&self) -> usize {
+        match self.species_coupling {
+            Some(ref coupling) => coupling.values().next().map_or(0, Vec::len),
+            None => 1,
+        }
+    }
+
+    /// Get the coupling coefficients of the given `species` with all the
+    /// pseudo-species channels
+    fn species_channels(&self, species: usize) -> Result<&[f64], Error> {
+        match self.species_coupling {
+            Some(ref coupling) => coupling.get(&species).map(Vec::as_slice).ok_or_else(|| Error::InvalidParameter(
+                format!("species {} is missing from species_coupling", species)
+            )),
+            None => Ok(&[1.0]),
+        }
+    }
+
+    /// Get the density weight for neighbors with the given `species`
+    fn species_weight(&self, species: usize) -> f64 {
+        self.species_weights.get(&species).copied().unwrap_or(1.0)
+    }
 }
 
 pub struct SphericalExpansion {
@@ -95,22 +325,20 @@ pub struct SphericalExpansion {
 }
 
 impl SphericalExpansion {
-    pub fn new(parameters: SphericalExpansionParameters) -> SphericalExpansion {
-        let radial_integral = match parameters.radial_basis {
-            RadialBasis::GTO => {
-                let parameters = GTOParameters {
-                    max_radial: parameters.max_radial,
-                    max_angular: parameters.max_angular,
-                    atomic_gaussian_width: parameters.atomic_gaussian_width,
-                    cutoff: parameters.cutoff,
-                };
-                Box::new(GTO::new(parameters))
-            }
-        };
+    pub fn new(parameters: SphericalExpansionParameters) -> Result<SphericalExpansion, Error> {
+        parameters.validate()?;
+
+        let radial_integral = parameters.radial_basis.radial_integral(
+            parameters.max_radial,
+            parameters.max_angular,
+            parameters.atomic_gaussian_width,
+            parameters.cutoff,
+            parameters.spline_accuracy,
+        )?;
 
         let spherical_harmonics = SphericalHarmonics::new(parameters.max_angular);
         let sph_values = SphericalHarmonicsArray::new(parameters.max_angular);
-        let sph_gradients = if parameters.gradients {
+        let sph_gradients = if parameters.gradients || parameters.cell_gradients {
             Some([
                 SphericalHarmonicsArray::new(parameters.max_angular),
                 SphericalHarmonicsArray::new(parameters.max_angular),
@@ -122,13 +350,13 @@ impl SphericalExpansion {
 
         let shape = (parameters.max_radial, parameters.max_angular + 1);
         let ri_values = Array2::from_elem(shape, 0.0);
-        let ri_gradients = if parameters.gradients {
+        let ri_gradients = if parameters.gradients || parameters.cell_gradients {
             Some(Array2::from_elem(shape, 0.0))
         } else {
             None
         };
 
-        SphericalExpansion {
+        Ok(SphericalExpansion {
             parameters: parameters,
             radial_integral: radial_integral,
             spherical_harmonics: spherical_harmonics,
@@ -136,16 +364,21 @@ impl SphericalExpansion {
             sph_gradients: sph_gradients,
             ri_values: ri_values,
             ri_gradients: ri_gradients,
-        }
+        })
     }
 
-    fn do_self_contributions(&mut self, descriptor: &mut Descriptor) {
-        // keep a list of centers which have already been computed
+    fn do_self_contributions(&mut self, descriptor: &mut Descriptor) -> Result<(), Error> {
+        if self.parameters.center_atom_weight == 0.0 {
+            return Ok(());
+        }
+
+        let coupled = self.parameters.species_coupling.is_some();
         for (i_env, requested_env) in descriptor.environments.iter().enumerate() {
             let alpha = requested_env[2];
-            let beta = requested_env[3];
 
-            if alpha == beta {
+            // without species coupling, the self contribution only goes in
+            // the environment where the neighbor species is the center species
+            if coupled || alpha == requested_env[3] {
                 // TODO: cache self contribution, they only depend on the
                 // gaussian atomic width
                 self.radial_integral.compute(0.0, self.ri_values.view_mut(), None);
@@ -154,17 +387,19 @@ impl SphericalExpansion {
                     Vector3D::new(0.0, 0.0, 1.0), &mut self.sph_values, None
                 );
                 let f_cut = self.parameters.cutoff_function.compute(0.0, self.parameters.cutoff);
+                let weight = self.parameters.center_atom_weight * f_cut;
+                let channels = self.parameters.species_channels(alpha.usize())?;
 
                 for (i_feature, feature) in descriptor.features.iter().enumerate() {
-                    let n = feature[0].usize();
-                    let l = feature[1].usize();
-                    let m = feature[1].isize();
+                    let (channel, n, l, m) = split_feature(feature);
 
-                    let n_l_m_value = f_cut * self.ri_values[[n, l]] * self.sph_values[[l as isize, m]];
-                    descriptor.values[[i_env, i_feature]] += n_l_m_value;
+                    let n_l_m_value = weight * self.ri_values[[n, l]] * self.sph_values[[l as isize, m]];
+                    descriptor.values[[i_env, i_feature]] += channels[channel] * n_l_m_value;
                 }
             }
         }
+
+        return Ok(());
     }
 }
 
@@ -184,17 +419,27 @@ impl CalculatorBase for SphericalExpansion {
     }
 
     fn features_names(&self) -> Vec<&str> {
-        vec!["n", "l", "m"]
+        if self.parameters.species_coupling.is_some() {
+            vec!["channel", "n", "l", "m"]
+        } else {
+            vec!["n", "l", "m"]
+        }
     }
 
     fn features(&self) -> Indexes {
+        let coupled = self.parameters.species_coupling.is_some();
         let mut features = IndexesBuilder::new(self.features_names());
-        for n in 0..(self.parameters.max_radial as isize) {
-            for l in 0..((self.parameters.max_angular + 1) as isize) {
-                for m in -l..=l {
-                    features.add(&[
-                        IndexValue::from(n), IndexValue::from(l), IndexValue::from(m)
-                    ]);
+        for channel in 0..self.parameters.channels_count() {
+            for n in 0..(self.parameters.max_radial as isize) {
+                for l in 0..((self.parameters.max_angular + 1) as isize) {
+                    for m in -l..=l {
+                        let n_l_m = [IndexValue::from(n), IndexValue::from(l), IndexValue::from(m)];
+                        if coupled {
+                            features.add(&[IndexValue::from(channel), n_l_m[0], n_l_m[1], n_l_m[2]]);
+                        } else {
+                            features.add(&n_l_m);
+                        }
+                    }
                 }
             }
         }
@@ -202,43 +447,49 @@ impl CalculatorBase for SphericalExpansion {
     }
 
     fn environments(&self) -> Box<dyn EnvironmentIndexes> {
-        Box::new(AtomSpeciesEnvironment::with_self_contribution(self.parameters.cutoff))
+        if self.parameters.species_coupling.is_some() {
+            Box::new(CenterSpeciesEnvironment::new(self.parameters.cutoff))
+        } else {
+            Box::new(AtomSpeciesEnvironment::with_self_contribution(self.parameters.cutoff))
+        }
     }
 
     fn compute_gradients(&self) -> bool {
         self.parameters.gradients
     }
 
-    fn check_features(&self, indexes: &Indexes) {
+    fn compute_cell_gradients(&self) -> bool {
+        self.parameters.cell_gradients
+    }
+
+    fn check_features(&self, indexes: &Indexes) -> Result<(), Error> {
         // TODO check for duplicated features?
-        assert_eq!(indexes.names(), &["n", "l", "m"]);
+        check_indexes_names("features", indexes, &self.features_names())?;
         for value in indexes {
-            let n = value[0].usize();
-            let l = value[0].isize();
-            let m = value[0].isize();
-            assert!(n < self.parameters.max_radial);
-            assert!(l <= self.parameters.max_angular as isize);
-            assert!(-l <= m && m <= l);
+            let (channel, n, l, m) = split_feature(value);
+            if channel >= self.parameters.channels_count() {
+                return Err(Error::InvalidParameter(format!(
+                    "invalid feature {:?}: channel must be lower than {}",
+                    value, self.parameters.channels_count()
+                )));
+            }
+            check_spherical_feature(value, n, l as isize, m, self.parameters.max_radial, self.parameters.max_angular)?;
         }
+        return Ok(());
     }
 
-    fn check_environments(&self, indexes: &Indexes, systems: &mut [&mut dyn System]) {
+    fn check_environments(&self, indexes: &Indexes, systems: &mut [&mut dyn System]) -> Result<(), Error> {
         // TODO: check for duplicated environments?
-        assert_eq!(indexes.names(), &["structure", "center", "species_center", "species_neighbor"]);
-        // This could be made much faster by not recomputing the full list of
-        // potential environments
-        let allowed = self.environments().indexes(systems);
-        for value in indexes.iter() {
-            assert!(allowed.contains(value), "{:?} is not a valid environment", value);
-        }
+        return check_environments_subset(indexes, &*self.environments(), systems);
     }
 
     #[allow(clippy::similar_names, clippy::too_many_lines, clippy::identity_op)]
-    fn compute(&mut self, systems: &mut [&mut dyn System], descriptor: &mut Descriptor) {
-        assert_eq!(descriptor.environments.names(), &["structure", "center", "species_center", "species_neighbor"]);
-        assert_eq!(descriptor.features.names(), &["n", "l", "m"]);
+    fn compute(&mut self, systems: &mut [&mut dyn System], descriptor: &mut Descriptor) -> Result<(), Error> {
+        assert_eq!(descriptor.environments.names(), self.environments().names());
+        assert_eq!(descriptor.features.names(), self.features_names());
 
-        self.do_self_contributions(descriptor);
+        self.do_self_contributions(descriptor)?;
+        let coupled = self.parameters.species_coupling.is_some();
 
         // keep the set of pairs already seen for each system
         let mut already_computed_pairs = vec![BTreeSet::new(); systems.len()];
@@ -247,20 +498,23 @@ impl CalculatorBase for SphericalExpansion {
             let i_system = requested_env[0];
             let center = requested_env[1].usize();
             let alpha = requested_env[2];
-            let beta = requested_env[3];
 
             let system = &mut *systems[i_system.usize()];
-            system.compute_neighbors(self.parameters.cutoff);
-            let species = system.species();
+            system.compute_neighbors(self.parameters.cutoff)?;
+            let species = system.species()?;
+            let cell = system.cell()?;
 
-            for pair in system.pairs_containing(center) {
+            for pair in system.pairs_containing(center)? {
                 let (neighbor, sign) = if center == pair.first {
                     (pair.second, 1.0)
                 } else {
                     (pair.first, -1.0)
                 };
 
-                if species[neighbor] != beta.usize() {
+                // with species coupling, all neighbors contribute to the
+                // same environment
+                let beta = IndexValue::from(species[neighbor]);
+                if !coupled && beta != requested_env[3] {
                     continue;
                 }
 
@@ -271,9 +525,11 @@ impl CalculatorBase for SphericalExpansion {
                 // we store the result for the center--neighbor pair in env_i,
                 // this code check where (it can not be part of the requested
                 // envs) to store the result for the neighbor--center pair.
-                let other_env_i = descriptor.environments.position(
-                    &[i_system, IndexValue::from(neighbor), beta, alpha]
-                );
+                let mut other_env = vec![i_system, IndexValue::from(neighbor), beta];
+                if !coupled {
+                    other_env.push(alpha);
+                }
+                let other_env_i = descriptor.environments.position(&other_env);
 
                 let distance = pair.vector.norm();
                 let direction = sign * pair.vector / distance;
@@ -286,54 +542,77 @@ impl CalculatorBase for SphericalExpansion {
                     direction, &mut self.sph_values, self.sph_gradients.as_mut()
                 );
                 let f_cut = self.parameters.cutoff_function.compute(distance, self.parameters.cutoff);
+                let f_scaling = self.parameters.radial_scaling.compute(distance);
+                // total weight of this pair in the spherical expansion
+                let weight = f_cut * f_scaling;
+
+                // the species weight depends on which atom is the neighbor:
+                // `neighbor` (with species beta) in the current environment,
+                // and `center` (with species alpha) in the other one
+                let neighbor_weight = self.parameters.species_weight(beta.usize());
+                let center_weight = self.parameters.species_weight(alpha.usize());
+                // same thing for the coupling to the pseudo-species channels
+                let neighbor_channels = self.parameters.species_channels(beta.usize())?;
+                let center_channels = self.parameters.species_channels(alpha.usize())?;
 
                 for (i_feature, feature) in descriptor.features.iter().enumerate() {
-                    let n = feature[0].usize();
-                    let l = feature[1].usize();
-                    let m = feature[2].isize();
+                    let (channel, n, l, m) = split_feature(feature);
 
-                    let n_l_m_value = f_cut * self.ri_values[[n, l]] * self.sph_values[[l as isize, m]];
-                    descriptor.values[[i_env, i_feature]] += n_l_m_value;
+                    let n_l_m_value = weight * self.ri_values[[n, l]] * self.sph_values[[l as isize, m]];
+                    descriptor.values[[i_env, i_feature]] += neighbor_weight * neighbor_channels[channel] * n_l_m_value;
                     if let Some(other_env_i) = other_env_i {
                         // Use the fact that `se[n, l, m](-r) = (-1)^l se[n, l, m](r)`
                         // where se === spherical_expansion.
-                        descriptor.values[[other_env_i, i_feature]] += f64::powi(-1.0, l as i32) * n_l_m_value;
+                        let parity = f64::powi(-1.0, l as i32);
+                        let factor = center_weight * center_channels[channel] * parity;
+                        descriptor.values[[other_env_i, i_feature]] += factor * n_l_m_value;
                     }
                 }
 
-                if self.parameters.gradients {
+                // pairs between an atom and its own periodic images move
+                // together with the atom, and do not contribute to gradients
+                // with respect to positions
+                let position_gradients = self.parameters.gradients && neighbor != center;
+                if position_gradients || self.parameters.cell_gradients {
                     // get the indexes where to store the gradient for this
                     // specific pair, if any
-                    let (center_grad_i, neighbor_grad_i) = if let Some(ref gradients_indexes) = descriptor.gradients_indexes {
-                        assert!(self.parameters.gradients);
-                        let center_grad = gradients_indexes.position(&[
-                            i_system, IndexValue::from(center), alpha, beta,
-                            IndexValue::from(neighbor), IndexValue::from(0_usize)
-                        ]);
-                        let neighbor_grad = gradients_indexes.position(&[
-                            i_system, IndexValue::from(neighbor), beta, alpha,
-                            IndexValue::from(center), IndexValue::from(0_usize)
-                        ]);
-                        (center_grad, neighbor_grad)
+                    let (center_grad_i, neighbor_grad_i) = match descriptor.gradients_indexes {
+                        Some(ref gradients_indexes) if position_gradients => {
+                            let mut center_grad = requested_env.to_vec();
+                            center_grad.extend_from_slice(&[IndexValue::from(neighbor), IndexValue::from(0_usize)]);
+                            let center_grad = gradients_indexes.position(&center_grad);
+                            assert!(center_grad.is_some(), "missing storage for gradient");
+
+                            let mut neighbor_grad = other_env;
+                            neighbor_grad.extend_from_slice(&[IndexValue::from(center), IndexValue::from(0_usize)]);
+                            let neighbor_grad = gradients_indexes.position(&neighbor_grad);
+                            (center_grad, neighbor_grad)
+                        }
+                        _ => (None, None),
+                    };
+
+                    // the pair vector in fractional coordinates gives its
+                    // gradient with respect to the cell matrix, when atoms
+                    // keep fixed fractional coordinates
+                    let fractional = if self.parameters.cell_gradients && !cell.is_infinite() {
+                        Some(cell.fractional(&(sign * pair.vector)))
                     } else {
-                        (None, None)
+                        None
                     };
 
                     let f_cut_grad = self.parameters.cutoff_function.derivative(distance, self.parameters.cutoff);
+                    let f_scaling_grad = self.parameters.radial_scaling.derivative(distance);
+                    let weight_grad = f_cut_grad * f_scaling + f_cut * f_scaling_grad;
 
                     let dr_dx = sign * pair.vector[0] / distance;
                     let dr_dy = sign * pair.vector[1] / distance;
                     let dr_dz = sign * pair.vector[2] / distance;
 
-                    let gradients = descriptor.gradients.as_mut().expect("missing storage for gradients");
-                    let center_grad_i = center_grad_i.expect("missing storage for gradient");
                     let ri_gradients = self.ri_gradients.as_ref().expect("missing radial integral gradients");
                     let sph_gradients = self.sph_gradients.as_ref().expect("missing spherical harmonics gradients");
 
                     for (i_feature, feature) in descriptor.features.iter().enumerate() {
-                        let n = feature[0].usize();
-                        let l = feature[1].usize();
-                        let m = feature[2].isize();
+                        let (channel, n, l, m) = split_feature(feature);
 
                         let sph_value = self.sph_values[[l as isize, m]];
                         let sph_grad_x = sph_gradients[0][[l as isize, m]];
@@ -343,65 +622,138 @@ impl CalculatorBase for SphericalExpansion {
                         let ri_value = self.ri_values[[n, l]];
                         let ri_grad = ri_gradients[[n, l]];
 
-                        let grad_x = f_cut_grad * dr_dx * ri_value * sph_value
-                                    + f_cut * ri_grad * dr_dx * sph_value
-                                    + f_cut * ri_value * sph_grad_x / distance;
-
-                        let grad_y = f_cut_grad * dr_dy * ri_value * sph_value
-                                    + f_cut * ri_grad * dr_dy * sph_value
-                                    + f_cut * ri_value * sph_grad_y / distance;
-
-                        let grad_z = f_cut_grad * dr_dz * ri_value * sph_value
-                                    + f_cut * ri_grad * dr_dz * sph_value
-                                    + f_cut * ri_value * sph_grad_z / distance;
-
-                        // assumes that the three spatial derivative are stored
-                        // one after the other
-                        gradients[[center_grad_i + 0, i_feature]] += grad_x;
-                        gradients[[center_grad_i + 1, i_feature]] += grad_y;
-                        gradients[[center_grad_i + 2, i_feature]] += grad_z;
-
-                        if let Some(neighbor_grad_i) = neighbor_grad_i {
-                            // Use the fact that `grad se[n, l, m](-r) = (-1)^(l + 1) grad se[n, l, m](r)`
-                            // where se === spherical_expansion.
-                            let parity = f64::powi(-1.0, l as i32 + 1);
-                            gradients[[neighbor_grad_i + 0, i_feature]] = parity * grad_x;
-                            gradients[[neighbor_grad_i + 1, i_feature]] = parity * grad_y;
-                            gradients[[neighbor_grad_i + 2, i_feature]] = parity * grad_z;
+                        let grad_x = weight_grad * dr_dx * ri_value * sph_value
+                                    + weight * ri_grad * dr_dx * sph_value
+                                    + weight * ri_value * sph_grad_x / distance;
+
+                        let grad_y = weight_grad * dr_dy * ri_value * sph_value
+                                    + weight * ri_grad * dr_dy * sph_value
+                                    + weight * ri_value * sph_grad_y / distance;
+
+                        let grad_z = weight_grad * dr_dz * ri_value * sph_value
+                                    + weight * ri_grad * dr_dz * sph_value
+                                    + weight * ri_value * sph_grad_z / distance;
+
+                        let factor = neighbor_weight * neighbor_channels[channel];
+                        // Use the fact that `grad se[n, l, m](-r) = (-1)^(l + 1) grad se[n, l, m](r)`
+                        // where se === spherical_expansion.
+                        let parity = f64::powi(-1.0, l as i32 + 1);
+                        let other_factor = center_weight * center_channels[channel] * parity;
+
+                        if let Some(center_grad_i) = center_grad_i {
+                            let gradients = descriptor.gradients.as_mut().expect("missing storage for gradients");
+                            // assumes that the three spatial derivative are
+                            // stored one after the other
+                            gradients[[center_grad_i + 0, i_feature]] += factor * grad_x;
+                            gradients[[center_grad_i + 1, i_feature]] += factor * grad_y;
+                            gradients[[center_grad_i + 2, i_feature]] += factor * grad_z;
+
+                            if let Some(neighbor_grad_i) = neighbor_grad_i {
+                                gradients[[neighbor_grad_i + 0, i_feature]] += other_factor * grad_x;
+                                gradients[[neighbor_grad_i + 1, i_feature]] += other_factor * grad_y;
+                                gradients[[neighbor_grad_i + 2, i_feature]] += other_factor * grad_z;
+                            }
+                        }
+
+                        if let Some(fractional) = fractional {
+                            let cell_gradients = descriptor.cell_gradients.as_mut().expect("missing storage for cell gradients");
+                            // assumes that the nine cell derivatives are
+                            // stored one after the other for each environment
+                            for cell_vector in 0..3 {
+                                for (spatial, grad) in [grad_x, grad_y, grad_z].iter().enumerate() {
+                                    let i_grad = 9 * i_env + 3 * cell_vector + spatial;
+                                    cell_gradients[[i_grad, i_feature]] += factor * fractional[cell_vector] * grad;
+
+                                    if let Some(other_env_i) = other_env_i {
+                                        // the vector from the neighbor to the
+                                        // center is `-r`
+                                        let i_grad = 9 * other_env_i + 3 * cell_vector + spatial;
+                                        cell_gradients[[i_grad, i_feature]] -= other_factor * fractional[cell_vector] * grad;
+                                    }
+                                }
+                            }
                         }
                     }
                 }
             }
         }
+
+        return Ok(());
+    }
+}
+
+/// Extract the `(channel, n, l, m)` values from a spherical expansion feature.
+/// Features computed without species coupling only use the first channel.
+fn split_feature(feature: &[IndexValue]) -> (usize, usize, usize, isize) {
+    if feature.len() == 4 {
+        (feature[0].usize(), feature[1].usize(), feature[2].usize(), feature[3].isize())
+    } else {
+        (0, feature[0].usize(), feature[1].usize(), feature[2].isize())
+    }
+}
+
+/// Check that the radial (`n`) and angular (`l`, `m`) parts of the `feature`
+/// are valid for the given `max_radial` and `max_angular`
+pub(super) fn check_spherical_feature(
+    feature: &[IndexValue],
+    n: usize,
+    l: isize,
+    m: isize,
+    max_radial: usize,
+    max_angular: usize,
+) -> Result<(), Error> {
+    if n >= max_radial {
+        return Err(Error::InvalidParameter(format!(
+            "invalid feature {:?}: n must be lower than max_radial ({})", feature, max_radial
+        )));
+    }
+
+    if l < 0 || l > max_angular as isize {
+        return Err(Error::InvalidParameter(format!(
+            "invalid feature {:?}: l must be between 0 and max_angular ({})", feature, max_angular
+        )));
     }
+
+    if m < -l || m > l {
+        return Err(Error::InvalidParameter(format!(
+            "invalid feature {:?}: m must be between -l and l", feature
+        )));
+    }
+
+    return Ok(());
 }
 
-fn sort_pair(pair: &Pair) -> (usize, usize) {
+/// Get a key identifying this pair independently of the order of the atoms,
+/// including the cell shift to distinguish between periodic images
+pub(super) fn sort_pair(pair: &Pair) -> (usize, usize, [i32; 3]) {
     if pair.first <= pair.second {
-        (pair.first, pair.second)
+        (pair.first, pair.second, pair.cell_shift)
     } else {
-        (pair.second, pair.first)
+        let shift = pair.cell_shift;
+        (pair.second, pair.first, [-shift[0], -shift[1], -shift[2]])
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::system::test_systems;
+    use std::collections::BTreeMap;
+
+    use crate::system::{test_systems, deform_cell, SimpleSystems, SimpleSystem, UnitCell};
     use crate::descriptor::{IndexValue, IndexesBuilder};
-    use crate::{Descriptor, Calculator, System};
+    use crate::{Descriptor, Calculator, System, Vector3D};
     use crate::{CalculationOptions, SelectedIndexes};
 
     use approx::assert_relative_eq;
     use ndarray::s;
 
     use super::{SphericalExpansion, SphericalExpansionParameters};
-    use super::{CutoffFunction, RadialBasis};
+    use super::{CutoffFunction, RadialBasis, RadialScaling};
     use super::super::super::CalculatorBase;
 
     /// Convenience macro to create IndexValue
     macro_rules! v {
         ($value: expr) => {
-            crate::descriptor::IndexValue::from($value as f64)
+            crate::descriptor::IndexValue::from($value as i64)
         };
     }
 
@@ -411,9 +763,15 @@ mod tests {
             cutoff: 3.5,
             cutoff_function: CutoffFunction::ShiftedCosine { width: 0.5 },
             gradients: gradients,
+            cell_gradients: false,
             max_radial: 6,
             max_angular: 6,
-            radial_basis: RadialBasis::GTO
+            radial_basis: RadialBasis::GTO,
+            radial_scaling: RadialScaling::None,
+            center_atom_weight: 1.0,
+            species_weights: BTreeMap::new(),
+            species_coupling: None,
+            spline_accuracy: None,
         }
     }
 
@@ -421,7 +779,7 @@ mod tests {
     fn values() {
         let mut calculator = Calculator::from(Box::new(SphericalExpansion::new(
             parameters(false)
-        )) as Box<dyn CalculatorBase>);
+        ).unwrap()) as Box<dyn CalculatorBase>);
 
         let mut systems = test_systems(&["water"]);
         let mut descriptor = Descriptor::new();
@@ -446,12 +804,51 @@ mod tests {
     }
 
     #[test]
-    fn finite_differences() {
+    fn splined_radial_integral() {
         let mut calculator = Calculator::from(Box::new(SphericalExpansion::new(
             parameters(true)
-        )) as Box<dyn CalculatorBase>);
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut splined = Calculator::from(Box::new(SphericalExpansion::new(
+            SphericalExpansionParameters {
+                spline_accuracy: Some(1e-8),
+                ..parameters(true)
+            }
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water", "methane"]);
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut descriptor, Default::default()).unwrap();
+
+        let mut splined_descriptor = Descriptor::new();
+        splined.compute(&mut systems.get(), &mut splined_descriptor, Default::default()).unwrap();
+
+        assert_relative_eq!(descriptor.values, splined_descriptor.values, epsilon=1e-7);
+        assert_relative_eq!(
+            descriptor.gradients.unwrap(),
+            splined_descriptor.gradients.unwrap(),
+            epsilon=1e-6
+        );
+    }
+
+    #[test]
+    fn finite_differences() {
+        check_finite_differences(parameters(true), test_systems(&["water"]));
+    }
+
+    #[test]
+    fn radial_scaling_finite_differences() {
+        check_finite_differences(SphericalExpansionParameters {
+            radial_scaling: RadialScaling::Willatt2018 { scale: 2.5, rate: 1.0, exponent: 4 },
+            ..parameters(true)
+        }, test_systems(&["water"]));
+    }
+
+    fn check_finite_differences(parameters: SphericalExpansionParameters, mut systems: SimpleSystems) {
+        let mut calculator = Calculator::from(Box::new(SphericalExpansion::new(
+            parameters
+        ).unwrap()) as Box<dyn CalculatorBase>);
 
-        let mut systems = test_systems(&["water"]);
         let mut reference = Descriptor::new();
         calculator.compute(&mut systems.get(), &mut reference, Default::default()).unwrap();
 
@@ -459,50 +856,56 @@ mod tests {
         // `rascaline/tests/spherical-expansion.rs`
 
         let gradients_indexes = reference.gradients_indexes.as_ref().unwrap();
-        assert_eq!(
-            gradients_indexes.names(),
-            ["structure", "center", "species_center", "species_neighbor", "neighbor", "spatial"]
-        );
+        let n_env_columns = reference.environments.names().len();
+        let mut expected_names = reference.environments.names();
+        expected_names.extend_from_slice(&["neighbor", "spatial"]);
+        assert_eq!(gradients_indexes.names(), expected_names);
 
         // get the list of modified gradient environments when moving atom_i
         let modified_indexes = |atom_i: usize, spatial_index: usize| {
             let mut results = Vec::new();
             for (env_i, env) in gradients_indexes.iter().enumerate() {
                 let center = env[1];
-                let neighbor = env[4];
-                let spatial = env[5];
+                let neighbor = env[n_env_columns];
+                let spatial = env[n_env_columns + 1];
                 if center.usize() != atom_i && neighbor.usize() == atom_i && spatial.usize() == spatial_index {
-                    results.push((env_i, &env[..4]));
+                    results.push((env_i, &env[..n_env_columns]));
                 }
             }
             return results;
         };
 
-        let delta = 1e-9;
+        // use centered finite differences, which are more accurate than
+        // forward ones
+        let delta = 1e-6;
         let gradients = reference.gradients.as_ref().unwrap();
-        for atom_i in 0..systems.systems[0].size() {
+        for atom_i in 0..systems.systems[0].size().unwrap() {
             for spatial in 0..3 {
                 systems.systems[0].positions_mut()[atom_i][spatial] += delta;
+                let mut updated_plus = Descriptor::new();
+                calculator.compute(&mut systems.get(), &mut updated_plus, Default::default()).unwrap();
 
-                let mut updated = Descriptor::new();
-                calculator.compute(&mut systems.get(), &mut updated, Default::default()).unwrap();
+                systems.systems[0].positions_mut()[atom_i][spatial] -= 2.0 * delta;
+                let mut updated_minus = Descriptor::new();
+                calculator.compute(&mut systems.get(), &mut updated_minus, Default::default()).unwrap();
 
                 for (grad_i, env) in modified_indexes(atom_i, spatial) {
                     let env_i = reference.environments.position(env).expect(
                         "missing environment in reference values"
                     );
-                    assert_eq!(updated.environments.position(env).unwrap(), env_i);
+                    assert_eq!(updated_plus.environments.position(env).unwrap(), env_i);
+                    assert_eq!(updated_minus.environments.position(env).unwrap(), env_i);
 
-                    let value = reference.values.slice(s![env_i, ..]);
-                    let value_delta = updated.values.slice(s![env_i, ..]);
+                    let value_plus = updated_plus.values.slice(s![env_i, ..]);
+                    let value_minus = updated_minus.values.slice(s![env_i, ..]);
                     let gradient = gradients.slice(s![grad_i, ..]);
 
-                    assert_eq!(value.shape(), value_delta.shape());
-                    assert_eq!(value.shape(), gradient.shape());
+                    assert_eq!(value_plus.shape(), value_minus.shape());
+                    assert_eq!(value_plus.shape(), gradient.shape());
 
-                    let mut finite_difference = value_delta.to_owned().clone();
-                    finite_difference -= &value;
-                    finite_difference /= delta;
+                    let mut finite_difference = value_plus.to_owned().clone();
+                    finite_difference -= &value_minus;
+                    finite_difference /= 2.0 * delta;
 
                     assert_relative_eq!(
                         finite_difference, gradient,
@@ -511,7 +914,133 @@ mod tests {
                     );
                 }
 
-                systems.systems[0].positions_mut()[atom_i][spatial] -= delta;
+                systems.systems[0].positions_mut()[atom_i][spatial] += delta;
+            }
+        }
+    }
+
+    /// Two atoms in a cell smaller than the cutoff, such that the neighbor
+    /// list contains multiple periodic images of the same pair, as well as
+    /// pairs between an atom and its own images
+    fn small_cell() -> SimpleSystem {
+        let mut system = SimpleSystem::new(UnitCell::cubic(2.0));
+        system.add_atom(1, Vector3D::new(0.0, 0.0, 0.0));
+        system.add_atom(8, Vector3D::new(0.3, 1.1, 0.4));
+        return system;
+    }
+
+    #[test]
+    fn periodic_images() {
+        let mut calculator = Calculator::from(Box::new(SphericalExpansion::new(
+            parameters(false)
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = SimpleSystems { systems: vec![small_cell()] };
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut descriptor, Default::default()).unwrap();
+
+        // explicitly replicate the periodic images in a non-periodic system,
+        // keeping the atoms from the original cell first
+        let small_cell = small_cell();
+        let mut cluster = SimpleSystem::new(UnitCell::infinite());
+        let mut shifts = vec![Vector3D::new(0.0, 0.0, 0.0)];
+        for a in -3..=3 {
+            for b in -3..=3 {
+                for c in -3..=3 {
+                    if (a, b, c) != (0, 0, 0) {
+                        shifts.push(Vector3D::new(a as f64, b as f64, c as f64));
+                    }
+                }
+            }
+        }
+        for shift in shifts {
+            let shift = small_cell.cell().unwrap().cartesian(&shift);
+            for (&species, &position) in small_cell.species().unwrap().iter().zip(small_cell.positions().unwrap()) {
+                cluster.add_atom(species, position + shift);
+            }
+        }
+
+        let mut samples = IndexesBuilder::new(vec!["structure", "center", "species_center", "species_neighbor"]);
+        for env in descriptor.environments.iter() {
+            samples.add(env);
+        }
+        let samples = samples.finish();
+
+        let mut systems = SimpleSystems { systems: vec![cluster] };
+        let mut expected = Descriptor::new();
+        let options = CalculationOptions {
+            selected_samples: SelectedIndexes::Some(samples),
+            ..Default::default()
+        };
+        calculator.compute(&mut systems.get(), &mut expected, options).unwrap();
+
+        assert!(descriptor.environments == expected.environments);
+        for (value, expected) in descriptor.values.iter().zip(expected.values.iter()) {
+            assert_relative_eq!(value, expected, max_relative=1e-9, epsilon=1e-12);
+        }
+    }
+
+    #[test]
+    fn periodic_images_finite_differences() {
+        check_finite_differences(parameters(true), SimpleSystems { systems: vec![small_cell()] });
+    }
+
+    #[test]
+    fn cell_gradients_finite_differences() {
+        let mut calculator = Calculator::from(Box::new(SphericalExpansion::new(
+            SphericalExpansionParameters {
+                cell_gradients: true,
+                ..parameters(false)
+            }
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut system = SimpleSystem::new(UnitCell::triclinic(2.5, 2.8, 3.1, 80.0, 95.0, 105.0));
+        system.add_atom(1, Vector3D::new(0.0, 0.0, 0.0));
+        system.add_atom(8, Vector3D::new(0.3, 1.1, 0.4));
+        system.add_atom(1, Vector3D::new(1.2, 0.5, 2.0));
+
+        let mut systems = SimpleSystems { systems: vec![system] };
+        let mut reference = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut reference, Default::default()).unwrap();
+
+        assert!(reference.gradients.is_none());
+        let cell_gradients = reference.cell_gradients.as_ref().unwrap();
+        let cell_gradients_indexes = reference.cell_gradients_indexes.as_ref().unwrap();
+        assert_eq!(cell_gradients_indexes.names(), [
+            "structure", "center", "species_center", "species_neighbor", "cell_vector", "spatial"
+        ]);
+        assert_eq!(cell_gradients.shape(), [9 * reference.environments.count(), reference.features.count()]);
+
+        let delta = 1e-6;
+        for cell_vector in 0..3 {
+            for spatial in 0..3 {
+                let mut plus = SimpleSystems {
+                    systems: vec![deform_cell(&systems.systems[0], cell_vector, spatial, delta)]
+                };
+                let mut updated_plus = Descriptor::new();
+                calculator.compute(&mut plus.get(), &mut updated_plus, Default::default()).unwrap();
+
+                let mut minus = SimpleSystems {
+                    systems: vec![deform_cell(&systems.systems[0], cell_vector, spatial, -delta)]
+                };
+                let mut updated_minus = Descriptor::new();
+                calculator.compute(&mut minus.get(), &mut updated_minus, Default::default()).unwrap();
+
+                for (env_i, env) in reference.environments.iter().enumerate() {
+                    assert_eq!(updated_plus.environments.position(env).unwrap(), env_i);
+                    assert_eq!(updated_minus.environments.position(env).unwrap(), env_i);
+
+                    let mut finite_difference = updated_plus.values.slice(s![env_i, ..]).to_owned();
+                    finite_difference -= &updated_minus.values.slice(s![env_i, ..]);
+                    finite_difference /= 2.0 * delta;
+
+                    let gradient = cell_gradients.slice(s![9 * env_i + 3 * cell_vector + spatial, ..]);
+                    assert_relative_eq!(
+                        finite_difference, gradient,
+                        epsilon=1e-9,
+                        max_relative=5e-4,
+                    );
+                }
             }
         }
     }
@@ -520,7 +1049,7 @@ mod tests {
     fn compute_partial() {
         let mut calculator = Calculator::from(Box::new(SphericalExpansion::new(
             parameters(true)
-        )) as Box<dyn CalculatorBase>);
+        ).unwrap()) as Box<dyn CalculatorBase>);
 
         let mut systems = test_systems(&["water", "methane"]);
         let mut full = Descriptor::new();
@@ -620,6 +1149,363 @@ mod tests {
         }
     }
 
+    #[test]
+    fn radial_scaling() {
+        let mut calculator = Calculator::from(Box::new(SphericalExpansion::new(
+            parameters(false)
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut scaled = Calculator::from(Box::new(SphericalExpansion::new(
+            SphericalExpansionParameters {
+                radial_scaling: RadialScaling::Willatt2018 { scale: 1.5, rate: 0.8, exponent: 2 },
+                ..parameters(false)
+            }
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        // single pair, the scaling only applies to the neighbor contribution
+        let mut systems = test_systems(&["CH"]);
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut descriptor, Default::default()).unwrap();
+
+        let mut scaled_descriptor = Descriptor::new();
+        scaled.compute(&mut systems.get(), &mut scaled_descriptor, Default::default()).unwrap();
+
+        let positions = systems.systems[0].positions().unwrap();
+        let distance = (positions[1] - positions[0]).norm();
+        let factor = 0.8 / (0.8 + (distance / 1.5) * (distance / 1.5));
+
+        for (i_env, env) in descriptor.environments.iter().enumerate() {
+            if env[2] == env[3] {
+                // self contribution
+                assert_relative_eq!(
+                    descriptor.values.slice(s![i_env, ..]),
+                    scaled_descriptor.values.slice(s![i_env, ..])
+                );
+            } else {
+                let expected = factor * &descriptor.values.slice(s![i_env, ..]);
+                assert_relative_eq!(expected, scaled_descriptor.values.slice(s![i_env, ..]), epsilon=1e-15);
+            }
+        }
+    }
+
+    #[test]
+    fn center_atom_weight() {
+        let mut calculator = Calculator::from(Box::new(SphericalExpansion::new(
+            parameters(false)
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut weighted = Calculator::from(Box::new(SphericalExpansion::new(
+            SphericalExpansionParameters {
+                center_atom_weight: 0.5,
+                ..parameters(false)
+            }
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut no_center = Calculator::from(Box::new(SphericalExpansion::new(
+            SphericalExpansionParameters {
+                center_atom_weight: 0.0,
+                ..parameters(false)
+            }
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        // in CH, the environments with species_center == species_neighbor
+        // only contain the self contribution
+        let mut systems = test_systems(&["CH"]);
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut descriptor, Default::default()).unwrap();
+
+        let mut weighted_descriptor = Descriptor::new();
+        weighted.compute(&mut systems.get(), &mut weighted_descriptor, Default::default()).unwrap();
+
+        let mut no_center_descriptor = Descriptor::new();
+        no_center.compute(&mut systems.get(), &mut no_center_descriptor, Default::default()).unwrap();
+
+        for (i_env, env) in descriptor.environments.iter().enumerate() {
+            let values = descriptor.values.slice(s![i_env, ..]);
+            let weighted_values = weighted_descriptor.values.slice(s![i_env, ..]);
+            let no_center_values = no_center_descriptor.values.slice(s![i_env, ..]);
+            if env[2] == env[3] {
+                assert_relative_eq!(0.5 * &values, weighted_values);
+                assert!(no_center_values.iter().all(|&v| v == 0.0));
+            } else {
+                assert_relative_eq!(values, weighted_values);
+                assert_relative_eq!(values, no_center_values);
+            }
+        }
+    }
+
+    #[test]
+    fn species_weights() {
+        let mut calculator = Calculator::from(Box::new(SphericalExpansion::new(
+            parameters(false)
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut species_weights = BTreeMap::new();
+        species_weights.insert(1, 0.3);
+        let mut weighted = Calculator::from(Box::new(SphericalExpansion::new(
+            SphericalExpansionParameters {
+                species_weights: species_weights,
+                ..parameters(false)
+            }
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["CH"]);
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut descriptor, Default::default()).unwrap();
+
+        let mut weighted_descriptor = Descriptor::new();
+        weighted.compute(&mut systems.get(), &mut weighted_descriptor, Default::default()).unwrap();
+
+        for (i_env, env) in descriptor.environments.iter().enumerate() {
+            let values = descriptor.values.slice(s![i_env, ..]);
+            let weighted_values = weighted_descriptor.values.slice(s![i_env, ..]);
+            if env[2] != env[3] && env[3] == v!(1) {
+                // hydrogen neighbors are down-weighted
+                assert_relative_eq!(0.3 * &values, weighted_values);
+            } else {
+                // carbon neighbors and self contributions are unchanged
+                assert_relative_eq!(values, weighted_values);
+            }
+        }
+    }
+
+    #[test]
+    fn weights_parameters() {
+        let mut species_weights = BTreeMap::new();
+        species_weights.insert(1, 0.3);
+        let calculator = SphericalExpansion::new(SphericalExpansionParameters {
+            center_atom_weight: 0.5,
+            species_weights: species_weights,
+            ..parameters(false)
+        }).unwrap();
+
+        let json = calculator.get_parameters();
+        let parameters: SphericalExpansionParameters = serde_json::from_str(&json).unwrap();
+        assert_eq!(parameters.center_atom_weight, 0.5);
+        assert_eq!(parameters.species_weights.len(), 1);
+        assert_eq!(parameters.species_weights[&1], 0.3);
+
+        // default values if the weights are not given
+        let parameters: SphericalExpansionParameters = serde_json::from_str(r#"{
+            "cutoff": 3.5,
+            "max_radial": 6,
+            "max_angular": 6,
+            "atomic_gaussian_width": 0.3,
+            "gradients": false,
+            "radial_basis": "GTO",
+            "cutoff_function": "Step",
+            "spline_accuracy": null
+        }"#).unwrap();
+        assert_eq!(parameters.center_atom_weight, 1.0);
+        assert!(parameters.species_weights.is_empty());
+        assert!(parameters.species_coupling.is_none());
+    }
+
+    #[test]
+    fn weights_finite_differences() {
+        let mut species_weights = BTreeMap::new();
+        species_weights.insert(1, 0.3);
+        species_weights.insert(8, 1.7);
+        check_finite_differences(SphericalExpansionParameters {
+            center_atom_weight: 0.4,
+            species_weights: species_weights,
+            ..parameters(true)
+        }, test_systems(&["water"]));
+    }
+
+    fn species_coupling() -> BTreeMap<usize, Vec<f64>> {
+        let mut coupling = BTreeMap::new();
+        coupling.insert(1, vec![1.0, 0.5]);
+        coupling.insert(6, vec![-0.3, 1.2]);
+        coupling.insert(123456, vec![0.7, -1.1]);
+        return coupling;
+    }
+
+    #[test]
+    fn species_coupling_values() {
+        let mut calculator = Calculator::from(Box::new(SphericalExpansion::new(
+            parameters(false)
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut coupled = Calculator::from(Box::new(SphericalExpansion::new(
+            SphericalExpansionParameters {
+                species_coupling: Some(species_coupling()),
+                ..parameters(false)
+            }
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water"]);
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut descriptor, Default::default()).unwrap();
+
+        let mut coupled_descriptor = Descriptor::new();
+        coupled.compute(&mut systems.get(), &mut coupled_descriptor, Default::default()).unwrap();
+
+        assert_eq!(coupled_descriptor.environments.names(), ["structure", "center", "species_center"]);
+        assert_eq!(coupled_descriptor.environments.count(), 3);
+        assert_eq!(coupled_descriptor.features.names(), ["channel", "n", "l", "m"]);
+        assert_eq!(coupled_descriptor.features.count(), 2 * descriptor.features.count());
+
+        // the coupled expansion is a linear combination of the expansions for
+        // each neighbor species
+        let n_features = descriptor.features.count();
+        let coupling = species_coupling();
+        for (i_env, env) in coupled_descriptor.environments.iter().enumerate() {
+            for channel in 0..2 {
+                let mut expected = ndarray::Array1::from_elem(n_features, 0.0);
+                for (other_env_i, other_env) in descriptor.environments.iter().enumerate() {
+                    if other_env[..3] == env[..] {
+                        let beta = other_env[3].usize();
+                        expected.scaled_add(coupling[&beta][channel], &descriptor.values.slice(s![other_env_i, ..]));
+                    }
+                }
+
+                let start = channel * n_features;
+                let values = coupled_descriptor.values.slice(s![i_env, start..(start + n_features)]);
+                assert_relative_eq!(values, expected, max_relative=1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn species_coupling_finite_differences() {
+        let mut species_weights = BTreeMap::new();
+        species_weights.insert(1, 0.3);
+        check_finite_differences(SphericalExpansionParameters {
+            species_weights: species_weights,
+            species_coupling: Some(species_coupling()),
+            ..parameters(true)
+        }, test_systems(&["water"]));
+    }
+
+    #[test]
+    fn species_coupling_compute_partial() {
+        let mut calculator = Calculator::from(Box::new(SphericalExpansion::new(
+            SphericalExpansionParameters {
+                species_coupling: Some(species_coupling()),
+                ..parameters(true)
+            }
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water", "methane"]);
+        let mut full = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut full, Default::default()).unwrap();
+
+        let mut samples = IndexesBuilder::new(vec!["structure", "center", "species_center"]);
+        samples.add(&[v!(1), v!(0), v!(6)]);
+        samples.add(&[v!(0), v!(1), v!(1)]);
+
+        let mut features = IndexesBuilder::new(vec!["channel", "n", "l", "m"]);
+        features.add(&[v!(1), v!(0), v!(1), v!(0)]);
+        features.add(&[v!(0), v!(3), v!(2), v!(-1)]);
+
+        let options = CalculationOptions {
+            selected_samples: SelectedIndexes::Some(samples.finish()),
+            selected_features: SelectedIndexes::Some(features.finish()),
+            ..Default::default()
+        };
+        let mut partial = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut partial, options).unwrap();
+
+        for (env_i, environment) in partial.environments.iter().enumerate() {
+            for (feature_i, feature) in partial.features.iter().enumerate() {
+                let full_env = full.environments.position(environment).unwrap();
+                let full_feature = full.features.position(feature).unwrap();
+                assert_eq!(partial.values[[env_i, feature_i]], full.values[[full_env, full_feature]]);
+            }
+        }
+
+        let partial_gradients = partial.gradients.as_ref().unwrap();
+        let full_gradients = full.gradients.as_ref().unwrap();
+        for (env_i, environment) in partial.gradients_indexes.as_ref().unwrap().iter().enumerate() {
+            for (feature_i, feature) in partial.features.iter().enumerate() {
+                let full_env = full.gradients_indexes.as_ref().unwrap().position(environment).unwrap();
+                let full_feature = full.features.position(feature).unwrap();
+                assert_eq!(partial_gradients[[env_i, feature_i]], full_gradients[[full_env, full_feature]]);
+            }
+        }
+    }
+
+    #[test]
+    fn species_coupling_channels_count() {
+        let mut coupling = species_coupling();
+        coupling.insert(8, vec![1.0]);
+        let error = SphericalExpansion::new(SphericalExpansionParameters {
+            species_coupling: Some(coupling),
+            ..parameters(false)
+        }).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: all species in species_coupling must have the same number of channels"
+        );
+    }
+
+    #[test]
+    fn species_coupling_missing_species() {
+        let mut coupling = species_coupling();
+        coupling.remove(&1);
+        let mut calculator = Calculator::from(Box::new(SphericalExpansion::new(
+            SphericalExpansionParameters {
+                species_coupling: Some(coupling),
+                ..parameters(false)
+            }
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water"]);
+        let mut descriptor = Descriptor::new();
+        let error = calculator.compute(&mut systems.get(), &mut descriptor, Default::default()).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: species 1 is missing from species_coupling");
+    }
+
+    #[test]
+    fn species_coupling_parameters() {
+        let calculator = SphericalExpansion::new(SphericalExpansionParameters {
+            species_coupling: Some(species_coupling()),
+            ..parameters(false)
+        }).unwrap();
+
+        let json = calculator.get_parameters();
+        let parameters: SphericalExpansionParameters = serde_json::from_str(&json).unwrap();
+        assert_eq!(parameters.species_coupling, Some(species_coupling()));
+    }
+
+    mod radial_scaling {
+        use approx::assert_relative_eq;
+        use super::super::RadialScaling;
+
+        #[test]
+        fn negative_scale() {
+            let error = RadialScaling::Willatt2018 { scale: -1.0, rate: 1.0, exponent: 4 }.validate().err().unwrap();
+            assert_eq!(error.to_string(), "invalid parameter: radial scaling scale must be a positive number");
+        }
+
+        #[test]
+        fn willatt2018() {
+            let function = RadialScaling::Willatt2018 { scale: 2.0, rate: 1.5, exponent: 4 };
+            assert_eq!(function.compute(0.0), 1.0);
+            assert_relative_eq!(function.compute(2.0), 0.6);
+            assert_relative_eq!(function.compute(3.0), 1.5 / (1.5 + 5.0625));
+
+            let function = RadialScaling::Willatt2018 { scale: 2.0, rate: 0.0, exponent: 3 };
+            assert_relative_eq!(function.compute(4.0), 0.125);
+        }
+
+        #[test]
+        fn willatt2018_gradient() {
+            let delta = 1e-9;
+            for function in &[
+                RadialScaling::Willatt2018 { scale: 2.0, rate: 1.5, exponent: 4 },
+                RadialScaling::Willatt2018 { scale: 2.0, rate: 0.0, exponent: 3 },
+                RadialScaling::Willatt2018 { scale: 2.0, rate: 1.5, exponent: 0 },
+            ] {
+                for &r in &[0.5, 2.0, 3.7] {
+                    let finite_difference = (function.compute(r + delta) - function.compute(r)) / delta;
+                    assert_relative_eq!(function.derivative(r), finite_difference, epsilon=1e-6, max_relative=1e-6);
+                }
+            }
+        }
+    }
+
     mod cutoff_function {
         use super::super::CutoffFunction;
 
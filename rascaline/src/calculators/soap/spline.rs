@@ -0,0 +1,306 @@
+use ndarray::{Array2, Array3, ArrayViewMut2, Axis, azip, s};
+
+use crate::Error;
+use super::RadialIntegral;
+
+/// Initial number of intervals in the spline grid
+const INITIAL_INTERVALS: usize = 16;
+/// Maximal number of intervals in the spline grid, to prevent using too much
+/// memory if the accuracy can not be reached
+const MAX_INTERVALS: usize = 16384;
+
+/// Parameters controlling the splined radial integral
+#[derive(Debug, Clone, Copy)]
+pub struct SplineParameters {
+    /// Number of radial components
+    pub max_radial: usize,
+    /// Number of angular components
+    pub max_angular: usize,
+    /// cutoff radius, the spline is defined on `[0, cutoff]`
+    pub cutoff: f64,
+    /// target absolute accuracy of the spline, compared to the splined radial
+    /// integral
+    pub accuracy: f64,
+}
+
+impl SplineParameters {
+    fn validate(&self) -> Result<(), Error> {
+        if self.max_radial == 0 {
+            return Err(Error::InvalidParameter("max_radial must be at least 1".into()));
+        }
+
+        if !(self.cutoff > 0.0 && self.cutoff.is_finite()) {
+            return Err(Error::InvalidParameter("cutoff must be a positive number".into()));
+        }
+
+        if !(self.accuracy > 0.0 && self.accuracy.is_finite()) {
+            return Err(Error::InvalidParameter("spline accuracy must be a positive number".into()));
+        }
+
+        return Ok(());
+    }
+}
+
+/// `SplinedRadialIntegral` tabulates another radial integral on a regular grid
+/// in `[0, cutoff]`, and uses cubic Hermite splines to evaluate the values and
+/// gradients of the radial integral.
+///
+/// The grid is refined by doubling the number of points until the error at
+/// the middle of all intervals is below the requested accuracy.
+#[derive(Debug, Clone)]
+pub struct SplinedRadialIntegral {
+    parameters: SplineParameters,
+    /// distance between two grid points
+    delta: f64,
+    /// values of the radial integral at the grid points, with shape
+    /// `(n_points, max_radial, max_angular + 1)`
+    values: Array3<f64>,
+    /// derivatives of the radial integral at the grid points, with shape
+    /// `(n_points, max_radial, max_angular + 1)`
+    derivatives: Array3<f64>,
+}
+
+impl SplinedRadialIntegral {
+    /// Create a new `SplinedRadialIntegral` approximating the given
+    /// `radial_integral` to the accuracy specified in `parameters`.
+    pub fn new(parameters: SplineParameters, radial_integral: &dyn RadialIntegral) -> Result<SplinedRadialIntegral, Error> {
+        parameters.validate()?;
+
+        let shape = (parameters.max_radial, parameters.max_angular + 1);
+        let mut values = Array2::from_elem(shape, 0.0);
+        let mut splined_values = Array2::from_elem(shape, 0.0);
+
+        let mut n_intervals = INITIAL_INTERVALS;
+        let mut spline = SplinedRadialIntegral::tabulate(parameters, n_intervals, radial_integral);
+        loop {
+            // check the accuracy at the middle of each interval
+            let mut max_error = 0.0;
+            for i in 0..n_intervals {
+                let x = (i as f64 + 0.5) * spline.delta;
+                radial_integral.compute(x, values.view_mut(), None);
+                spline.compute(x, splined_values.view_mut(), None);
+
+                azip!((&value in &values, &splined in &splined_values) {
+                    max_error = f64::max(max_error, f64::abs(value - splined));
+                });
+            }
+
+            if max_error < parameters.accuracy {
+                return Ok(spline);
+            }
+
+            n_intervals *= 2;
+            if n_intervals > MAX_INTERVALS {
+                return Err(Error::InvalidParameter(format!(
+                    "could not reach the requested spline accuracy of {:e} \
+                    with {} grid points, the best accuracy was {:e}",
+                    parameters.accuracy, MAX_INTERVALS + 1, max_error
+                )));
+            }
+
+            spline = SplinedRadialIntegral::tabulate(parameters, n_intervals, radial_integral);
+        }
+    }
+
+    /// Tabulate the values and derivatives of the `radial_integral` on a grid
+    /// with `n_intervals` regular intervals between 0 and the cutoff.
+    fn tabulate(parameters: SplineParameters, n_intervals: usize, radial_integral: &dyn RadialIntegral) -> SplinedRadialIntegral {
+        let n_points = n_intervals + 1;
+        let shape = (n_points, parameters.max_radial, parameters.max_angular + 1);
+        let mut values = Array3::from_elem(shape, 0.0);
+        let mut derivatives = Array3::from_elem(shape, 0.0);
+
+        let delta = parameters.cutoff / n_intervals as f64;
+        for i in 0..n_points {
+            radial_integral.compute(
+                i as f64 * delta,
+                values.index_axis_mut(Axis(0), i),
+                Some(derivatives.index_axis_mut(Axis(0), i)),
+            );
+        }
+
+        return SplinedRadialIntegral {
+            parameters: parameters,
+            delta: delta,
+            values: values,
+            derivatives: derivatives,
+        };
+    }
+
+    /// Get the number of grid points used by this spline
+    pub fn n_points(&self) -> usize {
+        self.values.shape()[0]
+    }
+}
+
+impl RadialIntegral for SplinedRadialIntegral {
+    #[allow(clippy::many_single_char_names)]
+    fn compute(
+        &self,
+        distance: f64,
+        mut values: ArrayViewMut2<f64>,
+        gradients: Option<ArrayViewMut2<f64>>
+    ) {
+        let expected_shape = [self.parameters.max_radial, self.parameters.max_angular + 1];
+        assert_eq!(
+            values.shape(), expected_shape,
+            "wrong size for values array, expected [{}, {}] but got [{}, {}]",
+            expected_shape[0], expected_shape[1], values.shape()[0], values.shape()[1]
+        );
+
+        if let Some(ref gradients) = gradients {
+            assert_eq!(
+                gradients.shape(), expected_shape,
+                "wrong size for gradients array, expected [{}, {}] but got [{}, {}]",
+                expected_shape[0], expected_shape[1], gradients.shape()[0], gradients.shape()[1]
+            );
+        }
+
+        assert!(
+            distance >= 0.0 && distance <= self.parameters.cutoff,
+            "splined radial integral can only be evaluated between 0 and the cutoff, got {}",
+            distance
+        );
+
+        // index of the interval containing `distance`
+        let i = usize::min((distance / self.delta) as usize, self.n_points() - 2);
+        let h = self.delta;
+        let t = (distance - i as f64 * h) / h;
+
+        let t2 = t * t;
+        let t3 = t2 * t;
+
+        // cubic Hermite basis functions
+        let h00 = 2.0 * t3 - 3.0 * t2 + 1.0;
+        let h10 = t3 - 2.0 * t2 + t;
+        let h01 = -2.0 * t3 + 3.0 * t2;
+        let h11 = t3 - t2;
+
+        let y0 = self.values.slice(s![i, .., ..]);
+        let y1 = self.values.slice(s![i + 1, .., ..]);
+        let m0 = self.derivatives.slice(s![i, .., ..]);
+        let m1 = self.derivatives.slice(s![i + 1, .., ..]);
+
+        azip!((value in &mut values, &y0 in &y0, &y1 in &y1, &m0 in &m0, &m1 in &m1) {
+            *value = h00 * y0 + h10 * h * m0 + h01 * y1 + h11 * h * m1;
+        });
+
+        if let Some(mut gradients) = gradients {
+            // derivatives of the basis functions w.r.t. t
+            let d_h00 = 6.0 * t2 - 6.0 * t;
+            let d_h10 = 3.0 * t2 - 4.0 * t + 1.0;
+            let d_h01 = -6.0 * t2 + 6.0 * t;
+            let d_h11 = 3.0 * t2 - 2.0 * t;
+
+            azip!((gradient in &mut gradients, &y0 in &y0, &y1 in &y1, &m0 in &m0, &m1 in &m1) {
+                *gradient = (d_h00 * y0 + d_h01 * y1) / h + d_h10 * m0 + d_h11 * m1;
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use ndarray::Array2;
+
+    use super::super::{GTO, GTOParameters, RadialIntegral};
+    use super::{SplinedRadialIntegral, SplineParameters};
+
+    fn gto(max_radial: usize, max_angular: usize) -> GTO {
+        GTO::new(GTOParameters {
+            max_radial: max_radial,
+            max_angular: max_angular,
+            cutoff: 5.0,
+            atomic_gaussian_width: 0.5,
+        }).unwrap()
+    }
+
+    #[test]
+    fn negative_accuracy() {
+        let error = SplinedRadialIntegral::new(SplineParameters {
+            max_radial: 4,
+            max_angular: 4,
+            cutoff: 5.0,
+            accuracy: -1e-6,
+        }, &gto(4, 4)).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: spline accuracy must be a positive number");
+    }
+
+    #[test]
+    fn unreachable_accuracy() {
+        let error = SplinedRadialIntegral::new(SplineParameters {
+            max_radial: 2,
+            max_angular: 2,
+            cutoff: 5.0,
+            accuracy: 1e-20,
+        }, &gto(2, 2)).err().unwrap();
+        assert!(error.to_string().starts_with(
+            "invalid parameter: could not reach the requested spline accuracy of 1e-20 with 16385 grid points"
+        ));
+    }
+
+    #[test]
+    #[should_panic = "splined radial integral can only be evaluated between 0 and the cutoff, got 5.5"]
+    fn outside_cutoff() {
+        let spline = SplinedRadialIntegral::new(SplineParameters {
+            max_radial: 2,
+            max_angular: 2,
+            cutoff: 5.0,
+            accuracy: 1e-6,
+        }, &gto(2, 2)).unwrap();
+
+        let mut values = Array2::from_elem((2, 3), 0.0);
+        spline.compute(5.5, values.view_mut(), None);
+    }
+
+    #[test]
+    fn accuracy() {
+        let max_radial = 8;
+        let max_angular = 8;
+        let gto = gto(max_radial, max_angular);
+
+        let accuracy = 1e-8;
+        let spline = SplinedRadialIntegral::new(SplineParameters {
+            max_radial: max_radial,
+            max_angular: max_angular,
+            cutoff: 5.0,
+            accuracy: accuracy,
+        }, &gto).unwrap();
+
+        let mut values = Array2::from_elem((max_radial, max_angular + 1), 0.0);
+        let mut splined_values = Array2::from_elem((max_radial, max_angular + 1), 0.0);
+        for &rij in &[0.0, 0.145, 0.585, 1.011, 1.704, 2.266, 2.942, 3.859, 4.462, 5.0] {
+            gto.compute(rij, values.view_mut(), None);
+            spline.compute(rij, splined_values.view_mut(), None);
+
+            // the accuracy is only checked in the middle of each interval, so
+            // allow some slack here
+            assert_relative_eq!(values, splined_values, epsilon=10.0 * accuracy);
+        }
+    }
+
+    #[test]
+    fn finite_differences() {
+        let max_radial = 8;
+        let max_angular = 8;
+        let spline = SplinedRadialIntegral::new(SplineParameters {
+            max_radial: max_radial,
+            max_angular: max_angular,
+            cutoff: 5.0,
+            accuracy: 1e-8,
+        }, &gto(max_radial, max_angular)).unwrap();
+
+        let delta = 1e-9;
+        let mut values = Array2::from_elem((max_radial, max_angular + 1), 0.0);
+        let mut values_delta = Array2::from_elem((max_radial, max_angular + 1), 0.0);
+        let mut gradients = Array2::from_elem((max_radial, max_angular + 1), 0.0);
+        for &rij in &[0.0, 0.3, 1.2, 3.4, 4.9] {
+            spline.compute(rij, values.view_mut(), Some(gradients.view_mut()));
+            spline.compute(rij + delta, values_delta.view_mut(), None);
+
+            let finite_differences = (&values_delta - &values) / delta;
+            assert_relative_eq!(finite_differences, gradients, epsilon=1e-5, max_relative=5e-5);
+        }
+    }
+}
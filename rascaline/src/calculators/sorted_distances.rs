@@ -1,8 +1,8 @@
 use std::collections::HashMap;
 
-use ndarray::{aview1, s};
+use super::{CalculatorBase, check_indexes_names, check_environments_subset};
 
-use super::CalculatorBase;
+use crate::Error;
 
 use crate::descriptor::Descriptor;
 use crate::descriptor::{Indexes, IndexesBuilder, IndexValue};
@@ -14,6 +14,9 @@ use crate::system::System;
 pub struct SortedDistances {
     cutoff: f64,
     max_neighbors: usize,
+    /// Should we also compute gradients with respect to the unit cell?
+    #[serde(default)]
+    cell_gradients: bool,
 }
 
 impl CalculatorBase for SortedDistances {
@@ -45,62 +48,75 @@ impl CalculatorBase for SortedDistances {
         false
     }
 
-    fn check_features(&self, indexes: &Indexes) {
-        assert_eq!(indexes.names(), &["neighbor"]);
-        for value in indexes.iter() {
-            assert!(value[0].usize() < self.max_neighbors);
-        }
+    fn compute_cell_gradients(&self) -> bool {
+        self.cell_gradients
     }
 
-    fn check_environments(&self, indexes: &Indexes, systems: &mut [&mut dyn System]) {
-        assert_eq!(indexes.names(), &["structure", "center", "species_center", "species_neighbor"]);
-        // This could be made much faster by not recomputing the full list of
-        // potential environments
-        let allowed = self.environments().indexes(systems);
+    fn check_features(&self, indexes: &Indexes) -> Result<(), Error> {
+        check_indexes_names("features", indexes, &["neighbor"])?;
         for value in indexes.iter() {
-            assert!(allowed.contains(value), "{:?} is not a valid environment", value);
+            if value[0].usize() >= self.max_neighbors {
+                return Err(Error::InvalidParameter(format!(
+                    "invalid feature {:?}: neighbor must be lower than max_neighbors ({})",
+                    value, self.max_neighbors
+                )));
+            }
         }
+        return Ok(());
     }
 
-    fn compute(&mut self, systems: &mut [&mut dyn System], descriptor: &mut Descriptor) {
-        let all_features = descriptor.features.count() == self.max_neighbors;
-        let mut requested_features = Vec::new();
-        if !all_features {
-            for feature in descriptor.features.iter() {
-                let neighbor = feature[0];
-                requested_features.push(neighbor);
-            }
-        }
+    fn check_environments(&self, indexes: &Indexes, systems: &mut [&mut dyn System]) -> Result<(), Error> {
+        return check_environments_subset(indexes, &*self.environments(), systems);
+    }
 
+    fn compute(&mut self, systems: &mut [&mut dyn System], descriptor: &mut Descriptor) -> Result<(), Error> {
         // index of the first entry of descriptor.values corresponding to
         // the current system
         let mut current = 0;
         for (i_system, system) in systems.iter_mut().enumerate() {
             // distance contains a vector of distances vector (one distance
-            // vector for each center) for each pair of species in the system
+            // vector for each center) for each pair of species in the system.
+            // Each distance is stored together with its gradient with respect
+            // to the cell matrix.
             let mut distances = HashMap::new();
+            let n_atoms = system.size()?;
             for idx in &descriptor.environments {
                 let alpha = idx[2].usize();
                 let beta = idx[3].usize();
                 distances.entry((alpha, beta)).or_insert_with(
-                    || vec![Vec::with_capacity(self.max_neighbors); system.size()]
+                    || vec![Vec::with_capacity(self.max_neighbors); n_atoms]
                 );
             }
 
+            let cell = system.cell()?;
+            let cell_gradients = self.cell_gradients && !cell.is_infinite();
+
             // Collect all distances around each center in `distances`
-            system.compute_neighbors(self.cutoff);
-            let species = system.species();
-            for pair in system.pairs() {
+            system.compute_neighbors(self.cutoff)?;
+            let species = system.species()?;
+            for pair in system.pairs()? {
                 let i = pair.first;
                 let j = pair.second;
                 let d = pair.vector.norm();
 
+                // gradient of the distance with respect to the cell matrix,
+                // when atoms keep fixed fractional coordinates
+                let mut d_cell = [0.0; 9];
+                if cell_gradients {
+                    let fractional = cell.fractional(&pair.vector);
+                    for cell_vector in 0..3 {
+                        for spatial in 0..3 {
+                            d_cell[3 * cell_vector + spatial] = fractional[cell_vector] * pair.vector[spatial] / d;
+                        }
+                    }
+                }
+
                 if let Some(distances) = distances.get_mut(&(species[i], species[j])) {
-                    distances[i].push(d);
+                    distances[i].push((d, d_cell));
                 }
 
                 if let Some(distances) = distances.get_mut(&(species[j], species[i])) {
-                    distances[j].push(d);
+                    distances[j].push((d, d_cell));
                 }
             }
 
@@ -108,8 +124,8 @@ impl CalculatorBase for SortedDistances {
             // and pad the distance vectors as needed
             for vectors in distances.iter_mut().map(|(_, vectors)| vectors) {
                 for vec in vectors {
-                    vec.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
-                    vec.resize(self.max_neighbors, self.cutoff);
+                    vec.sort_unstable_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+                    vec.resize(self.max_neighbors, (self.cutoff, [0.0; 9]));
                 }
             }
 
@@ -126,12 +142,16 @@ impl CalculatorBase for SortedDistances {
                     }
 
                     let distance_vector = &distances.get(&(alpha.usize(), beta.usize())).unwrap()[center.usize()];
-                    if all_features {
-                        descriptor.values.slice_mut(s![current, ..]).assign(&aview1(distance_vector));
-                    } else {
-                        // Only assign the requested values
-                        for (i, &neighbor) in requested_features.iter().enumerate() {
-                            descriptor.values[[current, i]] = distance_vector[neighbor.usize()];
+                    for (i_feature, feature) in descriptor.features.iter().enumerate() {
+                        let (distance, d_cell) = distance_vector[feature[0].usize()];
+                        descriptor.values[[current, i_feature]] = distance;
+
+                        if let Some(ref mut gradients) = descriptor.cell_gradients {
+                            // assumes that the nine cell derivatives are
+                            // stored one after the other for each environment
+                            for (i, &value) in d_cell.iter().enumerate() {
+                                gradients[[9 * current + i, i_feature]] = value;
+                            }
                         }
                     }
                 } else {
@@ -143,19 +163,22 @@ impl CalculatorBase for SortedDistances {
 
         // sanity check: did we get all environment in the above loop?
         assert_eq!(current, descriptor.environments.count());
+
+        return Ok(());
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::system::test_systems;
-    use crate::{Descriptor, Calculator};
+    use crate::system::{test_systems, deform_cell, SimpleSystem, SimpleSystems, UnitCell};
+    use crate::{Descriptor, Calculator, Vector3D};
     use crate::{CalculationOptions, SelectedIndexes};
     use crate::descriptor::{IndexesBuilder, IndexValue};
 
     use super::super::CalculatorBase;
 
     use ndarray::{s, aview1};
+    use approx::assert_relative_eq;
 
     use super::SortedDistances;
 
@@ -164,10 +187,11 @@ mod tests {
         let calculator = Calculator::from(Box::new(SortedDistances{
             cutoff: 1.5,
             max_neighbors: 3,
+            cell_gradients: false,
         }) as Box<dyn CalculatorBase>);
 
         assert_eq!(calculator.name(), "sorted distances vector");
-        assert_eq!(calculator.parameters(), "{\"cutoff\":1.5,\"max_neighbors\":3}");
+        assert_eq!(calculator.parameters(), "{\"cutoff\":1.5,\"max_neighbors\":3,\"cell_gradients\":false}");
     }
 
     #[test]
@@ -175,6 +199,7 @@ mod tests {
         let mut calculator = Calculator::from(Box::new(SortedDistances{
             cutoff: 1.5,
             max_neighbors: 3,
+            cell_gradients: false,
         }) as Box<dyn CalculatorBase>);
 
         let mut systems = test_systems(&["water"]);
@@ -194,11 +219,59 @@ mod tests {
         unimplemented!()
     }
 
+    #[test]
+    fn cell_gradients() {
+        let mut calculator = Calculator::from(Box::new(SortedDistances{
+            cutoff: 3.5,
+            max_neighbors: 12,
+            cell_gradients: true,
+        }) as Box<dyn CalculatorBase>);
+
+        let mut system = SimpleSystem::new(UnitCell::triclinic(2.5, 2.8, 3.1, 80.0, 95.0, 105.0));
+        system.add_atom(1, Vector3D::new(0.0, 0.0, 0.0));
+        system.add_atom(8, Vector3D::new(0.3, 1.1, 0.4));
+
+        let mut systems = SimpleSystems { systems: vec![system] };
+        let mut reference = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut reference, Default::default()).unwrap();
+
+        let cell_gradients = reference.cell_gradients.as_ref().unwrap();
+        assert_eq!(cell_gradients.shape(), [9 * reference.environments.count(), 12]);
+
+        let delta = 1e-6;
+        for cell_vector in 0..3 {
+            for spatial in 0..3 {
+                let mut plus = SimpleSystems {
+                    systems: vec![deform_cell(&systems.systems[0], cell_vector, spatial, delta)]
+                };
+                let mut updated_plus = Descriptor::new();
+                calculator.compute(&mut plus.get(), &mut updated_plus, Default::default()).unwrap();
+
+                let mut minus = SimpleSystems {
+                    systems: vec![deform_cell(&systems.systems[0], cell_vector, spatial, -delta)]
+                };
+                let mut updated_minus = Descriptor::new();
+                calculator.compute(&mut minus.get(), &mut updated_minus, Default::default()).unwrap();
+
+                for env_i in 0..reference.environments.count() {
+                    for feature_i in 0..12 {
+                        let finite_difference = (
+                            updated_plus.values[[env_i, feature_i]] - updated_minus.values[[env_i, feature_i]]
+                        ) / (2.0 * delta);
+                        let gradient = cell_gradients[[9 * env_i + 3 * cell_vector + spatial, feature_i]];
+                        assert_relative_eq!(finite_difference, gradient, epsilon=1e-6, max_relative=1e-6);
+                    }
+                }
+            }
+        }
+    }
+
     #[test]
     fn compute_partial() {
         let mut calculator = Calculator::from(Box::new(SortedDistances{
             cutoff: 1.5,
             max_neighbors: 3,
+            cell_gradients: false,
         }) as Box<dyn CalculatorBase>);
 
         let mut systems = test_systems(&["water"]);
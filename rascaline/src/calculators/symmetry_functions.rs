@@ -0,0 +1,685 @@
+use crate::descriptor::{IndexesBuilder, IndexValue, Indexes, EnvironmentIndexes};
+use crate::descriptor::{AtomSpeciesEnvironment, ThreeBodiesSpeciesEnvironment};
+use crate::{Descriptor, Error, System, Vector3D};
+
+use super::{CalculatorBase, check_indexes_names, check_environments_subset};
+use super::soap::CutoffFunction;
+
+/// A single Behler-Parrinello symmetry function, as defined in "Atom-centered
+/// symmetry functions for constructing high-dimensional neural network
+/// potentials", J. Behler, J. Chem. Phys. 134, 074106 (2011).
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum SymmetryFunction {
+    /// Radial symmetry function, summing over all neighbors `j` of the
+    /// central atom `i`: `G2 = \sum_j exp(-eta (r_ij - rs)^2) f_c(r_ij)`
+    G2 {
+        eta: f64,
+        rs: f64,
+    },
+    /// Angular symmetry function, summing over all pairs of neighbors `j, k`
+    /// of the central atom `i`, including the `j-k` distance:
+    /// `G4 = 2^{1 - zeta} \sum_{j < k} (1 + lambda cos(theta_ijk))^zeta
+    /// exp(-eta (r_ij^2 + r_ik^2 + r_jk^2)) f_c(r_ij) f_c(r_ik) f_c(r_jk)`
+    G4 {
+        eta: f64,
+        zeta: f64,
+        lambda: f64,
+    },
+    /// Angular symmetry function, summing over all pairs of neighbors `j, k`
+    /// of the central atom `i`, without the `j-k` distance:
+    /// `G5 = 2^{1 - zeta} \sum_{j < k} (1 + lambda cos(theta_ijk))^zeta
+    /// exp(-eta (r_ij^2 + r_ik^2)) f_c(r_ij) f_c(r_ik)`
+    G5 {
+        eta: f64,
+        zeta: f64,
+        lambda: f64,
+    },
+}
+
+impl SymmetryFunction {
+    #[allow(clippy::float_cmp)]
+    fn validate(&self) -> Result<(), Error> {
+        match *self {
+            SymmetryFunction::G2 { eta, rs } => {
+                if !(eta >= 0.0 && eta.is_finite()) {
+                    return Err(Error::InvalidParameter("eta must be a positive number for G2 functions".into()));
+                }
+
+                if !rs.is_finite() {
+                    return Err(Error::InvalidParameter("rs must be a finite number for G2 functions".into()));
+                }
+            }
+            SymmetryFunction::G4 { eta, zeta, lambda } | SymmetryFunction::G5 { eta, zeta, lambda } => {
+                if !(eta >= 0.0 && eta.is_finite()) {
+                    return Err(Error::InvalidParameter("eta must be a positive number for angular functions".into()));
+                }
+
+                if !(zeta >= 1.0 && zeta.is_finite()) {
+                    return Err(Error::InvalidParameter("zeta must be larger than 1 for angular functions".into()));
+                }
+
+                if !(lambda == 1.0 || lambda == -1.0) {
+                    return Err(Error::InvalidParameter("lambda must be either 1 or -1 for angular functions".into()));
+                }
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Is this a radial (i.e. two-body) symmetry function?
+    fn is_radial(&self) -> bool {
+        matches!(self, SymmetryFunction::G2 { .. })
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[allow(clippy::module_name_repetitions)]
+pub struct SymmetryFunctionsParameters {
+    /// Spherical cutoff to use for atomic environments
+    pub cutoff: f64,
+    /// cutoff function `f_c` used in the symmetry functions
+    pub cutoff_function: CutoffFunction,
+    /// Should we also compute gradients of the feature?
+    pub gradients: bool,
+    /// List of symmetry functions to compute. All functions must either be
+    /// radial (G2) or angular (G4/G5), since these use different environments.
+    pub functions: Vec<SymmetryFunction>,
+}
+
+/// Calculator implementing the Behler-Parrinello atom-centered symmetry
+/// functions.
+///
+/// Radial functions (G2) use `AtomSpeciesEnvironment`, with one environment
+/// for each pair of central atom and neighbor species. Angular functions
+/// (G4/G5) use `ThreeBodiesSpeciesEnvironment`, with one environment for each
+/// central atom and pair of neighbors species. There is one feature for each
+/// of the functions in the parameters, indexed by its position in the list.
+pub struct SymmetryFunctions {
+    parameters: SymmetryFunctionsParameters,
+    /// Are all the functions radial functions?
+    radial: bool,
+}
+
+impl SymmetryFunctions {
+    pub fn new(parameters: SymmetryFunctionsParameters) -> Result<SymmetryFunctions, Error> {
+        if !(parameters.cutoff > 0.0 && parameters.cutoff.is_finite()) {
+            return Err(Error::InvalidParameter("cutoff must be a positive number".into()));
+        }
+
+        if parameters.functions.is_empty() {
+            return Err(Error::InvalidParameter("at least one symmetry function is required".into()));
+        }
+
+        for function in &parameters.functions {
+            function.validate()?;
+        }
+
+        let radial = parameters.functions[0].is_radial();
+        if !parameters.functions.iter().all(|f| f.is_radial() == radial) {
+            return Err(Error::InvalidParameter(
+                "can not mix radial and angular symmetry functions in the same calculator".into()
+            ));
+        }
+
+        Ok(SymmetryFunctions {
+            parameters: parameters,
+            radial: radial,
+        })
+    }
+
+    fn compute_radial(&self, systems: &mut [&mut dyn System], descriptor: &mut Descriptor) -> Result<(), Error> {
+        let cutoff = self.parameters.cutoff;
+        for (i_env, requested_env) in descriptor.environments.iter().enumerate() {
+            let i_system = requested_env[0];
+            let center = requested_env[1].usize();
+            let beta = requested_env[3].usize();
+
+            let system = &mut *systems[i_system.usize()];
+            system.compute_neighbors(cutoff)?;
+            let species = system.species()?;
+
+            for (neighbor, vector) in neighbors_around(&*system, center)? {
+                if species[neighbor] != beta {
+                    continue;
+                }
+
+                let distance = vector.norm();
+                let direction = vector / distance;
+
+                let f_cut = self.parameters.cutoff_function.compute(distance, cutoff);
+                let f_cut_grad = self.parameters.cutoff_function.derivative(distance, cutoff);
+
+                for (i_feature, feature) in descriptor.features.iter().enumerate() {
+                    if let SymmetryFunction::G2 { eta, rs } = self.parameters.functions[feature[0].usize()] {
+                        let exp = f64::exp(-eta * (distance - rs) * (distance - rs));
+                        descriptor.values[[i_env, i_feature]] += exp * f_cut;
+                    } else {
+                        unreachable!("only radial functions are allowed here");
+                    }
+                }
+
+                // periodic self-images move together with the center, and do
+                // not contribute to the gradients
+                if self.parameters.gradients && neighbor != center {
+                    let gradients_indexes = descriptor.gradients_indexes.as_ref().expect("missing gradient indexes");
+                    let mut gradient_env = requested_env.to_vec();
+                    gradient_env.extend_from_slice(&[IndexValue::from(neighbor), IndexValue::from(0_usize)]);
+                    let grad_i = gradients_indexes.position(&gradient_env).expect("missing storage for gradient");
+
+                    let gradients = descriptor.gradients.as_mut().expect("missing storage for gradients");
+                    for (i_feature, feature) in descriptor.features.iter().enumerate() {
+                        if let SymmetryFunction::G2 { eta, rs } = self.parameters.functions[feature[0].usize()] {
+                            let exp = f64::exp(-eta * (distance - rs) * (distance - rs));
+                            let grad = exp * (f_cut_grad - 2.0 * eta * (distance - rs) * f_cut);
+
+                            // assumes that the three spatial derivative are
+                            // stored one after the other
+                            for spatial in 0..3 {
+                                gradients[[grad_i + spatial, i_feature]] += grad * direction[spatial];
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        return Ok(());
+    }
+
+    #[allow(clippy::similar_names, clippy::too_many_lines)]
+    fn compute_angular(&self, systems: &mut [&mut dyn System], descriptor: &mut Descriptor) -> Result<(), Error> {
+        let cutoff = self.parameters.cutoff;
+        let cutoff_function = &self.parameters.cutoff_function;
+        for (i_env, requested_env) in descriptor.environments.iter().enumerate() {
+            let i_system = requested_env[0];
+            let center = requested_env[1].usize();
+            let species_neighbor_1 = requested_env[3].usize();
+            let species_neighbor_2 = requested_env[4].usize();
+
+            let system = &mut *systems[i_system.usize()];
+            system.compute_neighbors(cutoff)?;
+            let species = system.species()?;
+
+            let neighbors = neighbors_around(&*system, center)?;
+            for (i_neighbor, &(neighbor_j, r_ij)) in neighbors.iter().enumerate() {
+                for &(neighbor_k, r_ik) in &neighbors[(i_neighbor + 1)..] {
+
+                    let (species_j, species_k) = (species[neighbor_j], species[neighbor_k]);
+                    let sorted = if species_j < species_k {
+                        (species_j, species_k)
+                    } else {
+                        (species_k, species_j)
+                    };
+                    if sorted != (species_neighbor_1, species_neighbor_2) {
+                        continue;
+                    }
+
+                    // the pair vectors already account for periodic boundary
+                    // conditions, so the angle is computed directly from them
+                    let distance_ij = r_ij.norm();
+                    let distance_ik = r_ik.norm();
+                    let direction_ij = r_ij / distance_ij;
+                    let direction_ik = r_ik / distance_ik;
+                    let cos = direction_ij * direction_ik;
+
+                    let r_jk = r_ik - r_ij;
+                    let distance_jk = r_jk.norm();
+
+                    let f_cut_ij = cutoff_function.compute(distance_ij, cutoff);
+                    let f_cut_ik = cutoff_function.compute(distance_ik, cutoff);
+                    let f_cut_jk = cutoff_function.compute(distance_jk, cutoff);
+
+                    // gradients of the different terms with respect to the
+                    // positions of the neighbors j and k
+                    let d_cos_j = (direction_ik - cos * direction_ij) / distance_ij;
+                    let d_cos_k = (direction_ij - cos * direction_ik) / distance_ik;
+                    let d_f_cut_ij = cutoff_function.derivative(distance_ij, cutoff) / distance_ij * r_ij;
+                    let d_f_cut_ik = cutoff_function.derivative(distance_ik, cutoff) / distance_ik * r_ik;
+                    let d_f_cut_jk = cutoff_function.derivative(distance_jk, cutoff) / distance_jk * r_jk;
+
+                    let mut contributions = Vec::with_capacity(descriptor.features.count());
+                    for feature in descriptor.features.iter() {
+                        let function = &self.parameters.functions[feature[0].usize()];
+                        let (eta, zeta, lambda, with_jk) = match *function {
+                            SymmetryFunction::G4 { eta, zeta, lambda } => (eta, zeta, lambda, true),
+                            SymmetryFunction::G5 { eta, zeta, lambda } => (eta, zeta, lambda, false),
+                            SymmetryFunction::G2 { .. } => unreachable!("only angular functions are allowed here"),
+                        };
+
+                        let normalization = f64::powf(2.0, 1.0 - zeta);
+                        let angular = normalization * f64::powf(1.0 + lambda * cos, zeta);
+                        let d_angular = normalization * zeta * lambda * f64::powf(1.0 + lambda * cos, zeta - 1.0);
+
+                        let mut distances2 = distance_ij * distance_ij + distance_ik * distance_ik;
+                        let mut f_cut = f_cut_ij * f_cut_ik;
+                        // gradients of f_cut with respect to j and k
+                        let mut d_f_cut_j = f_cut_ik * d_f_cut_ij;
+                        let mut d_f_cut_k = f_cut_ij * d_f_cut_ik;
+                        // gradients of the sum of squared distances
+                        let mut d_distances2_j = 2.0 * r_ij;
+                        let mut d_distances2_k = 2.0 * r_ik;
+                        if with_jk {
+                            distances2 += distance_jk * distance_jk;
+                            d_f_cut_j = f_cut_jk * d_f_cut_j - f_cut * d_f_cut_jk;
+                            d_f_cut_k = f_cut_jk * d_f_cut_k + f_cut * d_f_cut_jk;
+                            f_cut *= f_cut_jk;
+                            d_distances2_j -= 2.0 * r_jk;
+                            d_distances2_k += 2.0 * r_jk;
+                        }
+                        let exp = f64::exp(-eta * distances2);
+
+                        let value = angular * exp * f_cut;
+                        let grad_j = d_angular * exp * f_cut * d_cos_j
+                            - eta * value * d_distances2_j
+                            + angular * exp * d_f_cut_j;
+                        let grad_k = d_angular * exp * f_cut * d_cos_k
+                            - eta * value * d_distances2_k
+                            + angular * exp * d_f_cut_k;
+
+                        contributions.push((value, grad_j, grad_k));
+                    }
+
+                    for (i_feature, &(value, _, _)) in contributions.iter().enumerate() {
+                        descriptor.values[[i_env, i_feature]] += value;
+                    }
+
+                    if self.parameters.gradients {
+                        let gradients_indexes = descriptor.gradients_indexes.as_ref().expect("missing gradient indexes");
+                        let gradient_position = |neighbor: usize| {
+                            let mut gradient_env = requested_env.to_vec();
+                            gradient_env.extend_from_slice(&[IndexValue::from(neighbor), IndexValue::from(0_usize)]);
+                            gradients_indexes.position(&gradient_env).expect("missing storage for gradient")
+                        };
+                        // periodic self-images move together with the center,
+                        // and do not contribute to the gradients
+                        let grad_j_i = if neighbor_j == center { None } else { Some(gradient_position(neighbor_j)) };
+                        let grad_k_i = if neighbor_k == center { None } else { Some(gradient_position(neighbor_k)) };
+
+                        let gradients = descriptor.gradients.as_mut().expect("missing storage for gradients");
+                        for (i_feature, &(_, grad_j, grad_k)) in contributions.iter().enumerate() {
+                            // assumes that the three spatial derivative are
+                            // stored one after the other
+                            for spatial in 0..3 {
+                                if let Some(grad_j_i) = grad_j_i {
+                                    gradients[[grad_j_i + spatial, i_feature]] += grad_j[spatial];
+                                }
+                                if let Some(grad_k_i) = grad_k_i {
+                                    gradients[[grad_k_i + spatial, i_feature]] += grad_k[spatial];
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+/// Get all the neighbors of `center`, together with the vector from `center`
+/// to the neighbor. Pairs between `center` and one of its periodic images
+/// stand for two neighbors, at `+vector` and `-vector`.
+fn neighbors_around(system: &dyn System, center: usize) -> Result<Vec<(usize, Vector3D)>, Error> {
+    let mut neighbors = Vec::new();
+    for pair in system.pairs_containing(center)? {
+        if pair.first == pair.second {
+            neighbors.push((center, pair.vector));
+            neighbors.push((center, -pair.vector));
+        } else if pair.first == center {
+            neighbors.push((pair.second, pair.vector));
+        } else {
+            neighbors.push((pair.first, -pair.vector));
+        }
+    }
+    return Ok(neighbors);
+}
+
+impl std::fmt::Debug for SymmetryFunctions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.parameters)
+    }
+}
+
+impl CalculatorBase for SymmetryFunctions {
+    fn name(&self) -> String {
+        "Behler-Parrinello symmetry functions".into()
+    }
+
+    fn get_parameters(&self) -> String {
+        serde_json::to_string(&self.parameters).expect("failed to serialize to JSON")
+    }
+
+    fn features_names(&self) -> Vec<&str> {
+        vec!["function"]
+    }
+
+    fn features(&self) -> Indexes {
+        let mut features = IndexesBuilder::new(self.features_names());
+        for i in 0..self.parameters.functions.len() {
+            features.add(&[IndexValue::from(i)]);
+        }
+        return features.finish();
+    }
+
+    fn environments(&self) -> Box<dyn EnvironmentIndexes> {
+        if self.radial {
+            Box::new(AtomSpeciesEnvironment::new(self.parameters.cutoff))
+        } else {
+            Box::new(ThreeBodiesSpeciesEnvironment::new(self.parameters.cutoff))
+        }
+    }
+
+    fn compute_gradients(&self) -> bool {
+        self.parameters.gradients
+    }
+
+    fn check_features(&self, indexes: &Indexes) -> Result<(), Error> {
+        check_indexes_names("features", indexes, &["function"])?;
+        for value in indexes {
+            if value[0].usize() >= self.parameters.functions.len() {
+                return Err(Error::InvalidParameter(format!(
+                    "invalid feature {:?}: there are only {} symmetry functions",
+                    value, self.parameters.functions.len()
+                )));
+            }
+        }
+        return Ok(());
+    }
+
+    fn check_environments(&self, indexes: &Indexes, systems: &mut [&mut dyn System]) -> Result<(), Error> {
+        return check_environments_subset(indexes, &*self.environments(), systems);
+    }
+
+    fn compute(&mut self, systems: &mut [&mut dyn System], descriptor: &mut Descriptor) -> Result<(), Error> {
+        assert_eq!(descriptor.environments.names(), self.environments().names());
+        assert_eq!(descriptor.features.names(), &["function"]);
+
+        if self.radial {
+            return self.compute_radial(systems, descriptor);
+        } else {
+            return self.compute_angular(systems, descriptor);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use ndarray::s;
+
+    use crate::system::test_systems;
+    use crate::descriptor::IndexesBuilder;
+    use crate::{Descriptor, Calculator, System};
+    use crate::{CalculationOptions, SelectedIndexes};
+
+    use super::{SymmetryFunctions, SymmetryFunctionsParameters, SymmetryFunction};
+    use super::super::CalculatorBase;
+    use super::super::soap::CutoffFunction;
+
+    /// Convenience macro to create IndexValue
+    macro_rules! v {
+        ($value: expr) => {
+            crate::descriptor::IndexValue::from($value as i64)
+        };
+    }
+
+    fn radial_parameters(gradients: bool) -> SymmetryFunctionsParameters {
+        SymmetryFunctionsParameters {
+            cutoff: 3.5,
+            cutoff_function: CutoffFunction::ShiftedCosine { width: 3.5 },
+            gradients: gradients,
+            functions: vec![
+                SymmetryFunction::G2 { eta: 0.5, rs: 0.0 },
+                SymmetryFunction::G2 { eta: 2.0, rs: 1.1 },
+                SymmetryFunction::G2 { eta: 0.0, rs: 0.0 },
+            ],
+        }
+    }
+
+    fn angular_parameters(gradients: bool) -> SymmetryFunctionsParameters {
+        SymmetryFunctionsParameters {
+            cutoff: 3.5,
+            cutoff_function: CutoffFunction::ShiftedCosine { width: 3.5 },
+            gradients: gradients,
+            functions: vec![
+                SymmetryFunction::G4 { eta: 0.1, zeta: 1.0, lambda: 1.0 },
+                SymmetryFunction::G4 { eta: 0.3, zeta: 4.0, lambda: -1.0 },
+                SymmetryFunction::G5 { eta: 0.1, zeta: 2.0, lambda: 1.0 },
+                SymmetryFunction::G5 { eta: 0.2, zeta: 1.0, lambda: -1.0 },
+            ],
+        }
+    }
+
+    /// Behler's cosine cutoff function, for a cutoff of 3.5
+    fn f_cut(r: f64) -> f64 {
+        0.5 * (f64::cos(std::f64::consts::PI * r / 3.5) + 1.0)
+    }
+
+    #[test]
+    fn radial_values() {
+        let mut calculator = Calculator::from(Box::new(SymmetryFunctions::new(
+            radial_parameters(false)
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["CH", "water"]);
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut descriptor, Default::default()).unwrap();
+
+        assert_eq!(descriptor.environments.names(), ["structure", "center", "species_center", "species_neighbor"]);
+        assert_eq!(descriptor.features.names(), ["function"]);
+        assert_eq!(descriptor.features.count(), 3);
+
+        let g2 = |r: f64, eta: f64, rs: f64| f64::exp(-eta * (r - rs) * (r - rs)) * f_cut(r);
+
+        // H in CH
+        let env_i = descriptor.environments.position(&[v!(0), v!(0), v!(1), v!(6)]).unwrap();
+        assert_relative_eq!(descriptor.values[[env_i, 0]], g2(1.2, 0.5, 0.0), max_relative=1e-12);
+        assert_relative_eq!(descriptor.values[[env_i, 1]], g2(1.2, 2.0, 1.1), max_relative=1e-12);
+        assert_relative_eq!(descriptor.values[[env_i, 2]], f_cut(1.2), max_relative=1e-12);
+
+        // O in water, with two H neighbors
+        let r_oh = f64::sqrt(0.75545 * 0.75545 + 0.58895 * 0.58895);
+        let env_i = descriptor.environments.position(&[v!(1), v!(0), v!(123456), v!(1)]).unwrap();
+        assert_relative_eq!(descriptor.values[[env_i, 0]], 2.0 * g2(r_oh, 0.5, 0.0), max_relative=1e-12);
+        assert_relative_eq!(descriptor.values[[env_i, 1]], 2.0 * g2(r_oh, 2.0, 1.1), max_relative=1e-12);
+        assert_relative_eq!(descriptor.values[[env_i, 2]], 2.0 * f_cut(r_oh), max_relative=1e-12);
+    }
+
+    #[test]
+    fn angular_values() {
+        let mut calculator = Calculator::from(Box::new(SymmetryFunctions::new(
+            angular_parameters(false)
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water"]);
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut descriptor, Default::default()).unwrap();
+
+        assert_eq!(
+            descriptor.environments.names(),
+            ["structure", "center", "species_center", "species_neighbor_1", "species_neighbor_2"]
+        );
+        assert_eq!(descriptor.features.count(), 4);
+
+        // O center, with two H neighbors
+        let r_oh2 = 0.75545 * 0.75545 + 0.58895 * 0.58895;
+        let r_oh = f64::sqrt(r_oh2);
+        let r_hh = 2.0 * 0.75545;
+        let cos = (0.58895 * 0.58895 - 0.75545 * 0.75545) / r_oh2;
+
+        let env_i = descriptor.environments.position(&[v!(0), v!(0), v!(123456), v!(1), v!(1)]).unwrap();
+        let expected = [
+            (1.0 + cos) * f64::exp(-0.1 * (2.0 * r_oh2 + r_hh * r_hh)) * f_cut(r_oh) * f_cut(r_oh) * f_cut(r_hh),
+            0.125 * f64::powi(1.0 - cos, 4) * f64::exp(-0.3 * (2.0 * r_oh2 + r_hh * r_hh)) * f_cut(r_oh) * f_cut(r_oh) * f_cut(r_hh),
+            0.5 * f64::powi(1.0 + cos, 2) * f64::exp(-0.1 * 2.0 * r_oh2) * f_cut(r_oh) * f_cut(r_oh),
+            (1.0 - cos) * f64::exp(-0.2 * 2.0 * r_oh2) * f_cut(r_oh) * f_cut(r_oh),
+        ];
+        for (i, &expected) in expected.iter().enumerate() {
+            assert_relative_eq!(descriptor.values[[env_i, i]], expected, max_relative=1e-12);
+        }
+    }
+
+    #[test]
+    fn radial_finite_differences() {
+        check_finite_differences(radial_parameters(true));
+    }
+
+    #[test]
+    fn angular_finite_differences() {
+        check_finite_differences(angular_parameters(true));
+    }
+
+    fn check_finite_differences(parameters: SymmetryFunctionsParameters) {
+        let mut calculator = Calculator::from(Box::new(SymmetryFunctions::new(
+            parameters
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["methane"]);
+        let mut reference = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut reference, Default::default()).unwrap();
+
+        let gradients_indexes = reference.gradients_indexes.as_ref().unwrap();
+        let n_env_columns = reference.environments.names().len();
+
+        // get the list of modified gradient environments when moving atom_i
+        let modified_indexes = |atom_i: usize, spatial_index: usize| {
+            let mut results = Vec::new();
+            for (env_i, env) in gradients_indexes.iter().enumerate() {
+                let center = env[1];
+                let neighbor = env[n_env_columns];
+                let spatial = env[n_env_columns + 1];
+                if center.usize() != atom_i && neighbor.usize() == atom_i && spatial.usize() == spatial_index {
+                    results.push((env_i, &env[..n_env_columns]));
+                }
+            }
+            return results;
+        };
+
+        let delta = 1e-6;
+        let gradients = reference.gradients.as_ref().unwrap();
+        for atom_i in 0..systems.systems[0].size().unwrap() {
+            for spatial in 0..3 {
+                systems.systems[0].positions_mut()[atom_i][spatial] += delta;
+                let mut updated_plus = Descriptor::new();
+                calculator.compute(&mut systems.get(), &mut updated_plus, Default::default()).unwrap();
+
+                systems.systems[0].positions_mut()[atom_i][spatial] -= 2.0 * delta;
+                let mut updated_minus = Descriptor::new();
+                calculator.compute(&mut systems.get(), &mut updated_minus, Default::default()).unwrap();
+
+                let modified = modified_indexes(atom_i, spatial);
+                assert!(!modified.is_empty());
+                for (grad_i, env) in modified {
+                    let env_i = reference.environments.position(env).expect(
+                        "missing environment in reference values"
+                    );
+
+                    let value_plus = updated_plus.values.slice(s![env_i, ..]);
+                    let value_minus = updated_minus.values.slice(s![env_i, ..]);
+                    let gradient = gradients.slice(s![grad_i, ..]);
+
+                    let mut finite_difference = value_plus.to_owned().clone();
+                    finite_difference -= &value_minus;
+                    finite_difference /= 2.0 * delta;
+
+                    assert_relative_eq!(
+                        finite_difference, gradient,
+                        epsilon=1e-9,
+                        max_relative=1e-6,
+                    );
+                }
+
+                systems.systems[0].positions_mut()[atom_i][spatial] += delta;
+            }
+        }
+    }
+
+    #[test]
+    fn compute_partial() {
+        let mut calculator = Calculator::from(Box::new(SymmetryFunctions::new(
+            angular_parameters(true)
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water", "methane"]);
+        let mut full = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut full, Default::default()).unwrap();
+
+        let mut samples = IndexesBuilder::new(vec![
+            "structure", "center", "species_center", "species_neighbor_1", "species_neighbor_2"
+        ]);
+        samples.add(&[v!(1), v!(0), v!(6), v!(1), v!(1)]);
+        samples.add(&[v!(0), v!(1), v!(1), v!(1), v!(123456)]);
+
+        let mut features = IndexesBuilder::new(vec!["function"]);
+        features.add(&[v!(3)]);
+        features.add(&[v!(1)]);
+
+        let options = CalculationOptions {
+            selected_samples: SelectedIndexes::Some(samples.finish()),
+            selected_features: SelectedIndexes::Some(features.finish()),
+            ..Default::default()
+        };
+        let mut partial = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut partial, options).unwrap();
+
+        for (env_i, environment) in partial.environments.iter().enumerate() {
+            for (feature_i, feature) in partial.features.iter().enumerate() {
+                let full_env = full.environments.position(environment).unwrap();
+                let full_feature = full.features.position(feature).unwrap();
+                assert_eq!(partial.values[[env_i, feature_i]], full.values[[full_env, full_feature]]);
+            }
+        }
+
+        let partial_gradients = partial.gradients.as_ref().unwrap();
+        let full_gradients = full.gradients.as_ref().unwrap();
+        for (env_i, environment) in partial.gradients_indexes.as_ref().unwrap().iter().enumerate() {
+            for (feature_i, feature) in partial.features.iter().enumerate() {
+                let full_env = full.gradients_indexes.as_ref().unwrap().position(environment).unwrap();
+                let full_feature = full.features.position(feature).unwrap();
+                assert_eq!(partial_gradients[[env_i, feature_i]], full_gradients[[full_env, full_feature]]);
+            }
+        }
+    }
+
+    #[test]
+    fn parameters() {
+        let calculator = Calculator::new("symmetry_functions", r#"{
+            "cutoff": 3.5,
+            "cutoff_function": {"ShiftedCosine": {"width": 3.5}},
+            "gradients": false,
+            "functions": [
+                {"G4": {"eta": 0.1, "zeta": 1.0, "lambda": 1.0}},
+                {"G5": {"eta": 0.3, "zeta": 4.0, "lambda": -1.0}}
+            ]
+        }"#.into()).unwrap();
+
+        let parameters: SymmetryFunctionsParameters = serde_json::from_str(&calculator.parameters()).unwrap();
+        assert_eq!(parameters.functions, [
+            SymmetryFunction::G4 { eta: 0.1, zeta: 1.0, lambda: 1.0 },
+            SymmetryFunction::G5 { eta: 0.3, zeta: 4.0, lambda: -1.0 },
+        ]);
+    }
+
+    #[test]
+    fn mixed_functions() {
+        let error = SymmetryFunctions::new(SymmetryFunctionsParameters {
+            functions: vec![
+                SymmetryFunction::G2 { eta: 0.5, rs: 0.0 },
+                SymmetryFunction::G4 { eta: 0.1, zeta: 1.0, lambda: 1.0 },
+            ],
+            ..radial_parameters(false)
+        }).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: can not mix radial and angular symmetry functions in the same calculator");
+    }
+
+    #[test]
+    fn invalid_lambda() {
+        let error = SymmetryFunctions::new(SymmetryFunctionsParameters {
+            functions: vec![SymmetryFunction::G5 { eta: 0.1, zeta: 1.0, lambda: 0.5 }],
+            ..angular_parameters(false)
+        }).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: lambda must be either 1 or -1 for angular functions");
+    }
+}
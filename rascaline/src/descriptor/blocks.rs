@@ -0,0 +1,487 @@
+use std::collections::BTreeMap;
+
+use indexmap::IndexSet;
+use ndarray::Array2;
+
+use crate::Error;
+use super::{Descriptor, Indexes, IndexesBuilder, IndexValue};
+use super::descriptor::{DensifiedIndex, DensifyBlock, RemovedResult, densify_blocks};
+
+/// A single block in a `BlockDescriptor`, containing the values and gradients
+/// of the descriptor for a subset of the samples.
+pub struct DescriptorBlock {
+    /// An array of `samples.count()` by `features.count()` values
+    pub values: Array2<f64>,
+    pub samples: Indexes,
+    pub features: Indexes,
+    /// Gradients of the values with respect to one atomic position
+    pub gradients: Option<Array2<f64>>,
+    pub gradients_indexes: Option<Indexes>,
+    /// Gradients of the values with respect to the unit cell
+    pub cell_gradients: Option<Array2<f64>>,
+    pub cell_gradients_indexes: Option<Indexes>,
+}
+
+impl DescriptorBlock {
+    /// Create a block from a `descriptor` where all the samples have the same
+    /// value for the `keys` variables, removing these variables from the
+    /// samples and gradients indexes. The arrays of the descriptor are moved
+    /// to the block without copies.
+    pub(crate) fn from_key_descriptor(descriptor: Descriptor, keys: &[&str]) -> Result<DescriptorBlock, Error> {
+        if descriptor.sparse_gradients.is_some() {
+            return Err(Error::InvalidParameter(
+                "can not create a block from a descriptor with sparse gradients".into()
+            ));
+        }
+
+        if descriptor.environments.project(keys)?.count() > 1 {
+            return Err(Error::InvalidParameter(format!(
+                "all the samples in a block must have the same value for ({})", keys.join(", ")
+            )));
+        }
+
+        let samples = remove_keys(&descriptor.environments, keys)?;
+        let gradients_indexes = descriptor.gradients_indexes.as_ref()
+            .map(|indexes| remove_keys(indexes, keys))
+            .transpose()?;
+        let cell_gradients_indexes = descriptor.cell_gradients_indexes.as_ref()
+            .map(|indexes| remove_keys(indexes, keys))
+            .transpose()?;
+
+        return Ok(DescriptorBlock {
+            values: descriptor.values,
+            samples: samples,
+            features: descriptor.features,
+            gradients: descriptor.gradients,
+            gradients_indexes: gradients_indexes,
+            cell_gradients: descriptor.cell_gradients,
+            cell_gradients_indexes: cell_gradients_indexes,
+        });
+    }
+}
+
+/// Remove the `keys` variables from the `indexes`. The keys must take a
+/// single value in the indexes, so that the entries stay unique.
+fn remove_keys(indexes: &Indexes, keys: &[&str]) -> Result<Indexes, Error> {
+    let names = indexes.names().into_iter()
+        .filter(|name| !keys.contains(name))
+        .collect::<Vec<_>>();
+
+    if names.is_empty() {
+        return Err(Error::InvalidParameter(format!(
+            "can not use all the samples variables ({}) as block keys", keys.join(", ")
+        )));
+    }
+
+    return indexes.project(&names);
+}
+
+/// A descriptor stored as a collection of blocks, each block corresponding to
+/// a single value of the `keys` (for example a single pair of
+/// `(species_center, species_neighbor)`).
+///
+/// The key variables are removed from the samples of each block, and each
+/// block can have a different set of features. This avoids storing the zeros
+/// a dense `Descriptor` would contain for blocks without data.
+///
+/// A `BlockDescriptor` can be computed directly with
+/// `Calculator::compute_blocks`, or created from an existing descriptor with
+/// `BlockDescriptor::from_descriptor`.
+pub struct BlockDescriptor {
+    /// Values of the key variables, with one entry for each block
+    pub keys: Indexes,
+    /// Blocks corresponding to each entry in `keys`
+    pub blocks: Vec<DescriptorBlock>,
+}
+
+impl Default for BlockDescriptor {
+    fn default() -> Self { Self::new() }
+}
+
+impl BlockDescriptor {
+    /// Create a new `BlockDescriptor` without any block
+    pub fn new() -> BlockDescriptor {
+        BlockDescriptor {
+            keys: IndexesBuilder::new(vec![]).finish(),
+            blocks: Vec::new(),
+        }
+    }
+
+    /// Split the `descriptor` in blocks, using the given sample `variables`
+    /// as the keys of the blocks. The blocks are ordered by first occurrence
+    /// of their key in the descriptor samples.
+    pub fn from_descriptor(descriptor: &Descriptor, variables: Vec<&str>) -> Result<BlockDescriptor, Error> {
+        if variables.is_empty() {
+            return Err(Error::InvalidParameter(
+                "at least one variable is required to split a descriptor in blocks".into()
+            ));
+        }
+
+        if descriptor.sparse_gradients.is_some() {
+            return Err(Error::InvalidParameter(
+                "can not split a descriptor with sparse gradients in blocks".into()
+            ));
+        }
+
+        let keys = descriptor.environments.project(&variables)?;
+        let mut blocks = Vec::with_capacity(keys.count());
+        for key in &keys {
+            let selected = descriptor.select(Some(&key_selection(&keys, key)), None)?;
+            blocks.push(DescriptorBlock::from_key_descriptor(selected, &variables)?);
+        }
+
+        return Ok(BlockDescriptor {
+            keys: keys,
+            blocks: blocks,
+        });
+    }
+
+    /// Convert this block descriptor to a dense `Descriptor`, moving the keys
+    /// to the features in the same way as `Descriptor::densify`. The samples
+    /// are the union of the samples of all blocks, and samples missing from a
+    /// block have values and gradients of zero for this block features.
+    ///
+    /// All blocks must use the same names for samples and features, but can
+    /// have different samples and features. The dense descriptor only
+    /// contains gradients (resp. cell gradients) if all the blocks contain
+    /// them.
+    pub fn densify(&self) -> Result<Descriptor, Error> {
+        let first = if let Some(first) = self.blocks.first() {
+            first
+        } else {
+            return Ok(Descriptor::new());
+        };
+
+        if self.keys.count() != self.blocks.len() {
+            return Err(Error::InvalidParameter(format!(
+                "expected one block for each key, got {} keys and {} blocks",
+                self.keys.count(), self.blocks.len()
+            )));
+        }
+
+        for (i_block, block) in self.blocks.iter().enumerate() {
+            check_block(first, block, i_block)?;
+        }
+
+        let environments = self.merge_samples();
+
+        let gradients_indexes = self.blocks.iter()
+            .map(|block| block.gradients.as_ref().and(block.gradients_indexes.as_ref()))
+            .collect::<Option<Vec<_>>>();
+        let gradients = gradients_indexes
+            .map(|indexes| self.merge_gradients(&environments, &indexes))
+            .transpose()?;
+
+        let cell_gradients_indexes = self.blocks.iter()
+            .map(|block| block.cell_gradients.as_ref().and(block.cell_gradients_indexes.as_ref()))
+            .collect::<Option<Vec<_>>>();
+        let cell_gradients = cell_gradients_indexes
+            .map(|indexes| self.merge_gradients(&environments, &indexes))
+            .transpose()?;
+
+        let blocks = self.blocks.iter().map(|block| DensifyBlock {
+            features: &block.features,
+            values: &block.values,
+            gradients: block.gradients.as_ref(),
+            cell_gradients: block.cell_gradients.as_ref(),
+        }).collect::<Vec<_>>();
+
+        let mut feature_names = self.keys.names();
+        feature_names.extend(first.features.names());
+        return densify_blocks(feature_names, &blocks, environments, gradients, cell_gradients);
+    }
+
+    /// Get the union of the samples of all blocks, and the position of each
+    /// block sample in this union
+    fn merge_samples(&self) -> RemovedResult {
+        let mut samples = IndexSet::new();
+        let mut mapping = BTreeMap::new();
+        for (i_block, block) in self.blocks.iter().enumerate() {
+            for (row, sample) in block.samples.iter().enumerate() {
+                let (environment, _) = samples.insert_full(sample);
+                let densified = DensifiedIndex {
+                    environment: environment,
+                    feature_block: i_block,
+                };
+                mapping.insert(densified, row);
+            }
+        }
+
+        let first = &self.blocks[0].samples;
+        let mut builder = IndexesBuilder::with_types(first.names(), first.types().to_vec());
+        for sample in samples {
+            builder.add(sample);
+        }
+
+        return RemovedResult {
+            indexes: builder.finish(),
+            new_features: self.keys.iter().map(|key| key.to_vec()).collect(),
+            mapping: mapping,
+        };
+    }
+
+    /// Get the union of the gradients `indexes` of all blocks, keeping the
+    /// gradients grouped by sample in the same order as the merged `samples`.
+    fn merge_gradients(&self, samples: &RemovedResult, indexes: &[&Indexes]) -> Result<RemovedResult, Error> {
+        // gradient rows for each sample of each block
+        let blocks_rows = self.blocks.iter().zip(indexes).enumerate().map(|(i_block, (block, indexes))| {
+            let n_samples = block.samples.size();
+            if indexes.size() < n_samples || indexes.names()[..n_samples] != block.samples.names()[..] {
+                return Err(Error::InvalidParameter(format!(
+                    "the gradients indexes of block {} must start with the samples indexes", i_block
+                )));
+            }
+
+            let mut rows = vec![Vec::new(); block.samples.count()];
+            for (row, gradient) in indexes.iter().enumerate() {
+                let sample = block.samples.position(&gradient[..n_samples]).ok_or_else(|| Error::InvalidParameter(format!(
+                    "the gradients of block {} contain a sample which is not part of this block", i_block
+                )))?;
+                rows[sample].push(row);
+            }
+            Ok(rows)
+        }).collect::<Result<Vec<_>, Error>>()?;
+
+        let mut gradients = IndexSet::new();
+        let mut mapping = BTreeMap::new();
+        for (new, &row) in &samples.mapping {
+            let block_indexes = indexes[new.feature_block];
+            for &gradient_row in &blocks_rows[new.feature_block][row] {
+                let (environment, _) = gradients.insert_full(&block_indexes[gradient_row]);
+                let densified = DensifiedIndex {
+                    environment: environment,
+                    feature_block: new.feature_block,
+                };
+                mapping.insert(densified, gradient_row);
+            }
+        }
+
+        let mut builder = IndexesBuilder::with_types(indexes[0].names(), indexes[0].types().to_vec());
+        for gradient in gradients {
+            builder.add(gradient);
+        }
+
+        return Ok(RemovedResult {
+            indexes: builder.finish(),
+            new_features: samples.new_features.clone(),
+            mapping: mapping,
+        });
+    }
+}
+
+/// Get `Indexes` containing a single `key`, to select the corresponding
+/// samples with `Descriptor::select` or `Indexes::filter`
+fn key_selection(keys: &Indexes, key: &[IndexValue]) -> Indexes {
+    let mut selection = IndexesBuilder::with_types(keys.names(), keys.types().to_vec());
+    selection.add(key);
+    return selection.finish();
+}
+
+/// Check that `block` is consistent with itself and with the `first` block,
+/// i.e. that they can be densified together.
+fn check_block(first: &DescriptorBlock, block: &DescriptorBlock, i_block: usize) -> Result<(), Error> {
+    let same_kind = |first: &Indexes, indexes: &Indexes| {
+        first.names() == indexes.names() && first.types() == indexes.types()
+    };
+
+    if !same_kind(&first.samples, &block.samples) {
+        return Err(Error::InvalidParameter(format!(
+            "all blocks must have the same samples names and types, block {} has different ones", i_block
+        )));
+    }
+
+    if !same_kind(&first.features, &block.features) {
+        return Err(Error::InvalidParameter(format!(
+            "all blocks must have the same features names and types, block {} has different ones", i_block
+        )));
+    }
+
+    let check_shape = |array: &Array2<f64>, rows: usize, kind: &str| {
+        if array.shape() != [rows, block.features.count()] {
+            return Err(Error::InvalidParameter(format!(
+                "wrong shape for the {} of block {}: expected [{}, {}], got {:?}",
+                kind, i_block, rows, block.features.count(), array.shape()
+            )));
+        }
+        Ok(())
+    };
+
+    check_shape(&block.values, block.samples.count(), "values")?;
+
+    if let (Some(gradients), Some(indexes)) = (&block.gradients, &block.gradients_indexes) {
+        check_shape(gradients, indexes.count(), "gradients")?;
+        if let Some(first_indexes) = &first.gradients_indexes {
+            if !same_kind(first_indexes, indexes) {
+                return Err(Error::InvalidParameter(format!(
+                    "all blocks must have the same gradients names and types, block {} has different ones", i_block
+                )));
+            }
+        }
+    }
+
+    if let (Some(cell_gradients), Some(indexes)) = (&block.cell_gradients, &block.cell_gradients_indexes) {
+        check_shape(cell_gradients, indexes.count(), "cell gradients")?;
+        if let Some(first_indexes) = &first.cell_gradients_indexes {
+            if !same_kind(first_indexes, indexes) {
+                return Err(Error::InvalidParameter(format!(
+                    "all blocks must have the same cell gradients names and types, block {} has different ones", i_block
+                )));
+            }
+        }
+    }
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::s;
+    use crate::{Calculator, CalculationOptions};
+    use crate::system::test_systems;
+
+    fn calculator(gradients: bool) -> Calculator {
+        Calculator::new("spherical_expansion", format!(r#"{{
+            "cutoff": 3.5,
+            "max_radial": 3,
+            "max_angular": 2,
+            "atomic_gaussian_width": 0.3,
+            "gradients": {},
+            "cell_gradients": true,
+            "radial_basis": "GTO",
+            "cutoff_function": {{"ShiftedCosine": {{"width": 0.5}}}}
+        }}"#, gradients)).unwrap()
+    }
+
+    fn spherical_expansion(gradients: bool) -> Descriptor {
+        let mut systems = test_systems(&["water", "CH"]);
+        let mut descriptor = Descriptor::new();
+        calculator(gradients).compute(&mut systems.get(), &mut descriptor, CalculationOptions::default()).unwrap();
+        return descriptor;
+    }
+
+    #[test]
+    fn from_descriptor() {
+        let descriptor = spherical_expansion(true);
+        let blocks = BlockDescriptor::from_descriptor(&descriptor, vec!["species_center", "species_neighbor"]).unwrap();
+
+        assert_eq!(blocks.keys.names(), ["species_center", "species_neighbor"]);
+        // (H, H), (H, O), (O, H), (O, O), (H, C), (C, H), (C, C)
+        assert_eq!(blocks.keys.count(), 7);
+        assert_eq!(blocks.blocks.len(), 7);
+
+        let mut n_samples = 0;
+        let mut n_gradients = 0;
+        for (key, block) in blocks.keys.iter().zip(&blocks.blocks) {
+            assert_eq!(block.samples.names(), ["structure", "center"]);
+            assert!(block.features == descriptor.features);
+            assert_eq!(block.values.shape(), [block.samples.count(), descriptor.features.count()]);
+
+            for (i, sample) in block.samples.iter().enumerate() {
+                let mut environment = sample.to_vec();
+                environment.extend_from_slice(key);
+                let row = descriptor.environments.position(&environment).unwrap();
+                assert_eq!(block.values.row(i), descriptor.values.row(row));
+
+                let cell_gradients = block.cell_gradients.as_ref().unwrap();
+                assert_eq!(
+                    cell_gradients.slice(s![(9 * i)..(9 * i + 9), ..]),
+                    descriptor.cell_gradients.as_ref().unwrap().slice(s![(9 * row)..(9 * row + 9), ..])
+                );
+            }
+
+            let gradients_indexes = block.gradients_indexes.as_ref().unwrap();
+            assert_eq!(gradients_indexes.names(), ["structure", "center", "neighbor", "spatial"]);
+            let gradients = block.gradients.as_ref().unwrap();
+            for (i, gradient) in gradients_indexes.iter().enumerate() {
+                let mut index = gradient[..2].to_vec();
+                index.extend_from_slice(key);
+                index.extend_from_slice(&gradient[2..]);
+                let row = descriptor.gradients_indexes.as_ref().unwrap().position(&index).unwrap();
+                assert_eq!(gradients.row(i), descriptor.gradients.as_ref().unwrap().row(row));
+            }
+
+            n_samples += block.samples.count();
+            n_gradients += gradients_indexes.count();
+        }
+
+        assert_eq!(n_samples, descriptor.environments.count());
+        assert_eq!(n_gradients, descriptor.gradients_indexes.as_ref().unwrap().count());
+    }
+
+    #[test]
+    fn compute_blocks() {
+        let descriptor = spherical_expansion(true);
+        let variables = vec!["species_center", "species_neighbor"];
+        let expected = BlockDescriptor::from_descriptor(&descriptor, variables.clone()).unwrap();
+
+        let mut systems = test_systems(&["water", "CH"]);
+        let blocks = calculator(true).compute_blocks(&mut systems.get(), variables, CalculationOptions::default()).unwrap();
+
+        assert_eq!(blocks.keys, expected.keys);
+        for (block, expected) in blocks.blocks.iter().zip(&expected.blocks) {
+            assert_eq!(block.samples, expected.samples);
+            assert_eq!(block.features, expected.features);
+            assert_eq!(block.gradients_indexes, expected.gradients_indexes);
+            assert_eq!(block.cell_gradients_indexes, expected.cell_gradients_indexes);
+
+            assert_eq!(block.values, expected.values);
+            assert_eq!(block.gradients, expected.gradients);
+            assert_eq!(block.cell_gradients, expected.cell_gradients);
+        }
+    }
+
+    #[test]
+    fn densify() {
+        let variables = vec!["species_center", "species_neighbor"];
+
+        let mut systems = test_systems(&["water", "CH"]);
+        let blocks = calculator(true).compute_blocks(&mut systems.get(), variables.clone(), CalculationOptions::default()).unwrap();
+        let densified = blocks.densify().unwrap();
+
+        let mut expected = spherical_expansion(true);
+        expected.densify(variables).unwrap();
+
+        assert_eq!(densified.environments, expected.environments);
+        assert_eq!(densified.features, expected.features);
+        assert_eq!(densified.values, expected.values);
+        assert_eq!(densified.gradients_indexes, expected.gradients_indexes);
+        assert_eq!(densified.gradients, expected.gradients);
+        assert_eq!(densified.cell_gradients_indexes, expected.cell_gradients_indexes);
+        assert_eq!(densified.cell_gradients, expected.cell_gradients);
+    }
+
+    #[test]
+    fn invalid_blocks() {
+        let descriptor = spherical_expansion(false);
+
+        let error = BlockDescriptor::from_descriptor(&descriptor, vec![]).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: at least one variable is required to split a descriptor in blocks");
+
+        let error = BlockDescriptor::from_descriptor(&descriptor, vec!["not_there"]).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: there is no 'not_there' index in these indexes, available indexes \
+            are [structure, center, species_center, species_neighbor]"
+        );
+
+        let error = BlockDescriptor::from_descriptor(
+            &descriptor, vec!["structure", "center", "species_center", "species_neighbor"]
+        ).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: can not use all the samples variables \
+            (structure, center, species_center, species_neighbor) as block keys"
+        );
+
+        let mut sparse = spherical_expansion(true);
+        sparse.sparsify_gradients();
+        let error = BlockDescriptor::from_descriptor(&sparse, vec!["species_center"]).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: can not split a descriptor with sparse gradients in blocks");
+
+        let mut blocks = BlockDescriptor::from_descriptor(&descriptor, vec!["species_center"]).unwrap();
+        blocks.blocks[1].values = Array2::zeros((1, 1));
+        let error = blocks.densify().err().unwrap();
+        assert!(error.to_string().starts_with("invalid parameter: wrong shape for the values of block 1"));
+    }
+}
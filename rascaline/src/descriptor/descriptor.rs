@@ -1,10 +1,11 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use indexmap::set::IndexSet;
 
 use itertools::Itertools;
 use ndarray::{Array2, s};
 
-use super::{Indexes, IndexesBuilder, IndexValue};
+use crate::Error;
+use super::{Indexes, IndexesBuilder, IndexValue, SparseGradients};
 
 pub struct Descriptor {
     /// An array of environments.count() by features.count() values
@@ -14,6 +15,15 @@ pub struct Descriptor {
     /// Gradients of the descriptor with respect to one atomic position
     pub gradients: Option<Array2<f64>>,
     pub gradients_indexes: Option<Indexes>,
+    /// Gradients of the descriptor with respect to atomic positions, stored
+    /// as sparse blocks. This is used instead of `gradients` and
+    /// `gradients_indexes` when `CalculationOptions::sparse_gradients` is set.
+    pub sparse_gradients: Option<SparseGradients>,
+    /// Gradients of the descriptor with respect to the unit cell matrix. The
+    /// atoms are kept at fixed fractional coordinates when taking this
+    /// derivative, and the gradients are zero for non-periodic systems.
+    pub cell_gradients: Option<Array2<f64>>,
+    pub cell_gradients_indexes: Option<Indexes>,
 }
 
 impl Default for Descriptor {
@@ -29,6 +39,9 @@ impl Descriptor {
             features: indexes,
             gradients: None,
             gradients_indexes: None,
+            sparse_gradients: None,
+            cell_gradients: None,
+            cell_gradients_indexes: None,
         }
     }
 
@@ -46,6 +59,9 @@ impl Descriptor {
 
         self.gradients = None;
         self.gradients_indexes = None;
+        self.sparse_gradients = None;
+        self.cell_gradients = None;
+        self.cell_gradients_indexes = None;
     }
 
     pub fn prepare_gradients(
@@ -75,70 +91,95 @@ impl Descriptor {
             let array = Array2::from_elem(gradient_shape, 0.0);
             self.gradients = Some(array);
         }
+
+        self.sparse_gradients = None;
+        self.cell_gradients = None;
+        self.cell_gradients_indexes = None;
     }
 
-    pub fn densify(&mut self, variables: Vec<&str>) {
-        if variables.is_empty() {
-            return;
+    /// Allocate memory for the gradients with respect to the unit cell, for
+    /// all the environments already in this descriptor. This must be called
+    /// after `prepare` or `prepare_gradients`.
+    ///
+    /// The cell gradients indexes contain the environment indexes, together
+    /// with the `cell_vector` (i.e. a/b/c) and `spatial` (i.e x/y/z) indexes,
+    /// with the nine derivatives for each environment stored one after the
+    /// other.
+    pub fn prepare_cell_gradients(&mut self) {
+        let mut names = self.environments.names();
+        names.extend_from_slice(&["cell_vector", "spatial"]);
+        let mut indexes = IndexesBuilder::new(names);
+        for environment in &self.environments {
+            for cell_vector in 0..3_usize {
+                for spatial in 0..3_usize {
+                    let mut index = environment.to_vec();
+                    index.push(IndexValue::from(cell_vector));
+                    index.push(IndexValue::from(spatial));
+                    indexes.add(&index);
+                }
+            }
         }
+        let indexes = indexes.finish();
 
-        let new_environments = remove_from_indexes(&self.environments, &variables);
-        let new_gradients = self.gradients_indexes.as_ref().map(|indexes| {
-            let gradients = remove_from_indexes(indexes, &variables);
-
-            if gradients.new_features != new_environments.new_features {
-                let name = if variables.len() == 1 {
-                    variables[0].to_owned()
-                } else {
-                    format!("({})", variables.join(", "))
-                };
-                panic!("gradient indexes contains different values for {} than the environment indexes", name);
-            }
+        let shape = (indexes.count(), self.features.count());
+        self.cell_gradients = Some(Array2::zeros(shape));
+        self.cell_gradients_indexes = Some(indexes);
+    }
 
-            return gradients;
-        });
+    /// Convert the dense `gradients` of this descriptor to `sparse_gradients`,
+    /// removing the dense gradients. This does nothing if there are no dense
+    /// gradients.
+    pub fn sparsify_gradients(&mut self) {
+        if let (Some(gradients), Some(indexes)) = (self.gradients.take(), self.gradients_indexes.take()) {
+            self.sparse_gradients = Some(SparseGradients::from_dense(&self.environments, &indexes, &gradients));
+        }
+    }
 
-        // new feature indexes, add `variable` in the front
-        let mut feature_names = variables;
-        feature_names.extend(self.features.names());
-        let mut new_features = IndexesBuilder::new(feature_names);
-        for new in new_environments.new_features {
-            for feature in self.features.iter() {
-                let mut cleaned = new.clone();
-                cleaned.extend(feature);
-                new_features.add(&cleaned);
-            }
+    /// Move the given sample `variables` to the features: the new samples are
+    /// the old samples without these variables, and the new features contain
+    /// one block of the old features for each value taken by the variables.
+    /// Samples and gradients without a value for some of these blocks are
+    /// filled with zeros.
+    pub fn densify(&mut self, variables: Vec<&str>) -> Result<(), Error> {
+        if variables.is_empty() {
+            return Ok(());
         }
-        let new_features = new_features.finish();
-        let old_feature_size = self.features.count();
-
-        // copy values as needed
-        let mut new_values = Array2::zeros((new_environments.indexes.count(), new_features.count()));
-        for (new, old) in new_environments.mapping {
-            let value = self.values.slice(s![old, ..]);
-            let start = new.feature_block * old_feature_size;
-            let stop = (new.feature_block + 1) * old_feature_size;
-            new_values.slice_mut(s![new.environment, start..stop]).assign(&value);
+
+        if self.environments.names().iter().all(|name| variables.contains(name)) {
+            return Err(Error::InvalidParameter(format!(
+                "can not densify along all the variables of the environments: [{}]",
+                self.environments.names().join(", ")
+            )));
         }
 
-        if let Some(self_gradients) = &self.gradients {
-            let new_gradients = new_gradients.expect("missing densified gradients");
+        let new_environments = remove_from_indexes(&self.environments, &variables, None)?;
+        let new_gradients = self.gradients_indexes.as_ref().map(|indexes| {
+            remove_from_indexes(indexes, &variables, Some(&new_environments.new_features))
+        }).transpose()?;
+        let new_cell_gradients = self.cell_gradients_indexes.as_ref().map(|indexes| {
+            remove_from_indexes(indexes, &variables, Some(&new_environments.new_features))
+        }).transpose()?;
+
+        let sparse_gradients = self.sparse_gradients.as_ref().map(|sparse_gradients| {
+            densify_sparse(sparse_gradients, &new_environments, self.features.count())
+        });
 
-            let mut gradients = Array2::zeros((new_gradients.indexes.count(), new_features.count()));
-            for (new, old) in new_gradients.mapping {
-                let value = self_gradients.slice(s![old, ..]);
-                let start = new.feature_block * old_feature_size;
-                let stop = (new.feature_block + 1) * old_feature_size;
-                gradients.slice_mut(s![new.environment, start..stop]).assign(&value);
-            }
+        // all the feature blocks take their data from the same arrays
+        let block = DensifyBlock {
+            features: &self.features,
+            values: &self.values,
+            gradients: self.gradients.as_ref(),
+            cell_gradients: self.cell_gradients.as_ref(),
+        };
+        let blocks = vec![block; new_environments.new_features.len()];
 
-            self.gradients = Some(gradients);
-            self.gradients_indexes = Some(new_gradients.indexes);
-        }
+        let mut feature_names = variables;
+        feature_names.extend(self.features.names());
+        let mut densified = densify_blocks(feature_names, &blocks, new_environments, new_gradients, new_cell_gradients)?;
+        densified.sparse_gradients = sparse_gradients;
 
-        self.features = new_features;
-        self.environments = new_environments.indexes;
-        self.values = new_values;
+        *self = densified;
+        return Ok(());
     }
 }
 
@@ -157,39 +198,169 @@ fn resize_and_reset(array: &mut Array2<f64>, shape: (usize, usize)) {
 
 /// Representation of an environment/gradient index after a call to `densify`
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
-struct DensifiedIndex {
+pub(super) struct DensifiedIndex {
     /// Index of the new environment/gradient in the value/gradients array
-    environment: usize,
+    pub(super) environment: usize,
     /// Index of the feature **block** corresponding to the moved variable
-    feature_block: usize,
+    pub(super) feature_block: usize,
 }
 
 /// Results of removing a set of variables from Indexes
-struct RemovedResult {
+pub(super) struct RemovedResult {
     /// New Indexes, without the variables
-    indexes: Indexes,
+    pub(super) indexes: Indexes,
     /// Values taken by the variables in the original Index
     ///
     /// This needs to be a IndexSet to keep the same order as in the initial
     /// Indexes.
-    new_features: IndexSet<Vec<IndexValue>>,
-    /// Mapping from the updated index to the original position
-    mapping: BTreeMap<DensifiedIndex, usize>,
+    pub(super) new_features: IndexSet<Vec<IndexValue>>,
+    /// Mapping from the updated index to the original position, in the data
+    /// of the corresponding feature block
+    pub(super) mapping: BTreeMap<DensifiedIndex, usize>,
+}
+
+/// Data used to fill a single feature block in `densify_blocks`
+#[derive(Clone, Copy)]
+pub(super) struct DensifyBlock<'a> {
+    pub(super) features: &'a Indexes,
+    pub(super) values: &'a Array2<f64>,
+    pub(super) gradients: Option<&'a Array2<f64>>,
+    pub(super) cell_gradients: Option<&'a Array2<f64>>,
+}
+
+/// Create a dense descriptor from a set of feature `blocks`, one for each
+/// entry in `environments.new_features`. The data is copied according to the
+/// mapping in `environments`, `gradients` and `cell_gradients`; and the new
+/// features use the given `names` (the densified variables followed by the
+/// names of the blocks features).
+///
+/// `gradients` (resp. `cell_gradients`) must only be given if all the blocks
+/// contain gradients (resp. cell gradients).
+pub(super) fn densify_blocks(
+    names: Vec<&str>,
+    blocks: &[DensifyBlock<'_>],
+    environments: RemovedResult,
+    gradients: Option<RemovedResult>,
+    cell_gradients: Option<RemovedResult>,
+) -> Result<Descriptor, Error> {
+    debug_assert_eq!(blocks.len(), environments.new_features.len());
+    if names.iter().collect::<BTreeSet<_>>().len() != names.len() {
+        return Err(Error::InvalidParameter(format!(
+            "can not densify: the features would contain the same name multiple times: [{}]",
+            names.join(", ")
+        )));
+    }
+
+    // new feature indexes, with the densified variables in the front
+    let mut features = IndexesBuilder::new(names);
+    let mut features_offsets = Vec::with_capacity(blocks.len());
+    let mut n_features = 0;
+    for (new, block) in environments.new_features.iter().zip(blocks) {
+        features_offsets.push(n_features);
+        for feature in block.features {
+            let mut index = new.clone();
+            index.extend_from_slice(feature);
+            features.add(&index);
+        }
+        n_features += block.features.count();
+    }
+
+    let mut descriptor = Descriptor::new();
+
+    let values = blocks.iter().map(|block| block.values).collect::<Vec<_>>();
+    descriptor.values = densify_array(&values, &environments, &features_offsets, n_features);
+
+    if let Some(gradients) = gradients {
+        let arrays = blocks.iter()
+            .map(|block| block.gradients.expect("missing gradients in densified block"))
+            .collect::<Vec<_>>();
+        descriptor.gradients = Some(densify_array(&arrays, &gradients, &features_offsets, n_features));
+        descriptor.gradients_indexes = Some(gradients.indexes);
+    }
+
+    if let Some(cell_gradients) = cell_gradients {
+        let arrays = blocks.iter()
+            .map(|block| block.cell_gradients.expect("missing cell gradients in densified block"))
+            .collect::<Vec<_>>();
+        descriptor.cell_gradients = Some(densify_array(&arrays, &cell_gradients, &features_offsets, n_features));
+        descriptor.cell_gradients_indexes = Some(cell_gradients.indexes);
+    }
+
+    descriptor.environments = environments.indexes;
+    descriptor.features = features.finish();
+    return Ok(descriptor);
+}
+
+/// Copy the values from the `arrays` of each feature block to a new array
+/// with `n_features` columns, according to the mapping in `densified`. The
+/// features of block `i` start at `features_offsets[i]` in the new array.
+fn densify_array(
+    arrays: &[&Array2<f64>],
+    densified: &RemovedResult,
+    features_offsets: &[usize],
+    n_features: usize,
+) -> Array2<f64> {
+    let mut new_array = Array2::zeros((densified.indexes.count(), n_features));
+    for (new, &old) in &densified.mapping {
+        let array = arrays[new.feature_block];
+        let start = features_offsets[new.feature_block];
+        let stop = start + array.ncols();
+        new_array.slice_mut(s![new.environment, start..stop]).assign(&array.row(old));
+    }
+    return new_array;
+}
+
+/// Move the blocks in `sparse` gradients to the new samples and features
+/// according to the mapping in `densified`. Blocks which end up with the same
+/// new sample and atom are merged together.
+fn densify_sparse(sparse: &SparseGradients, densified: &RemovedResult, old_feature_size: usize) -> SparseGradients {
+    // there is exactly one entry in the mapping for each old sample
+    let mut new_samples = vec![None; densified.mapping.len()];
+    for (new, &old) in &densified.mapping {
+        new_samples[old] = Some(new);
+    }
+
+    let mut blocks = BTreeMap::new();
+    for block in sparse.iter() {
+        let new = new_samples[block.sample].expect("missing densified sample");
+        let start = new.feature_block * old_feature_size;
+        let (features, values) = blocks.entry((new.environment, block.atom)).or_insert_with(|| (Vec::new(), Vec::new()));
+        features.extend(block.features.iter().map(|feature| start + feature));
+        values.extend_from_slice(block.values);
+    }
+
+    let mut new_sparse = SparseGradients::new();
+    for ((sample, atom), (features, values)) in blocks {
+        let (features, values): (Vec<_>, Vec<_>) = features.into_iter()
+            .zip(values)
+            .sorted_by_key(|&(feature, _)| feature)
+            .unzip();
+        new_sparse.add_block(sample, atom, &features, &values);
+    }
+    return new_sparse;
 }
 
 /// Remove the given `variables` from the `indexes`, returning the updated
 /// `indexes` and a set of all the values taken by the removed variables.
-fn remove_from_indexes(indexes: &Indexes, variables: &[&str]) -> RemovedResult {
+///
+/// If `features` is given (for gradients indexes), the feature blocks are
+/// taken from it instead of being created from the values in `indexes`, and
+/// `indexes` must not contain values of the variables missing from
+/// `features`. Some of the `features` can be absent from the `indexes`, for
+/// example when there are no gradients for some samples.
+fn remove_from_indexes(
+    indexes: &Indexes,
+    variables: &[&str],
+    features: Option<&IndexSet<Vec<IndexValue>>>,
+) -> Result<RemovedResult, Error> {
+    let names = indexes.names();
     let variable_indexes = variables.iter()
         .map(|v| {
-            indexes.names()
-                .iter()
-                .position(|name| name == v)
-                .unwrap_or_else(|| panic!(
-                    "can not densify along '{}' which is not present in the environments: [{}]",
-                    v, indexes.names().join(", ")
-                ))
-        }).collect::<Vec<_>>();
+            names.iter().position(|name| name == v).ok_or_else(|| Error::InvalidParameter(format!(
+                "can not densify along '{}' which is not present in the environments: [{}]",
+                v, names.join(", ")
+            )))
+        }).collect::<Result<Vec<_>, _>>()?;
 
     let mut mapping = BTreeMap::new();
 
@@ -197,13 +368,18 @@ fn remove_from_indexes(indexes: &Indexes, variables: &[&str]) -> RemovedResult {
     // along the first index, we want to convert [[2, 3, 0], [1, 3, 0]]
     // to [[3, 0]].
     let mut new_indexes = IndexSet::new();
-    let mut new_features = IndexSet::new();
+    let mut new_features = features.cloned().unwrap_or_default();
     for (old, index) in indexes.iter().enumerate() {
-        let mut new_feature = Vec::new();
-        for &i in &variable_indexes {
-            new_feature.push(index[i]);
-        }
-        new_features.insert(new_feature.clone());
+        let new_feature = variable_indexes.iter().map(|&i| index[i]).collect::<Vec<_>>();
+        let feature_block = if features.is_some() {
+            new_features.get_index_of(&new_feature).ok_or_else(|| Error::InvalidParameter(format!(
+                "can not densify: gradients indexes contain [{}] for ({}), which is not present in the environments",
+                new_feature.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", "),
+                variables.join(", ")
+            )))?
+        } else {
+            new_features.insert_full(new_feature).0
+        };
 
         let mut new_index = index.to_vec();
         // sort and reverse the indexes to ensure the all the calls to `remove`
@@ -211,19 +387,16 @@ fn remove_from_indexes(indexes: &Indexes, variables: &[&str]) -> RemovedResult {
         for &i in variable_indexes.iter().sorted().rev() {
             new_index.remove(i);
         }
-        new_indexes.insert(new_index);
+        let (environment, _) = new_indexes.insert_full(new_index);
 
-        let densified = DensifiedIndex{
-            environment: new_indexes.len() - 1,
-            feature_block: new_features.iter()
-                .position(|feature| feature == &new_feature)
-                .expect("missing feature that was just added"),
+        let densified = DensifiedIndex {
+            environment: environment,
+            feature_block: feature_block,
         };
         mapping.insert(densified, old);
     }
 
-    let names = indexes.names()
-        .iter()
+    let names = names.iter()
         .filter(|&name| !variables.contains(name))
         .cloned()
         .collect();
@@ -232,11 +405,11 @@ fn remove_from_indexes(indexes: &Indexes, variables: &[&str]) -> RemovedResult {
         builder.add(&env);
     }
 
-    return RemovedResult {
+    return Ok(RemovedResult {
         indexes: builder.finish(),
         new_features: new_features,
         mapping: mapping,
-    };
+    });
 }
 
 
@@ -258,7 +431,7 @@ mod tests {
     /// Convenience macro to create IndexValue
     macro_rules! v {
         ($value: expr) => {
-        crate::descriptor::indexes::IndexValue::from($value as f64)
+        crate::descriptor::indexes::IndexValue::from($value as i64)
         };
     }
 
@@ -268,7 +441,7 @@ mod tests {
 
         let mut systems = test_systems(&["water", "CH"]);
         let features = dummy_features();
-        let environments = StructureSpeciesEnvironment.indexes(&mut systems.get());
+        let environments = StructureSpeciesEnvironment.indexes(&mut systems.get()).unwrap();
         descriptor.prepare(environments, features);
 
 
@@ -289,7 +462,7 @@ mod tests {
 
         let mut systems = test_systems(&["water", "CH"]);
         let features = dummy_features();
-        let (environments, gradients) = StructureSpeciesEnvironment.with_gradients(&mut systems.get());
+        let (environments, gradients) = StructureSpeciesEnvironment.with_gradients(&mut systems.get()).unwrap();
         descriptor.prepare_gradients(environments, gradients.unwrap(), features);
 
         let gradients = descriptor.gradients.unwrap();
@@ -324,7 +497,7 @@ mod tests {
 
         let mut systems = test_systems(&["water", "CH"]);
         let features = dummy_features();
-        let (environments, gradients) = StructureSpeciesEnvironment.with_gradients(&mut systems.get());
+        let (environments, gradients) = StructureSpeciesEnvironment.with_gradients(&mut systems.get()).unwrap();
         descriptor.prepare_gradients(environments, gradients.unwrap(), features);
 
         descriptor.values.assign(&array![
@@ -344,7 +517,7 @@ mod tests {
         ]);
 
         // where the magic happens
-        descriptor.densify(vec!["species"]);
+        descriptor.densify(vec!["species"]).unwrap();
 
         assert_eq!(descriptor.values.shape(), [2, 9]);
         assert_eq!(descriptor.environments.names(), ["structure"]);
@@ -404,7 +577,7 @@ mod tests {
 
         let mut systems = test_systems(&["water", "CH"]);
         let features = dummy_features();
-        let (environments, gradients) = AtomSpeciesEnvironment::new(3.0).with_gradients(&mut systems.get());
+        let (environments, gradients) = AtomSpeciesEnvironment::new(3.0).with_gradients(&mut systems.get()).unwrap();
         descriptor.prepare_gradients(environments, gradients.unwrap(), features);
 
         descriptor.values.assign(&array![
@@ -430,7 +603,7 @@ mod tests {
         ]);
 
         // where the magic happens
-        descriptor.densify(vec!["species_center", "species_neighbor"]);
+        descriptor.densify(vec!["species_center", "species_neighbor"]).unwrap();
 
         assert_eq!(descriptor.values.shape(), [5, 15]);
         assert_eq!(descriptor.environments.names(), ["structure", "center"]);
@@ -517,4 +690,157 @@ mod tests {
             [0.0, 0.0, 0.0,     0.0, 0.0, 0.0,      0.0, 0.0, 0.0,    0.0, 0.0, 0.0,   -22.0, -23.0, -24.0],
         ]);
     }
+
+    #[test]
+    fn densify_block_without_gradients() {
+        let mut descriptor = Descriptor::new();
+
+        let mut systems = test_systems(&["water", "CH"]);
+        let features = dummy_features();
+        let (environments, gradients) = StructureSpeciesEnvironment.with_gradients(&mut systems.get()).unwrap();
+        // remove the gradients of the O sample
+        let gradients = gradients.unwrap().filter(&["species"], |v| v[0] != v!(123456)).unwrap();
+        descriptor.prepare_gradients(environments, gradients, features);
+
+        let gradients = descriptor.gradients.as_mut().unwrap();
+        for (i, mut row) in gradients.outer_iter_mut().enumerate() {
+            row.fill(i as f64 + 1.0);
+        }
+
+        descriptor.densify(vec!["species"]).unwrap();
+        assert_eq!(descriptor.features.count(), 9);
+
+        let gradients_indexes = descriptor.gradients_indexes.as_ref().unwrap();
+        assert_eq!(gradients_indexes.names(), ["structure", "atom", "spatial"]);
+        assert_eq!(gradients_indexes.count(), 12);
+
+        let gradients = descriptor.gradients.as_ref().unwrap();
+        // H in water
+        assert_eq!(gradients.row(0).to_vec(), [1.0, 1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        // C in CH
+        assert_eq!(gradients_indexes[9], [v!(1), v!(1), v!(0)]);
+        assert_eq!(gradients.row(9).to_vec(), [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 10.0, 10.0, 10.0]);
+    }
+
+    #[test]
+    fn invalid_densify() {
+        let mut descriptor = Descriptor::new();
+
+        let mut systems = test_systems(&["water"]);
+        let (environments, gradients) = StructureSpeciesEnvironment.with_gradients(&mut systems.get()).unwrap();
+        descriptor.prepare_gradients(environments, gradients.unwrap(), dummy_features());
+
+        let error = descriptor.densify(vec!["not_there"]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: can not densify along 'not_there' which is not present in the environments: [structure, species]"
+        );
+
+        let error = descriptor.densify(vec!["structure", "species"]).unwrap_err();
+        assert!(error.to_string().contains("can not densify"));
+    }
+
+    #[test]
+    fn densify_sparse_gradients() {
+        let mut descriptor = Descriptor::new();
+
+        let mut systems = test_systems(&["water", "CH"]);
+        let features = dummy_features();
+        let (environments, gradients) = AtomSpeciesEnvironment::new(3.0).with_gradients(&mut systems.get()).unwrap();
+        descriptor.prepare_gradients(environments, gradients.unwrap(), features);
+
+        let gradients = descriptor.gradients.as_mut().unwrap();
+        for (i, mut row) in gradients.outer_iter_mut().enumerate() {
+            row.fill(i as f64 + 1.0);
+        }
+
+        let mut sparse = Descriptor::new();
+        sparse.prepare(descriptor.environments.clone(), descriptor.features.clone());
+        sparse.sparse_gradients = Some(SparseGradients::from_dense(
+            &descriptor.environments,
+            descriptor.gradients_indexes.as_ref().unwrap(),
+            descriptor.gradients.as_ref().unwrap(),
+        ));
+
+        descriptor.densify(vec!["species_neighbor"]).unwrap();
+        sparse.densify(vec!["species_neighbor"]).unwrap();
+        assert_eq!(sparse.environments, descriptor.environments);
+        assert_eq!(sparse.features, descriptor.features);
+
+        let expected = SparseGradients::from_dense(
+            &descriptor.environments,
+            descriptor.gradients_indexes.as_ref().unwrap(),
+            descriptor.gradients.as_ref().unwrap(),
+        );
+        let sorted_blocks = |gradients: &SparseGradients| {
+            gradients.iter()
+                .map(|block| (block.sample, block.atom, block.features.to_vec(), block.values.to_vec()))
+                .sorted_by_key(|block| (block.0, block.1))
+                .collect::<Vec<_>>()
+        };
+
+        let sparse_gradients = sparse.sparse_gradients.as_ref().unwrap();
+        assert_eq!(sparse_gradients.count(), expected.count());
+        assert_eq!(sorted_blocks(sparse_gradients), sorted_blocks(&expected));
+    }
+
+    #[test]
+    fn prepare_cell_gradients() {
+        let mut descriptor = Descriptor::new();
+
+        let mut systems = test_systems(&["water", "CH"]);
+        let features = dummy_features();
+        let environments = StructureSpeciesEnvironment.indexes(&mut systems.get()).unwrap();
+        descriptor.prepare(environments, features);
+        assert!(descriptor.cell_gradients.is_none());
+
+        descriptor.prepare_cell_gradients();
+        let cell_gradients = descriptor.cell_gradients.as_ref().unwrap();
+        assert_eq!(cell_gradients.shape(), [36, 3]);
+
+        let indexes = descriptor.cell_gradients_indexes.as_ref().unwrap();
+        assert_eq!(indexes.names(), ["structure", "species", "cell_vector", "spatial"]);
+        assert_eq!(indexes[0], [v!(0), v!(1), v!(0), v!(0)]);
+        assert_eq!(indexes[1], [v!(0), v!(1), v!(0), v!(1)]);
+        assert_eq!(indexes[5], [v!(0), v!(1), v!(1), v!(2)]);
+        assert_eq!(indexes[9], [v!(0), v!(123456), v!(0), v!(0)]);
+        assert_eq!(indexes[35], [v!(1), v!(6), v!(2), v!(2)]);
+
+        // preparing the descriptor again removes the cell gradients
+        let environments = StructureSpeciesEnvironment.indexes(&mut systems.get()).unwrap();
+        descriptor.prepare(environments, dummy_features());
+        assert!(descriptor.cell_gradients.is_none());
+        assert!(descriptor.cell_gradients_indexes.is_none());
+    }
+
+    #[test]
+    fn densify_cell_gradients() {
+        let mut descriptor = Descriptor::new();
+
+        let mut systems = test_systems(&["water", "CH"]);
+        let features = dummy_features();
+        let environments = StructureSpeciesEnvironment.indexes(&mut systems.get()).unwrap();
+        descriptor.prepare(environments, features);
+        descriptor.prepare_cell_gradients();
+
+        let cell_gradients = descriptor.cell_gradients.as_mut().unwrap();
+        for (i, mut row) in cell_gradients.outer_iter_mut().enumerate() {
+            row.fill(i as f64);
+        }
+
+        descriptor.densify(vec!["species"]).unwrap();
+
+        let indexes = descriptor.cell_gradients_indexes.as_ref().unwrap();
+        assert_eq!(indexes.names(), ["structure", "cell_vector", "spatial"]);
+        assert_eq!(indexes.count(), 18);
+        assert_eq!(indexes[0], [v!(0), v!(0), v!(0)]);
+        assert_eq!(indexes[17], [v!(1), v!(2), v!(2)]);
+
+        let cell_gradients = descriptor.cell_gradients.as_ref().unwrap();
+        assert_eq!(cell_gradients.shape(), [18, 9]);
+        // H in water
+        assert_eq!(cell_gradients.row(4).to_vec(), [4.0, 4.0, 4.0, 13.0, 13.0, 13.0, 0.0, 0.0, 0.0]);
+        // C in CH
+        assert_eq!(cell_gradients.row(10).to_vec(), [19.0, 19.0, 19.0, 0.0, 0.0, 0.0, 28.0, 28.0, 28.0]);
+    }
 }
@@ -1,6 +1,7 @@
 use indexmap::IndexSet;
 
 use crate::system::System;
+use crate::Error;
 use super::{Indexes, IndexesBuilder, EnvironmentIndexes, IndexValue};
 
 /// `StructureEnvironment` is used to represents environments corresponding to
@@ -19,28 +20,28 @@ impl EnvironmentIndexes for StructureEnvironment {
         vec!["structure"]
     }
 
-    fn indexes(&self, systems: &mut [&mut dyn System]) -> Indexes {
+    fn indexes(&self, systems: &mut [&mut dyn System]) -> Result<Indexes, Error> {
         let mut indexes = IndexesBuilder::new(self.names());
         for system in 0..systems.len() {
             indexes.add(&[IndexValue::from(system)]);
         }
-        return indexes.finish();
+        return Ok(indexes.finish());
     }
 
-    fn gradients_for(&self, systems: &mut [&mut dyn System], samples: &Indexes) -> Option<Indexes> {
+    fn gradients_for(&self, systems: &mut [&mut dyn System], samples: &Indexes) -> Result<Option<Indexes>, Error> {
         assert_eq!(samples.names(), self.names());
 
         let mut gradients = IndexesBuilder::new(vec!["structure", "atom", "spatial"]);
         for value in samples.iter() {
             let system = value[0];
-            for atom in 0..systems[system.usize()].size() {
+            for atom in 0..systems[system.usize()].size()? {
                 gradients.add(&[system, IndexValue::from(atom), IndexValue::from(0_usize)]);
                 gradients.add(&[system, IndexValue::from(atom), IndexValue::from(1_usize)]);
                 gradients.add(&[system, IndexValue::from(atom), IndexValue::from(2_usize)]);
             }
         }
 
-        Some(gradients.finish())
+        return Ok(Some(gradients.finish()));
     }
 }
 
@@ -73,17 +74,17 @@ impl EnvironmentIndexes for AtomEnvironment {
         vec!["structure", "center"]
     }
 
-    fn indexes(&self, systems: &mut [&mut dyn System]) -> Indexes {
+    fn indexes(&self, systems: &mut [&mut dyn System]) -> Result<Indexes, Error> {
         let mut indexes = IndexesBuilder::new(self.names());
         for (i_system, system) in systems.iter().enumerate() {
-            for center in 0..system.size() {
+            for center in 0..system.size()? {
                 indexes.add(&[IndexValue::from(i_system), IndexValue::from(center)]);
             }
         }
-        return indexes.finish();
+        return Ok(indexes.finish());
     }
 
-    fn gradients_for(&self, systems: &mut [&mut dyn System], samples: &Indexes) -> Option<Indexes> {
+    fn gradients_for(&self, systems: &mut [&mut dyn System], samples: &Indexes) -> Result<Option<Indexes>, Error> {
         assert_eq!(samples.names(), self.names());
 
         // We need IndexSet to yield the indexes in the right order, i.e. the
@@ -93,9 +94,15 @@ impl EnvironmentIndexes for AtomEnvironment {
             let i_system = requested[0];
             let center = requested[1].usize();
             let system = &mut *systems[i_system.usize()];
-            system.compute_neighbors(self.cutoff);
+            system.compute_neighbors(self.cutoff)?;
+
+            for pair in system.pairs_containing(center)? {
+                if pair.first == pair.second {
+                    // periodic self-images move together with the center, and
+                    // do not contribute to the gradients
+                    continue;
+                }
 
-            for pair in system.pairs_containing(center) {
                 if pair.first == center {
                     indexes.insert((i_system, pair.first, pair.second));
                 } else if pair.second == center {
@@ -113,7 +120,7 @@ impl EnvironmentIndexes for AtomEnvironment {
             gradients.add(&[structure, atom, neighbor, IndexValue::from(2_usize)]);
         }
 
-        return Some(gradients.finish());
+        return Ok(Some(gradients.finish()));
     }
 }
 
@@ -126,14 +133,14 @@ mod tests {
     /// Convenience macro to create IndexValue
     macro_rules! v {
         ($value: expr) => {
-            crate::descriptor::indexes::IndexValue::from($value as f64)
+            crate::descriptor::indexes::IndexValue::from($value as i64)
         };
     }
 
     #[test]
     fn structure() {
         let mut systems = test_systems(&["methane", "methane", "water"]);
-        let indexes = StructureEnvironment.indexes(&mut systems.get());
+        let indexes = StructureEnvironment.indexes(&mut systems.get()).unwrap();
         assert_eq!(indexes.count(), 3);
         assert_eq!(indexes.names(), &["structure"]);
         assert_eq!(indexes.iter().collect::<Vec<_>>(), vec![&[v!(0)], &[v!(1)], &[v!(2)]]);
@@ -143,7 +150,7 @@ mod tests {
     fn structure_gradient() {
         let mut systems = test_systems(&["methane", "water"]);
 
-        let (_, gradients) = StructureEnvironment.with_gradients(&mut systems.get());
+        let (_, gradients) = StructureEnvironment.with_gradients(&mut systems.get()).unwrap();
         let gradients = gradients.unwrap();
         assert_eq!(gradients.count(), 24);
         assert_eq!(gradients.names(), &["structure", "atom", "spatial"]);
@@ -168,7 +175,7 @@ mod tests {
         indexes.add(&[v!(0)]);
 
         let mut systems = test_systems(&["water", "methane", "water", "methane"]);
-        let gradients = StructureEnvironment.gradients_for(&mut systems.get(), &indexes.finish());
+        let gradients = StructureEnvironment.gradients_for(&mut systems.get(), &indexes.finish()).unwrap();
         let gradients = gradients.unwrap();
 
         assert_eq!(gradients.names(), &["structure", "atom", "spatial"]);
@@ -188,7 +195,7 @@ mod tests {
     fn atoms() {
         let mut systems = test_systems(&["methane", "water"]);
         let strategy = AtomEnvironment { cutoff: 2.0 };
-        let indexes = strategy.indexes(&mut systems.get());
+        let indexes = strategy.indexes(&mut systems.get()).unwrap();
         assert_eq!(indexes.count(), 8);
         assert_eq!(indexes.names(), &["structure", "center"]);
         assert_eq!(indexes.iter().collect::<Vec<_>>(), vec![
@@ -201,7 +208,7 @@ mod tests {
     fn atom_gradients() {
         let mut systems = test_systems(&["methane"]);
         let strategy = AtomEnvironment { cutoff: 1.5 };
-        let (_, gradients) = strategy.with_gradients(&mut systems.get());
+        let (_, gradients) = strategy.with_gradients(&mut systems.get()).unwrap();
         let gradients = gradients.unwrap();
 
         assert_eq!(gradients.count(), 24);
@@ -252,7 +259,7 @@ mod tests {
 
         let mut systems = test_systems(&["methane"]);
         let strategy = AtomEnvironment { cutoff: 1.5 };
-        let gradients = strategy.gradients_for(&mut systems.get(), &indexes.finish());
+        let gradients = strategy.gradients_for(&mut systems.get(), &indexes.finish()).unwrap();
         let gradients = gradients.unwrap();
 
         assert_eq!(gradients.names(), &["structure", "center", "neighbor", "spatial"]);
@@ -1,102 +1,235 @@
 use std::ffi::CString;
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
+use std::collections::hash_map::{DefaultHasher, Entry};
+use std::hash::{Hash, Hasher};
+use std::convert::TryFrom;
 
 use crate::system::System;
+use crate::Error;
 
-/// Biggest integer value such that all integer values up to it can be stored in
-/// a f64 value.
-const MAX_SAFE_INTEGER: i64 = 9007199254740991;
-
-/// Smallest integer value such that all integers value between it and zero can
-/// be stored in a f64 value.
-const MIN_SAFE_INTEGER: i64 = -9007199254740991;
+/// Type of the values in a single index (i.e. a single column of `Indexes`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[allow(clippy::module_name_repetitions)]
+pub enum IndexType {
+    /// Signed 64-bit integers
+    Int,
+    /// 64-bit floating point values
+    Float,
+}
 
+impl std::fmt::Display for IndexType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IndexType::Int => write!(f, "integer"),
+            IndexType::Float => write!(f, "float"),
+        }
+    }
+}
 
-#[derive(Clone, Copy, PartialEq)]
+/// A single value inside `Indexes`. Values are either integers or floating
+/// point numbers, depending on the type of the corresponding index.
+///
+/// Integer and floating point values compare equal if they represent the same
+/// number, so code creating indexes from `f64` keeps working with integer
+/// indexes. The layout of this enum is part of the C API, and must match
+/// `rascal_index_value_t`.
+#[repr(C)]
+#[derive(Clone, Copy)]
 #[allow(clippy::module_name_repetitions)]
-pub struct IndexValue(f64);
+pub enum IndexValue {
+    /// Integer value, used for indexes of type `IndexType::Int`
+    Int(i64),
+    /// Floating point value, used for indexes of type `IndexType::Float`
+    Float(f64),
+}
+
+/// Get the integer corresponding to `value`, if `value` is an integer which
+/// can be represented as an `i64`
+#[allow(clippy::cast_possible_truncation)]
+fn f64_to_i64(value: f64) -> Option<i64> {
+    // i64::MIN and -i64::MIN are exactly representable as f64
+    if value % 1.0 == 0.0 && value >= i64::MIN as f64 && value < -(i64::MIN as f64) {
+        Some(value as i64)
+    } else {
+        None
+    }
+}
 
-#[allow(clippy::derive_hash_xor_eq)]
 impl std::hash::Hash for IndexValue {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.0.to_le_bytes().hash(state);
+        // hash floating point values representing integers as the
+        // corresponding integer, to be consistent with `PartialEq`
+        match *self {
+            IndexValue::Int(value) => value.hash(state),
+            IndexValue::Float(value) => match f64_to_i64(value) {
+                Some(value) => value.hash(state),
+                None => value.to_le_bytes().hash(state),
+            },
+        }
+    }
+}
+
+impl PartialEq for IndexValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
     }
 }
 
+impl Eq for IndexValue {}
+
 // This is fine since we can not construct a NaN IndexValue
 impl Ord for IndexValue {
+    #[allow(clippy::cast_precision_loss)]
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.0.partial_cmp(&other.0).expect("a NaN slipped through!")
+        match (*self, *other) {
+            (IndexValue::Int(a), IndexValue::Int(b)) => a.cmp(&b),
+            (IndexValue::Float(a), IndexValue::Float(b)) => {
+                a.partial_cmp(&b).expect("a NaN slipped through!")
+            }
+            (IndexValue::Int(a), IndexValue::Float(b)) => match f64_to_i64(b) {
+                Some(b) => a.cmp(&b),
+                // b is either too large to be an i64, or not an integer
+                None if b >= -(i64::MIN as f64) => std::cmp::Ordering::Less,
+                None if b < i64::MIN as f64 => std::cmp::Ordering::Greater,
+                None => (a as f64).partial_cmp(&b).expect("a NaN slipped through!"),
+            },
+            (IndexValue::Float(_), IndexValue::Int(_)) => other.cmp(self).reverse(),
+        }
     }
 }
 
 impl PartialOrd for IndexValue {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(&other))
+        Some(self.cmp(other))
     }
 }
 
-impl Eq for IndexValue {}
-
 impl std::fmt::Debug for IndexValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        std::fmt::Display::fmt(self, f)
     }
 }
 
 impl std::fmt::Display for IndexValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        match self {
+            IndexValue::Int(value) => write!(f, "{}", value),
+            IndexValue::Float(value) => write!(f, "{}", value),
+        }
     }
 }
 
 impl From<f64> for IndexValue {
     fn from(value: f64) -> IndexValue {
         assert!(!value.is_nan());
-        IndexValue(value)
+        IndexValue::Float(value)
+    }
+}
+
+impl From<i64> for IndexValue {
+    fn from(value: i64) -> IndexValue {
+        IndexValue::Int(value)
+    }
+}
+
+impl From<i32> for IndexValue {
+    fn from(value: i32) -> IndexValue {
+        IndexValue::Int(i64::from(value))
     }
 }
 
 impl From<usize> for IndexValue {
     fn from(value: usize) -> IndexValue {
-        assert!(value < MAX_SAFE_INTEGER as usize);
-        IndexValue(value as f64)
+        IndexValue::Int(i64::try_from(value).expect("index value is too large for i64"))
     }
 }
 
 impl From<isize> for IndexValue {
     fn from(value: isize) -> IndexValue {
-        assert!(MIN_SAFE_INTEGER < (value as i64) && (value as i64) < MAX_SAFE_INTEGER);
-        IndexValue(value as f64)
+        IndexValue::Int(value as i64)
     }
 }
 
 impl IndexValue {
+    /// Get the type of this value
+    pub fn index_type(self) -> IndexType {
+        match self {
+            IndexValue::Int(_) => IndexType::Int,
+            IndexValue::Float(_) => IndexType::Float,
+        }
+    }
+
+    /// Convert this value to the given `index_type`, returning `None` if the
+    /// value can not be exactly represented with this type.
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+    pub fn cast(self, index_type: IndexType) -> Option<IndexValue> {
+        match (self, index_type) {
+            (IndexValue::Int(_), IndexType::Int) |
+            (IndexValue::Float(_), IndexType::Float) => Some(self),
+            (IndexValue::Float(value), IndexType::Int) => f64_to_i64(value).map(IndexValue::Int),
+            (IndexValue::Int(value), IndexType::Float) => {
+                let float = value as f64;
+                if f64_to_i64(float) == Some(value) {
+                    Some(IndexValue::Float(float))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Get this value as a `f64`. Large integer values might be rounded.
+    #[allow(clippy::cast_precision_loss)]
     pub fn f64(self) -> f64 {
-        self.0
+        match self {
+            IndexValue::Int(value) => value as f64,
+            IndexValue::Float(value) => value,
+        }
     }
 
-    #[allow(clippy::cast_sign_loss)]
+    /// Get this value as an `i64`. Floating point values must represent an
+    /// integer.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn i64(self) -> i64 {
+        match self {
+            IndexValue::Int(value) => value,
+            IndexValue::Float(value) => {
+                debug_assert!(f64_to_i64(value).is_some());
+                value as i64
+            }
+        }
+    }
+
+    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
     pub fn usize(self) -> usize {
-        debug_assert!(self.0 >= 0.0 && self.0 < MAX_SAFE_INTEGER as f64 && self.0 % 1.0 == 0.0);
-        self.0 as usize
+        let value = self.i64();
+        debug_assert!(value >= 0);
+        value as usize
     }
 
+    #[allow(clippy::cast_possible_truncation)]
     pub fn isize(self) -> isize {
-        debug_assert!((MIN_SAFE_INTEGER as f64) < self.0 && self.0 < MAX_SAFE_INTEGER as f64 && self.0 % 1.0 == 0.0);
-        self.0 as isize
+        self.i64() as isize
     }
 }
 
 pub struct IndexesBuilder {
     /// Names of the indexes
     names: Vec<String>,
+    /// Types of the indexes, `None` until the types are known, either from
+    /// `IndexesBuilder::with_types` or from the first added entry
+    types: Option<Vec<IndexType>>,
     /// Values of the indexes, as a linearized 2D array in row-major order
     values: Vec<IndexValue>,
+    /// Position of each entry in `values`, used to check for duplicated
+    /// entries in `add`
+    positions: Positions,
 }
 
 impl IndexesBuilder {
-    /// Create a new empty `IndexesBuilder` with the given `names`
+    /// Create a new empty `IndexesBuilder` with the given `names`. The types
+    /// of the indexes are taken from the first entry added to the builder,
+    /// and default to `IndexType::Int` if no entry is added.
     pub fn new(names: Vec<&str>) -> IndexesBuilder {
         for name in &names {
             if !is_valid_ident(name) {
@@ -110,15 +243,37 @@ impl IndexesBuilder {
 
         IndexesBuilder {
             names: names.into_iter().map(|s| s.into()).collect(),
+            types: None,
             values: Vec::new(),
+            positions: Positions::default(),
         }
     }
 
+    /// Create a new empty `IndexesBuilder` with the given `names` and
+    /// `types`. Values added to this builder are converted to the
+    /// corresponding type.
+    pub fn with_types(names: Vec<&str>, types: Vec<IndexType>) -> IndexesBuilder {
+        assert_eq!(
+            names.len(), types.len(),
+            "got {} indexes names but {} indexes types", names.len(), types.len()
+        );
+
+        let mut builder = IndexesBuilder::new(names);
+        builder.types = Some(types);
+        return builder;
+    }
+
     /// Get the number of indexes in a single value
     pub fn size(&self) -> usize {
         self.names.len()
     }
 
+    /// Check whether an entry with the given `values` was already added to
+    /// this builder
+    pub fn contains(&self, values: &[IndexValue]) -> bool {
+        self.positions.get(&self.values, self.size(), values).is_some()
+    }
+
     /// Add a single entry with the given `values` for this set of indexes
     pub fn add(&mut self, values: &[IndexValue]) {
         assert_eq!(
@@ -126,29 +281,42 @@ impl IndexesBuilder {
             "wrong size for added index: got {}, but expected {}", values.len(), self.size()
         );
 
-        for chunk in self.values.chunks_exact(self.size()) {
-            if chunk == values {
-                panic!(
-                    "can not have the same index value multiple time: [{}] is already present",
-                    values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ")
-                );
-            }
+        let types = self.types.get_or_insert_with(|| {
+            values.iter().map(|v| v.index_type()).collect()
+        });
+
+        let values = values.iter().zip(types.iter()).zip(&self.names)
+            .map(|((&value, &index_type), name)| value.cast(index_type).unwrap_or_else(|| panic!(
+                "can not convert {} to {} for the '{}' index", value, index_type, name
+            )))
+            .collect::<Vec<_>>();
+
+        if self.contains(&values) {
+            panic!(
+                "can not have the same index value multiple time: [{}] is already present",
+                values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ")
+            );
         }
 
-        self.values.extend(values);
+        self.positions.push(&values);
+        self.values.extend_from_slice(&values);
     }
 
     pub fn finish(self) -> Indexes {
+        let size = self.names.len();
         Indexes {
             names: self.names.into_iter()
                 .map(|s| CString::new(s).expect("invalid C string"))
                 .collect(),
+            types: self.types.unwrap_or_else(|| vec![IndexType::Int; size]),
             values: self.values,
+            positions: self.positions,
         }
     }
 }
 
-fn is_valid_ident(name: &str) -> bool {
+/// Check if `name` can be used as the name of an index
+pub(crate) fn is_valid_ident(name: &str) -> bool {
     if name.is_empty() {
         return false;
     }
@@ -166,13 +334,24 @@ fn is_valid_ident(name: &str) -> bool {
     return true;
 }
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone)]
 pub struct Indexes {
     /// Names of the indexes, stored as C strings for easier integration
     /// with the C API
     names: Vec<CString>,
+    /// Types of the indexes
+    types: Vec<IndexType>,
     /// Values of the indexes, as a linearized 2D array in row-major order
     values: Vec<IndexValue>,
+    /// Position of each entry in `values`, for fast lookup of entries
+    positions: Positions,
+}
+
+impl PartialEq for Indexes {
+    fn eq(&self, other: &Indexes) -> bool {
+        // `positions` is fully determined by `values`
+        self.names == other.names && self.types == other.types && self.values == other.values
+    }
 }
 
 impl std::fmt::Debug for Indexes {
@@ -184,10 +363,9 @@ impl std::fmt::Debug for Indexes {
         for values in self {
             write!(f, "    ")?;
             for (value, width) in values.iter().zip(&widths) {
-                if value.f64() % 1.0 == 0.0 {
-                    write!(f, "{:^width$}  ", value.isize(), width=width)?;
-                } else {
-                    write!(f, "{:.width$}  ", value.f64(), width=width - 2)?;
+                match value {
+                    IndexValue::Int(value) => write!(f, "{:^width$}  ", value, width=width)?,
+                    IndexValue::Float(value) => write!(f, "{:.width$}  ", value, width=width - 2)?,
                 }
             }
             writeln!(f)?;
@@ -209,6 +387,11 @@ impl Indexes {
         self.names.iter().map(|s| s.to_str().expect("invalid UTF8")).collect()
     }
 
+    /// Types of the indexes
+    pub fn types(&self) -> &[IndexType] {
+        &self.types
+    }
+
     /// Names of the indexes as C-compatible (null terminated) strings
     pub fn c_names(&self) -> &[CString] {
         &self.names
@@ -223,8 +406,8 @@ impl Indexes {
         }
     }
 
-    pub fn iter(&self) -> Iter {
-        debug_assert!(self.values.len() % self.names.len() == 0);
+    pub fn iter(&self) -> Iter<'_> {
+        debug_assert!(self.names.is_empty() || self.values.len() % self.names.len() == 0);
         return Iter {
             size: self.names.len(),
             values: &self.values
@@ -238,17 +421,201 @@ impl Indexes {
 
     /// Get the position of the given value on this set of indexes, or None.
     pub fn position(&self, value: &[IndexValue]) -> Option<usize> {
-        if value.len() != self.size() {
-            return None;
+        self.positions.get(&self.values, self.size(), value)
+    }
+
+    /// Get the position of the index with the given `name`, or an error if
+    /// there is no index with this name.
+    fn name_position(&self, name: &str) -> Result<usize, Error> {
+        self.names().iter().position(|&n| n == name).ok_or_else(|| Error::InvalidParameter(format!(
+            "there is no '{}' index in these indexes, available indexes are [{}]",
+            name, self.names().join(", ")
+        )))
+    }
+
+    /// Check that `self` and `other` can be used together in the set
+    /// `operation`, i.e. that they have the same names and types
+    fn check_compatible(&self, other: &Indexes, operation: &str) -> Result<(), Error> {
+        if self.names != other.names {
+            return Err(Error::InvalidParameter(format!(
+                "can not compute the {} of indexes with different names: [{}] and [{}]",
+                operation, self.names().join(", "), other.names().join(", ")
+            )));
+        }
+
+        if self.types != other.types {
+            return Err(Error::InvalidParameter(format!(
+                "can not compute the {} of indexes with different types: [{}] and [{}]",
+                operation,
+                self.types.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(", "),
+                other.types.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(", "),
+            )));
+        }
+
+        return Ok(());
+    }
+
+    /// Create new empty indexes with the same names and types as `self`
+    fn empty_builder(&self) -> IndexesBuilder {
+        IndexesBuilder::with_types(self.names(), self.types.clone())
+    }
+
+    /// Get the union of `self` and `other`, containing all entries in `self`
+    /// followed by the entries of `other` which are not in `self`. Both
+    /// indexes must have the same names and types.
+    pub fn union(&self, other: &Indexes) -> Result<Indexes, Error> {
+        self.check_compatible(other, "union")?;
+
+        let mut builder = self.empty_builder();
+        for value in self.iter().chain(other.iter().filter(|v| !self.contains(v))) {
+            builder.add(value);
+        }
+
+        return Ok(builder.finish());
+    }
+
+    /// Get the intersection of `self` and `other`, containing the entries of
+    /// `self` which are also in `other`, in the same order as in `self`. Both
+    /// indexes must have the same names and types.
+    pub fn intersection(&self, other: &Indexes) -> Result<Indexes, Error> {
+        self.check_compatible(other, "intersection")?;
+        return Ok(self.filter_entries(|value| other.contains(value)));
+    }
+
+    /// Get the difference of `self` and `other`, containing the entries of
+    /// `self` which are not in `other`, in the same order as in `self`. Both
+    /// indexes must have the same names and types.
+    pub fn difference(&self, other: &Indexes) -> Result<Indexes, Error> {
+        self.check_compatible(other, "difference")?;
+        return Ok(self.filter_entries(|value| !other.contains(value)));
+    }
+
+    /// Get the entries of `self` for which `predicate` returns `true`
+    fn filter_entries<F>(&self, mut predicate: F) -> Indexes where F: FnMut(&[IndexValue]) -> bool {
+        let mut builder = self.empty_builder();
+        for value in self.iter().filter(|v| predicate(v)) {
+            builder.add(value);
         }
+        return builder.finish();
+    }
+
+    /// Get the entries of `self` for which `predicate` returns `true`. The
+    /// predicate is called with the values of the indexes with the given
+    /// `names`, in the same order as `names`.
+    ///
+    /// ```
+    /// # use rascaline::descriptor::{IndexesBuilder, IndexValue};
+    /// let mut builder = IndexesBuilder::new(vec!["center", "species_center"]);
+    /// builder.add(&[IndexValue::from(0), IndexValue::from(8)]);
+    /// builder.add(&[IndexValue::from(1), IndexValue::from(1)]);
+    /// let samples = builder.finish();
+    ///
+    /// let oxygen = samples.filter(&["species_center"], |v| v[0] == IndexValue::from(8)).unwrap();
+    /// assert_eq!(oxygen.count(), 1);
+    /// ```
+    pub fn filter<F>(&self, names: &[&str], mut predicate: F) -> Result<Indexes, Error> where F: FnMut(&[IndexValue]) -> bool {
+        let columns = names.iter()
+            .map(|name| self.name_position(name))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut selected = Vec::with_capacity(columns.len());
+        return Ok(self.filter_entries(|value| {
+            selected.clear();
+            selected.extend(columns.iter().map(|&i| value[i]));
+            predicate(&selected)
+        }));
+    }
+
+    /// Project these indexes onto the indexes with the given `names`, keeping
+    /// only these indexes (in the order of `names`) and removing duplicated
+    /// entries. Entries are kept in the order of their first occurrence.
+    pub fn project(&self, names: &[&str]) -> Result<Indexes, Error> {
+        if names.is_empty() {
+            return Err(Error::InvalidParameter(
+                "at least one index name is required to project indexes".into()
+            ));
+        }
+
+        let columns = names.iter()
+            .map(|name| self.name_position(name))
+            .collect::<Result<Vec<_>, _>>()?;
 
-        for (i, v) in self.iter().enumerate() {
-            if v == value {
-                return Some(i);
+        let types = columns.iter().map(|&i| self.types[i]).collect();
+        let mut builder = IndexesBuilder::with_types(names.to_vec(), types);
+        for value in self {
+            let value = columns.iter().map(|&i| value[i]).collect::<Vec<_>>();
+            if !builder.contains(&value) {
+                builder.add(&value);
             }
         }
 
-        return None;
+        return Ok(builder.finish());
+    }
+
+    /// Sort the entries in these indexes in lexicographic order
+    pub fn sort(&mut self) {
+        let mut entries = self.iter().map(|v| v.to_vec()).collect::<Vec<_>>();
+        entries.sort_unstable();
+
+        self.values = entries.iter().flatten().copied().collect();
+        self.positions = Positions::default();
+        for value in &entries {
+            self.positions.push(value);
+        }
+    }
+}
+
+/// Lookup table from the values of an entry to its position in some indexes.
+/// Only the hash of each entry is stored, and the candidate entries are
+/// compared against the actual values on lookup. Entries with the same hash
+/// are chained together through `previous`.
+#[derive(Clone, Default)]
+struct Positions {
+    /// Position of the last added entry for each hash
+    last: HashMap<u64, usize>,
+    /// For each entry, position of the previous entry with the same hash, or
+    /// `usize::MAX` if there is none
+    previous: Vec<usize>,
+}
+
+impl Positions {
+    fn hash(values: &[IndexValue]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        values.hash(&mut hasher);
+        return hasher.finish();
+    }
+
+    /// Get the position of the entry with the given `values`, where `all`
+    /// contains the values of all the entries as a linearized 2D array with
+    /// `size` columns.
+    fn get(&self, all: &[IndexValue], size: usize, values: &[IndexValue]) -> Option<usize> {
+        let mut position = *self.last.get(&Positions::hash(values))?;
+        loop {
+            if &all[(position * size)..((position + 1) * size)] == values {
+                return Some(position);
+            }
+
+            position = self.previous[position];
+            if position == usize::MAX {
+                return None;
+            }
+        }
+    }
+
+    /// Register a new entry with the given `values`, after all the entries
+    /// already in this table
+    fn push(&mut self, values: &[IndexValue]) {
+        let position = self.previous.len();
+        match self.last.entry(Positions::hash(values)) {
+            Entry::Occupied(mut entry) => {
+                let previous = std::mem::replace(entry.get_mut(), position);
+                self.previous.push(previous);
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(position);
+                self.previous.push(usize::MAX);
+            }
+        }
     }
 }
 
@@ -272,6 +639,9 @@ impl<'a> Iterator for Iter<'a> {
 
 impl<'a> ExactSizeIterator for Iter<'a> {
     fn len(&self) -> usize {
+        if self.size == 0 {
+            return 0;
+        }
         self.values.len() / self.size
     }
 }
@@ -297,17 +667,23 @@ impl std::ops::Index<usize> for Indexes {
 pub trait EnvironmentIndexes {
     fn names(&self) -> Vec<&str>;
 
-    fn indexes(&self, systems: &mut [&mut dyn System]) -> Indexes;
+    /// Get the types of the indexes, all environment indexes are integers by
+    /// default.
+    fn types(&self) -> Vec<IndexType> {
+        vec![IndexType::Int; self.names().len()]
+    }
+
+    fn indexes(&self, systems: &mut [&mut dyn System]) -> Result<Indexes, Error>;
 
-    fn with_gradients(&self, systems: &mut [&mut dyn System]) -> (Indexes, Option<Indexes>) {
-        let indexes = self.indexes(systems);
-        let gradients = self.gradients_for(systems, &indexes);
-        return (indexes, gradients);
+    fn with_gradients(&self, systems: &mut [&mut dyn System]) -> Result<(Indexes, Option<Indexes>), Error> {
+        let indexes = self.indexes(systems)?;
+        let gradients = self.gradients_for(systems, &indexes)?;
+        return Ok((indexes, gradients));
     }
 
     #[allow(unused_variables)]
-    fn gradients_for(&self, systems: &mut [&mut dyn System], samples: &Indexes) -> Option<Indexes> {
-        None
+    fn gradients_for(&self, systems: &mut [&mut dyn System], samples: &Indexes) -> Result<Option<Indexes>, Error> {
+        Ok(None)
     }
 }
 
@@ -332,6 +708,67 @@ mod tests {
         assert_eq!(idx[2], [IndexValue::from(-4_isize), IndexValue::from(-24e13)]);
     }
 
+    #[test]
+    fn indexes_types() {
+        let mut builder = IndexesBuilder::new(vec!["foo", "bar"]);
+        builder.add(&[IndexValue::from(2_isize), IndexValue::from(3.9)]);
+        // values are converted to the type of the first entry
+        builder.add(&[IndexValue::from(1.0), IndexValue::from(2_isize)]);
+
+        let idx = builder.finish();
+        assert_eq!(idx.types(), &[IndexType::Int, IndexType::Float]);
+        assert!(matches!(idx[1][0], IndexValue::Int(1)));
+        assert!(matches!(idx[1][1], IndexValue::Float(value) if value == 2.0));
+
+        let idx = IndexesBuilder::new(vec!["foo", "bar"]).finish();
+        assert_eq!(idx.types(), &[IndexType::Int, IndexType::Int]);
+
+        let idx = IndexesBuilder::with_types(vec!["foo"], vec![IndexType::Float]).finish();
+        assert_eq!(idx.types(), &[IndexType::Float]);
+    }
+
+    #[test]
+    fn large_integers() {
+        // this value can not be represented exactly as f64
+        let large = 9007199254740993_i64;
+
+        let mut builder = IndexesBuilder::new(vec!["structure"]);
+        builder.add(&[IndexValue::from(large)]);
+        builder.add(&[IndexValue::from(large - 1)]);
+
+        let idx = builder.finish();
+        assert_eq!(idx.count(), 2);
+        assert_eq!(idx[0][0].i64(), large);
+        assert_eq!(idx[1][0].i64(), large - 1);
+
+        assert_eq!(IndexValue::from(large).cast(IndexType::Float), None);
+        assert_ne!(IndexValue::from(large), IndexValue::from(large as f64));
+    }
+
+    #[test]
+    fn mixed_types_comparison() {
+        assert_eq!(IndexValue::from(3_usize), IndexValue::from(3.0));
+        assert_eq!(IndexValue::from(-0.0), IndexValue::from(0_isize));
+        assert_ne!(IndexValue::from(3_usize), IndexValue::from(3.5));
+
+        assert!(IndexValue::from(3_usize) < IndexValue::from(3.5));
+        assert!(IndexValue::from(-3.5) < IndexValue::from(-3_isize));
+        assert!(IndexValue::from(i64::MAX) < IndexValue::from(f64::INFINITY));
+        assert!(IndexValue::from(i64::MIN) > IndexValue::from(-1e30));
+
+        let mut set = std::collections::HashSet::new();
+        set.insert(IndexValue::from(42_usize));
+        assert!(set.contains(&IndexValue::from(42.0)));
+    }
+
+    #[test]
+    #[should_panic(expected = "can not convert 2.5 to integer for the 'foo' index")]
+    fn invalid_index_type() {
+        let mut builder = IndexesBuilder::new(vec!["foo"]);
+        builder.add(&[IndexValue::from(0_usize)]);
+        builder.add(&[IndexValue::from(2.5)]);
+    }
+
     #[test]
     fn indexes_iter() {
         let mut builder = IndexesBuilder::new(vec!["foo", "bar"]);
@@ -368,4 +805,119 @@ mod tests {
         builder.add(&[IndexValue::from(0_usize), IndexValue::from(1_usize)]);
         builder.add(&[IndexValue::from(0_usize), IndexValue::from(1_usize)]);
     }
+
+    fn build_indexes(names: Vec<&str>, values: &[&[i32]]) -> Indexes {
+        let mut builder = IndexesBuilder::new(names);
+        for value in values {
+            builder.add(&value.iter().map(|&v| IndexValue::from(v)).collect::<Vec<_>>());
+        }
+        return builder.finish();
+    }
+
+    #[test]
+    fn position() {
+        let idx = build_indexes(vec!["foo", "bar"], &[&[2, 3], &[1, 2], &[4, 3]]);
+        assert_eq!(idx.position(&[IndexValue::from(1), IndexValue::from(2)]), Some(1));
+        assert_eq!(idx.position(&[IndexValue::from(4.0), IndexValue::from(3)]), Some(2));
+        assert_eq!(idx.position(&[IndexValue::from(4), IndexValue::from(4)]), None);
+        assert_eq!(idx.position(&[IndexValue::from(4)]), None);
+
+        assert!(idx.contains(&[IndexValue::from(2), IndexValue::from(3)]));
+        assert!(!idx.contains(&[IndexValue::from(3), IndexValue::from(2)]));
+    }
+
+    #[test]
+    fn positions_collisions() {
+        let values = [1, 2, 3].iter().map(|&v| IndexValue::from(v)).collect::<Vec<_>>();
+        // chain all entries under the same hash, as if they collided
+        let positions = Positions {
+            last: std::iter::once((Positions::hash(&values[0..1]), 2)).collect(),
+            previous: vec![usize::MAX, 0, 1],
+        };
+        assert_eq!(positions.get(&values, 1, &values[0..1]), Some(0));
+        assert_eq!(positions.get(&values, 1, &[IndexValue::from(4)]), None);
+    }
+
+    #[test]
+    fn empty_names() {
+        let idx = IndexesBuilder::new(vec![]).finish();
+        assert_eq!(idx.count(), 0);
+        assert_eq!(idx.iter().len(), 0);
+        assert_eq!(idx.iter().count(), 0);
+    }
+
+    #[test]
+    fn set_operations() {
+        let first = build_indexes(vec!["foo", "bar"], &[&[2, 3], &[1, 2], &[4, 3]]);
+        let second = build_indexes(vec!["foo", "bar"], &[&[4, 3], &[0, 0], &[2, 3]]);
+
+        let union = first.union(&second).unwrap();
+        assert_eq!(union, build_indexes(vec!["foo", "bar"], &[&[2, 3], &[1, 2], &[4, 3], &[0, 0]]));
+        assert_eq!(union.position(&[IndexValue::from(0), IndexValue::from(0)]), Some(3));
+
+        let intersection = first.intersection(&second).unwrap();
+        assert_eq!(intersection, build_indexes(vec!["foo", "bar"], &[&[2, 3], &[4, 3]]));
+
+        let difference = first.difference(&second).unwrap();
+        assert_eq!(difference, build_indexes(vec!["foo", "bar"], &[&[1, 2]]));
+
+        let other = build_indexes(vec!["bar", "foo"], &[&[2, 3]]);
+        let error = first.union(&other).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: can not compute the union of indexes with different names: [foo, bar] and [bar, foo]");
+
+        let other = IndexesBuilder::with_types(vec!["foo", "bar"], vec![IndexType::Int, IndexType::Float]).finish();
+        let error = first.difference(&other).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: can not compute the difference of indexes with different types: [integer, integer] and [integer, float]");
+    }
+
+    #[test]
+    fn filter() {
+        let idx = build_indexes(vec!["structure", "center", "species_center"], &[
+            &[0, 0, 8], &[0, 1, 1], &[0, 2, 1], &[1, 0, 8], &[1, 1, 6],
+        ]);
+
+        let oxygen = idx.filter(&["species_center"], |v| v[0] == IndexValue::from(8)).unwrap();
+        assert_eq!(oxygen.names(), idx.names());
+        assert_eq!(oxygen, build_indexes(vec!["structure", "center", "species_center"], &[
+            &[0, 0, 8], &[1, 0, 8],
+        ]));
+
+        let filtered = idx.filter(&["species_center", "structure"], |v| {
+            v[0] != IndexValue::from(8) && v[1] == IndexValue::from(0)
+        }).unwrap();
+        assert_eq!(filtered, build_indexes(vec!["structure", "center", "species_center"], &[
+            &[0, 1, 1], &[0, 2, 1],
+        ]));
+
+        let error = idx.filter(&["species"], |_| true).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: there is no 'species' index in these indexes, available indexes are [structure, center, species_center]");
+    }
+
+    #[test]
+    fn project() {
+        let idx = build_indexes(vec!["structure", "center", "species_center"], &[
+            &[0, 0, 8], &[0, 1, 1], &[0, 2, 1], &[1, 0, 8], &[1, 1, 6],
+        ]);
+
+        let projected = idx.project(&["species_center", "structure"]).unwrap();
+        assert_eq!(projected, build_indexes(vec!["species_center", "structure"], &[
+            &[8, 0], &[1, 0], &[8, 1], &[6, 1],
+        ]));
+
+        let error = idx.project(&["structure", "neighbor"]).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: there is no 'neighbor' index in these indexes, available indexes are [structure, center, species_center]");
+
+        let error = idx.project(&[]).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: at least one index name is required to project indexes");
+    }
+
+    #[test]
+    fn sort() {
+        let mut idx = build_indexes(vec!["foo", "bar"], &[&[2, 3], &[1, 2], &[-4, 3], &[1, -1]]);
+        idx.sort();
+
+        assert_eq!(idx, build_indexes(vec!["foo", "bar"], &[&[-4, 3], &[1, -1], &[1, 2], &[2, 3]]));
+        assert_eq!(idx.position(&[IndexValue::from(2), IndexValue::from(3)]), Some(3));
+        assert_eq!(idx.position(&[IndexValue::from(-4), IndexValue::from(3)]), Some(0));
+    }
 }
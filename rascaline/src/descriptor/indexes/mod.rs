@@ -1,9 +1,11 @@
 mod index;
-pub use self::index::{IndexValue, Indexes, IndexesBuilder, EnvironmentIndexes};
+pub use self::index::{IndexValue, IndexType, Indexes, IndexesBuilder, EnvironmentIndexes};
+pub(crate) use self::index::is_valid_ident;
 
 mod environments;
 pub use self::environments::{StructureEnvironment, AtomEnvironment};
 
 mod species;
-pub use self::species::{StructureSpeciesEnvironment, AtomSpeciesEnvironment};
-pub use self::species::{ThreeBodiesSpeciesEnvironment};
+pub use self::species::{StructureSpeciesEnvironment, AtomSpeciesEnvironment, CenterSpeciesEnvironment};
+pub use self::species::LongRangeSpeciesEnvironment;
+pub use self::species::{ThreeBodiesSpeciesEnvironment, FourBodiesSpeciesEnvironment};
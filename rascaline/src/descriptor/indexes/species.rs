@@ -4,6 +4,7 @@ use indexmap::IndexSet;
 use itertools::Itertools;
 
 use crate::system::System;
+use crate::Error;
 use super::{EnvironmentIndexes, Indexes, IndexesBuilder, IndexValue};
 
 /// `StructureSpeciesEnvironment` is used to represents environments
@@ -20,19 +21,19 @@ impl EnvironmentIndexes for StructureSpeciesEnvironment {
         vec!["structure", "species"]
     }
 
-    fn indexes(&self, systems: &mut [&mut dyn System]) -> Indexes {
+    fn indexes(&self, systems: &mut [&mut dyn System]) -> Result<Indexes, Error> {
         let mut indexes = IndexesBuilder::new(self.names());
         for (i_system, system) in systems.iter().enumerate() {
-            for &species in system.species().iter().collect::<BTreeSet<_>>() {
+            for &species in system.species()?.iter().collect::<BTreeSet<_>>() {
                 indexes.add(&[
                     IndexValue::from(i_system), IndexValue::from(species)
                 ]);
             }
         }
-        return indexes.finish();
+        return Ok(indexes.finish());
     }
 
-    fn gradients_for(&self, systems: &mut [&mut dyn System], samples: &Indexes) -> Option<Indexes> {
+    fn gradients_for(&self, systems: &mut [&mut dyn System], samples: &Indexes) -> Result<Option<Indexes>, Error> {
         assert_eq!(samples.names(), self.names());
 
         let mut gradients = IndexesBuilder::new(vec!["structure", "species", "atom", "spatial"]);
@@ -41,7 +42,7 @@ impl EnvironmentIndexes for StructureSpeciesEnvironment {
             let alpha = value[1];
 
             let system = &systems[i_system.usize()];
-            let species = system.species();
+            let species = system.species()?;
             for (i_atom, &species) in species.iter().enumerate() {
                 // only atoms with the same species participate to the gradient
                 if species == alpha.usize() {
@@ -52,7 +53,7 @@ impl EnvironmentIndexes for StructureSpeciesEnvironment {
             }
         }
 
-        return Some(gradients.finish());
+        return Ok(Some(gradients.finish()));
     }
 }
 
@@ -100,15 +101,15 @@ impl EnvironmentIndexes for AtomSpeciesEnvironment {
         vec!["structure", "center", "species_center", "species_neighbor"]
     }
 
-    fn indexes(&self, systems: &mut [&mut dyn System]) -> Indexes {
+    fn indexes(&self, systems: &mut [&mut dyn System]) -> Result<Indexes, Error> {
         // Accumulate indexes in a set first to ensure uniqueness of the indexes
         // even if their are multiple neighbors of the same specie around a
         // given center
         let mut set = BTreeSet::new();
         for (i_system, system) in systems.iter_mut().enumerate() {
-            system.compute_neighbors(self.cutoff);
-            let species = system.species();
-            for pair in system.pairs() {
+            system.compute_neighbors(self.cutoff)?;
+            let species = system.species()?;
+            for pair in system.pairs()? {
                 let species_first = species[pair.first];
                 let species_second = species[pair.second];
 
@@ -129,10 +130,10 @@ impl EnvironmentIndexes for AtomSpeciesEnvironment {
                 IndexValue::from(s), IndexValue::from(c), IndexValue::from(a), IndexValue::from(b)
             ]);
         }
-        return indexes.finish();
+        return Ok(indexes.finish());
     }
 
-    fn gradients_for(&self, systems: &mut [&mut dyn System], samples: &Indexes) -> Option<Indexes> {
+    fn gradients_for(&self, systems: &mut [&mut dyn System], samples: &Indexes) -> Result<Option<Indexes>, Error> {
         assert_eq!(samples.names(), self.names());
 
         // We need IndexSet to yield the indexes in the right order, i.e. the
@@ -144,10 +145,16 @@ impl EnvironmentIndexes for AtomSpeciesEnvironment {
             let species_neighbor = requested[3].usize();
 
             let system = &mut *systems[i_system.usize()];
-            system.compute_neighbors(self.cutoff);
+            system.compute_neighbors(self.cutoff)?;
+
+            let species = system.species()?;
+            for pair in system.pairs_containing(center)? {
+                if pair.first == pair.second {
+                    // periodic self-images move together with the center, and
+                    // do not contribute to the gradients
+                    continue;
+                }
 
-            let species = system.species();
-            for pair in system.pairs_containing(center) {
                 let species_first = species[pair.first];
                 let species_second = species[pair.second];
 
@@ -173,7 +180,159 @@ impl EnvironmentIndexes for AtomSpeciesEnvironment {
             gradients.add(&[system, center, alpha, beta, neighbor, IndexValue::from(2_usize)]);
         }
 
-        return Some(gradients.finish());
+        return Ok(Some(gradients.finish()));
+    }
+}
+
+/// `CenterSpeciesEnvironment` is used to represents atom-centered
+/// environments, where each atom in a structure is described with a feature
+/// vector based on other atoms inside a sphere centered on the central atom.
+/// Contrary to `AtomSpeciesEnvironment`, there is a single environment for
+/// each atom, which includes the contributions of all neighbors species.
+///
+/// The base set of indexes contains `structure`, `center` (i.e. central atom
+/// index inside the structure) and `species_center`; the gradient indexes also
+/// contains the `neighbor` inside the spherical cutoff with respect to which
+/// the gradient is taken and the `spatial` (i.e x/y/z) index.
+pub struct CenterSpeciesEnvironment {
+    /// spherical cutoff radius used to construct the atom-centered environments
+    cutoff: f64,
+}
+
+impl CenterSpeciesEnvironment {
+    /// Create a new `CenterSpeciesEnvironment` with the given `cutoff`.
+    pub fn new(cutoff: f64) -> CenterSpeciesEnvironment {
+        assert!(cutoff > 0.0 && cutoff.is_finite(), "cutoff must be positive for CenterSpeciesEnvironment");
+        CenterSpeciesEnvironment {
+            cutoff: cutoff,
+        }
+    }
+}
+
+impl EnvironmentIndexes for CenterSpeciesEnvironment {
+    fn names(&self) -> Vec<&str> {
+        vec!["structure", "center", "species_center"]
+    }
+
+    fn indexes(&self, systems: &mut [&mut dyn System]) -> Result<Indexes, Error> {
+        let mut indexes = IndexesBuilder::new(self.names());
+        for (i_system, system) in systems.iter().enumerate() {
+            for (center, &species) in system.species()?.iter().enumerate() {
+                indexes.add(&[
+                    IndexValue::from(i_system), IndexValue::from(center), IndexValue::from(species)
+                ]);
+            }
+        }
+        return Ok(indexes.finish());
+    }
+
+    fn gradients_for(&self, systems: &mut [&mut dyn System], samples: &Indexes) -> Result<Option<Indexes>, Error> {
+        assert_eq!(samples.names(), self.names());
+
+        // We need IndexSet to yield the indexes in the right order, i.e. the
+        // order corresponding to whatever was passed in `samples`
+        let mut indexes = IndexSet::new();
+        for requested in samples {
+            let i_system = requested[0];
+            let center = requested[1].usize();
+            let alpha = requested[2];
+
+            let system = &mut *systems[i_system.usize()];
+            system.compute_neighbors(self.cutoff)?;
+
+            for pair in system.pairs_containing(center)? {
+                if pair.first == pair.second {
+                    // periodic self-images move together with the center, and
+                    // do not contribute to the gradients
+                    continue;
+                }
+
+                if pair.first == center {
+                    indexes.insert((i_system, pair.first, alpha, pair.second));
+                } else if pair.second == center {
+                    indexes.insert((i_system, pair.second, alpha, pair.first));
+                }
+            }
+        }
+
+        let mut gradients = IndexesBuilder::new(vec![
+            "structure", "center", "species_center", "neighbor", "spatial"
+        ]);
+        for (system, c, alpha, n) in indexes {
+            let center = IndexValue::from(c);
+            let neighbor = IndexValue::from(n);
+            gradients.add(&[system, center, alpha, neighbor, IndexValue::from(0_usize)]);
+            gradients.add(&[system, center, alpha, neighbor, IndexValue::from(1_usize)]);
+            gradients.add(&[system, center, alpha, neighbor, IndexValue::from(2_usize)]);
+        }
+
+        return Ok(Some(gradients.finish()));
+    }
+}
+
+/// `LongRangeSpeciesEnvironment` is used to represents atom-centered
+/// environments without any spherical cutoff, where all the atoms in a
+/// structure contribute to the environment of each central atom. This is
+/// needed for long-range descriptors. These environments include chemical
+/// species information.
+///
+/// The base set of indexes contains `structure`, `center` (i.e. central atom
+/// index inside the structure), `species_center` and `species_neighbor`, with
+/// one environment for each species in the structure; the gradient indexes also
+/// contains all the atoms with `species_neighbor` species (except the central
+/// atom) as `neighbor`, and the `spatial` (i.e x/y/z) index.
+pub struct LongRangeSpeciesEnvironment;
+
+impl EnvironmentIndexes for LongRangeSpeciesEnvironment {
+    fn names(&self) -> Vec<&str> {
+        vec!["structure", "center", "species_center", "species_neighbor"]
+    }
+
+    fn indexes(&self, systems: &mut [&mut dyn System]) -> Result<Indexes, Error> {
+        let mut indexes = IndexesBuilder::new(self.names());
+        for (i_system, system) in systems.iter().enumerate() {
+            let species = system.species()?;
+            let all_species = species.iter().collect::<BTreeSet<_>>();
+            for (center, &species_center) in species.iter().enumerate() {
+                for &&species_neighbor in &all_species {
+                    indexes.add(&[
+                        IndexValue::from(i_system),
+                        IndexValue::from(center),
+                        IndexValue::from(species_center),
+                        IndexValue::from(species_neighbor),
+                    ]);
+                }
+            }
+        }
+        return Ok(indexes.finish());
+    }
+
+    fn gradients_for(&self, systems: &mut [&mut dyn System], samples: &Indexes) -> Result<Option<Indexes>, Error> {
+        assert_eq!(samples.names(), self.names());
+
+        let mut gradients = IndexesBuilder::new(vec![
+            "structure", "center", "species_center", "species_neighbor", "neighbor", "spatial"
+        ]);
+        for requested in samples {
+            let i_system = requested[0];
+            let center = requested[1];
+            let alpha = requested[2];
+            let beta = requested[3];
+
+            let species = systems[i_system.usize()].species()?;
+            for (neighbor, &species_neighbor) in species.iter().enumerate() {
+                if neighbor == center.usize() || species_neighbor != beta.usize() {
+                    continue;
+                }
+
+                let neighbor = IndexValue::from(neighbor);
+                gradients.add(&[i_system, center, alpha, beta, neighbor, IndexValue::from(0_usize)]);
+                gradients.add(&[i_system, center, alpha, beta, neighbor, IndexValue::from(1_usize)]);
+                gradients.add(&[i_system, center, alpha, beta, neighbor, IndexValue::from(2_usize)]);
+            }
+        }
+
+        return Ok(Some(gradients.finish()));
     }
 }
 
@@ -221,7 +380,7 @@ impl EnvironmentIndexes for ThreeBodiesSpeciesEnvironment {
         vec!["structure", "center", "species_center", "species_neighbor_1", "species_neighbor_2"]
     }
 
-    fn indexes(&self, systems: &mut [&mut dyn System]) -> Indexes {
+    fn indexes(&self, systems: &mut [&mut dyn System]) -> Result<Indexes, Error> {
         // Accumulate indexes in a set first to ensure uniqueness of the indexes
         // even if their are multiple neighbors of the same specie around a
         // given center
@@ -231,19 +390,27 @@ impl EnvironmentIndexes for ThreeBodiesSpeciesEnvironment {
             if i < j { (i, j) } else { (j, i) }
         };
         for (i_system, system) in systems.iter_mut().enumerate() {
-            system.compute_neighbors(self.cutoff);
-            let species = system.species();
+            system.compute_neighbors(self.cutoff)?;
+            let species = system.species()?;
 
-            for center in 0..system.size() {
-                for (i, j) in triplets_around(*system, center) {
+            for center in 0..system.size()? {
+                for (i, j) in triplets_around(*system, center)? {
                     let (species_1, species_2) = sort_pair(species[i], species[j]);
                     set.insert((i_system, center, species[center], species_1, species_2));
                 }
             }
 
             if self.self_contribution {
-                for (center, &species) in species.iter().enumerate() {
-                    set.insert((i_system, center, species, species, species));
+                for (center, &species_center) in species.iter().enumerate() {
+                    // the central atom is also part of the triplets built
+                    // with any of its neighbors
+                    for pair in system.pairs_containing(center)? {
+                        let neighbor = if pair.first == center { pair.second } else { pair.first };
+                        let (species_1, species_2) = sort_pair(species_center, species[neighbor]);
+                        set.insert((i_system, center, species_center, species_1, species_2));
+                    }
+
+                    set.insert((i_system, center, species_center, species_center, species_center));
                 }
             }
         }
@@ -258,10 +425,10 @@ impl EnvironmentIndexes for ThreeBodiesSpeciesEnvironment {
                 IndexValue::from(species_2)
             ]);
         }
-        return indexes.finish();
+        return Ok(indexes.finish());
     }
 
-    fn gradients_for(&self, systems: &mut [&mut dyn System], samples: &Indexes) -> Option<Indexes> {
+    fn gradients_for(&self, systems: &mut [&mut dyn System], samples: &Indexes) -> Result<Option<Indexes>, Error> {
         assert_eq!(samples.names(), self.names());
 
         let sort_pair = |i, j| {
@@ -274,15 +441,41 @@ impl EnvironmentIndexes for ThreeBodiesSpeciesEnvironment {
         for requested in samples {
             let i_system = requested[0];
             let center = requested[1].usize();
+            let requested_species = (requested[3].usize(), requested[4].usize());
 
             let system = &mut *systems[i_system.usize()];
-            system.compute_neighbors(self.cutoff);
+            system.compute_neighbors(self.cutoff)?;
 
-            let species = system.species();
-            for (i, j) in triplets_around(&*system, center) {
+            let species = system.species()?;
+            for (i, j) in triplets_around(&*system, center)? {
                 let (species_1, species_2) = sort_pair(species[i], species[j]);
-                indexes.insert((i_system, center, species[center], species_1, species_2, i));
-                indexes.insert((i_system, center, species[center], species_1, species_2, j));
+                // only triplets with the requested species contribute to the
+                // gradients of this sample
+                if (species_1, species_2) != requested_species {
+                    continue;
+                }
+
+                // periodic self-images move together with the center, and do
+                // not contribute to the gradients
+                if i != center {
+                    indexes.insert((i_system, center, species[center], species_1, species_2, i));
+                }
+                if j != center {
+                    indexes.insert((i_system, center, species[center], species_1, species_2, j));
+                }
+            }
+
+            if self.self_contribution {
+                for pair in system.pairs_containing(center)? {
+                    if pair.first == pair.second {
+                        continue;
+                    }
+                    let neighbor = if pair.first == center { pair.second } else { pair.first };
+                    let (species_1, species_2) = sort_pair(species[center], species[neighbor]);
+                    if (species_1, species_2) == requested_species {
+                        indexes.insert((i_system, center, species[center], species_1, species_2, neighbor));
+                    }
+                }
             }
         }
 
@@ -304,15 +497,145 @@ impl EnvironmentIndexes for ThreeBodiesSpeciesEnvironment {
             }
         }
 
-        return Some(gradients.finish());
+        return Ok(Some(gradients.finish()));
+    }
+}
+
+/// `FourBodiesSpeciesEnvironment` is used to represents atom-centered
+/// environments representing four body atomic density correlation; where the
+/// four bodies include the central atom and three neighbors. These environments
+/// include chemical species information.
+///
+/// The base set of indexes contains `structure`, `center` (i.e. central atom
+/// index inside the structure), `species_center`, `species_neighbor_1`,
+/// `species_neighbor_2` and `species_neighbor_3`, with the neighbor species
+/// sorted in increasing order; the gradient indexes also contains the
+/// `neighbor` inside the spherical cutoff with respect to which the gradient is
+/// taken and the `spatial` (i.e x/y/z) index.
+pub struct FourBodiesSpeciesEnvironment {
+    /// spherical cutoff radius used to construct the atom-centered environments
+    cutoff: f64,
+    /// Is the central atom considered to be its own neighbor?
+    self_contribution: bool,
+}
+
+impl FourBodiesSpeciesEnvironment {
+    /// Create a new `FourBodiesSpeciesEnvironment` with the given `cutoff`,
+    /// excluding self contributions.
+    pub fn new(cutoff: f64) -> FourBodiesSpeciesEnvironment {
+        assert!(cutoff > 0.0 && cutoff.is_finite(), "cutoff must be positive for FourBodiesSpeciesEnvironment");
+        FourBodiesSpeciesEnvironment {
+            cutoff: cutoff,
+            self_contribution: false,
+        }
+    }
+
+    /// Create a new `FourBodiesSpeciesEnvironment` with the given `cutoff`,
+    /// including self contributions.
+    pub fn with_self_contribution(cutoff: f64) -> FourBodiesSpeciesEnvironment {
+        assert!(cutoff > 0.0 && cutoff.is_finite(), "cutoff must be positive for FourBodiesSpeciesEnvironment");
+        FourBodiesSpeciesEnvironment {
+            cutoff: cutoff,
+            self_contribution: true,
+        }
+    }
+
+    /// Get the set of species around the given `center`, including the center
+    /// species when using self contributions
+    fn neighbors_species(&self, system: &dyn System, center: usize) -> Result<BTreeSet<usize>, Error> {
+        let species = system.species()?;
+
+        let mut neighbors_species = BTreeSet::new();
+        for pair in system.pairs_containing(center)? {
+            let neighbor = if pair.first == center { pair.second } else { pair.first };
+            neighbors_species.insert(species[neighbor]);
+        }
+
+        if self.self_contribution {
+            neighbors_species.insert(species[center]);
+        }
+
+        return Ok(neighbors_species);
+    }
+}
+
+impl EnvironmentIndexes for FourBodiesSpeciesEnvironment {
+    fn names(&self) -> Vec<&str> {
+        vec!["structure", "center", "species_center", "species_neighbor_1", "species_neighbor_2", "species_neighbor_3"]
+    }
+
+    fn indexes(&self, systems: &mut [&mut dyn System]) -> Result<Indexes, Error> {
+        let mut indexes = IndexesBuilder::new(self.names());
+        for (i_system, system) in systems.iter_mut().enumerate() {
+            system.compute_neighbors(self.cutoff)?;
+            let species = system.species()?;
+
+            for center in 0..system.size()? {
+                let neighbors_species = self.neighbors_species(&**system, center)?;
+                // the set is sorted, so the combinations are sorted as well
+                for combination in neighbors_species.iter().combinations_with_replacement(3) {
+                    indexes.add(&[
+                        IndexValue::from(i_system),
+                        IndexValue::from(center),
+                        IndexValue::from(species[center]),
+                        IndexValue::from(*combination[0]),
+                        IndexValue::from(*combination[1]),
+                        IndexValue::from(*combination[2]),
+                    ]);
+                }
+            }
+        }
+        return Ok(indexes.finish());
+    }
+
+    fn gradients_for(&self, systems: &mut [&mut dyn System], samples: &Indexes) -> Result<Option<Indexes>, Error> {
+        assert_eq!(samples.names(), self.names());
+
+        let mut gradients = IndexesBuilder::new(vec![
+            "structure", "center", "species_center", "species_neighbor_1",
+            "species_neighbor_2", "species_neighbor_3", "neighbor", "spatial"
+        ]);
+        for requested in samples {
+            let i_system = requested[0];
+            let center = requested[1].usize();
+            let requested_species = [requested[3].usize(), requested[4].usize(), requested[5].usize()];
+
+            let system = &mut *systems[i_system.usize()];
+            system.compute_neighbors(self.cutoff)?;
+            let species = system.species()?;
+
+            // only neighbors with one of the requested species contribute to
+            // the gradients
+            let mut neighbors = BTreeSet::new();
+            for pair in system.pairs_containing(center)? {
+                let neighbor = if pair.first == center { pair.second } else { pair.first };
+                // periodic self-images move together with the center, and do
+                // not contribute to the gradients
+                if neighbor != center && requested_species.contains(&species[neighbor]) {
+                    neighbors.insert(neighbor);
+                }
+            }
+
+            for neighbor in neighbors {
+                for spatial in 0..3_usize {
+                    gradients.add(&[
+                        requested[0], requested[1], requested[2], requested[3],
+                        requested[4], requested[5], IndexValue::from(neighbor),
+                        IndexValue::from(spatial)
+                    ]);
+                }
+            }
+        }
+
+        return Ok(Some(gradients.finish()));
     }
 }
 
 /// Build the list of triplet i-center-j
-fn triplets_around<'a>(system: &'a dyn System, center: usize) -> impl Iterator<Item=(usize, usize)> + 'a {
-    let pairs = system.pairs_containing(center);
+fn triplets_around<'a>(system: &'a dyn System, center: usize) -> Result<impl Iterator<Item=(usize, usize)> + 'a, Error> {
+    let pairs = system.pairs_containing(center)?;
 
-    return pairs.iter().cartesian_product(pairs).map(move |(first_pair, second_pair)| {
+    return Ok(pairs.iter().cartesian_product(pairs).map(move |(first_pair, second_pair)| {
         let i = if first_pair.first == center {
             first_pair.second
         } else {
@@ -326,7 +649,7 @@ fn triplets_around<'a>(system: &'a dyn System, center: usize) -> impl Iterator<I
         };
 
         return (i, j);
-    });
+    }));
 }
 
 
@@ -338,14 +661,14 @@ mod tests {
     /// Convenience macro to create IndexValue
     macro_rules! v {
         ($value: expr) => {
-            crate::descriptor::indexes::IndexValue::from($value as f64)
+            crate::descriptor::indexes::IndexValue::from($value as i64)
         };
     }
 
     #[test]
     fn structure() {
         let mut systems = test_systems(&["methane", "methane", "water"]);
-        let indexes = StructureSpeciesEnvironment.indexes(&mut systems.get());
+        let indexes = StructureSpeciesEnvironment.indexes(&mut systems.get()).unwrap();
         assert_eq!(indexes.count(), 6);
         assert_eq!(indexes.names(), &["structure", "species"]);
         assert_eq!(indexes.iter().collect::<Vec<_>>(), vec![
@@ -358,7 +681,7 @@ mod tests {
     #[test]
     fn structure_gradient() {
         let mut systems = test_systems(&["CH", "water"]);
-        let (_, gradients) = StructureSpeciesEnvironment.with_gradients(&mut systems.get());
+        let (_, gradients) = StructureSpeciesEnvironment.with_gradients(&mut systems.get()).unwrap();
         let gradients = gradients.unwrap();
         assert_eq!(gradients.count(), 15);
         assert_eq!(gradients.names(), &["structure", "species", "atom", "spatial"]);
@@ -383,7 +706,7 @@ mod tests {
         indexes.add(&[v!(0), v!(6)]);
 
         let mut systems = test_systems(&["CH", "water", "CH"]);
-        let gradients = StructureSpeciesEnvironment.gradients_for(&mut systems.get(), &indexes.finish());
+        let gradients = StructureSpeciesEnvironment.gradients_for(&mut systems.get(), &indexes.finish()).unwrap();
         let gradients = gradients.unwrap();
         assert_eq!(gradients.names(), &["structure", "species", "atom", "spatial"]);
 
@@ -403,7 +726,7 @@ mod tests {
     fn atoms() {
         let mut systems = test_systems(&["CH", "water"]);
         let strategy = AtomSpeciesEnvironment::new(2.0);
-        let indexes = strategy.indexes(&mut systems.get());
+        let indexes = strategy.indexes(&mut systems.get()).unwrap();
         assert_eq!(indexes.count(), 7);
         assert_eq!(indexes.names(), &["structure", "center", "species_center", "species_neighbor"]);
         assert_eq!(indexes.iter().collect::<Vec<_>>(), vec![
@@ -426,7 +749,7 @@ mod tests {
     fn atoms_self_contribution() {
         let mut systems = test_systems(&["CH"]);
         let strategy = AtomSpeciesEnvironment::new(2.0);
-        let indexes = strategy.indexes(&mut systems.get());
+        let indexes = strategy.indexes(&mut systems.get()).unwrap();
         assert_eq!(indexes.count(), 2);
         assert_eq!(indexes.names(), &["structure", "center", "species_center", "species_neighbor"]);
         assert_eq!(indexes.iter().collect::<Vec<_>>(), vec![
@@ -437,7 +760,7 @@ mod tests {
         ]);
 
         let strategy = AtomSpeciesEnvironment::with_self_contribution(2.0);
-        let indexes = strategy.indexes(&mut systems.get());
+        let indexes = strategy.indexes(&mut systems.get()).unwrap();
         assert_eq!(indexes.count(), 4);
         assert_eq!(indexes.names(), &["structure", "center", "species_center", "species_neighbor"]);
         assert_eq!(indexes.iter().collect::<Vec<_>>(), vec![
@@ -451,7 +774,7 @@ mod tests {
 
         // we get entries even without proper neighbors
         let strategy = AtomSpeciesEnvironment::with_self_contribution(1.0);
-        let indexes = strategy.indexes(&mut systems.get());
+        let indexes = strategy.indexes(&mut systems.get()).unwrap();
         assert_eq!(indexes.count(), 2);
         assert_eq!(indexes.names(), &["structure", "center", "species_center", "species_neighbor"]);
         assert_eq!(indexes.iter().collect::<Vec<_>>(), vec![
@@ -466,7 +789,7 @@ mod tests {
     fn atoms_gradient() {
         let mut systems = test_systems(&["CH", "water"]);
         let strategy = AtomSpeciesEnvironment::new(2.0);
-        let (_, gradients) = strategy.with_gradients(&mut systems.get());
+        let (_, gradients) = strategy.with_gradients(&mut systems.get()).unwrap();
         let gradients = gradients.unwrap();
 
         assert_eq!(gradients.count(), 24);
@@ -515,7 +838,7 @@ mod tests {
 
         let mut systems = test_systems(&["CH", "water"]);
         let strategy = AtomSpeciesEnvironment::new(2.0);
-        let gradients = strategy.gradients_for(&mut systems.get(), &indexes.finish());
+        let gradients = strategy.gradients_for(&mut systems.get(), &indexes.finish()).unwrap();
         let gradients = gradients.unwrap();
 
         assert_eq!(gradients.names(), &["structure", "center", "species_center", "species_neighbor", "neighbor", "spatial"]);
@@ -538,11 +861,120 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn centers() {
+        let mut systems = test_systems(&["CH", "water"]);
+        let strategy = CenterSpeciesEnvironment::new(2.0);
+        let indexes = strategy.indexes(&mut systems.get()).unwrap();
+        assert_eq!(indexes.count(), 5);
+        assert_eq!(indexes.names(), &["structure", "center", "species_center"]);
+        assert_eq!(indexes.iter().collect::<Vec<_>>(), vec![
+            &[v!(0), v!(0), v!(1)], &[v!(0), v!(1), v!(6)],
+            &[v!(1), v!(0), v!(123456)], &[v!(1), v!(1), v!(1)], &[v!(1), v!(2), v!(1)],
+        ]);
+
+        // we get entries even without proper neighbors
+        let strategy = CenterSpeciesEnvironment::new(1.0);
+        let indexes = strategy.indexes(&mut systems.get()).unwrap();
+        assert_eq!(indexes.count(), 5);
+    }
+
+    #[test]
+    fn centers_gradient() {
+        let mut systems = test_systems(&["CH", "water"]);
+        let strategy = CenterSpeciesEnvironment::new(2.0);
+        let (_, gradients) = strategy.with_gradients(&mut systems.get()).unwrap();
+        let gradients = gradients.unwrap();
+
+        assert_eq!(gradients.count(), 24);
+        assert_eq!(gradients.names(), &["structure", "center", "species_center", "neighbor", "spatial"]);
+        assert_eq!(gradients.iter().collect::<Vec<_>>(), vec![
+            // H in CH
+            &[v!(0), v!(0), v!(1), v!(1), v!(0)],
+            &[v!(0), v!(0), v!(1), v!(1), v!(1)],
+            &[v!(0), v!(0), v!(1), v!(1), v!(2)],
+            // C in CH
+            &[v!(0), v!(1), v!(6), v!(0), v!(0)],
+            &[v!(0), v!(1), v!(6), v!(0), v!(1)],
+            &[v!(0), v!(1), v!(6), v!(0), v!(2)],
+            // O in water
+            &[v!(1), v!(0), v!(123456), v!(1), v!(0)],
+            &[v!(1), v!(0), v!(123456), v!(1), v!(1)],
+            &[v!(1), v!(0), v!(123456), v!(1), v!(2)],
+            &[v!(1), v!(0), v!(123456), v!(2), v!(0)],
+            &[v!(1), v!(0), v!(123456), v!(2), v!(1)],
+            &[v!(1), v!(0), v!(123456), v!(2), v!(2)],
+            // 1st H in water
+            &[v!(1), v!(1), v!(1), v!(0), v!(0)],
+            &[v!(1), v!(1), v!(1), v!(0), v!(1)],
+            &[v!(1), v!(1), v!(1), v!(0), v!(2)],
+            &[v!(1), v!(1), v!(1), v!(2), v!(0)],
+            &[v!(1), v!(1), v!(1), v!(2), v!(1)],
+            &[v!(1), v!(1), v!(1), v!(2), v!(2)],
+            // 2nd H in water
+            &[v!(1), v!(2), v!(1), v!(0), v!(0)],
+            &[v!(1), v!(2), v!(1), v!(0), v!(1)],
+            &[v!(1), v!(2), v!(1), v!(0), v!(2)],
+            &[v!(1), v!(2), v!(1), v!(1), v!(0)],
+            &[v!(1), v!(2), v!(1), v!(1), v!(1)],
+            &[v!(1), v!(2), v!(1), v!(1), v!(2)],
+        ]);
+    }
+
+    #[test]
+    fn long_range() {
+        let mut systems = test_systems(&["CH", "water"]);
+        let indexes = LongRangeSpeciesEnvironment.indexes(&mut systems.get()).unwrap();
+        assert_eq!(indexes.count(), 10);
+        assert_eq!(indexes.names(), &["structure", "center", "species_center", "species_neighbor"]);
+        assert_eq!(indexes.iter().collect::<Vec<_>>(), vec![
+            // H in CH
+            &[v!(0), v!(0), v!(1), v!(1)], &[v!(0), v!(0), v!(1), v!(6)],
+            // C in CH
+            &[v!(0), v!(1), v!(6), v!(1)], &[v!(0), v!(1), v!(6), v!(6)],
+            // O in water
+            &[v!(1), v!(0), v!(123456), v!(1)], &[v!(1), v!(0), v!(123456), v!(123456)],
+            // first H in water
+            &[v!(1), v!(1), v!(1), v!(1)], &[v!(1), v!(1), v!(1), v!(123456)],
+            // second H in water
+            &[v!(1), v!(2), v!(1), v!(1)], &[v!(1), v!(2), v!(1), v!(123456)],
+        ]);
+    }
+
+    #[test]
+    fn long_range_gradient() {
+        let mut indexes = IndexesBuilder::new(vec!["structure", "center", "species_center", "species_neighbor"]);
+        indexes.add(&[v!(1), v!(1), v!(1), v!(1)]);
+        indexes.add(&[v!(1), v!(0), v!(123456), v!(1)]);
+        indexes.add(&[v!(0), v!(0), v!(1), v!(1)]);
+
+        let mut systems = test_systems(&["CH", "water"]);
+        let gradients = LongRangeSpeciesEnvironment.gradients_for(&mut systems.get(), &indexes.finish()).unwrap();
+        let gradients = gradients.unwrap();
+
+        assert_eq!(gradients.count(), 9);
+        assert_eq!(gradients.names(), &["structure", "center", "species_center", "species_neighbor", "neighbor", "spatial"]);
+        assert_eq!(gradients.iter().collect::<Vec<_>>(), vec![
+            // first H in water, H neighbors
+            &[v!(1), v!(1), v!(1), v!(1), v!(2), v!(0)],
+            &[v!(1), v!(1), v!(1), v!(1), v!(2), v!(1)],
+            &[v!(1), v!(1), v!(1), v!(1), v!(2), v!(2)],
+            // O in water, H neighbors
+            &[v!(1), v!(0), v!(123456), v!(1), v!(1), v!(0)],
+            &[v!(1), v!(0), v!(123456), v!(1), v!(1), v!(1)],
+            &[v!(1), v!(0), v!(123456), v!(1), v!(1), v!(2)],
+            &[v!(1), v!(0), v!(123456), v!(1), v!(2), v!(0)],
+            &[v!(1), v!(0), v!(123456), v!(1), v!(2), v!(1)],
+            &[v!(1), v!(0), v!(123456), v!(1), v!(2), v!(2)],
+            // H in CH has no other H neighbor
+        ]);
+    }
+
     #[test]
     fn three_bodies() {
         let mut systems = test_systems(&["CH", "water"]);
         let strategy = ThreeBodiesSpeciesEnvironment::new(2.0);
-        let indexes = strategy.indexes(&mut systems.get());
+        let indexes = strategy.indexes(&mut systems.get()).unwrap();
         assert_eq!(indexes.count(), 9);
         assert_eq!(indexes.names(), &["structure", "center", "species_center", "species_neighbor_1", "species_neighbor_2"]);
         assert_eq!(indexes.iter().collect::<Vec<_>>(), vec![
@@ -574,22 +1006,28 @@ mod tests {
         let mut systems = test_systems(&["water"]);
         // Only include O-H neighbors
         let strategy = ThreeBodiesSpeciesEnvironment::with_self_contribution(1.2);
-        let indexes = strategy.indexes(&mut systems.get());
-        assert_eq!(indexes.count(), 6);
+        let indexes = strategy.indexes(&mut systems.get()).unwrap();
+        assert_eq!(indexes.count(), 9);
         assert_eq!(indexes.names(), &["structure", "center", "species_center", "species_neighbor_1", "species_neighbor_2"]);
         assert_eq!(indexes.iter().collect::<Vec<_>>(), vec![
             // H-O-H
             &[v!(0), v!(0), v!(123456), v!(1), v!(1)],
+            // H-O-O, using the central atom as a neighbor
+            &[v!(0), v!(0), v!(123456), v!(1), v!(123456)],
             // O-O-O
             &[v!(0), v!(0), v!(123456), v!(123456), v!(123456)],
             // first H in water
             // H-H-H
             &[v!(0), v!(1), v!(1), v!(1), v!(1)],
+            // H-H-O, using the central atom as a neighbor
+            &[v!(0), v!(1), v!(1), v!(1), v!(123456)],
             // O-H-O
             &[v!(0), v!(1), v!(1), v!(123456), v!(123456)],
             // second H in water
             // H-H-H
             &[v!(0), v!(2), v!(1), v!(1), v!(1)],
+            // H-H-O, using the central atom as a neighbor
+            &[v!(0), v!(2), v!(1), v!(1), v!(123456)],
             // O-H-O
             &[v!(0), v!(2), v!(1), v!(123456), v!(123456)],
         ]);
@@ -599,7 +1037,7 @@ mod tests {
     fn three_bodies_gradients() {
         let mut systems = test_systems(&["water"]);
         let strategy = ThreeBodiesSpeciesEnvironment::new(2.0);
-        let (_, gradients) = strategy.with_gradients(&mut systems.get());
+        let (_, gradients) = strategy.with_gradients(&mut systems.get()).unwrap();
         let gradients = gradients.unwrap();
 
         assert_eq!(gradients.count(), 30);
@@ -612,10 +1050,10 @@ mod tests {
             &[v!(0), v!(0), v!(123456), v!(1), v!(1), v!(2), v!(0)],
             &[v!(0), v!(0), v!(123456), v!(1), v!(1), v!(2), v!(1)],
             &[v!(0), v!(0), v!(123456), v!(1), v!(1), v!(2), v!(2)],
-            // O-H-O, 1rst H
-            &[v!(0), v!(1), v!(1), v!(123456), v!(123456), v!(0), v!(0)],
-            &[v!(0), v!(1), v!(1), v!(123456), v!(123456), v!(0), v!(1)],
-            &[v!(0), v!(1), v!(1), v!(123456), v!(123456), v!(0), v!(2)],
+            // H-H-H, 1rst H
+            &[v!(0), v!(1), v!(1), v!(1), v!(1), v!(2), v!(0)],
+            &[v!(0), v!(1), v!(1), v!(1), v!(1), v!(2), v!(1)],
+            &[v!(0), v!(1), v!(1), v!(1), v!(1), v!(2), v!(2)],
             // H-H-O, 1rst H
             &[v!(0), v!(1), v!(1), v!(1), v!(123456), v!(0), v!(0)],
             &[v!(0), v!(1), v!(1), v!(1), v!(123456), v!(0), v!(1)],
@@ -623,14 +1061,14 @@ mod tests {
             &[v!(0), v!(1), v!(1), v!(1), v!(123456), v!(2), v!(0)],
             &[v!(0), v!(1), v!(1), v!(1), v!(123456), v!(2), v!(1)],
             &[v!(0), v!(1), v!(1), v!(1), v!(123456), v!(2), v!(2)],
-            // H-H-H 1rst H
-            &[v!(0), v!(1), v!(1), v!(1), v!(1), v!(2), v!(0)],
-            &[v!(0), v!(1), v!(1), v!(1), v!(1), v!(2), v!(1)],
-            &[v!(0), v!(1), v!(1), v!(1), v!(1), v!(2), v!(2)],
-            // O-H-O, 2nd H
-            &[v!(0), v!(2), v!(1), v!(123456), v!(123456), v!(0), v!(0)],
-            &[v!(0), v!(2), v!(1), v!(123456), v!(123456), v!(0), v!(1)],
-            &[v!(0), v!(2), v!(1), v!(123456), v!(123456), v!(0), v!(2)],
+            // O-H-O, 1rst H
+            &[v!(0), v!(1), v!(1), v!(123456), v!(123456), v!(0), v!(0)],
+            &[v!(0), v!(1), v!(1), v!(123456), v!(123456), v!(0), v!(1)],
+            &[v!(0), v!(1), v!(1), v!(123456), v!(123456), v!(0), v!(2)],
+            // H-H-H, 2nd H
+            &[v!(0), v!(2), v!(1), v!(1), v!(1), v!(1), v!(0)],
+            &[v!(0), v!(2), v!(1), v!(1), v!(1), v!(1), v!(1)],
+            &[v!(0), v!(2), v!(1), v!(1), v!(1), v!(1), v!(2)],
             // H-H-O, 2nd H
             &[v!(0), v!(2), v!(1), v!(1), v!(123456), v!(0), v!(0)],
             &[v!(0), v!(2), v!(1), v!(1), v!(123456), v!(0), v!(1)],
@@ -638,10 +1076,155 @@ mod tests {
             &[v!(0), v!(2), v!(1), v!(1), v!(123456), v!(1), v!(0)],
             &[v!(0), v!(2), v!(1), v!(1), v!(123456), v!(1), v!(1)],
             &[v!(0), v!(2), v!(1), v!(1), v!(123456), v!(1), v!(2)],
-            // H-H-H 2nd H
+            // O-H-O, 2nd H
+            &[v!(0), v!(2), v!(1), v!(123456), v!(123456), v!(0), v!(0)],
+            &[v!(0), v!(2), v!(1), v!(123456), v!(123456), v!(0), v!(1)],
+            &[v!(0), v!(2), v!(1), v!(123456), v!(123456), v!(0), v!(2)]
+        ]);
+    }
+
+    #[test]
+    fn three_bodies_gradients_self_contribution() {
+        let mut systems = test_systems(&["CH"]);
+        let strategy = ThreeBodiesSpeciesEnvironment::with_self_contribution(2.0);
+        let (_, gradients) = strategy.with_gradients(&mut systems.get()).unwrap();
+        let gradients = gradients.unwrap();
+
+        assert_eq!(gradients.count(), 12);
+        assert_eq!(gradients.names(), &["structure", "center", "species_center", "species_neighbor_1", "species_neighbor_2", "neighbor", "spatial"]);
+        assert_eq!(gradients.iter().collect::<Vec<_>>(), vec![
+            // H-H-C, using the central atom as a neighbor
+            &[v!(0), v!(0), v!(1), v!(1), v!(6), v!(1), v!(0)],
+            &[v!(0), v!(0), v!(1), v!(1), v!(6), v!(1), v!(1)],
+            &[v!(0), v!(0), v!(1), v!(1), v!(6), v!(1), v!(2)],
+            // C-H-C
+            &[v!(0), v!(0), v!(1), v!(6), v!(6), v!(1), v!(0)],
+            &[v!(0), v!(0), v!(1), v!(6), v!(6), v!(1), v!(1)],
+            &[v!(0), v!(0), v!(1), v!(6), v!(6), v!(1), v!(2)],
+            // H-C-H
+            &[v!(0), v!(1), v!(6), v!(1), v!(1), v!(0), v!(0)],
+            &[v!(0), v!(1), v!(6), v!(1), v!(1), v!(0), v!(1)],
+            &[v!(0), v!(1), v!(6), v!(1), v!(1), v!(0), v!(2)],
+            // H-C-C, using the central atom as a neighbor
+            &[v!(0), v!(1), v!(6), v!(1), v!(6), v!(0), v!(0)],
+            &[v!(0), v!(1), v!(6), v!(1), v!(6), v!(0), v!(1)],
+            &[v!(0), v!(1), v!(6), v!(1), v!(6), v!(0), v!(2)],
+        ]);
+    }
+
+    #[test]
+    fn partial_three_bodies_gradient() {
+        let mut indexes = IndexesBuilder::new(vec!["structure", "center", "species_center", "species_neighbor_1", "species_neighbor_2"]);
+        indexes.add(&[v!(0), v!(1), v!(1), v!(123456), v!(123456)]);
+        indexes.add(&[v!(0), v!(2), v!(1), v!(1), v!(1)]);
+
+        let mut systems = test_systems(&["water"]);
+        let strategy = ThreeBodiesSpeciesEnvironment::new(2.0);
+        let gradients = strategy.gradients_for(&mut systems.get(), &indexes.finish()).unwrap();
+        let gradients = gradients.unwrap();
+
+        assert_eq!(gradients.names(), &["structure", "center", "species_center", "species_neighbor_1", "species_neighbor_2", "neighbor", "spatial"]);
+        assert_eq!(gradients.iter().collect::<Vec<_>>(), vec![
+            // O-H-O, 1rst H
+            &[v!(0), v!(1), v!(1), v!(123456), v!(123456), v!(0), v!(0)],
+            &[v!(0), v!(1), v!(1), v!(123456), v!(123456), v!(0), v!(1)],
+            &[v!(0), v!(1), v!(1), v!(123456), v!(123456), v!(0), v!(2)],
+            // H-H-H, 2nd H
             &[v!(0), v!(2), v!(1), v!(1), v!(1), v!(1), v!(0)],
             &[v!(0), v!(2), v!(1), v!(1), v!(1), v!(1), v!(1)],
-            &[v!(0), v!(2), v!(1), v!(1), v!(1), v!(1), v!(2)]
+            &[v!(0), v!(2), v!(1), v!(1), v!(1), v!(1), v!(2)],
+        ]);
+    }
+
+    #[test]
+    fn four_bodies() {
+        let mut systems = test_systems(&["CH", "water"]);
+        let strategy = FourBodiesSpeciesEnvironment::new(2.0);
+        let indexes = strategy.indexes(&mut systems.get()).unwrap();
+        assert_eq!(indexes.count(), 11);
+        assert_eq!(indexes.names(), &["structure", "center", "species_center", "species_neighbor_1", "species_neighbor_2", "species_neighbor_3"]);
+        assert_eq!(indexes.iter().collect::<Vec<_>>(), vec![
+            // C-C-H-C in CH
+            &[v!(0), v!(0), v!(1), v!(6), v!(6), v!(6)],
+            // H-H-C-H in CH
+            &[v!(0), v!(1), v!(6), v!(1), v!(1), v!(1)],
+            // H-H-O-H in water
+            &[v!(1), v!(0), v!(123456), v!(1), v!(1), v!(1)],
+            // first H in water
+            &[v!(1), v!(1), v!(1), v!(1), v!(1), v!(1)],
+            &[v!(1), v!(1), v!(1), v!(1), v!(1), v!(123456)],
+            &[v!(1), v!(1), v!(1), v!(1), v!(123456), v!(123456)],
+            &[v!(1), v!(1), v!(1), v!(123456), v!(123456), v!(123456)],
+            // second H in water
+            &[v!(1), v!(2), v!(1), v!(1), v!(1), v!(1)],
+            &[v!(1), v!(2), v!(1), v!(1), v!(1), v!(123456)],
+            &[v!(1), v!(2), v!(1), v!(1), v!(123456), v!(123456)],
+            &[v!(1), v!(2), v!(1), v!(123456), v!(123456), v!(123456)],
+        ]);
+    }
+
+    #[test]
+    fn four_bodies_self_contribution() {
+        let mut systems = test_systems(&["water"]);
+        // Only include O-H neighbors
+        let strategy = FourBodiesSpeciesEnvironment::with_self_contribution(1.2);
+        let indexes = strategy.indexes(&mut systems.get()).unwrap();
+        assert_eq!(indexes.count(), 12);
+        assert_eq!(indexes.iter().collect::<Vec<_>>(), vec![
+            // O in water, using the central atom as a neighbor
+            &[v!(0), v!(0), v!(123456), v!(1), v!(1), v!(1)],
+            &[v!(0), v!(0), v!(123456), v!(1), v!(1), v!(123456)],
+            &[v!(0), v!(0), v!(123456), v!(1), v!(123456), v!(123456)],
+            &[v!(0), v!(0), v!(123456), v!(123456), v!(123456), v!(123456)],
+            // first H in water, using the central atom as a neighbor
+            &[v!(0), v!(1), v!(1), v!(1), v!(1), v!(1)],
+            &[v!(0), v!(1), v!(1), v!(1), v!(1), v!(123456)],
+            &[v!(0), v!(1), v!(1), v!(1), v!(123456), v!(123456)],
+            &[v!(0), v!(1), v!(1), v!(123456), v!(123456), v!(123456)],
+            // second H in water, using the central atom as a neighbor
+            &[v!(0), v!(2), v!(1), v!(1), v!(1), v!(1)],
+            &[v!(0), v!(2), v!(1), v!(1), v!(1), v!(123456)],
+            &[v!(0), v!(2), v!(1), v!(1), v!(123456), v!(123456)],
+            &[v!(0), v!(2), v!(1), v!(123456), v!(123456), v!(123456)],
+        ]);
+    }
+
+    #[test]
+    fn four_bodies_gradients() {
+        let mut systems = test_systems(&["CH"]);
+        let strategy = FourBodiesSpeciesEnvironment::with_self_contribution(2.0);
+        let (_, gradients) = strategy.with_gradients(&mut systems.get()).unwrap();
+        let gradients = gradients.unwrap();
+
+        assert_eq!(gradients.count(), 18);
+        assert_eq!(gradients.names(), &["structure", "center", "species_center", "species_neighbor_1", "species_neighbor_2", "species_neighbor_3", "neighbor", "spatial"]);
+        assert_eq!(gradients.iter().collect::<Vec<_>>(), vec![
+            // H-H-H-H does not depend on the position of C
+            // H-H-H-C
+            &[v!(0), v!(0), v!(1), v!(1), v!(1), v!(6), v!(1), v!(0)],
+            &[v!(0), v!(0), v!(1), v!(1), v!(1), v!(6), v!(1), v!(1)],
+            &[v!(0), v!(0), v!(1), v!(1), v!(1), v!(6), v!(1), v!(2)],
+            // H-H-C-C
+            &[v!(0), v!(0), v!(1), v!(1), v!(6), v!(6), v!(1), v!(0)],
+            &[v!(0), v!(0), v!(1), v!(1), v!(6), v!(6), v!(1), v!(1)],
+            &[v!(0), v!(0), v!(1), v!(1), v!(6), v!(6), v!(1), v!(2)],
+            // C-H-C-C
+            &[v!(0), v!(0), v!(1), v!(6), v!(6), v!(6), v!(1), v!(0)],
+            &[v!(0), v!(0), v!(1), v!(6), v!(6), v!(6), v!(1), v!(1)],
+            &[v!(0), v!(0), v!(1), v!(6), v!(6), v!(6), v!(1), v!(2)],
+            // H-C-H-H
+            &[v!(0), v!(1), v!(6), v!(1), v!(1), v!(1), v!(0), v!(0)],
+            &[v!(0), v!(1), v!(6), v!(1), v!(1), v!(1), v!(0), v!(1)],
+            &[v!(0), v!(1), v!(6), v!(1), v!(1), v!(1), v!(0), v!(2)],
+            // H-C-H-C
+            &[v!(0), v!(1), v!(6), v!(1), v!(1), v!(6), v!(0), v!(0)],
+            &[v!(0), v!(1), v!(6), v!(1), v!(1), v!(6), v!(0), v!(1)],
+            &[v!(0), v!(1), v!(6), v!(1), v!(1), v!(6), v!(0), v!(2)],
+            // H-C-C-C
+            &[v!(0), v!(1), v!(6), v!(1), v!(6), v!(6), v!(0), v!(0)],
+            &[v!(0), v!(1), v!(6), v!(1), v!(6), v!(6), v!(0), v!(1)],
+            &[v!(0), v!(1), v!(6), v!(1), v!(6), v!(6), v!(0), v!(2)],
+            // C-C-C-C does not depend on the position of H
         ]);
     }
 }
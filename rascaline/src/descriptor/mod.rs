@@ -1,10 +1,20 @@
 mod indexes;
-pub use self::indexes::{Indexes, IndexesBuilder, IndexValue};
+pub use self::indexes::{Indexes, IndexesBuilder, IndexValue, IndexType};
 pub use self::indexes::EnvironmentIndexes;
 pub use self::indexes::{StructureEnvironment, AtomEnvironment};
-pub use self::indexes::{StructureSpeciesEnvironment, AtomSpeciesEnvironment};
-pub use self::indexes::{ThreeBodiesSpeciesEnvironment};
+pub use self::indexes::{StructureSpeciesEnvironment, AtomSpeciesEnvironment, CenterSpeciesEnvironment};
+pub use self::indexes::LongRangeSpeciesEnvironment;
+pub use self::indexes::{ThreeBodiesSpeciesEnvironment, FourBodiesSpeciesEnvironment};
 
 #[allow(clippy::module_inception)]
 mod descriptor;
 pub use self::descriptor::Descriptor;
+
+mod npz;
+mod slicing;
+
+mod sparse;
+pub use self::sparse::{SparseGradients, SparseGradientsBlock};
+
+mod blocks;
+pub use self::blocks::{BlockDescriptor, DescriptorBlock};
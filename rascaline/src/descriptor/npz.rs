@@ -0,0 +1,640 @@
+use std::convert::TryFrom;
+use std::io::{Read, Write, Seek};
+use std::fs::File;
+use std::path::Path;
+
+use ndarray::{Array2, ShapeBuilder};
+use zip::{ZipArchive, ZipWriter, CompressionMethod};
+use zip::result::ZipError;
+use zip::write::FileOptions;
+
+use crate::Error;
+use super::{Descriptor, Indexes, IndexesBuilder, IndexValue, IndexType};
+use super::indexes::is_valid_ident;
+
+const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+
+/// Error happening while reading or writing a `.npz` archive
+#[derive(Debug)]
+enum NpzError {
+    /// Failed to read or write the underlying file
+    Io(std::io::Error),
+    /// The archive content is invalid or not supported
+    Format(String),
+}
+
+impl NpzError {
+    /// Convert this error to `Error`, prefixing the message with `context`.
+    /// I/O failures become `Error::Io` and invalid content becomes
+    /// `Error::InvalidParameter`.
+    fn into_error(self, context: String) -> Error {
+        match self {
+            NpzError::Io(error) => Error::Io(std::io::Error::new(
+                error.kind(), format!("{}: {}", context, error)
+            )),
+            NpzError::Format(message) => Error::InvalidParameter(
+                format!("{}: {}", context, message)
+            ),
+        }
+    }
+}
+
+impl From<String> for NpzError {
+    fn from(message: String) -> NpzError {
+        NpzError::Format(message)
+    }
+}
+
+impl From<std::io::Error> for NpzError {
+    fn from(error: std::io::Error) -> NpzError {
+        NpzError::Io(error)
+    }
+}
+
+impl From<ZipError> for NpzError {
+    fn from(error: ZipError) -> NpzError {
+        match error {
+            ZipError::Io(error) => NpzError::Io(error),
+            error => NpzError::Format(error.to_string()),
+        }
+    }
+}
+
+impl Descriptor {
+    /// Save this descriptor to the file at `path`, using numpy's `.npz`
+    /// format. The archive contains the `values`, `gradients` and
+    /// `cell_gradients` arrays as 2D arrays of `float64`; and the
+    /// `environments`, `features`, `gradients_indexes` and
+    /// `cell_gradients_indexes` as structured arrays, where the field names
+    /// are the names of the indexes and the fields are `int64` or `float64`
+    /// depending on the type of the indexes. Gradients are only stored if they
+    /// are present in the descriptor.
+    ///
+    /// The file can be loaded with `Descriptor::load`, or with `numpy.load`
+    /// from Python.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let file = File::create(path).map_err(|error| Error::Io(std::io::Error::new(
+            error.kind(), format!("failed to create '{}': {}", path.display(), error)
+        )))?;
+        return self.write_npz(file).map_err(|error| {
+            error.into_error(format!("failed to save descriptor to '{}'", path.display()))
+        });
+    }
+
+    /// Load a descriptor saved with `Descriptor::save` from the file at
+    /// `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Descriptor, Error> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|error| Error::Io(std::io::Error::new(
+            error.kind(), format!("failed to open '{}': {}", path.display(), error)
+        )))?;
+        return Descriptor::read_npz(file).map_err(|error| {
+            error.into_error(format!("failed to load descriptor from '{}'", path.display()))
+        });
+    }
+
+    fn write_npz<W: Write + Seek>(&self, writer: W) -> Result<(), NpzError> {
+        if self.sparse_gradients.is_some() {
+            return Err(NpzError::Format("can not save a descriptor with sparse gradients".into()));
+        }
+
+        let mut npz = NpzWriter {
+            zip: ZipWriter::new(writer),
+        };
+
+        npz.add_array("values", &self.values)?;
+        npz.add_indexes("environments", &self.environments)?;
+        npz.add_indexes("features", &self.features)?;
+
+        if let (Some(gradients), Some(indexes)) = (&self.gradients, &self.gradients_indexes) {
+            npz.add_array("gradients", gradients)?;
+            npz.add_indexes("gradients_indexes", indexes)?;
+        }
+
+        if let (Some(gradients), Some(indexes)) = (&self.cell_gradients, &self.cell_gradients_indexes) {
+            npz.add_array("cell_gradients", gradients)?;
+            npz.add_indexes("cell_gradients_indexes", indexes)?;
+        }
+
+        npz.zip.finish()?;
+        return Ok(());
+    }
+
+    fn read_npz<R: Read + Seek>(reader: R) -> Result<Descriptor, NpzError> {
+        let mut npz = NpzReader {
+            zip: ZipArchive::new(reader)?,
+        };
+
+        let mut descriptor = Descriptor::new();
+        descriptor.values = npz.array("values")?;
+        descriptor.environments = npz.indexes("environments")?;
+        descriptor.features = npz.indexes("features")?;
+
+        if npz.contains("gradients") {
+            descriptor.gradients = Some(npz.array("gradients")?);
+            descriptor.gradients_indexes = Some(npz.indexes("gradients_indexes")?);
+        }
+
+        if npz.contains("cell_gradients") {
+            descriptor.cell_gradients = Some(npz.array("cell_gradients")?);
+            descriptor.cell_gradients_indexes = Some(npz.indexes("cell_gradients_indexes")?);
+        }
+
+        let shape = (descriptor.environments.count(), descriptor.features.count());
+        if descriptor.values.dim() != shape {
+            return Err(format!(
+                "values shape is {:?}, but expected {:?} from environments and features",
+                descriptor.values.dim(), shape
+            ).into());
+        }
+
+        for (gradients, indexes) in &[
+            (&descriptor.gradients, &descriptor.gradients_indexes),
+            (&descriptor.cell_gradients, &descriptor.cell_gradients_indexes),
+        ] {
+            if let (Some(gradients), Some(indexes)) = (gradients, indexes) {
+                if gradients.dim() != (indexes.count(), descriptor.features.count()) {
+                    return Err(format!(
+                        "gradients shape is {:?}, but expected {:?} from gradients indexes and features",
+                        gradients.dim(), (indexes.count(), descriptor.features.count())
+                    ).into());
+                }
+            }
+        }
+
+        return Ok(descriptor);
+    }
+}
+
+/// Write arrays in a zip archive, using the same format as `numpy.savez`
+struct NpzWriter<W: Write + Seek> {
+    zip: ZipWriter<W>,
+}
+
+impl<W: Write + Seek> NpzWriter<W> {
+    /// Add a 2D array of `float64` with the given `name` to the archive
+    fn add_array(&mut self, name: &str, array: &Array2<f64>) -> Result<(), NpzError> {
+        let (rows, columns) = array.dim();
+        let header = format!(
+            "{{'descr': '<f8', 'fortran_order': False, 'shape': ({}, {}), }}",
+            rows, columns
+        );
+        return self.add_npy(name, &header, array.iter().map(|value| value.to_le_bytes()));
+    }
+
+    /// Add the `indexes` as a 1D structured array with the given `name` to
+    /// the archive
+    fn add_indexes(&mut self, name: &str, indexes: &Indexes) -> Result<(), NpzError> {
+        let descr = indexes.names().iter().zip(indexes.types())
+            .map(|(name, &index_type)| format!("('{}', '{}')", name, npy_type(index_type)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let header = format!(
+            "{{'descr': [{}], 'fortran_order': False, 'shape': ({},), }}",
+            descr, indexes.count()
+        );
+        let values = (0..indexes.count()).flat_map(|i| indexes[i].iter().map(|value| match *value {
+            IndexValue::Int(value) => value.to_le_bytes(),
+            IndexValue::Float(value) => value.to_le_bytes(),
+        }));
+        return self.add_npy(name, &header, values);
+    }
+
+    /// Add a single `.npy` file to the archive, containing the given
+    /// `header` and `values`, given as little-endian bytes
+    fn add_npy(&mut self, name: &str, header: &str, values: impl Iterator<Item=[u8; 8]>) -> Result<(), NpzError> {
+        // numpy does not compress the data in `savez`
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+        self.zip.start_file(format!("{}.npy", name), options)?;
+
+        // the header is padded with spaces and terminated by a newline, such
+        // that the data starts at a multiple of 64 bytes
+        let mut header = header.to_owned();
+        let unpadded = NPY_MAGIC.len() + 2 + 2 + header.len() + 1;
+        for _ in 0..((64 - unpadded % 64) % 64) {
+            header.push(' ');
+        }
+        header.push('\n');
+
+        let header_len = u16::try_from(header.len()).map_err(|_| "npy header is too large".to_owned())?;
+
+        let mut buffer = Vec::with_capacity(NPY_MAGIC.len() + 4 + header.len());
+        buffer.extend_from_slice(NPY_MAGIC);
+        buffer.extend_from_slice(&[1, 0]);
+        buffer.extend_from_slice(&header_len.to_le_bytes());
+        buffer.extend_from_slice(header.as_bytes());
+        for value in values {
+            buffer.extend_from_slice(&value);
+        }
+
+        self.zip.write_all(&buffer)?;
+        return Ok(());
+    }
+}
+
+/// Get the numpy type string corresponding to `index_type`
+fn npy_type(index_type: IndexType) -> &'static str {
+    match index_type {
+        IndexType::Int => "<i8",
+        IndexType::Float => "<f8",
+    }
+}
+
+/// Data type of an array in a `.npy` file
+enum NpyType {
+    /// A simple array of `float64`
+    Float64,
+    /// A structured array where all the fields are either `int64` or
+    /// `float64`, containing the name and type of all fields
+    Structured(Vec<(String, IndexType)>),
+}
+
+/// Content of a `.npy` file
+struct Npy {
+    dtype: NpyType,
+    fortran_order: bool,
+    shape: Vec<usize>,
+    /// little-endian bytes for all the values in the array
+    data: Vec<[u8; 8]>,
+}
+
+/// Read arrays from a zip archive created by `numpy.savez` or `NpzWriter`
+struct NpzReader<R: Read + Seek> {
+    zip: ZipArchive<R>,
+}
+
+impl<R: Read + Seek> NpzReader<R> {
+    /// Check if the archive contains an array with the given `name`
+    fn contains(&self, name: &str) -> bool {
+        let name = format!("{}.npy", name);
+        return self.zip.file_names().any(|file| file == name);
+    }
+
+    /// Read the 2D array of `float64` with the given `name`
+    fn array(&mut self, name: &str) -> Result<Array2<f64>, NpzError> {
+        let npy = self.npy(name)?;
+        if !matches!(npy.dtype, NpyType::Float64) {
+            return Err(format!("expected '{}' to be an array of float64", name).into());
+        }
+
+        if npy.shape.len() != 2 {
+            return Err(format!("expected '{}' to be a 2D array, got shape {:?}", name, npy.shape).into());
+        }
+
+        let shape = (npy.shape[0], npy.shape[1]).set_f(npy.fortran_order);
+        let data = npy.data.into_iter().map(f64::from_le_bytes).collect();
+        let array = Array2::from_shape_vec(shape, data).map_err(|error| error.to_string())?;
+        // make sure the data is in row-major order
+        return Ok(array.as_standard_layout().into_owned());
+    }
+
+    /// Read the structured array with the given `name` as `Indexes`
+    fn indexes(&mut self, name: &str) -> Result<Indexes, NpzError> {
+        let npy = self.npy(name)?;
+        let fields = match &npy.dtype {
+            NpyType::Structured(fields) => fields,
+            NpyType::Float64 => return Err(format!("expected '{}' to be a structured array", name).into()),
+        };
+
+        if npy.shape.len() != 1 {
+            return Err(format!("expected '{}' to be a 1D array, got shape {:?}", name, npy.shape).into());
+        }
+
+        let mut names = Vec::with_capacity(fields.len());
+        for (field, _) in fields {
+            if !is_valid_ident(field) {
+                return Err(format!("invalid index name '{}' in '{}'", field, name).into());
+            }
+
+            if names.contains(&&**field) {
+                return Err(format!("the '{}' index is present multiple times in '{}'", field, name).into());
+            }
+            names.push(&**field);
+        }
+        let types = fields.iter().map(|&(_, index_type)| index_type).collect();
+        let mut indexes = IndexesBuilder::with_types(names, types);
+        if !fields.is_empty() {
+            for chunk in npy.data.chunks_exact(fields.len()) {
+                let values = chunk.iter().zip(fields).map(|(&bytes, &(_, index_type))| match index_type {
+                    IndexType::Int => Ok(IndexValue::from(i64::from_le_bytes(bytes))),
+                    IndexType::Float => {
+                        let value = f64::from_le_bytes(bytes);
+                        if value.is_nan() {
+                            return Err(format!("got NaN in '{}'", name).into());
+                        }
+                        Ok(IndexValue::from(value))
+                    }
+                }).collect::<Result<Vec<_>, String>>()?;
+
+                if indexes.contains(&values) {
+                    return Err(format!(
+                        "[{}] is present multiple times in '{}'",
+                        values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", "), name
+                    ).into());
+                }
+                indexes.add(&values);
+            }
+        }
+
+        return Ok(indexes.finish());
+    }
+
+    /// Read and parse the `.npy` file for the array with the given `name`
+    fn npy(&mut self, name: &str) -> Result<Npy, NpzError> {
+        let mut file = self.zip.by_name(&format!("{}.npy", name)).map_err(|error| match error {
+            ZipError::Io(error) => NpzError::Io(error),
+            error => NpzError::Format(format!("failed to read '{}': {}", name, error)),
+        })?;
+        let mut buffer = Vec::new();
+        file.read_to_end(&mut buffer).map_err(|error| match error.kind() {
+            // invalid compressed data or checksum mismatch
+            std::io::ErrorKind::InvalidData => NpzError::Format(format!("failed to read '{}': {}", name, error)),
+            _ => NpzError::Io(error),
+        })?;
+
+        if buffer.len() < NPY_MAGIC.len() + 4 || &buffer[..NPY_MAGIC.len()] != NPY_MAGIC {
+            return Err(format!("'{}' is not a npy file", name).into());
+        }
+
+        let major = buffer[NPY_MAGIC.len()];
+        let start = NPY_MAGIC.len() + 2;
+        let (header_start, header_len) = match major {
+            1 => (start + 2, u16::from_le_bytes([buffer[start], buffer[start + 1]]) as usize),
+            2 | 3 if buffer.len() >= start + 4 => {
+                let bytes = [buffer[start], buffer[start + 1], buffer[start + 2], buffer[start + 3]];
+                (start + 4, u32::from_le_bytes(bytes) as usize)
+            }
+            _ => return Err(format!("unsupported npy format version {} in '{}'", major, name).into()),
+        };
+
+        let data_start = header_start + header_len;
+        if buffer.len() < data_start {
+            return Err(format!("'{}' is not a npy file", name).into());
+        }
+
+        let header = std::str::from_utf8(&buffer[header_start..data_start])
+            .map_err(|_| format!("invalid npy header in '{}'", name))?;
+        let mut npy = parse_npy_header(header).map_err(|error| {
+            format!("invalid npy header in '{}': {}", name, error)
+        })?;
+
+        let n_fields = match &npy.dtype {
+            NpyType::Float64 => 1,
+            NpyType::Structured(fields) => fields.len(),
+        };
+        let expected = npy.shape.iter()
+            .try_fold(n_fields, |count, &size| count.checked_mul(size))
+            .ok_or_else(|| format!("shape {:?} of '{}' is too large", npy.shape, name))?;
+        let data = &buffer[data_start..];
+        if Some(data.len()) != expected.checked_mul(8) {
+            return Err(format!("expected {} values in '{}', got {} bytes of data", expected, name, data.len()).into());
+        }
+
+        npy.data = data.chunks_exact(8).map(|chunk| {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(chunk);
+            bytes
+        }).collect();
+
+        return Ok(npy);
+    }
+}
+
+/// Parse the python dictionary in a `.npy` file header. Only little-endian
+/// `float64` arrays and structured arrays containing little-endian `int64` or
+/// `float64` fields are supported.
+fn parse_npy_header(header: &str) -> Result<Npy, String> {
+    let descr = header_entry(header, "descr")?;
+    let dtype = if descr.starts_with('[') {
+        let fields = descr.trim_start_matches('[').trim_end_matches(']');
+        let mut parsed = Vec::new();
+        for field in fields.split(')') {
+            let field = field.trim().trim_start_matches(',').trim();
+            if field.is_empty() {
+                continue;
+            }
+
+            let field = field.trim_start_matches('(');
+            let mut parts = field.split(',').map(str::trim);
+            let name = parts.next().map(unquote).unwrap_or_default();
+            let field_type = parts.next().map(unquote).unwrap_or_default();
+            let index_type = match field_type {
+                "<i8" => IndexType::Int,
+                "<f8" => IndexType::Float,
+                _ => return Err(format!("unsupported field ({}) in structured array", field)),
+            };
+            if parts.next().is_some() {
+                return Err(format!("unsupported field ({}) in structured array", field));
+            }
+            parsed.push((name.to_owned(), index_type));
+        }
+        NpyType::Structured(parsed)
+    } else if unquote(descr) == "<f8" {
+        NpyType::Float64
+    } else {
+        return Err(format!("unsupported data type {}", descr));
+    };
+
+    let fortran_order = match header_entry(header, "fortran_order")? {
+        "False" => false,
+        "True" => true,
+        other => return Err(format!("invalid value for fortran_order: {}", other)),
+    };
+
+    let shape = header_entry(header, "shape")?;
+    let shape = shape.trim_start_matches('(').trim_end_matches(')')
+        .split(',')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(|value| value.parse().map_err(|_| format!("invalid shape {}", shape)))
+        .collect::<Result<Vec<usize>, _>>()?;
+
+    return Ok(Npy {
+        dtype: dtype,
+        fortran_order: fortran_order,
+        shape: shape,
+        data: Vec::new(),
+    });
+}
+
+/// Get the value associated with `key` in the python dictionary `header`
+fn header_entry<'a>(header: &'a str, key: &str) -> Result<&'a str, String> {
+    let key = format!("'{}':", key);
+    let start = header.find(&key).ok_or_else(|| format!("missing {}", key))? + key.len();
+    let value = header[start..].trim_start();
+
+    let mut depth = 0;
+    let mut in_string = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            '\'' | '"' => in_string = !in_string,
+            '(' | '[' if !in_string => depth += 1,
+            ')' | ']' if !in_string => depth -= 1,
+            ',' | '}' if !in_string && depth == 0 => return Ok(value[..i].trim()),
+            _ => {}
+        }
+    }
+
+    return Err(format!("unterminated value for {}", key));
+}
+
+/// Remove python quotes around `value`
+fn unquote(value: &str) -> &str {
+    value.trim_matches(|c| c == '\'' || c == '"')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::{Calculator, CalculationOptions};
+    use crate::system::test_systems;
+
+    fn check_round_trip(descriptor: &Descriptor) {
+        let mut buffer = Cursor::new(Vec::new());
+        descriptor.write_npz(&mut buffer).unwrap();
+        buffer.set_position(0);
+        let loaded = Descriptor::read_npz(buffer).unwrap();
+
+        assert_eq!(loaded.values, descriptor.values);
+        assert!(loaded.environments == descriptor.environments);
+        assert!(loaded.features == descriptor.features);
+        assert_eq!(loaded.gradients, descriptor.gradients);
+        assert!(loaded.gradients_indexes == descriptor.gradients_indexes);
+        assert_eq!(loaded.cell_gradients, descriptor.cell_gradients);
+        assert!(loaded.cell_gradients_indexes == descriptor.cell_gradients_indexes);
+    }
+
+    #[test]
+    fn round_trip() {
+        check_round_trip(&Descriptor::new());
+
+        let mut calculator = Calculator::new("spherical_expansion", r#"{
+            "cutoff": 3.5,
+            "max_radial": 3,
+            "max_angular": 2,
+            "atomic_gaussian_width": 0.3,
+            "gradients": true,
+            "cell_gradients": true,
+            "radial_basis": "GTO",
+            "cutoff_function": {"ShiftedCosine": {"width": 0.5}}
+        }"#.into()).unwrap();
+
+        let mut systems = test_systems(&["water", "CH"]);
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut descriptor, CalculationOptions::default()).unwrap();
+        check_round_trip(&descriptor);
+
+        descriptor.densify(vec!["species_neighbor"]).unwrap();
+        descriptor.gradients = None;
+        descriptor.gradients_indexes = None;
+        check_round_trip(&descriptor);
+    }
+
+    #[test]
+    fn save_load() {
+        let mut calculator = Calculator::new("dummy_calculator", r#"{
+            "cutoff": 3.0,
+            "delta": 5,
+            "name": "",
+            "gradients": true
+        }"#.into()).unwrap();
+
+        let mut systems = test_systems(&["water"]);
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut descriptor, CalculationOptions::default()).unwrap();
+
+        let path = std::env::temp_dir().join("rascaline-save-load.npz");
+        descriptor.save(&path).unwrap();
+        let loaded = Descriptor::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.values, descriptor.values);
+        assert!(loaded.environments == descriptor.environments);
+        assert!(loaded.features == descriptor.features);
+        assert_eq!(loaded.gradients, descriptor.gradients);
+        assert!(loaded.gradients_indexes == descriptor.gradients_indexes);
+    }
+
+    #[test]
+    fn errors() {
+        let descriptor = Descriptor::new();
+        let error = descriptor.save("/not/a/directory/descriptor.npz").err().unwrap();
+        assert!(matches!(error, Error::Io(_)));
+
+        let mut buffer = Cursor::new(Vec::new());
+        match Descriptor::read_npz(&mut buffer).err().unwrap() {
+            NpzError::Format(message) => assert_eq!(message, "invalid Zip archive"),
+            NpzError::Io(error) => panic!("unexpected I/O error: {}", error),
+        }
+
+        let path = std::env::temp_dir().join("rascaline-invalid.npz");
+        std::fs::write(&path, b"this is not a zip archive").unwrap();
+        let error = Descriptor::load(&path).err().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(error, Error::InvalidParameter(_)));
+    }
+
+    /// Create an archive containing a single structured array named
+    /// `environments` with the given `descr`, `shape` and `values`
+    fn single_indexes_npz(descr: &str, shape: &str, values: &[i64]) -> Cursor<Vec<u8>> {
+        let mut npz = NpzWriter {
+            zip: ZipWriter::new(Cursor::new(Vec::new())),
+        };
+        let header = format!("{{'descr': {}, 'fortran_order': False, 'shape': {}, }}", descr, shape);
+        npz.add_npy("environments", &header, values.iter().map(|v| v.to_le_bytes())).unwrap();
+        let mut buffer = npz.zip.finish().unwrap();
+        buffer.set_position(0);
+        return buffer;
+    }
+
+    fn read_indexes_error(buffer: Cursor<Vec<u8>>) -> String {
+        let mut npz = NpzReader {
+            zip: ZipArchive::new(buffer).unwrap(),
+        };
+        match npz.indexes("environments").err().unwrap() {
+            NpzError::Format(message) => message,
+            NpzError::Io(error) => panic!("unexpected I/O error: {}", error),
+        }
+    }
+
+    #[test]
+    fn invalid_indexes() {
+        let buffer = single_indexes_npz("[('center', '<i8'), ('center', '<i8')]", "(1,)", &[0, 1]);
+        assert_eq!(read_indexes_error(buffer), "the 'center' index is present multiple times in 'environments'");
+
+        let buffer = single_indexes_npz("[('33 center', '<i8')]", "(1,)", &[0]);
+        assert_eq!(read_indexes_error(buffer), "invalid index name '33 center' in 'environments'");
+
+        let buffer = single_indexes_npz("[('structure', '<i8'), ('center', '<i8')]", "(2,)", &[0, 1, 0, 1]);
+        assert_eq!(read_indexes_error(buffer), "[0, 1] is present multiple times in 'environments'");
+
+        let buffer = single_indexes_npz("[('center', '<i8')]", "(4294967296, 4294967296)", &[]);
+        assert_eq!(read_indexes_error(buffer), "shape [4294967296, 4294967296] of 'environments' is too large");
+    }
+
+    #[test]
+    fn header() {
+        let npy = parse_npy_header(
+            "{'descr': [('structure', '<i8'), ('distance', '<f8')], 'fortran_order': False, 'shape': (5,), }"
+        ).unwrap();
+        match npy.dtype {
+            NpyType::Structured(fields) => assert_eq!(fields, [
+                ("structure".to_owned(), IndexType::Int),
+                ("distance".to_owned(), IndexType::Float),
+            ]),
+            NpyType::Float64 => panic!("expected a structured array"),
+        }
+        assert!(!npy.fortran_order);
+        assert_eq!(npy.shape, [5]);
+
+        let npy = parse_npy_header("{'descr': '<f8', 'fortran_order': True, 'shape': (3, 4), }").unwrap();
+        assert!(matches!(npy.dtype, NpyType::Float64));
+        assert!(npy.fortran_order);
+        assert_eq!(npy.shape, [3, 4]);
+
+        let error = parse_npy_header("{'descr': '<i4', 'fortran_order': False, 'shape': (3,), }").err().unwrap();
+        assert_eq!(error, "unsupported data type '<i4'");
+    }
+}
@@ -0,0 +1,492 @@
+use ndarray::{Array2, ArrayView2, Axis};
+
+use crate::Error;
+use super::{Descriptor, Indexes, IndexesBuilder, IndexType, IndexValue};
+
+impl Descriptor {
+    /// Extract a new descriptor containing a subset of the samples and
+    /// features of this descriptor. Use `None` to keep all the samples or all
+    /// the features.
+    ///
+    /// The selections can contain only some of the samples/features indexes,
+    /// in which case all the entries matching the selection on these indexes
+    /// are kept: for example using a selection with a single `structure`
+    /// index will keep all samples for the given structures. The selected
+    /// entries are kept in the same order as in this descriptor, and the
+    /// gradients rows are updated to only contain gradients of the selected
+    /// samples.
+    pub fn select(&self, samples: Option<&Indexes>, features: Option<&Indexes>) -> Result<Descriptor, Error> {
+        if self.sparse_gradients.is_some() {
+            return Err(Error::InvalidParameter(
+                "can not select from a descriptor with sparse gradients".into()
+            ));
+        }
+
+        let samples = selected_entries(&self.environments, samples, "samples")?;
+        let features = selected_entries(&self.features, features, "features")?;
+
+        let environments = indexes_subset(&self.environments, &samples);
+        let values = self.values.select(Axis(0), &samples).select(Axis(1), &features);
+
+        let (gradients, gradients_indexes) = select_gradients(
+            self.gradients.as_ref(), self.gradients_indexes.as_ref(), &environments, &features
+        );
+        let (cell_gradients, cell_gradients_indexes) = select_gradients(
+            self.cell_gradients.as_ref(), self.cell_gradients_indexes.as_ref(), &environments, &features
+        );
+
+        return Ok(Descriptor {
+            values: values,
+            environments: environments,
+            features: indexes_subset(&self.features, &features),
+            gradients: gradients,
+            gradients_indexes: gradients_indexes,
+            sparse_gradients: None,
+            cell_gradients: cell_gradients,
+            cell_gradients_indexes: cell_gradients_indexes,
+        });
+    }
+
+    /// Concatenate multiple `descriptors` along the samples. All descriptors
+    /// must have the same features, and either all or none of them must
+    /// contain gradients.
+    ///
+    /// If the samples contain a `structure` index, it is renumbered so that
+    /// the structures in each descriptor come after the structures of the
+    /// previous descriptors: this allows to merge descriptors computed on
+    /// separate batches of systems. Otherwise, the same sample can not be
+    /// present in multiple descriptors.
+    pub fn concatenate_samples(descriptors: &[&Descriptor]) -> Result<Descriptor, Error> {
+        let first = if let Some(first) = descriptors.first() {
+            first
+        } else {
+            return Ok(Descriptor::new());
+        };
+
+        for descriptor in descriptors {
+            check_concatenate(first, descriptor, "samples")?;
+            if descriptor.features != first.features {
+                return Err(Error::InvalidParameter(
+                    "can not concatenate descriptors along samples: all descriptors must have the same features".into()
+                ));
+            }
+
+            check_same_indexes(&first.environments, &descriptor.environments, "samples", "samples")?;
+            if let (Some(first), Some(indexes)) = (&first.gradients_indexes, &descriptor.gradients_indexes) {
+                check_same_indexes(first, indexes, "samples", "gradients")?;
+            }
+            if let (Some(first), Some(indexes)) = (&first.cell_gradients_indexes, &descriptor.cell_gradients_indexes) {
+                check_same_indexes(first, indexes, "samples", "cell gradients")?;
+            }
+        }
+
+        let mut environments = empty_builder(&first.environments);
+        let mut gradients_indexes = first.gradients_indexes.as_ref().map(empty_builder);
+        let mut cell_gradients_indexes = first.cell_gradients_indexes.as_ref().map(empty_builder);
+
+        let mut structures_offset = 0;
+        for descriptor in descriptors {
+            add_renumbered(&mut environments, &descriptor.environments, structures_offset)?;
+            if let (Some(builder), Some(indexes)) = (&mut gradients_indexes, &descriptor.gradients_indexes) {
+                add_renumbered(builder, indexes, structures_offset)?;
+            }
+            if let (Some(builder), Some(indexes)) = (&mut cell_gradients_indexes, &descriptor.cell_gradients_indexes) {
+                add_renumbered(builder, indexes, structures_offset)?;
+            }
+
+            structures_offset += structures_count(&descriptor.environments);
+        }
+
+        let values = concatenate(Axis(0), descriptors.iter().map(|d| d.values.view()));
+        let gradients = gradients_indexes.as_ref().map(|_| concatenate(Axis(0), descriptors.iter().map(|d| {
+            d.gradients.as_ref().expect("missing gradients").view()
+        })));
+        let cell_gradients = cell_gradients_indexes.as_ref().map(|_| concatenate(Axis(0), descriptors.iter().map(|d| {
+            d.cell_gradients.as_ref().expect("missing cell gradients").view()
+        })));
+
+        return Ok(Descriptor {
+            values: values,
+            environments: environments.finish(),
+            features: first.features.clone(),
+            gradients: gradients,
+            gradients_indexes: gradients_indexes.map(IndexesBuilder::finish),
+            sparse_gradients: None,
+            cell_gradients: cell_gradients,
+            cell_gradients_indexes: cell_gradients_indexes.map(IndexesBuilder::finish),
+        });
+    }
+
+    /// Concatenate multiple `descriptors` along the features. All descriptors
+    /// must have the same samples and gradients indexes, and the same
+    /// feature can not be present in multiple descriptors.
+    pub fn concatenate_features(descriptors: &[&Descriptor]) -> Result<Descriptor, Error> {
+        let first = if let Some(first) = descriptors.first() {
+            first
+        } else {
+            return Ok(Descriptor::new());
+        };
+
+        for descriptor in descriptors {
+            check_concatenate(first, descriptor, "features")?;
+            if descriptor.environments != first.environments ||
+               descriptor.gradients_indexes != first.gradients_indexes ||
+               descriptor.cell_gradients_indexes != first.cell_gradients_indexes {
+                return Err(Error::InvalidParameter(
+                    "can not concatenate descriptors along features: all descriptors must have the same samples and gradients".into()
+                ));
+            }
+
+            check_same_indexes(&first.features, &descriptor.features, "features", "features")?;
+        }
+
+        let mut features = empty_builder(&first.features);
+        for descriptor in descriptors {
+            add_renumbered(&mut features, &descriptor.features, 0)?;
+        }
+
+        let values = concatenate(Axis(1), descriptors.iter().map(|d| d.values.view()));
+        let gradients = first.gradients.as_ref().map(|_| concatenate(Axis(1), descriptors.iter().map(|d| {
+            d.gradients.as_ref().expect("missing gradients").view()
+        })));
+        let cell_gradients = first.cell_gradients.as_ref().map(|_| concatenate(Axis(1), descriptors.iter().map(|d| {
+            d.cell_gradients.as_ref().expect("missing cell gradients").view()
+        })));
+
+        return Ok(Descriptor {
+            values: values,
+            environments: first.environments.clone(),
+            features: features.finish(),
+            gradients: gradients,
+            gradients_indexes: first.gradients_indexes.clone(),
+            sparse_gradients: None,
+            cell_gradients: cell_gradients,
+            cell_gradients_indexes: first.cell_gradients_indexes.clone(),
+        });
+    }
+}
+
+/// Get the position of all entries in `indexes` matching the `selection`, or
+/// all entries if `selection` is `None`. `kind` is used in error messages.
+fn selected_entries(indexes: &Indexes, selection: Option<&Indexes>, kind: &str) -> Result<Vec<usize>, Error> {
+    let selection = if let Some(selection) = selection {
+        selection
+    } else {
+        return Ok((0..indexes.count()).collect());
+    };
+
+    let names = indexes.names();
+    let columns = selection.names().iter()
+        .map(|name| names.iter().position(|n| n == name).ok_or_else(|| Error::InvalidParameter(format!(
+            "can not select {} using '{}', which is not part of the {} indexes: [{}]",
+            kind, name, kind, names.join(", ")
+        ))))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut selected = Vec::new();
+    let mut value = Vec::with_capacity(columns.len());
+    for (i, entry) in indexes.iter().enumerate() {
+        value.clear();
+        value.extend(columns.iter().map(|&c| entry[c]));
+        if selection.contains(&value) {
+            selected.push(i);
+        }
+    }
+
+    return Ok(selected);
+}
+
+/// Create new indexes containing only the given `entries` of `indexes`
+fn indexes_subset(indexes: &Indexes, entries: &[usize]) -> Indexes {
+    let mut builder = empty_builder(indexes);
+    for &i in entries {
+        builder.add(&indexes[i]);
+    }
+    return builder.finish();
+}
+
+/// Keep the rows of `gradients` corresponding to one of the `environments`,
+/// and the columns corresponding to `features`.
+fn select_gradients(
+    gradients: Option<&Array2<f64>>,
+    indexes: Option<&Indexes>,
+    environments: &Indexes,
+    features: &[usize],
+) -> (Option<Array2<f64>>, Option<Indexes>) {
+    if let (Some(gradients), Some(indexes)) = (gradients, indexes) {
+        let size = environments.size();
+        let rows = indexes.iter().enumerate()
+            .filter(|(_, index)| environments.contains(&index[..size]))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+
+        let gradients = gradients.select(Axis(0), &rows).select(Axis(1), features);
+        return (Some(gradients), Some(indexes_subset(indexes, &rows)));
+    } else {
+        return (None, None);
+    }
+}
+
+/// Create an empty builder with the same names and types as `indexes`
+fn empty_builder(indexes: &Indexes) -> IndexesBuilder {
+    IndexesBuilder::with_types(indexes.names(), indexes.types().to_vec())
+}
+
+/// Check the parts of `descriptor` that must match `first` when
+/// concatenating descriptors along the given `axis`
+fn check_concatenate(first: &Descriptor, descriptor: &Descriptor, axis: &str) -> Result<(), Error> {
+    if descriptor.sparse_gradients.is_some() {
+        return Err(Error::InvalidParameter(format!(
+            "can not concatenate descriptors along {}: some descriptors have sparse gradients", axis
+        )));
+    }
+
+    if descriptor.gradients.is_some() != first.gradients.is_some() ||
+       descriptor.cell_gradients.is_some() != first.cell_gradients.is_some() {
+        return Err(Error::InvalidParameter(format!(
+            "can not concatenate descriptors along {}: some descriptors have gradients and some do not", axis
+        )));
+    }
+
+    return Ok(());
+}
+
+/// Check that `first` and `indexes` have the same names and types
+fn check_same_indexes(first: &Indexes, indexes: &Indexes, axis: &str, kind: &str) -> Result<(), Error> {
+    if first.names() != indexes.names() || first.types() != indexes.types() {
+        return Err(Error::InvalidParameter(format!(
+            "can not concatenate descriptors along {}: {} indexes have different names or types ([{}] and [{}])",
+            axis, kind, first.names().join(", "), indexes.names().join(", ")
+        )));
+    }
+    return Ok(());
+}
+
+/// Position of the `structure` index in `indexes`, if there is one
+fn structure_position(indexes: &Indexes) -> Option<usize> {
+    indexes.names().iter()
+        .zip(indexes.types())
+        .position(|(&name, &index_type)| name == "structure" && index_type == IndexType::Int)
+}
+
+/// Get the number of structures used in `environments`, i.e. the largest
+/// value of the `structure` index plus one
+fn structures_count(environments: &Indexes) -> i64 {
+    if let Some(structure) = structure_position(environments) {
+        environments.iter().map(|env| env[structure].i64() + 1).max().unwrap_or(0)
+    } else {
+        0
+    }
+}
+
+/// Add all the entries in `indexes` to `builder`, adding `structures_offset`
+/// to the `structure` index if there is one
+fn add_renumbered(builder: &mut IndexesBuilder, indexes: &Indexes, structures_offset: i64) -> Result<(), Error> {
+    let structure = structure_position(indexes);
+    for entry in indexes {
+        let mut entry = entry.to_vec();
+        if let Some(structure) = structure {
+            entry[structure] = IndexValue::from(entry[structure].i64() + structures_offset);
+        }
+
+        if builder.contains(&entry) {
+            return Err(Error::InvalidParameter(format!(
+                "can not concatenate descriptors: [{}] is present in multiple descriptors",
+                entry.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ")
+            )));
+        }
+        builder.add(&entry);
+    }
+
+    return Ok(());
+}
+
+/// Concatenate the given `arrays` along `axis`. The shapes of the arrays
+/// must already have been checked to be compatible.
+fn concatenate<'a>(axis: Axis, arrays: impl Iterator<Item = ArrayView2<'a, f64>>) -> Array2<f64> {
+    let arrays = arrays.collect::<Vec<_>>();
+    return ndarray::concatenate(axis, &arrays).expect("invalid shapes for concatenation");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Calculator, CalculationOptions};
+    use crate::system::test_systems;
+
+    /// Convenience macro to create IndexValue
+    macro_rules! v {
+        ($value: expr) => {
+            crate::descriptor::indexes::IndexValue::from($value as i64)
+        };
+    }
+
+    fn compute(systems: &[&str]) -> Descriptor {
+        let mut calculator = Calculator::new("spherical_expansion", r#"{
+            "cutoff": 3.5,
+            "max_radial": 3,
+            "max_angular": 2,
+            "atomic_gaussian_width": 0.3,
+            "gradients": true,
+            "cell_gradients": true,
+            "radial_basis": "GTO",
+            "cutoff_function": {"ShiftedCosine": {"width": 0.5}}
+        }"#.into()).unwrap();
+
+        let mut systems = test_systems(systems);
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems.get(), &mut descriptor, CalculationOptions::default()).unwrap();
+        return descriptor;
+    }
+
+    fn selection(names: Vec<&str>, values: &[&[i32]]) -> Indexes {
+        let mut builder = IndexesBuilder::new(names);
+        for value in values {
+            builder.add(&value.iter().map(|&v| IndexValue::from(v)).collect::<Vec<_>>());
+        }
+        return builder.finish();
+    }
+
+    #[test]
+    fn select_samples() {
+        let full = compute(&["water", "CH"]);
+        let ch = compute(&["CH"]);
+
+        let structures = selection(vec!["structure"], &[&[1]]);
+        let selected = full.select(Some(&structures), None).unwrap();
+
+        assert_eq!(selected.values, ch.values);
+        assert_eq!(selected.gradients, ch.gradients);
+        assert_eq!(selected.cell_gradients, ch.cell_gradients);
+        assert_eq!(selected.features, full.features);
+
+        assert_eq!(selected.environments.count(), ch.environments.count());
+        for (selected, expected) in selected.environments.iter().zip(&ch.environments) {
+            assert_eq!(selected[0], v!(1));
+            assert_eq!(selected[1..], expected[1..]);
+        }
+
+        let gradients_indexes = selected.gradients_indexes.as_ref().unwrap();
+        assert_eq!(gradients_indexes.count(), ch.gradients_indexes.as_ref().unwrap().count());
+        for index in gradients_indexes {
+            assert!(selected.environments.contains(&index[..4]));
+        }
+
+        let samples = selection(vec!["center", "structure"], &[&[1, 0], &[0, 1]]);
+        let selected = full.select(Some(&samples), None).unwrap();
+        assert_eq!(selected.environments.count(), 4);
+        for (i, environment) in selected.environments.iter().enumerate() {
+            let position = full.environments.position(environment).unwrap();
+            assert_eq!(selected.values.row(i), full.values.row(position));
+        }
+    }
+
+    #[test]
+    fn select_features() {
+        let full = compute(&["water", "CH"]);
+
+        let features = selection(vec!["l"], &[&[1]]);
+        let selected = full.select(None, Some(&features)).unwrap();
+
+        assert_eq!(selected.environments, full.environments);
+        assert_eq!(selected.gradients_indexes, full.gradients_indexes);
+        assert_eq!(selected.features.count(), 9);
+        assert_eq!(selected.values.shape(), [full.environments.count(), 9]);
+
+        for (i, feature) in selected.features.iter().enumerate() {
+            assert_eq!(feature[1], v!(1));
+            let position = full.features.position(feature).unwrap();
+            assert_eq!(selected.values.column(i), full.values.column(position));
+
+            let gradients = selected.gradients.as_ref().unwrap();
+            assert_eq!(gradients.column(i), full.gradients.as_ref().unwrap().column(position));
+        }
+    }
+
+    #[test]
+    fn invalid_select() {
+        let full = compute(&["water"]);
+
+        let samples = selection(vec!["species"], &[&[1]]);
+        let error = full.select(Some(&samples), None).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: can not select samples using 'species', which is not part of the samples indexes: [structure, center, species_center, species_neighbor]");
+
+        let mut sparse = compute(&["water"]);
+        sparse.sparsify_gradients();
+        let error = sparse.select(None, None).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: can not select from a descriptor with sparse gradients");
+    }
+
+    #[test]
+    fn concatenate_samples() {
+        let full = compute(&["water", "CH", "water"]);
+        let first = compute(&["water", "CH"]);
+        let second = compute(&["water"]);
+
+        let concatenated = Descriptor::concatenate_samples(&[&first, &second]).unwrap();
+        assert_eq!(concatenated.environments, full.environments);
+        assert_eq!(concatenated.features, full.features);
+        assert_eq!(concatenated.values, full.values);
+        assert_eq!(concatenated.gradients_indexes, full.gradients_indexes);
+        assert_eq!(concatenated.gradients, full.gradients);
+        assert_eq!(concatenated.cell_gradients_indexes, full.cell_gradients_indexes);
+        assert_eq!(concatenated.cell_gradients, full.cell_gradients);
+
+        // selecting and concatenating gives back the initial descriptor
+        let water = full.select(Some(&selection(vec!["structure"], &[&[0], &[2]])), None).unwrap();
+        let ch = full.select(Some(&selection(vec!["structure"], &[&[1]])), None).unwrap();
+        let merged = Descriptor::concatenate_samples(&[&water, &ch]).unwrap();
+        assert_eq!(merged.environments.count(), full.environments.count());
+        assert_eq!(merged.values.shape(), full.values.shape());
+        assert!(merged.environments.contains(&[v!(4), v!(1), v!(6), v!(1)]));
+    }
+
+    #[test]
+    fn concatenate_features() {
+        let full = compute(&["water", "CH"]);
+
+        let first = full.select(None, Some(&selection(vec!["l"], &[&[0], &[2]]))).unwrap();
+        let second = full.select(None, Some(&selection(vec!["l"], &[&[1]]))).unwrap();
+
+        let concatenated = Descriptor::concatenate_features(&[&first, &second]).unwrap();
+        assert_eq!(concatenated.environments, full.environments);
+        assert_eq!(concatenated.gradients_indexes, full.gradients_indexes);
+        assert_eq!(concatenated.features.count(), full.features.count());
+
+        for (i, feature) in concatenated.features.iter().enumerate() {
+            let position = full.features.position(feature).unwrap();
+            assert_eq!(concatenated.values.column(i), full.values.column(position));
+
+            let gradients = concatenated.gradients.as_ref().unwrap();
+            assert_eq!(gradients.column(i), full.gradients.as_ref().unwrap().column(position));
+
+            let cell_gradients = concatenated.cell_gradients.as_ref().unwrap();
+            assert_eq!(cell_gradients.column(i), full.cell_gradients.as_ref().unwrap().column(position));
+        }
+    }
+
+    #[test]
+    fn invalid_concatenate() {
+        let water = compute(&["water"]);
+        let ch = compute(&["CH"]);
+
+        let error = Descriptor::concatenate_features(&[&water, &ch]).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: can not concatenate descriptors along features: all descriptors must have the same samples and gradients");
+
+        let error = Descriptor::concatenate_features(&[&water, &water]).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: can not concatenate descriptors: [0, 0, 0] is present in multiple descriptors");
+
+        let features = water.select(None, Some(&selection(vec!["l"], &[&[0]]))).unwrap();
+        let error = Descriptor::concatenate_samples(&[&water, &features]).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: can not concatenate descriptors along samples: all descriptors must have the same features");
+
+        let mut no_gradients = compute(&["water"]);
+        no_gradients.gradients = None;
+        no_gradients.gradients_indexes = None;
+        let error = Descriptor::concatenate_samples(&[&water, &no_gradients]).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: can not concatenate descriptors along samples: some descriptors have gradients and some do not");
+
+        let empty = Descriptor::concatenate_samples(&[]).unwrap();
+        assert_eq!(empty.values.shape(), [0, 0]);
+    }
+}
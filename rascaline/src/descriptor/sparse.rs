@@ -0,0 +1,233 @@
+use ndarray::Array2;
+
+use super::Indexes;
+
+/// Gradients of a descriptor with respect to atomic positions, stored as
+/// sparse blocks.
+///
+/// Each block contains the gradients of a single sample with respect to the
+/// position of a single atom, and only contains the features for which at
+/// least one of the x/y/z components of the gradient is not zero. Blocks
+/// without any non-zero gradient are not stored.
+#[derive(Debug, Clone)]
+pub struct SparseGradients {
+    /// Sample (i.e. row in `Descriptor::values`) of each block
+    samples: Vec<usize>,
+    /// Atom with respect to which the gradients are taken for each block
+    atoms: Vec<usize>,
+    /// Start of each block in `features` and `values`, with an additional
+    /// entry containing the total number of stored features
+    offsets: Vec<usize>,
+    /// Features (i.e. column in `Descriptor::values`) with non-zero gradients
+    features: Vec<usize>,
+    /// Values of the x/y/z gradients for each entry in `features`
+    values: Vec<[f64; 3]>,
+}
+
+/// A single block of `SparseGradients`
+#[derive(Debug, Clone, Copy)]
+pub struct SparseGradientsBlock<'a> {
+    /// Sample (i.e. row in `Descriptor::values`) for this block
+    pub sample: usize,
+    /// Atom with respect to which the gradients are taken
+    pub atom: usize,
+    /// Features (i.e. column in `Descriptor::values`) with non-zero gradients
+    pub features: &'a [usize],
+    /// Values of the x/y/z gradients for each entry in `features`
+    pub values: &'a [[f64; 3]],
+}
+
+impl Default for SparseGradients {
+    fn default() -> Self { Self::new() }
+}
+
+impl SparseGradients {
+    /// Create empty sparse gradients
+    pub fn new() -> SparseGradients {
+        SparseGradients {
+            samples: Vec::new(),
+            atoms: Vec::new(),
+            offsets: vec![0],
+            features: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+
+    /// Create sparse gradients from the dense `gradients`, as stored in
+    /// `Descriptor::gradients`.
+    pub fn from_dense(environments: &Indexes, gradients_indexes: &Indexes, gradients: &Array2<f64>) -> SparseGradients {
+        let mut sparse = SparseGradients::new();
+        sparse.add_dense(environments, gradients_indexes, gradients, 0);
+        return sparse;
+    }
+
+    /// Add the non-zero blocks from the dense `gradients` to these sparse
+    /// gradients. The gradients rows must be grouped by sample, in the same
+    /// order as `environments`; and the samples are shifted by
+    /// `first_sample` when adding them.
+    pub(crate) fn add_dense(
+        &mut self,
+        environments: &Indexes,
+        gradients_indexes: &Indexes,
+        gradients: &Array2<f64>,
+        first_sample: usize,
+    ) {
+        let n_environment = environments.size();
+        assert_eq!(gradients_indexes.size(), n_environment + 2, "gradients indexes should contain samples, atom and spatial");
+        assert_eq!(gradients_indexes.count(), gradients.nrows());
+        assert_eq!(gradients.nrows() % 3, 0, "gradients should contain x/y/z for each atom");
+
+        let mut sample = 0;
+        for block in 0..(gradients.nrows() / 3) {
+            let index = &gradients_indexes[3 * block];
+            while environments[sample] != index[..n_environment] {
+                sample += 1;
+                assert!(sample < environments.count(), "gradients are not in the same order as samples");
+            }
+
+            let x = gradients.row(3 * block);
+            let y = gradients.row(3 * block + 1);
+            let z = gradients.row(3 * block + 2);
+            for (feature, ((&x, &y), &z)) in x.iter().zip(y).zip(z).enumerate() {
+                if x != 0.0 || y != 0.0 || z != 0.0 {
+                    self.features.push(feature);
+                    self.values.push([x, y, z]);
+                }
+            }
+
+            if self.features.len() != self.offsets[self.offsets.len() - 1] {
+                self.samples.push(first_sample + sample);
+                self.atoms.push(index[n_environment].usize());
+                self.offsets.push(self.features.len());
+            }
+        }
+    }
+
+    /// Add a single block containing the gradients of `sample` with respect
+    /// to the position of `atom`. The blocks should be added in the same order
+    /// as the samples.
+    pub(crate) fn add_block(&mut self, sample: usize, atom: usize, features: &[usize], values: &[[f64; 3]]) {
+        assert_eq!(features.len(), values.len());
+        self.samples.push(sample);
+        self.atoms.push(atom);
+        self.features.extend_from_slice(features);
+        self.values.extend_from_slice(values);
+        self.offsets.push(self.features.len());
+    }
+
+    /// Get the number of blocks in these sparse gradients
+    pub fn count(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Get the number of features stored in all blocks, i.e. the number of
+    /// non-zero gradients
+    pub fn non_zero(&self) -> usize {
+        self.features.len()
+    }
+
+    /// Get the block at index `i`
+    pub fn block(&self, i: usize) -> SparseGradientsBlock<'_> {
+        let start = self.offsets[i];
+        let stop = self.offsets[i + 1];
+        SparseGradientsBlock {
+            sample: self.samples[i],
+            atom: self.atoms[i],
+            features: &self.features[start..stop],
+            values: &self.values[start..stop],
+        }
+    }
+
+    /// Get the sample (i.e. row in `Descriptor::values`) of all blocks
+    pub fn samples(&self) -> &[usize] {
+        &self.samples
+    }
+
+    /// Get the atom with respect to which the gradients are taken for all
+    /// blocks
+    pub fn atoms(&self) -> &[usize] {
+        &self.atoms
+    }
+
+    /// Get the start of each block in `features()` and `values()`, with an
+    /// additional entry containing the total number of stored features
+    pub fn offsets(&self) -> &[usize] {
+        &self.offsets
+    }
+
+    /// Get the features with non-zero gradients for all blocks, one block
+    /// after the other
+    pub fn features(&self) -> &[usize] {
+        &self.features
+    }
+
+    /// Get the values of the x/y/z gradients for all entries in `features()`
+    pub fn values(&self) -> &[[f64; 3]] {
+        &self.values
+    }
+
+    /// Iterate over all blocks in these sparse gradients
+    pub fn iter(&self) -> impl Iterator<Item = SparseGradientsBlock<'_>> + '_ {
+        (0..self.count()).map(move |i| self.block(i))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptor::{IndexesBuilder, IndexValue};
+    use ndarray::array;
+
+    #[test]
+    fn from_dense() {
+        let mut environments = IndexesBuilder::new(vec!["structure", "center"]);
+        environments.add(&[IndexValue::from(0_usize), IndexValue::from(0_usize)]);
+        environments.add(&[IndexValue::from(0_usize), IndexValue::from(1_usize)]);
+        environments.add(&[IndexValue::from(1_usize), IndexValue::from(0_usize)]);
+        let environments = environments.finish();
+
+        let mut gradients_indexes = IndexesBuilder::new(vec!["structure", "center", "neighbor", "spatial"]);
+        for &(structure, center, neighbor) in &[(0_usize, 0_usize, 1_usize), (0, 1, 0), (1, 0, 3)] {
+            for spatial in 0..3_usize {
+                gradients_indexes.add(&[
+                    IndexValue::from(structure),
+                    IndexValue::from(center),
+                    IndexValue::from(neighbor),
+                    IndexValue::from(spatial),
+                ]);
+            }
+        }
+        let gradients_indexes = gradients_indexes.finish();
+
+        let gradients = array![
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 2.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 3.0],
+            // all zeros, this block should not be stored
+            [0.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 0.0],
+            [4.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 0.0],
+            [5.0, 0.0, 6.0, 0.0],
+        ];
+
+        let sparse = SparseGradients::from_dense(&environments, &gradients_indexes, &gradients);
+        assert_eq!(sparse.count(), 2);
+        assert_eq!(sparse.non_zero(), 4);
+
+        let block = sparse.block(0);
+        assert_eq!(block.sample, 0);
+        assert_eq!(block.atom, 1);
+        assert_eq!(block.features, &[1, 3]);
+        assert_eq!(block.values, &[[1.0, 2.0, 0.0], [0.0, 0.0, 3.0]]);
+
+        let block = sparse.block(1);
+        assert_eq!(block.sample, 2);
+        assert_eq!(block.atom, 3);
+        assert_eq!(block.features, &[0, 2]);
+        assert_eq!(block.values, &[[4.0, 0.0, 5.0], [0.0, 0.0, 6.0]]);
+
+        assert_eq!(sparse.iter().count(), 2);
+    }
+}
@@ -9,6 +9,14 @@ pub enum Error {
     JSON(serde_json::Error),
     /// Error due to C strings containing non-utf8 data
     Utf8(Utf8Error),
+    /// A system contains invalid data (non-finite positions, inconsistent
+    /// number of atoms, NULL pointers, ...)
+    InvalidSystem(String),
+    /// A user-provided callback (for example the functions in
+    /// `rascal_system_t`) reported a failure
+    Callback(String),
+    /// Error while reading or writing files
+    Io(std::io::Error),
     /// Error used when a panic was caught
     Panic(String),
 }
@@ -19,6 +27,9 @@ impl std::fmt::Display for Error {
             Error::InvalidParameter(e) => write!(f, "invalid parameter: {}", e),
             Error::JSON(e) => write!(f, "json error: {}", e),
             Error::Utf8(e) => write!(f, "utf8 decoding error: {}", e),
+            Error::InvalidSystem(e) => write!(f, "invalid system: {}", e),
+            Error::Callback(e) => write!(f, "error in callback: {}", e),
+            Error::Io(e) => write!(f, "I/O error: {}", e),
             Error::Panic(e) => write!(f, "internal error: {}", e),
         }
     }
@@ -27,9 +38,11 @@ impl std::fmt::Display for Error {
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
-            Error::InvalidParameter(_) | Error::Panic(_) => None,
+            Error::InvalidParameter(_) | Error::InvalidSystem(_) |
+            Error::Callback(_) | Error::Panic(_) => None,
             Error::JSON(e) => Some(e),
             Error::Utf8(e) => Some(e),
+            Error::Io(e) => Some(e),
         }
     }
 }
@@ -46,6 +59,11 @@ impl From<Utf8Error> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Error {
+        Error::Io(error)
+    }
+}
 
 // Box<dyn Any + Send + 'static> is the error type in std::panic::catch_unwind
 impl From<Box<dyn std::any::Any + Send + 'static>> for Error {
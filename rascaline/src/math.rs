@@ -52,6 +52,199 @@ pub fn gamma(x: f64) -> f64 {
     }
 }
 
+/// Value above which the values in Miller's downward recurrence are rescaled
+/// to prevent overflow
+const MILLER_RESCALE_THRESHOLD: f64 = 1e200;
+
+/// Run Miller's downward recurrence `f_{l-1} = (2l + 1) / x f_l + sign *
+/// f_{l+1}` for a function of the spherical Bessel family, storing the
+/// unnormalized values for `l = 0 .. values.len()` in `values`.
+fn miller_downward_recurrence(x: f64, sign: f64, values: &mut [f64]) {
+    debug_assert!(x > 0.0);
+    let n_values = values.len();
+    // starting far enough above both `l_max` and `x` ensure that the error
+    // from the arbitrary initial values is damped below machine precision
+    let start = n_values + 40 + x as usize;
+
+    let mut f_l_plus_1 = 0.0;
+    let mut f_l = 1.0;
+    for l in (1..=start).rev() {
+        let f_l_minus_1 = (2 * l + 1) as f64 / x * f_l + sign * f_l_plus_1;
+        f_l_plus_1 = f_l;
+        f_l = f_l_minus_1;
+
+        if f_l.abs() > MILLER_RESCALE_THRESHOLD {
+            f_l /= MILLER_RESCALE_THRESHOLD;
+            f_l_plus_1 /= MILLER_RESCALE_THRESHOLD;
+            for value in values.iter_mut() {
+                *value /= MILLER_RESCALE_THRESHOLD;
+            }
+        }
+
+        if l - 1 < n_values {
+            values[l - 1] = f_l;
+        }
+    }
+}
+
+/// Compute the spherical Bessel functions of the first kind `j_l(x)` for all
+/// `l` between 0 and `values.len() - 1`, and store them in `values`.
+pub fn spherical_bessel(x: f64, values: &mut [f64]) {
+    assert!(x >= 0.0, "spherical Bessel functions are only implemented for positive x");
+    if values.is_empty() {
+        return;
+    }
+
+    if x == 0.0 {
+        values.fill(0.0);
+        values[0] = 1.0;
+        return;
+    }
+
+    // j_0 and j_1 are used for normalization, make sure they are computed
+    let mut first_values = [0.0; 2];
+    if values.len() < 2 {
+        miller_downward_recurrence(x, -1.0, &mut first_values);
+        values[0] = first_values[0];
+    } else {
+        miller_downward_recurrence(x, -1.0, values);
+        first_values.copy_from_slice(&values[..2]);
+    }
+
+    // normalize with whichever of j_0 or j_1 is the largest, since any of
+    // them can be zero
+    let (sin, cos) = x.sin_cos();
+    let j_0 = sin / x;
+    let j_1 = sin / (x * x) - cos / x;
+    let factor = if j_0.abs() > j_1.abs() {
+        j_0 / first_values[0]
+    } else {
+        j_1 / first_values[1]
+    };
+
+    for value in values.iter_mut() {
+        *value *= factor;
+    }
+}
+
+/// Compute the exponentially scaled modified spherical Bessel functions of the
+/// first kind `exp(-x) i_l(x)` for all `l` between 0 and `values.len() - 1`,
+/// and store them in `values`.
+pub fn scaled_modified_spherical_bessel(x: f64, values: &mut [f64]) {
+    assert!(x >= 0.0, "modified spherical Bessel functions are only implemented for positive x");
+    if values.is_empty() {
+        return;
+    }
+
+    if x == 0.0 {
+        values.fill(0.0);
+        values[0] = 1.0;
+        return;
+    }
+
+    miller_downward_recurrence(x, 1.0, values);
+
+    // exp(-x) i_0(x) = exp(-x) sinh(x) / x
+    let i_0 = -f64::exp_m1(-2.0 * x) / (2.0 * x);
+    let factor = i_0 / values[0];
+    for value in values.iter_mut() {
+        *value *= factor;
+    }
+}
+
+/// Get the points and weights of the `n`-points Gauss-Legendre quadrature on
+/// the `[-1, 1]` interval
+pub fn gauss_legendre(n: usize) -> (Vec<f64>, Vec<f64>) {
+    let mut points = vec![0.0; n];
+    let mut weights = vec![0.0; n];
+
+    for i in 0..n {
+        // initial guess for the i-th root of the Legendre polynomial P_n,
+        // refined with Newton's method
+        let mut x = f64::cos(std::f64::consts::PI * (i as f64 + 0.75) / (n as f64 + 0.5));
+        let mut derivative;
+        loop {
+            // evaluate P_n(x) and P_{n-1}(x) with Bonnet's recursion
+            let mut p_n = 1.0;
+            let mut p_n_minus_1 = 0.0;
+            for k in 1..=n {
+                let p_n_minus_2 = p_n_minus_1;
+                p_n_minus_1 = p_n;
+                p_n = ((2 * k - 1) as f64 * x * p_n_minus_1 - (k - 1) as f64 * p_n_minus_2) / k as f64;
+            }
+
+            derivative = n as f64 * (x * p_n - p_n_minus_1) / (x * x - 1.0);
+            let dx = p_n / derivative;
+            x -= dx;
+
+            if dx.abs() < 1e-15 {
+                break;
+            }
+        }
+
+        // store the points in increasing order
+        points[n - i - 1] = x;
+        weights[n - i - 1] = 2.0 / ((1.0 - x * x) * derivative * derivative);
+    }
+
+    return (points, weights);
+}
+
+/// Compute the error function `erf(x)` with an accuracy close to the floating
+/// point precision.
+///
+/// Small arguments use the series expansion `erf(x) = 2/sqrt(pi) exp(-x^2)
+/// sum_n 2^n x^(2n + 1) / (2n + 1)!!`, which only contains positive terms;
+/// while larger arguments use the continued fraction representation of
+/// `erfc(x)`.
+pub fn erf(x: f64) -> f64 {
+    if x < 0.0 {
+        return -erf(-x);
+    }
+
+    if x < 2.0 {
+        return x * erf_over_x_series(x);
+    }
+
+    return 1.0 - erfc_continued_fraction(x);
+}
+
+/// Compute the complementary error function `erfc(x) = 1 - erf(x)`, without
+/// loss of relative precision for large `x`.
+pub fn erfc(x: f64) -> f64 {
+    if x < 2.0 {
+        return 1.0 - erf(x);
+    }
+
+    return erfc_continued_fraction(x);
+}
+
+/// Evaluate `erf(x) / x` using its series expansion, for `|x| < 2`
+fn erf_over_x_series(x: f64) -> f64 {
+    let x2 = x * x;
+    let mut term = 1.0;
+    let mut sum = 1.0;
+    let mut n = 0.0;
+    while term > 1e-17 * sum {
+        n += 1.0;
+        term *= 2.0 * x2 / (2.0 * n + 1.0);
+        sum += term;
+    }
+
+    return std::f64::consts::FRAC_2_SQRT_PI * f64::exp(-x2) * sum;
+}
+
+/// Evaluate `erfc(x)` using a continued fraction, for `x >= 2`
+fn erfc_continued_fraction(x: f64) -> f64 {
+    // erfc(x) = exp(-x^2) / sqrt(pi) / (x + 1/2 / (x + 1 / (x + 3/2 / ...)))
+    let mut fraction = x;
+    for n in (1..=100).rev() {
+        fraction = x + (n as f64 / 2.0) / fraction;
+    }
+
+    return f64::exp(-x * x) / (std::f64::consts::PI.sqrt() * fraction);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -88,4 +281,85 @@ mod tests {
         assert_relative_eq!(gamma(10.1), 454760.7514415859508673358368319076190405047458218916492282448, max_relative=1e-13);
         assert_relative_eq!(gamma(150.0 + 1.0e-12), 3.8089226376496421386707466577615064443807882167327097140e+260, max_relative=1e-12);
     }
+
+    #[test]
+    fn test_spherical_bessel() {
+        let mut values = [0.0; 3];
+        spherical_bessel(1.0, &mut values);
+        assert_relative_eq!(values[0], 0.84147098480789651, max_relative=1e-13);
+        assert_relative_eq!(values[1], 0.30116867893975679, max_relative=1e-13);
+        assert_relative_eq!(values[2], 0.062035052011373861, max_relative=1e-13);
+
+        spherical_bessel(0.0, &mut values);
+        assert_eq!(values, [1.0, 0.0, 0.0]);
+
+        let mut values = [0.0; 11];
+        spherical_bessel(10.0, &mut values);
+        assert_relative_eq!(values[5], -0.055534511621452181, max_relative=1e-13);
+
+        spherical_bessel(0.01, &mut values);
+        assert_relative_eq!(values[3], 9.5237566138768643e-9, max_relative=1e-13);
+
+        spherical_bessel(3.7, &mut values);
+        assert_relative_eq!(values[10], 2.5876217463288222e-5, max_relative=1e-13);
+
+        // close to the first zero of j_0
+        spherical_bessel(3.14159, &mut values);
+        assert_relative_eq!(values[0], 8.4466457855822376e-7, max_relative=1e-8);
+    }
+
+    #[test]
+    fn test_scaled_modified_spherical_bessel() {
+        let mut values = [0.0; 2];
+        scaled_modified_spherical_bessel(1.0, &mut values);
+        assert_relative_eq!(values[0], 0.43233235838169365, max_relative=1e-13);
+        assert_relative_eq!(values[1], 0.13533528323661269, max_relative=1e-13);
+
+        scaled_modified_spherical_bessel(0.0, &mut values);
+        assert_eq!(values, [1.0, 0.0]);
+
+        let mut values = [0.0; 11];
+        scaled_modified_spherical_bessel(0.01, &mut values);
+        assert_relative_eq!(values[3], 9.429098419414504e-9, max_relative=1e-13);
+
+        scaled_modified_spherical_bessel(10.0, &mut values);
+        assert_relative_eq!(values[5], 0.01075250041985184, max_relative=1e-13);
+
+        scaled_modified_spherical_bessel(3.7, &mut values);
+        assert_relative_eq!(values[10], 1.160383241617892e-6, max_relative=1e-13);
+
+        scaled_modified_spherical_bessel(250.0, &mut values);
+        assert_relative_eq!(values[2], 0.001976096, max_relative=1e-6);
+    }
+
+    #[test]
+    fn test_gauss_legendre() {
+        let (points, weights) = gauss_legendre(3);
+        let expected = f64::sqrt(3.0 / 5.0);
+        assert_relative_eq!(points[0], -expected, max_relative=1e-14);
+        assert_relative_eq!(points[1], 0.0, epsilon=1e-14);
+        assert_relative_eq!(points[2], expected, max_relative=1e-14);
+        assert_relative_eq!(weights[0], 5.0 / 9.0, max_relative=1e-14);
+        assert_relative_eq!(weights[1], 8.0 / 9.0, max_relative=1e-14);
+        assert_relative_eq!(weights[2], 5.0 / 9.0, max_relative=1e-14);
+
+        // n-points quadrature is exact for polynomials up to degree 2n - 1
+        let (points, weights) = gauss_legendre(10);
+        let integral = points.iter().zip(&weights).map(|(x, w)| w * x.powi(18)).sum::<f64>();
+        assert_relative_eq!(integral, 2.0 / 19.0, max_relative=1e-13);
+    }
+
+    #[test]
+    fn test_erf() {
+        assert_eq!(erf(0.0), 0.0);
+        assert_relative_eq!(erf(0.5), 0.5204998778130465, max_relative=1e-14);
+        assert_relative_eq!(erf(-1.0), -0.8427007929497149, max_relative=1e-14);
+        assert_relative_eq!(erf(1.9), 0.9927904292352575, max_relative=1e-14);
+        assert_relative_eq!(erf(2.5), 0.9995930479825550, max_relative=1e-14);
+
+        assert_relative_eq!(erfc(0.5), 0.4795001221869535, max_relative=1e-14);
+        assert_relative_eq!(erfc(2.0), 4.677734981047266e-3, max_relative=1e-13);
+        assert_relative_eq!(erfc(3.0), 2.209049699858544e-5, max_relative=1e-13);
+        assert_relative_eq!(erfc(6.0), 2.151973671249892e-17, max_relative=1e-13);
+    }
 }
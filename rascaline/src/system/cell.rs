@@ -36,7 +36,7 @@ pub struct UnitCell {
 
 impl From<Matrix3> for UnitCell {
     fn from(matrix: Matrix3) -> UnitCell {
-        assert!(matrix.determinant() > 1e-6, "matrix is not invertible");
+        assert!(matrix.determinant().abs() > 1e-6, "matrix is not invertible");
         let mut cell = UnitCell {
             cell: matrix,
             inv: matrix.inverse(),
@@ -201,11 +201,12 @@ impl UnitCell {
             CellShape::Infinite => 0.0,
             CellShape::Orthorhombic => self.a() * self.b() * self.c(),
             CellShape::Triclinic => {
-                // The volume is the mixed product of the three cell vectors
+                // The volume is the mixed product of the three cell vectors,
+                // which is negative for left-handed cells
                 let a = self.a_vector();
                 let b = self.b_vector();
                 let c = self.c_vector();
-                a * (b ^ c)
+                f64::abs(a * (b ^ c))
             }
         };
         assert!(volume >= 0.0, "Volume is not positive!");
@@ -0,0 +1,165 @@
+use std::path::Path;
+
+use crate::{Error, Matrix3};
+use super::{UnitCell, System, Vector3D, Pair};
+use super::neighbors::NeighborsList;
+
+impl From<&chemfiles::UnitCell> for UnitCell {
+    fn from(cell: &chemfiles::UnitCell) -> UnitCell {
+        match cell.shape() {
+            chemfiles::CellShape::Infinite => UnitCell::infinite(),
+            chemfiles::CellShape::Orthorhombic | chemfiles::CellShape::Triclinic => {
+                // chemfiles stores the cell vectors as rows of the matrix,
+                // while we store them as columns
+                UnitCell::from(Matrix3::new(cell.matrix()).transposed())
+            }
+        }
+    }
+}
+
+/// Implementation of `System` wrapping a `chemfiles::Frame`, allowing to use
+/// any file format supported by chemfiles as input for calculators.
+///
+/// The atomic number of each atom is used as its species. Atoms whose name is
+/// not an element of the periodic table have an atomic number of 0.
+pub struct ChemfilesSystem {
+    frame: chemfiles::Frame,
+    cell: UnitCell,
+    species: Vec<usize>,
+    positions: Vec<Vector3D>,
+    neighbors: Option<NeighborsList>,
+}
+
+impl ChemfilesSystem {
+    /// Get the chemfiles frame wrapped by this system
+    pub fn frame(&self) -> &chemfiles::Frame {
+        &self.frame
+    }
+}
+
+impl From<chemfiles::Frame> for ChemfilesSystem {
+    fn from(frame: chemfiles::Frame) -> ChemfilesSystem {
+        let cell = UnitCell::from(&*frame.cell());
+        let species = (0..frame.size())
+            .map(|i| frame.atom(i).atomic_number() as usize)
+            .collect();
+        let positions = frame.positions().iter()
+            .map(|&position| Vector3D::from(position))
+            .collect();
+
+        ChemfilesSystem {
+            frame: frame,
+            cell: cell,
+            species: species,
+            positions: positions,
+            neighbors: None,
+        }
+    }
+}
+
+impl System for ChemfilesSystem {
+    fn size(&self) -> Result<usize, Error> {
+        Ok(self.species.len())
+    }
+
+    fn positions(&self) -> Result<&[Vector3D], Error> {
+        Ok(&self.positions)
+    }
+
+    fn species(&self) -> Result<&[usize], Error> {
+        Ok(&self.species)
+    }
+
+    fn cell(&self) -> Result<UnitCell, Error> {
+        Ok(self.cell)
+    }
+
+    #[allow(clippy::float_cmp)]
+    fn compute_neighbors(&mut self, cutoff: f64) -> Result<(), Error> {
+        // re-use already computed NL is possible
+        if let Some(ref nl) = self.neighbors {
+            if nl.cutoff == cutoff {
+                return Ok(());
+            }
+        }
+
+        self.neighbors = Some(NeighborsList::new(self, cutoff)?);
+        return Ok(());
+    }
+
+    fn pairs(&self) -> Result<&[Pair], Error> {
+        Ok(&self.neighbors.as_ref().expect("neighbor list is not initialized").pairs)
+    }
+
+    fn pairs_containing(&self, center: usize) -> Result<&[Pair], Error> {
+        Ok(&self.neighbors.as_ref().expect("neighbor list is not initialized").pairs_by_center[center])
+    }
+}
+
+/// Read all the frames in the file at `path` with chemfiles. The file format
+/// is guessed from the file extension.
+pub fn read_chemfiles(path: impl AsRef<Path>) -> Result<Vec<ChemfilesSystem>, Error> {
+    let path = path.as_ref();
+    let chemfiles_error = |error: chemfiles::Error| Error::Io(std::io::Error::new(
+        std::io::ErrorKind::Other,
+        format!("chemfiles failed to read '{}': {}", path.display(), error)
+    ));
+
+    let mut trajectory = chemfiles::Trajectory::open(path, 'r').map_err(chemfiles_error)?;
+
+    let mut systems = Vec::new();
+    for _ in 0..trajectory.nsteps() {
+        let mut frame = chemfiles::Frame::new();
+        trajectory.read(&mut frame).map_err(chemfiles_error)?;
+        systems.push(ChemfilesSystem::from(frame));
+    }
+
+    return Ok(systems);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::cell::CellShape;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn cell() {
+        let cell = UnitCell::from(&chemfiles::UnitCell::new([10.0, 11.0, 12.0]));
+        assert_eq!(cell.shape(), CellShape::Orthorhombic);
+        assert_relative_eq!(cell.lengths(), Vector3D::new(10.0, 11.0, 12.0));
+
+        let cell = UnitCell::from(&chemfiles::UnitCell::triclinic([10.0, 11.0, 12.0], [90.0, 80.0, 120.0]));
+        let expected = UnitCell::triclinic(10.0, 11.0, 12.0, 90.0, 80.0, 120.0);
+        assert_eq!(cell.shape(), CellShape::Triclinic);
+        assert_relative_eq!(cell.matrix(), expected.matrix(), epsilon = 1e-12);
+
+        let cell = UnitCell::from(&chemfiles::UnitCell::infinite());
+        assert!(cell.is_infinite());
+    }
+
+    #[test]
+    fn system() {
+        let mut frame = chemfiles::Frame::new();
+        frame.set_cell(&chemfiles::UnitCell::new([3.0, 3.0, 3.0]));
+        frame.add_atom(&chemfiles::Atom::new("O"), [0.0, 0.0, 0.0], None);
+        frame.add_atom(&chemfiles::Atom::new("H"), [1.0, 0.0, 0.0], None);
+        frame.add_atom(&chemfiles::Atom::new("H"), [0.0, 2.5, 0.0], None);
+
+        let mut system = ChemfilesSystem::from(frame);
+        assert_eq!(system.size().unwrap(), 3);
+        assert_eq!(system.species().unwrap(), &[8, 1, 1]);
+        assert_eq!(system.positions().unwrap()[1], Vector3D::new(1.0, 0.0, 0.0));
+        assert_eq!(system.cell().unwrap().shape(), CellShape::Orthorhombic);
+
+        system.compute_neighbors(1.1).unwrap();
+        let pairs = system.pairs().unwrap();
+        assert_eq!(pairs.len(), 2);
+
+        // the second hydrogen is a neighbor of the oxygen through the
+        // periodic boundaries
+        assert_eq!((pairs[0].first, pairs[0].second), (0, 1));
+        assert_eq!((pairs[1].first, pairs[1].second), (0, 2));
+        assert_relative_eq!(pairs[1].vector, Vector3D::new(0.0, -0.5, 0.0), epsilon = 1e-12);
+    }
+}
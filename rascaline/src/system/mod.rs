@@ -1,15 +1,25 @@
-use crate::Vector3D;
+use crate::{Error, Vector3D};
 
 mod cell;
 pub use self::cell::UnitCell;
 
+mod neighbors;
+
 mod simple_system;
 pub use self::simple_system::SimpleSystem;
 
+mod xyz;
+pub use self::xyz::{read_xyz, parse_xyz, XyzFrame, XyzValue, XyzArray};
+
+#[cfg(feature = "chemfiles")]
+mod chemfiles;
+#[cfg(feature = "chemfiles")]
+pub use self::chemfiles::{ChemfilesSystem, read_chemfiles};
+
 #[cfg(test)]
 mod test_utils;
 #[cfg(test)]
-pub use self::test_utils::test_systems;
+pub use self::test_utils::{test_systems, deform_cell, SimpleSystems};
 
 /// Pair of atoms coming from a neighbor list.
 // WARNING: any change to this definition MUST be reflected in rascal_pair_t as
@@ -24,44 +34,61 @@ pub struct Pair {
     /// vector from the first atom to the second atom, wrapped inside the unit
     /// cell as required
     pub vector: Vector3D,
+    /// number of unit cell vectors (along a, b and c) that must be added to
+    /// `positions[second] - positions[first]` to get `vector`. This
+    /// distinguishes between different periodic images of the same pair of
+    /// atoms.
+    pub cell_shift: [i32; 3],
 }
 
 /// A `System` deals with the storage of atoms and related information, as well
 /// as the computation of neighbor lists.
+///
+/// All functions can fail, for example when the data is coming from a
+/// user-provided callback through the C API. In this case, they should return
+/// an `Error::Callback` or `Error::InvalidSystem` describing the failure.
 pub trait System {
     /// Get the unit cell for this system
-    fn cell(&self) -> UnitCell;
+    fn cell(&self) -> Result<UnitCell, Error>;
 
     /// Get the number of atoms in this system
-    fn size(&self) -> usize;
+    fn size(&self) -> Result<usize, Error>;
 
     /// Get the atomic species for all atoms in this system. The returned value
     /// must be a slice of length `self.size()`, where each different atomic
     /// species is identified with a different usize value. These values are
     /// usually the atomic number, but don't have to.
-    fn species(&self) -> &[usize];
+    fn species(&self) -> Result<&[usize], Error>;
 
     /// Get the positions for all atoms in this system. The returned value must
     /// be a slice of length `self.size()` containing the cartesian coordinates
     /// of all atoms in the system.
-    fn positions(&self) -> &[Vector3D];
+    fn positions(&self) -> Result<&[Vector3D], Error>;
 
     /// Compute the neighbor list according to the given cutoff, and store it
     /// for later access with `pairs` or `pairs_around`.
-    fn compute_neighbors(&mut self, cutoff: f64);
+    fn compute_neighbors(&mut self, cutoff: f64) -> Result<(), Error>;
 
     /// Get the list of pairs in this system. This list of pair should only
-    /// contain each pair once (and not twice as `i-j` and `j-i`), should not
-    /// contain self pairs (`i-i`); and should only contains pairs where the
-    /// distance between atoms is actually bellow the cutoff passed in the last
-    /// call to `compute_neighbors`. This function is only valid to call after a
-    /// call to `compute_neighbors`.
-    fn pairs(&self) -> &[Pair];
+    /// contain each pair once (and not twice as `i-j` and `j-i`); and should
+    /// only contains pairs where the distance between atoms is actually bellow
+    /// the cutoff passed in the last call to `compute_neighbors`. This function
+    /// is only valid to call after a call to `compute_neighbors`.
+    ///
+    /// In periodic systems with small cells, the same `i-j` pair can appear
+    /// multiple times, once for each periodic image of `j` inside the cutoff,
+    /// with different `cell_shift`. Pairs between an atom and its own
+    /// periodic images (`i-i` with a non-zero `cell_shift`) are also allowed,
+    /// but only one of the `+cell_shift` and `-cell_shift` images should be
+    /// included. Pairs between an atom and itself with a zero `cell_shift`
+    /// should not be included.
+    fn pairs(&self) -> Result<&[Pair], Error>;
 
     /// Get the list of pairs in this system which include the atom at index
     /// `center`. The same restrictions on the list of pairs as `System::pairs`
     /// applies, with the additional condition that the pair `i-j` should be
     /// included both in the return of `pairs_containing(i)` and
-    /// `pairs_containing(j)`.
-    fn pairs_containing(&self, center: usize) -> &[Pair];
+    /// `pairs_containing(j)`. Self pairs `i-i` should be included only once in
+    /// `pairs_containing(i)`.
+    fn pairs_containing(&self, center: usize) -> Result<&[Pair], Error>;
 }
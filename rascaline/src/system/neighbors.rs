@@ -0,0 +1,456 @@
+use crate::Error;
+use super::{UnitCell, System, Vector3D, Pair};
+
+/// Neighbor list built with a linked-cells algorithm, which runs in `O(N)`
+/// time for systems with a fixed density.
+///
+/// Atoms are first sorted into bins of size larger than or close to the
+/// cutoff, and pairs are then searched for between neighboring bins only. For
+/// periodic systems, the bins are defined in fractional coordinates to
+/// support triclinic cells, and the search wraps around the cell as many
+/// times as needed to find all periodic images within the cutoff, including
+/// the images of an atom with itself.
+pub(crate) struct NeighborsList {
+    /// cutoff used to create this neighbor list
+    pub cutoff: f64,
+    /// all pairs in the system
+    pub pairs: Vec<Pair>,
+    /// all pairs associated with a given atom
+    pub pairs_by_center: Vec<Vec<Pair>>,
+}
+
+/// Set of bins used by the linked-cells algorithm
+struct CellList {
+    /// number of bins in each direction
+    n_bins: [usize; 3],
+    /// number of bins to search in each direction around a given bin to
+    /// find all the atoms within the cutoff
+    search_range: [isize; 3],
+    /// list of atoms in each bin, indexed by `bin_index`
+    atoms: Vec<Vec<usize>>,
+}
+
+impl CellList {
+    fn new(n_bins: [usize; 3], search_range: [isize; 3]) -> CellList {
+        CellList {
+            n_bins: n_bins,
+            search_range: search_range,
+            atoms: vec![Vec::new(); n_bins[0] * n_bins[1] * n_bins[2]],
+        }
+    }
+
+    /// Get the linear index of the bin `[a, b, c]`
+    fn bin_index(&self, bin: [usize; 3]) -> usize {
+        return (bin[0] * self.n_bins[1] + bin[1]) * self.n_bins[2] + bin[2];
+    }
+
+    /// Add the atom at index `atom` to the bin containing the (scaled)
+    /// position `fractional`, where all components of `fractional` should be
+    /// in `[0, 1]`.
+    fn add_atom(&mut self, atom: usize, fractional: [f64; 3]) {
+        let mut bin = [0; 3];
+        for spatial in 0..3 {
+            let n_bins = self.n_bins[spatial];
+            bin[spatial] = usize::min((fractional[spatial] * n_bins as f64) as usize, n_bins - 1);
+        }
+        let index = self.bin_index(bin);
+        self.atoms[index].push(atom);
+    }
+}
+
+/// Get the number of bins to use along a direction where the system extends
+/// over `length`, for the given `cutoff`
+fn bins_count(length: f64, cutoff: f64) -> usize {
+    if !length.is_finite() {
+        return 1;
+    }
+
+    return usize::max(1, (length / cutoff).floor() as usize);
+}
+
+/// Reduce the number of bins to have at most one bin per atom on average,
+/// preventing a huge memory usage for very large and sparse systems.
+fn limit_bins_count(n_bins: &mut [usize; 3], natoms: usize) {
+    let total = n_bins[0] * n_bins[1] * n_bins[2];
+    let natoms = usize::max(natoms, 1);
+    if total > natoms {
+        let factor = f64::cbrt(natoms as f64 / total as f64);
+        for n in n_bins {
+            *n = usize::max(1, (*n as f64 * factor).floor() as usize);
+        }
+    }
+}
+
+/// Does the `shift` between periodic images belongs to the "positive" half of
+/// all possible shifts? This is used to only include one of the `+shift` and
+/// `-shift` self-images of an atom.
+fn is_positive(shift: [isize; 3]) -> bool {
+    return shift[0] > 0
+        || (shift[0] == 0 && shift[1] > 0)
+        || (shift[0] == 0 && shift[1] == 0 && shift[2] > 0);
+}
+
+impl NeighborsList {
+    pub fn new<S: System + ?Sized>(system: &S, cutoff: f64) -> Result<NeighborsList, Error> {
+        assert!(cutoff > 0.0 && cutoff.is_finite(), "cutoff must be positive for neighbors list");
+
+        let cell = system.cell()?;
+        let natoms = system.size()?;
+
+        let positions = system.positions()?;
+        let (cell_list, offsets) = if cell.is_infinite() {
+            NeighborsList::infinite_cell_list(positions, cutoff)
+        } else {
+            NeighborsList::periodic_cell_list(&cell, positions, cutoff)
+        };
+
+        let cutoff2 = cutoff * cutoff;
+        let periodic = !cell.is_infinite();
+        let n_bins = cell_list.n_bins;
+        let range = cell_list.search_range;
+
+        let mut pairs = Vec::new();
+        for a in 0..n_bins[0] {
+            for b in 0..n_bins[1] {
+                for c in 0..n_bins[2] {
+                    let current = &cell_list.atoms[cell_list.bin_index([a, b, c])];
+                    if current.is_empty() {
+                        continue;
+                    }
+
+                    for delta_a in -range[0]..=range[0] {
+                        for delta_b in -range[1]..=range[1] {
+                            for delta_c in -range[2]..=range[2] {
+                                let mut other = [a as isize + delta_a, b as isize + delta_b, c as isize + delta_c];
+                                let mut shift = [0, 0, 0];
+                                for spatial in 0..3 {
+                                    let n = n_bins[spatial] as isize;
+                                    if periodic {
+                                        shift[spatial] = other[spatial].div_euclid(n);
+                                        other[spatial] = other[spatial].rem_euclid(n);
+                                    }
+                                }
+
+                                if other.iter().zip(&n_bins).any(|(&o, &n)| o < 0 || o >= n as isize) {
+                                    // outside of a non-periodic system
+                                    continue;
+                                }
+
+                                let other_bin = [other[0] as usize, other[1] as usize, other[2] as usize];
+                                for &i in current {
+                                    for &j in &cell_list.atoms[cell_list.bin_index(other_bin)] {
+                                        // each pair is found twice, as i-j
+                                        // with `shift` and j-i with `-shift`,
+                                        // only keep one of them
+                                        if i > j || (i == j && !is_positive(shift)) {
+                                            continue;
+                                        }
+
+                                        // shift between the actual positions
+                                        // of the atoms, which might be
+                                        // outside of the unit cell
+                                        let cell_shift = [
+                                            shift[0] + offsets[i][0] - offsets[j][0],
+                                            shift[1] + offsets[i][1] - offsets[j][1],
+                                            shift[2] + offsets[i][2] - offsets[j][2],
+                                        ];
+
+                                        let mut vector = positions[j] - positions[i];
+                                        if cell_shift != [0, 0, 0] {
+                                            vector += cell.cartesian(&Vector3D::new(
+                                                cell_shift[0] as f64, cell_shift[1] as f64, cell_shift[2] as f64
+                                            ));
+                                        }
+
+                                        if vector.norm2() < cutoff2 {
+                                            pairs.push(Pair {
+                                                first: i,
+                                                second: j,
+                                                vector: vector,
+                                                cell_shift: [cell_shift[0] as i32, cell_shift[1] as i32, cell_shift[2] as i32],
+                                            });
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // sort the pairs to get a deterministic order, independent of the
+        // binning of atoms
+        pairs.sort_by_key(|pair| (pair.first, pair.second, pair.cell_shift));
+
+        let mut pairs_by_center = vec![Vec::new(); natoms];
+        for pair in &pairs {
+            pairs_by_center[pair.first].push(*pair);
+            if pair.second != pair.first {
+                pairs_by_center[pair.second].push(*pair);
+            }
+        }
+
+        return Ok(NeighborsList {
+            cutoff: cutoff,
+            pairs: pairs,
+            pairs_by_center: pairs_by_center,
+        });
+    }
+
+    /// Create the cell list for a periodic system, binning atoms according to
+    /// their fractional coordinates. This also returns the number of cell
+    /// vectors separating each atom from its image inside the unit cell.
+    fn periodic_cell_list(cell: &UnitCell, positions: &[Vector3D], cutoff: f64) -> (CellList, Vec<[isize; 3]>) {
+        // distances between opposite faces of the cell
+        let lengths = cell.lengths();
+
+        let mut n_bins = [0; 3];
+        for spatial in 0..3 {
+            n_bins[spatial] = bins_count(lengths[spatial], cutoff);
+        }
+        limit_bins_count(&mut n_bins, positions.len());
+
+        let mut search_range = [0; 3];
+        for spatial in 0..3 {
+            let bin_width = lengths[spatial] / n_bins[spatial] as f64;
+            search_range[spatial] = (cutoff / bin_width).ceil() as isize;
+        }
+
+        let mut cell_list = CellList::new(n_bins, search_range);
+        let mut offsets = Vec::with_capacity(positions.len());
+        for (atom, position) in positions.iter().enumerate() {
+            let mut fractional = cell.fractional(position);
+            let mut offset = [0; 3];
+            for spatial in 0..3 {
+                let floor = f64::floor(fractional[spatial]);
+                fractional[spatial] -= floor;
+                offset[spatial] = floor as isize;
+            }
+            cell_list.add_atom(atom, [fractional[0], fractional[1], fractional[2]]);
+            offsets.push(offset);
+        }
+
+        return (cell_list, offsets);
+    }
+
+    /// Create the cell list for a non-periodic system, binning atoms inside
+    /// the bounding box of all positions.
+    fn infinite_cell_list(positions: &[Vector3D], cutoff: f64) -> (CellList, Vec<[isize; 3]>) {
+        let mut min = Vector3D::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        let mut max = Vector3D::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for position in positions {
+            for spatial in 0..3 {
+                min[spatial] = f64::min(min[spatial], position[spatial]);
+                max[spatial] = f64::max(max[spatial], position[spatial]);
+            }
+        }
+
+        let extent = max - min;
+        let mut n_bins = [0; 3];
+        for spatial in 0..3 {
+            n_bins[spatial] = bins_count(extent[spatial], cutoff);
+        }
+        limit_bins_count(&mut n_bins, positions.len());
+
+        let mut search_range = [0; 3];
+        for spatial in 0..3 {
+            let bin_width = extent[spatial] / n_bins[spatial] as f64;
+            search_range[spatial] = if bin_width > 0.0 {
+                (cutoff / bin_width).ceil() as isize
+            } else {
+                0
+            };
+        }
+
+        let mut cell_list = CellList::new(n_bins, search_range);
+        for (atom, position) in positions.iter().enumerate() {
+            let mut scaled = [0.0; 3];
+            for spatial in 0..3 {
+                if extent[spatial] > 0.0 {
+                    scaled[spatial] = (position[spatial] - min[spatial]) / extent[spatial];
+                }
+            }
+            cell_list.add_atom(atom, scaled);
+        }
+
+        return (cell_list, vec![[0, 0, 0]; positions.len()]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::SimpleSystem;
+    use approx::assert_relative_eq;
+
+    /// Find all pairs (including all periodic images) with a brute force
+    /// search over a large number of images, sorted by atoms indexes and
+    /// distance
+    fn brute_force(system: &SimpleSystem, cutoff: f64, max_image: isize) -> Vec<(usize, usize, f64)> {
+        let cell = system.cell().unwrap();
+        let positions = system.positions().unwrap();
+        let images = if cell.is_infinite() { 0 } else { max_image };
+
+        let mut pairs = Vec::new();
+        for i in 0..system.size().unwrap() {
+            for j in i..system.size().unwrap() {
+                for a in -images..=images {
+                    for b in -images..=images {
+                        for c in -images..=images {
+                            if i == j && !is_positive([a, b, c]) {
+                                continue;
+                            }
+
+                            let shift = Vector3D::new(a as f64, b as f64, c as f64);
+                            let vector = positions[j] + cell.cartesian(&shift) - positions[i];
+                            if vector.norm() < cutoff {
+                                pairs.push((i, j, vector.norm()));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        sort(&mut pairs);
+        return pairs;
+    }
+
+    fn sort(pairs: &mut Vec<(usize, usize, f64)>) {
+        pairs.sort_by(|a, b| {
+            (a.0, a.1).cmp(&(b.0, b.1)).then(a.2.partial_cmp(&b.2).unwrap())
+        });
+    }
+
+    fn check_neighbors(system: &SimpleSystem, cutoff: f64, max_image: isize) {
+        let neighbors = NeighborsList::new(system, cutoff).unwrap();
+        let positions = system.positions().unwrap();
+        let cell = system.cell().unwrap();
+
+        let mut pairs = Vec::new();
+        for pair in &neighbors.pairs {
+            assert!(pair.first <= pair.second);
+
+            // the pair vector should correspond to the periodic image of the
+            // vector between the two atoms given by the cell shift
+            let difference = pair.vector - (positions[pair.second] - positions[pair.first]);
+            if cell.is_infinite() {
+                assert_eq!(pair.cell_shift, [0, 0, 0]);
+                assert_relative_eq!(difference.norm(), 0.0, epsilon=1e-12);
+            } else {
+                let fractional = cell.fractional(&difference);
+                for spatial in 0..3 {
+                    assert_relative_eq!(fractional[spatial], pair.cell_shift[spatial] as f64, epsilon=1e-9);
+                }
+            }
+
+            if pair.first == pair.second {
+                assert_ne!(pair.cell_shift, [0, 0, 0]);
+            }
+
+            pairs.push((pair.first, pair.second, pair.vector.norm()));
+        }
+        sort(&mut pairs);
+
+        let expected = brute_force(system, cutoff, max_image);
+        assert_eq!(pairs.len(), expected.len());
+        for (pair, expected) in pairs.iter().zip(&expected) {
+            assert_eq!((pair.0, pair.1), (expected.0, expected.1));
+            assert_relative_eq!(pair.2, expected.2, max_relative=1e-12);
+        }
+
+        for (center, pairs) in neighbors.pairs_by_center.iter().enumerate() {
+            let count = neighbors.pairs.iter()
+                .filter(|pair| pair.first == center || pair.second == center)
+                .count();
+            assert_eq!(pairs.len(), count);
+        }
+    }
+
+    /// Create a system with `natoms` atoms at pseudo-random positions
+    fn random_system(cell: UnitCell, natoms: usize, scale: f64) -> SimpleSystem {
+        let mut system = SimpleSystem::new(cell);
+        // simple linear congruential generator, good enough for tests
+        let mut state = 42_u64;
+        let mut random = || {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 11) as f64 / (1_u64 << 53) as f64
+        };
+
+        for _ in 0..natoms {
+            let position = Vector3D::new(random(), random(), random());
+            system.add_atom(1, scale * position);
+        }
+        return system;
+    }
+
+    #[test]
+    fn cubic() {
+        let system = random_system(UnitCell::cubic(10.0), 100, 10.0);
+        check_neighbors(&system, 3.0, 1);
+    }
+
+    #[test]
+    fn triclinic() {
+        let cell = UnitCell::triclinic(9.0, 10.0, 11.0, 70.0, 85.0, 100.0);
+        let system = random_system(cell, 100, 10.0);
+        check_neighbors(&system, 3.2, 3);
+    }
+
+    #[test]
+    fn infinite() {
+        let system = random_system(UnitCell::infinite(), 100, 10.0);
+        check_neighbors(&system, 2.5, 0);
+
+        // atoms outside of [0, 1]^3
+        let system = random_system(UnitCell::infinite(), 50, -20.0);
+        check_neighbors(&system, 4.5, 0);
+    }
+
+    #[test]
+    fn atoms_outside_cell() {
+        let system = random_system(UnitCell::cubic(10.0), 50, 25.0);
+        check_neighbors(&system, 3.0, 4);
+    }
+
+    #[test]
+    fn large_cutoff() {
+        // the cutoff is larger than the cell, there are multiple images for
+        // each pair and self-images
+        let system = random_system(UnitCell::cubic(3.0), 5, 3.0);
+        check_neighbors(&system, 4.5, 3);
+
+        let cell = UnitCell::triclinic(3.0, 4.0, 3.5, 80.0, 95.0, 110.0);
+        let system = random_system(cell, 4, 3.0);
+        check_neighbors(&system, 5.0, 5);
+    }
+
+    #[test]
+    fn self_images() {
+        let mut system = SimpleSystem::new(UnitCell::cubic(2.0));
+        system.add_atom(1, Vector3D::new(0.5, 0.5, 0.5));
+
+        let neighbors = NeighborsList::new(&system, 2.5).unwrap();
+        // the 6 closest images, each self pair only included once
+        assert_eq!(neighbors.pairs.len(), 3);
+        for pair in &neighbors.pairs {
+            assert_eq!(pair.first, 0);
+            assert_eq!(pair.second, 0);
+            assert_relative_eq!(pair.vector.norm(), 2.0);
+        }
+        assert_eq!(neighbors.pairs_by_center[0].len(), 3);
+    }
+
+    #[test]
+    fn sparse_system() {
+        // very large cell with few atoms should not use a lot of bins
+        let mut system = SimpleSystem::new(UnitCell::cubic(10000.0));
+        system.add_atom(1, Vector3D::new(0.0, 0.0, 0.0));
+        system.add_atom(1, Vector3D::new(0.0, 0.0, 1.0));
+        system.add_atom(1, Vector3D::new(0.0, 0.0, 9999.8));
+
+        let neighbors = NeighborsList::new(&system, 1.5).unwrap();
+        assert_eq!(neighbors.pairs.len(), 3);
+    }
+}
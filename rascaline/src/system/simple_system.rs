@@ -1,54 +1,15 @@
-use super::{UnitCell, System, Vector3D, Pair};
-
-struct CrappyNeighborsList {
-    cutoff: f64,
-    pairs: Vec<Pair>,
-    pairs_by_center: Vec<Vec<Pair>>,
-}
-
-impl CrappyNeighborsList {
-    pub fn new<S: System + ?Sized>(system: &S, cutoff: f64) -> CrappyNeighborsList {
-        let cutoff2 = cutoff * cutoff;
-        let cell = system.cell();
-        let natoms = system.size();
-        let positions = system.positions();
-
-        let mut pairs = Vec::new();
-        // crappy O(n^2) implementation, looping over all atoms in the system
-        for i in 0..natoms {
-            for j in (i + 1)..natoms {
-                let mut vector = positions[j] - positions[i];
-                cell.vector_image(&mut vector);
-                if vector.norm2() < cutoff2 {
-                    if i < j {
-                        pairs.push(Pair{ first: i, second: j, vector: vector});
-                    } else {
-                        pairs.push(Pair{ first: j, second: i, vector: -vector});
-                    }
-                }
-            }
-        }
+use std::convert::TryFrom;
 
-        let mut pairs_by_center = vec![Vec::new(); natoms];
-        for pair in &pairs {
-            pairs_by_center[pair.first].push(*pair);
-            pairs_by_center[pair.second].push(*pair);
-        }
-
-        return CrappyNeighborsList {
-            cutoff: cutoff,
-            pairs: pairs,
-            pairs_by_center: pairs_by_center,
-        };
-    }
-}
+use crate::Error;
+use super::{UnitCell, System, Vector3D, Pair};
+use super::neighbors::NeighborsList;
 
 /// A simple implementation of `System` to use when no other is available
 pub struct SimpleSystem {
     cell: UnitCell,
     species: Vec<usize>,
     positions: Vec<Vector3D>,
-    neighbors: Option<CrappyNeighborsList>,
+    neighbors: Option<NeighborsList>,
 }
 
 impl SimpleSystem {
@@ -77,7 +38,9 @@ impl SimpleSystem {
         for line in lines {
             let mut split = line.split(' ').filter(|s| !s.is_empty());
             let name = split.next().expect("missing atomic name in XYZ");
-            let species = atomic_number(name);
+            let species = atomic_number(name).unwrap_or_else(
+                || panic!("atom type '{}' not found in periodic table", name)
+            );
 
             let x = split.next().expect("missing atomic name in XYZ")
                 .parse().expect("failed to parse x coordinate in XYZ");
@@ -88,7 +51,7 @@ impl SimpleSystem {
             system.add_atom(species, Vector3D::new(x, y, z))
         }
 
-        assert_eq!(natoms, system.species().len());
+        assert_eq!(natoms, system.species.len());
 
         return system;
     }
@@ -120,61 +83,66 @@ static ATOMIC_NAMES: [&str; 118] = [
     "Rg", "Cn", "Nh", "Fl", "Mc", "Lv", "Ts", "Og",
 ];
 
-fn atomic_number(name: &str) -> usize {
+/// Get the atomic number corresponding to the given atomic `name`, or `None`
+/// if this name is not in the periodic table
+pub(crate) fn atomic_number(name: &str) -> Option<usize> {
     for (i, &symbol) in ATOMIC_NAMES.iter().enumerate() {
         if name == symbol {
-            return i + 1;
+            return Some(i + 1);
         }
     }
 
-    panic!("atom type '{}' not found in periodic table", name);
+    return None;
 }
 
 impl System for SimpleSystem {
-    fn size(&self) -> usize {
-        self.species.len()
+    fn size(&self) -> Result<usize, Error> {
+        Ok(self.species.len())
     }
 
-    fn positions(&self) -> &[Vector3D] {
-        &self.positions
+    fn positions(&self) -> Result<&[Vector3D], Error> {
+        Ok(&self.positions)
     }
 
-    fn species(&self) -> &[usize] {
-        &self.species
+    fn species(&self) -> Result<&[usize], Error> {
+        Ok(&self.species)
     }
 
-    fn cell(&self) -> UnitCell {
-        self.cell
+    fn cell(&self) -> Result<UnitCell, Error> {
+        Ok(self.cell)
     }
 
     #[allow(clippy::float_cmp)]
-    fn compute_neighbors(&mut self, cutoff: f64) {
+    fn compute_neighbors(&mut self, cutoff: f64) -> Result<(), Error> {
         // re-use already computed NL is possible
         if let Some(ref nl) = self.neighbors {
             if nl.cutoff == cutoff {
-                return;
+                return Ok(());
             }
         }
 
-        self.neighbors = Some(CrappyNeighborsList::new(self, cutoff));
+        self.neighbors = Some(NeighborsList::new(self, cutoff)?);
+        return Ok(());
     }
 
-    fn pairs(&self) -> &[Pair] {
-        &self.neighbors.as_ref().expect("neighbor list is not initialized").pairs
+    fn pairs(&self) -> Result<&[Pair], Error> {
+        Ok(&self.neighbors.as_ref().expect("neighbor list is not initialized").pairs)
     }
 
-    fn pairs_containing(&self, center: usize) -> &[Pair] {
-        &self.neighbors.as_ref().expect("neighbor list is not initialized").pairs_by_center[center]
+    fn pairs_containing(&self, center: usize) -> Result<&[Pair], Error> {
+        Ok(&self.neighbors.as_ref().expect("neighbor list is not initialized").pairs_by_center[center])
     }
 }
 
-impl From<&dyn System> for SimpleSystem {
-    fn from(system: &dyn System) -> SimpleSystem {
-        let mut new = SimpleSystem::new(system.cell());
-        for (&species, &position) in system.species().iter().zip(system.positions()) {
+impl TryFrom<&dyn System> for SimpleSystem {
+    type Error = Error;
+
+    fn try_from(system: &dyn System) -> Result<SimpleSystem, Error> {
+        let mut new = SimpleSystem::new(system.cell()?);
+        for (&species, &position) in system.species()?.iter().zip(system.positions()?) {
             new.add_atom(species, position);
         }
-        return new;
+        return Ok(new);
     }
 }
 
@@ -189,12 +157,12 @@ mod tests {
         system.add_atom(1, Vector3D::new(1.0, 3.0, 4.0));
         system.add_atom(3, Vector3D::new(5.0, 3.0, 4.0));
 
-        assert_eq!(system.size(), 3);
+        assert_eq!(system.size().unwrap(), 3);
         assert_eq!(system.species.len(), 3);
         assert_eq!(system.positions.len(), 3);
 
-        assert_eq!(system.species(), &[3, 1, 3]);
-        assert_eq!(system.positions(), &[
+        assert_eq!(system.species().unwrap(), &[3, 1, 3]);
+        assert_eq!(system.positions().unwrap(), &[
             Vector3D::new(2.0, 3.0, 4.0),
             Vector3D::new(1.0, 3.0, 4.0),
             Vector3D::new(5.0, 3.0, 4.0),
@@ -210,10 +178,10 @@ O 1.0 3.0 4.0
 Zn 5.0 3.0 4.0
 ");
 
-        assert_eq!(system.size(), 3);
+        assert_eq!(system.size().unwrap(), 3);
 
-        assert_eq!(system.species(), &[6, 8, 30]);
-        assert_eq!(system.positions(), &[
+        assert_eq!(system.species().unwrap(), &[6, 8, 30]);
+        assert_eq!(system.positions().unwrap(), &[
             Vector3D::new(2.0, 3.0, 4.0),
             Vector3D::new(1.0, 3.0, 4.0),
             Vector3D::new(5.0, 3.0, 4.0),
@@ -15,6 +15,22 @@ impl SimpleSystems {
     }
 }
 
+/// Deform the unit cell of `system`, changing the `spatial` component of the
+/// `cell_vector`-th cell vector by `delta`, while keeping the atoms at fixed
+/// fractional coordinates.
+pub fn deform_cell(system: &SimpleSystem, cell_vector: usize, spatial: usize, delta: f64) -> SimpleSystem {
+    let cell = system.cell().unwrap();
+    let mut matrix = cell.matrix();
+    matrix[spatial][cell_vector] += delta;
+
+    let mut deformed = SimpleSystem::new(UnitCell::from(matrix));
+    for (&species, position) in system.species().unwrap().iter().zip(system.positions().unwrap()) {
+        let fractional = cell.fractional(position);
+        deformed.add_atom(species, deformed.cell().unwrap().cartesian(&fractional));
+    }
+    return deformed;
+}
+
 pub fn test_systems(names: &[&str]) -> SimpleSystems {
     let systems = names.iter().map(|&name| {
         match name {
@@ -0,0 +1,611 @@
+use std::collections::BTreeMap;
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::Chars;
+
+use ndarray::Array2;
+
+use crate::{Error, Matrix3, Vector3D};
+use super::{SimpleSystem, UnitCell};
+use super::simple_system::atomic_number;
+
+/// Value of a per-frame property, read from the comment line of an extended
+/// XYZ frame
+#[derive(Debug, Clone, PartialEq)]
+pub enum XyzValue {
+    Bool(bool),
+    Integer(i64),
+    Real(f64),
+    String(String),
+    BoolArray(Vec<bool>),
+    IntegerArray(Vec<i64>),
+    RealArray(Vec<f64>),
+}
+
+/// Values of a per-atom property of an extended XYZ frame, with one row per
+/// atom and one column per component of the property
+#[derive(Debug, Clone, PartialEq)]
+pub enum XyzArray {
+    Bool(Array2<bool>),
+    Integer(Array2<i64>),
+    Real(Array2<f64>),
+    String(Array2<String>),
+}
+
+/// A single frame read from an extended XYZ file
+pub struct XyzFrame {
+    /// The system in this frame. The unit cell is taken from the `Lattice`
+    /// key, and is infinite when there is no `Lattice` or when `pbc` is set to
+    /// `F F F`.
+    pub system: SimpleSystem,
+    /// Per-frame properties (energy, stress, …) from the comment line, except
+    /// for `Lattice`, `pbc` and `Properties`
+    pub info: BTreeMap<String, XyzValue>,
+    /// Per-atom properties (forces, charges, …) from the `Properties` key,
+    /// except for `species` and `pos`
+    pub arrays: BTreeMap<String, XyzArray>,
+}
+
+/// Read all the frames in the extended XYZ file at `path`.
+///
+/// See [`parse_xyz`] for a description of the supported format.
+pub fn read_xyz(path: impl AsRef<Path>) -> Result<Vec<XyzFrame>, Error> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path).map_err(|error| Error::Io(std::io::Error::new(
+        error.kind(), format!("failed to read XYZ file at '{}': {}", path.display(), error)
+    )))?;
+    return parse_xyz(&content);
+}
+
+/// Parse all the frames in `content`, using the [extended XYZ] format.
+///
+/// The comment line of each frame contains a list of `key=value` pairs, where
+/// values can be quoted with `"` or `{}`. Keys without a value are read as
+/// `true`. The `Lattice` key contains the three cell vectors `a`, `b` and `c`;
+/// `pbc` must be either `T T T` or `F F F`, since partially periodic systems
+/// are not supported. The `Properties` key describes the columns of the atomic
+/// lines, and defaults to `species:S:1:pos:R:3`, making plain XYZ files valid
+/// extended XYZ files.
+///
+/// [extended XYZ]: https://github.com/libAtoms/extxyz
+pub fn parse_xyz(content: &str) -> Result<Vec<XyzFrame>, Error> {
+    let mut lines = content.lines().enumerate().map(|(i, line)| (i + 1, line)).peekable();
+
+    let mut frames = Vec::new();
+    loop {
+        // skip empty lines between frames and at the end of the file
+        while lines.peek().map_or(false, |(_, line)| line.trim().is_empty()) {
+            lines.next();
+        }
+
+        let (natoms_line, natoms) = match lines.next() {
+            Some(line) => line,
+            None => break,
+        };
+        let natoms = natoms.trim().parse::<usize>().map_err(|_| xyz_error(
+            natoms_line, format!("expected the number of atoms, got '{}'", natoms.trim())
+        ))?;
+
+        let (comment_line, comment) = lines.next().ok_or_else(
+            || xyz_error(natoms_line + 1, "missing comment line")
+        )?;
+        let header = FrameHeader::parse(comment).map_err(|e| xyz_error(comment_line, e))?;
+
+        let mut atoms = Vec::with_capacity(natoms);
+        for i in 0..natoms {
+            let (line_number, line) = lines.next().ok_or_else(|| xyz_error(
+                comment_line + i + 1, format!("expected {} atoms, got {}", natoms, i)
+            ))?;
+
+            let columns = line.split_whitespace().collect::<Vec<_>>();
+            if columns.len() != header.n_columns() {
+                return Err(xyz_error(line_number, format!(
+                    "expected {} columns in atomic line, got {}",
+                    header.n_columns(), columns.len()
+                )));
+            }
+            atoms.push(columns);
+        }
+
+        frames.push(header.into_frame(&atoms, comment_line + 1)?);
+    }
+
+    return Ok(frames);
+}
+
+fn xyz_error(line: usize, message: impl std::fmt::Display) -> Error {
+    Error::InvalidParameter(format!("invalid XYZ file at line {}: {}", line, message))
+}
+
+/// Description of one of the per-atom property from the `Properties` key
+struct Property {
+    name: String,
+    kind: char,
+    count: usize,
+}
+
+/// Data parsed from the comment line of an extended XYZ frame
+struct FrameHeader {
+    cell: UnitCell,
+    info: BTreeMap<String, XyzValue>,
+    properties: Vec<Property>,
+}
+
+impl FrameHeader {
+    fn parse(comment: &str) -> Result<FrameHeader, String> {
+        let mut lattice = None;
+        let mut pbc = None;
+        let mut properties = None;
+        let mut info = BTreeMap::new();
+
+        for (key, value) in parse_key_values(comment)? {
+            let value = match value {
+                Some(value) => value,
+                None => {
+                    info.insert(key, XyzValue::Bool(true));
+                    continue;
+                }
+            };
+
+            if key.eq_ignore_ascii_case("lattice") {
+                lattice = Some(parse_lattice(&value)?);
+            } else if key.eq_ignore_ascii_case("pbc") {
+                pbc = Some(parse_pbc(&value)?);
+            } else if key.eq_ignore_ascii_case("properties") {
+                properties = Some(parse_properties(&value)?);
+            } else {
+                info.insert(key, parse_value(&value));
+            }
+        }
+
+        let cell = match (lattice, pbc) {
+            (None, None) | (_, Some([false, false, false])) => UnitCell::infinite(),
+            (None, Some(_)) => {
+                return Err("periodic boundary conditions require a Lattice".into());
+            }
+            (Some(matrix), None | Some([true, true, true])) => {
+                if matrix.determinant().abs() <= 1e-6 {
+                    return Err("the Lattice vectors do not define a valid unit cell".into());
+                }
+                UnitCell::from(matrix)
+            }
+            (Some(_), Some(_)) => {
+                return Err("partially periodic systems are not supported".into());
+            }
+        };
+
+        let properties = match properties {
+            Some(properties) => properties,
+            None => parse_properties("species:S:1:pos:R:3")?,
+        };
+
+        return Ok(FrameHeader {
+            cell: cell,
+            info: info,
+            properties: properties,
+        });
+    }
+
+    fn n_columns(&self) -> usize {
+        self.properties.iter().map(|p| p.count).sum()
+    }
+
+    /// Create a frame from the `atoms` columns, where `first_line` is the line
+    /// number of the first atom, used in error messages.
+    fn into_frame(self, atoms: &[Vec<&str>], first_line: usize) -> Result<XyzFrame, Error> {
+        let mut species = None;
+        let mut positions = None;
+        let mut arrays = BTreeMap::new();
+
+        let mut start = 0;
+        for property in self.properties {
+            let columns = Columns {
+                atoms: atoms,
+                start: start,
+                count: property.count,
+                first_line: first_line,
+            };
+            start += property.count;
+
+            if property.name == "species" {
+                if property.kind != 'S' || property.count != 1 {
+                    return Err(xyz_error(first_line - 1, "'species' property must be 'S:1'"));
+                }
+                species = Some(columns.parse("atomic species", atomic_number)?);
+            } else if property.name == "pos" {
+                if property.kind != 'R' || property.count != 3 {
+                    return Err(xyz_error(first_line - 1, "'pos' property must be 'R:3'"));
+                }
+                positions = Some(columns.parse("real number", |value| value.parse::<f64>().ok())?);
+            } else {
+                let array = match property.kind {
+                    'S' => XyzArray::String(columns.parse("string", |value| Some(value.to_owned()))?),
+                    'R' => XyzArray::Real(columns.parse("real number", |value| value.parse().ok())?),
+                    'I' => XyzArray::Integer(columns.parse("integer", |value| value.parse().ok())?),
+                    'L' => XyzArray::Bool(columns.parse("boolean", parse_bool)?),
+                    _ => unreachable!("property kind was checked when parsing"),
+                };
+                arrays.insert(property.name, array);
+            }
+        }
+
+        let species = species.ok_or_else(|| xyz_error(first_line - 1, "missing 'species' property"))?;
+        let positions = positions.ok_or_else(|| xyz_error(first_line - 1, "missing 'pos' property"))?;
+
+        let mut system = SimpleSystem::new(self.cell);
+        for (species, position) in species.iter().zip(positions.outer_iter()) {
+            system.add_atom(*species, Vector3D::new(position[0], position[1], position[2]));
+        }
+
+        return Ok(XyzFrame {
+            system: system,
+            info: self.info,
+            arrays: arrays,
+        });
+    }
+}
+
+/// A subset of the columns of the atomic lines in a frame
+struct Columns<'a> {
+    atoms: &'a [Vec<&'a str>],
+    start: usize,
+    count: usize,
+    first_line: usize,
+}
+
+impl Columns<'_> {
+    /// Parse all values in these columns with `parse`, using `expected` to
+    /// describe the expected values in error messages
+    fn parse<T, F>(&self, expected: &str, parse: F) -> Result<Array2<T>, Error> where F: Fn(&str) -> Option<T> {
+        let mut values = Vec::with_capacity(self.atoms.len() * self.count);
+        for (i, atom) in self.atoms.iter().enumerate() {
+            for &value in &atom[self.start..(self.start + self.count)] {
+                let value = parse(value).ok_or_else(|| xyz_error(
+                    self.first_line + i, format!("expected {}, got '{}'", expected, value)
+                ))?;
+                values.push(value);
+            }
+        }
+
+        let array = Array2::from_shape_vec((self.atoms.len(), self.count), values);
+        return Ok(array.expect("wrong array shape"));
+    }
+}
+
+/// Split a comment line in a list of `key=value` or `key` entries
+fn parse_key_values(comment: &str) -> Result<Vec<(String, Option<String>)>, String> {
+    let mut chars = comment.chars().peekable();
+    let mut key_values = Vec::new();
+    loop {
+        skip_whitespace(&mut chars);
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let key = read_token(&mut chars, true)?;
+        skip_whitespace(&mut chars);
+        if chars.peek() == Some(&'=') {
+            chars.next();
+            skip_whitespace(&mut chars);
+            let value = read_token(&mut chars, false)?;
+            key_values.push((key, Some(value)));
+        } else {
+            key_values.push((key, None));
+        }
+    }
+
+    return Ok(key_values);
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars<'_>>) {
+    while chars.peek().map_or(false, |c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+/// Read a single key or value, which can be quoted with `"` or `{}`
+fn read_token(chars: &mut Peekable<Chars<'_>>, is_key: bool) -> Result<String, String> {
+    let mut token = String::new();
+    match chars.peek() {
+        Some('"') => {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some('n') => token.push('\n'),
+                        Some(c) => token.push(c),
+                        None => return Err("unterminated quoted string".into()),
+                    },
+                    Some(c) => token.push(c),
+                    None => return Err("unterminated quoted string".into()),
+                }
+            }
+        }
+        Some('{') => {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('}') => break,
+                    Some(c) => token.push(c),
+                    None => return Err("unterminated '{' in value".into()),
+                }
+            }
+        }
+        _ => {
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || (is_key && c == '=') {
+                    break;
+                }
+                token.push(c);
+                chars.next();
+            }
+
+            if token.is_empty() {
+                return Err(format!("expected a {}", if is_key {"key"} else {"value"}));
+            }
+        }
+    }
+
+    return Ok(token);
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "T" | "True" | "TRUE" | "true" => Some(true),
+        "F" | "False" | "FALSE" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// Split an array value (`"1 2 3"` or `{1, 2, 3}`) in its elements
+fn split_array(value: &str) -> Vec<&str> {
+    value.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Parse all the elements in `value` with `parse`, returning `None` if any of
+/// them fails to parse
+fn parse_all<T>(elements: &[&str], parse: impl Fn(&str) -> Option<T>) -> Option<Vec<T>> {
+    elements.iter().map(|&element| parse(element)).collect()
+}
+
+/// Parse a value from the comment line, trying integers, then real numbers,
+/// then booleans before falling back to strings
+fn parse_value(value: &str) -> XyzValue {
+    let elements = split_array(value);
+    if elements.is_empty() {
+        return XyzValue::String(value.into());
+    }
+
+    if let Some(mut values) = parse_all(&elements, |e| e.parse::<i64>().ok()) {
+        if values.len() == 1 {
+            return XyzValue::Integer(values.remove(0));
+        }
+        return XyzValue::IntegerArray(values);
+    }
+
+    if let Some(mut values) = parse_all(&elements, |e| e.parse::<f64>().ok()) {
+        if values.len() == 1 {
+            return XyzValue::Real(values.remove(0));
+        }
+        return XyzValue::RealArray(values);
+    }
+
+    if let Some(mut values) = parse_all(&elements, parse_bool) {
+        if values.len() == 1 {
+            return XyzValue::Bool(values.remove(0));
+        }
+        return XyzValue::BoolArray(values);
+    }
+
+    return XyzValue::String(value.into());
+}
+
+/// Parse the `Lattice` value, containing the `a`, `b` and `c` vectors. These
+/// are stored as columns of the matrix, as in `UnitCell`.
+fn parse_lattice(value: &str) -> Result<Matrix3, String> {
+    let values = parse_all(&split_array(value), |e| e.parse::<f64>().ok());
+    match values {
+        Some(values) if values.len() == 9 => {
+            let mut matrix = Matrix3::zero();
+            for vector in 0..3 {
+                for spatial in 0..3 {
+                    matrix[spatial][vector] = values[3 * vector + spatial];
+                }
+            }
+            return Ok(matrix);
+        }
+        _ => return Err(format!("expected 9 real numbers for Lattice, got '{}'", value)),
+    }
+}
+
+fn parse_pbc(value: &str) -> Result<[bool; 3], String> {
+    match parse_all(&split_array(value), parse_bool) {
+        Some(values) if values.len() == 3 => Ok([values[0], values[1], values[2]]),
+        _ => Err(format!("expected 3 booleans for pbc, got '{}'", value)),
+    }
+}
+
+/// Parse the `Properties` value, with the format `name:kind:count:...`
+fn parse_properties(value: &str) -> Result<Vec<Property>, String> {
+    let fields = value.split(':').collect::<Vec<_>>();
+    if fields.len() % 3 != 0 {
+        return Err(format!("expected name:kind:count triplets in Properties, got '{}'", value));
+    }
+
+    let mut properties = Vec::new();
+    for chunk in fields.chunks(3) {
+        let kind = match chunk[1] {
+            "S" => 'S',
+            "R" => 'R',
+            "I" => 'I',
+            "L" => 'L',
+            kind => return Err(format!("unknown property kind '{}' for '{}'", kind, chunk[0])),
+        };
+
+        let count = match chunk[2].parse::<usize>() {
+            Ok(count) if count > 0 => count,
+            _ => return Err(format!("invalid number of columns '{}' for '{}'", chunk[2], chunk[0])),
+        };
+
+        if properties.iter().any(|p: &Property| p.name == chunk[0]) {
+            return Err(format!("duplicated property '{}'", chunk[0]));
+        }
+
+        properties.push(Property {
+            name: chunk[0].into(),
+            kind: kind,
+            count: count,
+        });
+    }
+
+    return Ok(properties);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::System;
+    use crate::system::cell::CellShape;
+
+    use approx::assert_relative_eq;
+    use ndarray::arr2;
+
+    #[test]
+    fn plain_xyz() {
+        let frames = parse_xyz("3
+water molecule
+O 0.0 0.0 0.0
+H 0.75 0.58 0.0
+H -0.75 0.58 0.0
+").unwrap();
+
+        assert_eq!(frames.len(), 1);
+        let frame = &frames[0];
+        assert!(frame.system.cell().unwrap().is_infinite());
+        assert_eq!(frame.system.species().unwrap(), &[8, 1, 1]);
+        assert_eq!(frame.system.positions().unwrap()[1], Vector3D::new(0.75, 0.58, 0.0));
+
+        assert!(frame.arrays.is_empty());
+        assert_eq!(frame.info.len(), 2);
+        assert_eq!(frame.info["water"], XyzValue::Bool(true));
+    }
+
+    #[test]
+    fn multiple_frames() {
+        let frames = parse_xyz(r#"2
+Lattice="10.0 0.0 0.0 0.0 10.0 0.0 0.0 0.0 10.0" Properties=species:S:1:pos:R:3:forces:R:3 energy=-3.5 pbc="T T T"
+C 0.0 0.0 0.0 0.1 0.2 0.3
+O 1.2 0.0 0.0 -0.1 -0.2 -0.3
+1
+Lattice="4.0 0.0 0.0 1.0 4.0 0.0 0.0 0.0 4.0" name="single atom" stress={1, 2, 3} tag=3 converged=F
+Zn 1.0 2.0 3.0
+
+"#).unwrap();
+
+        assert_eq!(frames.len(), 2);
+
+        let frame = &frames[0];
+        assert_eq!(frame.system.size().unwrap(), 2);
+        assert_eq!(frame.system.species().unwrap(), &[6, 8]);
+        assert_eq!(frame.system.cell().unwrap().shape(), CellShape::Orthorhombic);
+        assert_relative_eq!(frame.system.cell().unwrap().lengths(), Vector3D::new(10.0, 10.0, 10.0));
+        assert_eq!(frame.info.len(), 1);
+        assert_eq!(frame.info["energy"], XyzValue::Real(-3.5));
+        assert_eq!(frame.arrays.len(), 1);
+        assert_eq!(frame.arrays["forces"], XyzArray::Real(arr2(&[
+            [0.1, 0.2, 0.3], [-0.1, -0.2, -0.3]
+        ])));
+
+        let frame = &frames[1];
+        assert_eq!(frame.system.species().unwrap(), &[30]);
+        let cell = frame.system.cell().unwrap();
+        assert_eq!(cell.shape(), CellShape::Triclinic);
+        // cell vectors are stored as columns of the matrix
+        assert_eq!(cell.matrix()[0], [4.0, 1.0, 0.0]);
+        assert_eq!(frame.info["name"], XyzValue::String("single atom".into()));
+        assert_eq!(frame.info["stress"], XyzValue::IntegerArray(vec![1, 2, 3]));
+        assert_eq!(frame.info["tag"], XyzValue::Integer(3));
+        assert_eq!(frame.info["converged"], XyzValue::Bool(false));
+    }
+
+    #[test]
+    fn left_handed_cell() {
+        let frames = parse_xyz(r#"1
+Lattice="4.0 0.0 0.0 1.0 0.0 4.0 0.0 4.0 0.0"
+Zn 1.0 2.0 3.0
+"#).unwrap();
+
+        let cell = frames[0].system.cell().unwrap();
+        assert!(cell.matrix().determinant() < 0.0);
+        assert_eq!(cell.shape(), CellShape::Triclinic);
+        assert_relative_eq!(cell.volume(), 64.0);
+    }
+
+    #[test]
+    fn arrays() {
+        let frames = parse_xyz("2
+Properties=species:S:1:pos:R:3:label:S:1:fixed:L:3:id:I:1 pbc=\"F F F\" Lattice=\"3 0 0 0 3 0 0 0 3\"
+H 0 0 0 first T F F 4
+H 0 0 1 second F F T 5
+").unwrap();
+
+        let frame = &frames[0];
+        assert!(frame.system.cell().unwrap().is_infinite());
+        assert!(frame.info.is_empty());
+        assert_eq!(frame.arrays["label"], XyzArray::String(arr2(&[
+            ["first".to_owned()], ["second".to_owned()]
+        ])));
+        assert_eq!(frame.arrays["fixed"], XyzArray::Bool(arr2(&[
+            [true, false, false], [false, false, true]
+        ])));
+        assert_eq!(frame.arrays["id"], XyzArray::Integer(arr2(&[[4], [5]])));
+    }
+
+    #[test]
+    fn errors() {
+        let message = |content: &str| match parse_xyz(content) {
+            Ok(_) => panic!("expected an error"),
+            Err(error) => error.to_string(),
+        };
+
+        assert_eq!(
+            message("two\n\nH 0 0 0\n"),
+            "invalid parameter: invalid XYZ file at line 1: expected the number of atoms, got 'two'"
+        );
+        assert_eq!(
+            message("2\n\nH 0 0 0\n"),
+            "invalid parameter: invalid XYZ file at line 4: expected 2 atoms, got 1"
+        );
+        assert_eq!(
+            message("1\n\nXx 0 0 0\n"),
+            "invalid parameter: invalid XYZ file at line 3: expected atomic species, got 'Xx'"
+        );
+        assert_eq!(
+            message("1\n\nH 0 0\n"),
+            "invalid parameter: invalid XYZ file at line 3: expected 4 columns in atomic line, got 3"
+        );
+        assert_eq!(
+            message("1\nenergy=\"3.0\n H 0 0 0\n"),
+            "invalid parameter: invalid XYZ file at line 2: unterminated quoted string"
+        );
+        assert_eq!(
+            message("1\nLattice=\"1 0 0 0 1 0\"\nH 0 0 0\n"),
+            "invalid parameter: invalid XYZ file at line 2: expected 9 real numbers for Lattice, got '1 0 0 0 1 0'"
+        );
+        assert_eq!(
+            message("1\nLattice=\"1 0 0 0 1 0 0 0 1\" pbc=\"T T F\"\nH 0 0 0\n"),
+            "invalid parameter: invalid XYZ file at line 2: partially periodic systems are not supported"
+        );
+        assert_eq!(
+            message("1\nProperties=species:S:1:pos:R:3:charge:X:1\nH 0 0 0 1\n"),
+            "invalid parameter: invalid XYZ file at line 2: unknown property kind 'X' for 'charge'"
+        );
+        assert_eq!(
+            message("1\nProperties=species:S:1\nH\n"),
+            "invalid parameter: invalid XYZ file at line 2: missing 'pos' property"
+        );
+    }
+}